@@ -0,0 +1,319 @@
+//! Request-scoped bump allocation.
+//!
+//! A [`RequestArena`] hands out memory by bumping a pointer in a chunk, and frees all of it at
+//! once when it is [`reset`](RequestArena::reset) after the response is written. Objects in the
+//! arena are never dropped, so an [`ArenaMap`] of them needs no reclamation at all.
+
+use core::alloc::Layout;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use core::slice;
+use core::str;
+use std::collections::hash_map::RandomState;
+
+/// Size of the first chunk of an arena created by [`RequestArena::new`].
+const DEFAULT_CHUNK: usize = 4096;
+
+/// Bump allocator for the objects that live as long as a request.
+///
+/// Allocation takes `&self` and returns a reference that is valid until the arena is reset or
+/// dropped, both of which take `&mut self`. Destructors of the allocated objects are never run.
+pub struct RequestArena {
+    /// All chunks, the last one being the current one. Boxes don't move when the `Vec` grows.
+    chunks: RefCell<Vec<Box<[MaybeUninit<u8>]>>>,
+    /// The free part of the current chunk.
+    ptr: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+    /// Number of bytes handed out since the last reset, including the padding.
+    allocated: Cell<usize>,
+}
+
+// The chunks are owned, and the allocated objects borrow the arena.
+unsafe impl Send for RequestArena {}
+
+impl fmt::Debug for RequestArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestArena")
+            .field("capacity", &self.capacity())
+            .field("allocated", &self.allocated())
+            .finish()
+    }
+}
+
+impl Default for RequestArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestArena {
+    /// Creates a new arena.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CHUNK)
+    }
+
+    /// Creates a new arena whose first chunk has `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let arena = Self {
+            chunks: RefCell::new(Vec::new()),
+            ptr: Cell::new(ptr::null_mut()),
+            end: Cell::new(ptr::null_mut()),
+            allocated: Cell::new(0),
+        };
+        if capacity > 0 {
+            arena.push_chunk(capacity);
+        }
+        arena
+    }
+
+    /// Returns the total size of the chunks.
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(|chunk| chunk.len()).sum()
+    }
+
+    /// Returns the number of bytes allocated since the last reset.
+    pub fn allocated(&self) -> usize {
+        self.allocated.get()
+    }
+
+    /// Frees everything allocated in the arena.
+    ///
+    /// The chunks are merged into one that is as large as all of them, so a workload of similar
+    /// requests soon stops allocating at all.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let capacity = chunks.iter().map(|chunk| chunk.len()).sum();
+            chunks.clear();
+            self.push_chunk(capacity);
+        } else if let Some(chunk) = chunks.first_mut() {
+            let range = chunk.as_mut_ptr_range();
+            self.ptr.set(range.start as *mut u8);
+            self.end.set(range.end as *mut u8);
+        }
+        self.allocated.set(0);
+    }
+
+    fn push_chunk(&self, capacity: usize) {
+        let mut chunk = (0..capacity)
+            .map(|_| MaybeUninit::uninit())
+            .collect::<Box<[_]>>();
+        let range = chunk.as_mut_ptr_range();
+        self.ptr.set(range.start as *mut u8);
+        self.end.set(range.end as *mut u8);
+        self.chunks.borrow_mut().push(chunk);
+    }
+
+    /// Allocates uninitialized memory for `layout`.
+    fn alloc_layout(&self, layout: Layout) -> *mut u8 {
+        if layout.size() == 0 {
+            // any aligned non-null pointer will do
+            return layout.align() as *mut u8;
+        }
+        loop {
+            let ptr = self.ptr.get() as usize;
+            let start = (ptr + layout.align() - 1) & !(layout.align() - 1);
+            if ptr != 0 && start + layout.size() <= self.end.get() as usize {
+                self.ptr.set((start + layout.size()) as *mut u8);
+                self.allocated
+                    .set(self.allocated.get() + start + layout.size() - ptr);
+                return start as *mut u8;
+            }
+            // Double the chunk size, so that a request needs only a few chunks.
+            let last = self.chunks.borrow().last().map_or(0, |chunk| chunk.len());
+            self.push_chunk(
+                (2 * last)
+                    .max(layout.size() + layout.align())
+                    .max(DEFAULT_CHUNK),
+            );
+        }
+    }
+
+    /// Moves `value` into the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()) as *mut T;
+        unsafe {
+            ptr.write(value);
+            &mut *ptr
+        }
+    }
+
+    /// Copies `src` into the arena.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> &mut [T] {
+        let ptr = self.alloc_layout(Layout::for_value(src)) as *mut T;
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), ptr, src.len());
+            slice::from_raw_parts_mut(ptr, src.len())
+        }
+    }
+
+    /// Allocates a slice of `len` items, each created by `f` from its index.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice_fill_with<T, F: FnMut(usize) -> T>(&self, len: usize, mut f: F) -> &mut [T] {
+        let layout = Layout::array::<T>(len).expect("arena allocation too large");
+        let ptr = self.alloc_layout(layout) as *mut T;
+        for i in 0..len {
+            unsafe { ptr.add(i).write(f(i)) };
+        }
+        unsafe { slice::from_raw_parts_mut(ptr, len) }
+    }
+
+    /// Allocates a zeroed buffer of `len` bytes, e.g., to read a request into.
+    #[allow(clippy::mut_from_ref)]
+    pub fn buffer(&self, len: usize) -> &mut [u8] {
+        let ptr = self.alloc_layout(Layout::array::<u8>(len).unwrap());
+        unsafe {
+            ptr::write_bytes(ptr, 0, len);
+            slice::from_raw_parts_mut(ptr, len)
+        }
+    }
+
+    /// Copies `src` into the arena.
+    pub fn alloc_str(&self, src: &str) -> &str {
+        unsafe { str::from_utf8_unchecked(self.alloc_slice_copy(src.as_bytes())) }
+    }
+
+    /// Formats `args` into the arena, e.g., `arena.alloc_fmt(format_args!("{}", 42))`.
+    pub fn alloc_fmt(&self, args: fmt::Arguments<'_>) -> &str {
+        /// Counts the bytes, or writes them into a buffer of exactly that size.
+        struct Writer<'a> {
+            buf: Option<&'a mut [u8]>,
+            len: usize,
+        }
+
+        impl fmt::Write for Writer<'_> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                if let Some(buf) = &mut self.buf {
+                    buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+                }
+                self.len += s.len();
+                Ok(())
+            }
+        }
+
+        let mut counter = Writer { buf: None, len: 0 };
+        fmt::write(&mut counter, args).expect("a formatting trait returned an error");
+        let mut writer = Writer {
+            buf: Some(self.buffer(counter.len)),
+            len: 0,
+        };
+        fmt::write(&mut writer, args).expect("a formatting trait returned an error");
+        // a `Display` impl may write differently the second time
+        assert_eq!(writer.len, counter.len, "formatting is not deterministic");
+        unsafe { str::from_utf8_unchecked(writer.buf.unwrap()) }
+    }
+}
+
+/// Hash map whose slots live in a [`RequestArena`].
+///
+/// Growing the map leaves the old slots in the arena, and the entries are never dropped, so
+/// nothing is freed before the arena is reset. It suits small maps of borrowed data such as the
+/// headers of a request.
+pub struct ArenaMap<'a, K, V> {
+    arena: &'a RequestArena,
+    hasher: RandomState,
+    /// Open-addressed slots, with linear probing. The length is zero or a power of two.
+    slots: &'a mut [Option<(K, V)>],
+    len: usize,
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ArenaMap<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K: Hash + Eq, V> ArenaMap<'a, K, V> {
+    /// Creates an empty map in `arena`. It doesn't allocate until the first insertion.
+    pub fn new_in(arena: &'a RequestArena) -> Self {
+        Self {
+            arena,
+            hasher: RandomState::new(),
+            slots: &mut [],
+            len: 0,
+        }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the index of the slot of `key`, or of the empty slot where it would be.
+    fn probe(&self, key: &K) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        let mask = self.slots.len() - 1;
+        let mut index = hasher.finish() as usize & mask;
+        loop {
+            match &self.slots[index] {
+                Some((k, _)) if k != key => index = (index + 1) & mask,
+                _ => return index,
+            }
+        }
+    }
+
+    /// Inserts a key-value pair, returning the previous value of the key.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        // keep the load factor below 3/4
+        if 4 * (self.len + 1) > 3 * self.slots.len() {
+            let slots = self
+                .arena
+                .alloc_slice_fill_with((2 * self.slots.len()).max(8), |_| None);
+            let old = mem::replace(&mut self.slots, slots);
+            for (k, v) in old.iter_mut().filter_map(Option::take) {
+                let index = self.probe(&k);
+                self.slots[index] = Some((k, v));
+            }
+        }
+
+        let index = self.probe(&key);
+        match &mut self.slots[index] {
+            Some((_, v)) => Some(mem::replace(v, value)),
+            slot => {
+                *slot = Some((key, value));
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        if self.len == 0 {
+            return None;
+        }
+        self.slots[self.probe(key)].as_ref().map(|(_, v)| v)
+    }
+
+    /// Returns the value of `key` mutably.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if self.len == 0 {
+            return None;
+        }
+        let index = self.probe(key);
+        self.slots[index].as_mut().map(|(_, v)| v)
+    }
+
+    /// Returns `true` if the map contains `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+}
+
+impl<'a, K, V> ArenaMap<'a, K, V> {
+    /// Returns an iterator over the entries, in an arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots.iter().flatten().map(|(k, v)| (k, v))
+    }
+}
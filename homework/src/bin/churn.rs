@@ -0,0 +1,110 @@
+//! Delete-heavy benchmark of `SplitOrderedList`, with and without tombstones.
+//!
+//! Run e.g. `cargo run --release --bin churn -- --threads 8 --duration 5s`. Each thread inserts
+//! and deletes random keys with equal probability, so about half of the keys are present at any
+//! time. The throughput of each mode is printed to stdout.
+
+mod common;
+
+use common::Args;
+use crossbeam_epoch as epoch;
+use cs431_homework::{workload_rng, Config, NonblockingMap, SplitOrderedList};
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: churn [OPTIONS]
+
+options:
+  --duration D      how long to run each mode, e.g. 500ms, 5s (default: 5s)
+  --threads N       number of threads (default: number of CPUs)
+  --keys N          number of distinct keys (default: 65536)
+  --ttl D           how long the tombstones are kept (default: 0ms)
+  --help            show this message";
+
+#[derive(Debug)]
+struct Options {
+    duration: Duration,
+    threads: usize,
+    keys: usize,
+    ttl: Duration,
+}
+
+impl Options {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut options = Options {
+            duration: Duration::from_secs(5),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            keys: 65536,
+            ttl: Duration::ZERO,
+        };
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--duration" => options.duration = args.duration(&option)?,
+                "--threads" => options.threads = args.positive(&option)?,
+                "--keys" => options.keys = args.positive(&option)?,
+                "--ttl" => options.ttl = args.duration(&option)?,
+                _ => return Err(common::unknown(&option)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Runs the workload on a list with the given configuration, and returns the throughput in
+/// operations per second.
+fn run(options: &Options, config: Config) -> f64 {
+    let list = SplitOrderedList::<usize>::with_config(config);
+    let stop = AtomicBool::new(false);
+    let ops = AtomicUsize::new(0);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..options.threads {
+            let _ = s.spawn(|| {
+                let mut rng = workload_rng();
+                let mut n = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let guard = epoch::pin();
+                    for _ in 0..64 {
+                        let key = rng.gen_range(0..options.keys);
+                        if rng.gen() {
+                            let _ = list.insert(&key, key, &guard);
+                        } else {
+                            let _ = list.delete(&key, &guard);
+                        }
+                    }
+                    n += 64;
+                }
+                let _ = ops.fetch_add(n, Ordering::Relaxed);
+            });
+        }
+        thread::sleep(options.duration);
+        stop.store(true, Ordering::Relaxed);
+    });
+    ops.into_inner() as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let options = common::parse_options(USAGE, Options::parse);
+    common::report_seed();
+
+    println!(
+        "{} threads, {} keys, 50% inserts / 50% deletes, {:?} per mode",
+        options.threads, options.keys, options.duration
+    );
+    let unlinking = run(&options, Config::default());
+    println!("unlinking:   {:.0} ops/s", unlinking);
+    let tombstones = run(
+        &options,
+        Config {
+            tombstone_ttl: Some(options.ttl),
+            ..Config::default()
+        },
+    );
+    println!(
+        "tombstones:  {:.0} ops/s ({:+.1}%)",
+        tombstones,
+        (tombstones / unlinking - 1.0) * 100.0
+    );
+}
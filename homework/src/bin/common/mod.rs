@@ -0,0 +1,103 @@
+//! Command line parsing shared by the binaries, which include it with `mod common;`.
+
+// each binary uses a part of it
+#![allow(dead_code)]
+
+use std::env;
+use std::iter::Skip;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Parses a duration with a unit of `us`, `ms`, `s`, `m` or `h`, e.g. `500ms`.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let (num, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit())?);
+    let num = num.parse::<u64>().ok()?;
+    match unit {
+        "us" => Some(Duration::from_micros(num)),
+        "ms" => Some(Duration::from_millis(num)),
+        "s" => Some(Duration::from_secs(num)),
+        "m" => Some(Duration::from_secs(num * 60)),
+        "h" => Some(Duration::from_secs(num * 60 * 60)),
+        _ => None,
+    }
+}
+
+/// Parses `arg` as the `what`, e.g. an argument of a command.
+pub fn parse<T: FromStr>(arg: Option<&str>, what: &str) -> Result<T, String> {
+    arg.ok_or_else(|| format!("missing {}", what))?
+        .parse()
+        .map_err(|_| format!("invalid {}", what))
+}
+
+/// Returns the error of an unknown option.
+pub fn unknown(option: &str) -> String {
+    format!("unknown option `{}`", option)
+}
+
+/// Prints the seed of the workload generators to stderr with the `deterministic` feature, so that
+/// the run can be reproduced.
+pub fn report_seed() {
+    if cfg!(feature = "deterministic") {
+        eprintln!(
+            "[deterministic] CS431_SEED={}",
+            cs431_homework::workload_seed()
+        );
+    }
+}
+
+/// Options of the command line of a binary.
+#[derive(Debug)]
+pub struct Args {
+    args: Skip<env::Args>,
+    usage: &'static str,
+}
+
+impl Args {
+    /// Returns the next option. `--help` prints the usage and exits.
+    pub fn next_option(&mut self) -> Option<String> {
+        let option = self.args.next()?;
+        if option == "--help" {
+            println!("{}", self.usage);
+            process::exit(0);
+        }
+        Some(option)
+    }
+
+    /// Parses the value of `option`, which is named without its dashes in the errors.
+    pub fn value<T: FromStr>(&mut self, option: &str) -> Result<T, String> {
+        parse(self.args.next().as_deref(), option.trim_start_matches('-'))
+    }
+
+    /// Parses the value of `option` as a positive number.
+    pub fn positive(&mut self, option: &str) -> Result<usize, String> {
+        Some(self.value(option)?)
+            .filter(|&n| n > 0)
+            .ok_or_else(|| format!("invalid {}", option.trim_start_matches('-')))
+    }
+
+    /// Parses the value of `option` as a duration.
+    pub fn duration(&mut self, option: &str) -> Result<Duration, String> {
+        let value = self.value::<String>(option)?;
+        parse_duration(&value).ok_or_else(|| format!("invalid {}", option.trim_start_matches('-')))
+    }
+}
+
+/// Parses the options of the command line with `parse`, or prints the error with the usage and
+/// exits.
+pub fn parse_options<T>(
+    usage: &'static str,
+    parse: impl FnOnce(&mut Args) -> Result<T, String>,
+) -> T {
+    let mut args = Args {
+        args: env::args().skip(1),
+        usage,
+    };
+    match parse(&mut args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, usage);
+            process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Contended benchmark of Treiber's stack, with and without elimination backoff.
+//!
+//! Run e.g. `cargo run --release --bin elim_stack -- --threads 16 --elim-size 4`. Each thread
+//! pushes and pops in turn on a single stack, so that all threads contend on its head. The
+//! throughput of each stack is printed to stdout.
+
+mod common;
+
+use common::Args;
+use cs431_homework::{ElimStack, Stack, TreiberStack};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: elim_stack [OPTIONS]
+
+options:
+  --duration D      how long to run each stack, e.g. 500ms, 5s (default: 5s)
+  --threads N       number of threads (default: twice the number of CPUs)
+  --elim-size N     number of slots of elimination (default: 16)
+  --delay D         how long a push waits for a pop in a slot, e.g. 0us, 10ms (default: 100us)
+  --help            show this message";
+
+#[derive(Debug)]
+struct Options {
+    duration: Duration,
+    threads: usize,
+    elim_size: usize,
+    delay: Duration,
+}
+
+impl Options {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut options = Options {
+            duration: Duration::from_secs(5),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()) * 2,
+            elim_size: 16,
+            delay: Duration::from_micros(100),
+        };
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--duration" => options.duration = args.duration(&option)?,
+                "--threads" => options.threads = args.positive(&option)?,
+                "--elim-size" => options.elim_size = args.positive(&option)?,
+                "--delay" => options.delay = args.duration(&option)?,
+                _ => return Err(common::unknown(&option)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Runs the workload on the stack, and returns the throughput in operations per second.
+fn run<S: Stack<usize> + Sync>(options: &Options, stack: S) -> f64 {
+    let stop = AtomicBool::new(false);
+    let ops = AtomicUsize::new(0);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..options.threads {
+            let _ = s.spawn(|| {
+                let mut n = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    stack.push(n);
+                    let _ = stack.pop();
+                    n += 2;
+                }
+                let _ = ops.fetch_add(n, Ordering::Relaxed);
+            });
+        }
+        thread::sleep(options.duration);
+        stop.store(true, Ordering::Relaxed);
+    });
+    ops.into_inner() as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let options = common::parse_options(USAGE, Options::parse);
+
+    println!(
+        "{} threads, 50% pushes / 50% pops, {:?} per stack",
+        options.threads, options.duration
+    );
+    let treiber = run(&options, TreiberStack::default());
+    println!("treiber:      {:.0} ops/s", treiber);
+    let elim = run(
+        &options,
+        ElimStack::with_elim_size(options.elim_size).elim_delay(options.delay),
+    );
+    println!(
+        "elimination:  {:.0} ops/s ({:+.1}%, {} slots, {:?} delay)",
+        elim,
+        (elim / treiber - 1.0) * 100.0,
+        options.elim_size,
+        options.delay
+    );
+}
@@ -1,7 +1,8 @@
-use crossbeam_channel::{bounded, unbounded};
-use cs431_homework::hello_server::{CancellableTcpListener, Handler, Statistics, ThreadPool};
-use std::io;
-use std::sync::Arc;
+use cs431_homework::collector_flush;
+use cs431_homework::hello_server::{Overflow, Server, ServerConfig, StdoutLogger};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
 
 const ADDR: &str = "localhost:7878";
 
@@ -10,74 +11,123 @@ fn main() -> io::Result<()> {
     // Firefox works well.  If you want to test using command line only, use curl. If you want to
     // run it on the lab server, you may need to change the port number to something else.
     println!(
-        "Run `curl http://{}/KEY` to query the server with KEY",
-        ADDR
+        "Run `curl http://{}/KEY` to query the server with KEY, and `curl http://{}/stats` for the \
+         statistics",
+        ADDR, ADDR
     );
 
-    // The thread pool.
+    // With `--shards N`, the connections are handled by N single-threaded shards, each owning the
+    // cache of its keys, instead of by the thread pool sharing a cache.
     //
-    // In the thread pool, we'll execute:
+    // With `--cache-file PATH`, the shared cache is warmed up from the file if it exists, and
+    // written back to it on exit, so that a restarted server doesn't compute everything again.
     //
-    // - A listener: it accepts incoming connections, and creates a new worker for each connection.
+    // With `--max-connections N`, the thread pool handles at most N connections at a time (by
+    // default 1024). With `--overflow reject`, the connections over the limit are
+    // answered with 503 instead of waiting in the backlog of the listener.
     //
-    // - Workers (once for each incoming connection): a worker handles an incoming connection and
-    //   sends a corresponding report to the reporter.
+    // With `--unix-socket PATH`, the server also listens to the Unix domain socket at PATH, e.g.
+    // for a local reverse proxy. The socket file is removed on exit.
     //
-    // - A reporter: it aggregates the reports from the workers and processes the
-    //   statistics.  When it ends, it sends the statistics to the main thread.
-    let pool = Arc::new(ThreadPool::new(7));
-
-    // The (MPSC) channel of reports between workers and the reporter.
-    let (report_sender, report_receiver) = unbounded();
-
-    // The (SPSC one-shot) channel of stats between the reporter and the main thread.
-    let (stat_sender, stat_receiver) = bounded(0);
+    // With `--log stdout`, a line is printed for each request, with its method, path, status and
+    // how long the handler took.
+    //
+    // With the `tls` feature, `--tls-cert PATH --tls-key PATH` serves HTTPS with the certificate
+    // chain and the private key of the PEM files, e.g. for `curl --cacert CA https://...`.
+    let usage = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "usage: hello_server [--shards N | --cache-file PATH] [--max-connections N] \
+             [--overflow wait|reject] [--unix-socket PATH] [--log stdout] \
+             [--tls-cert PATH --tls-key PATH]",
+        )
+    };
+    let mut args = std::env::args().skip(1);
+    let mut config = ServerConfig::new().addr(ADDR);
+    let mut shards = None;
+    let mut cache_file = None;
+    #[cfg(feature = "tls")]
+    let (mut tls_cert, mut tls_key) = (None, None);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--shards", Some(n)) => {
+                shards = Some(n.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "invalid number of shards")
+                })?)
+            }
+            ("--cache-file", Some(path)) => cache_file = Some(PathBuf::from(path)),
+            ("--max-connections", Some(n)) => {
+                config =
+                    config.max_connections(n.parse().ok().filter(|&n| n > 0).ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "invalid number of connections")
+                    })?)
+            }
+            ("--overflow", Some(o)) if o == "wait" => config = config.overflow(Overflow::Wait),
+            ("--overflow", Some(o)) if o == "reject" => config = config.overflow(Overflow::Reject),
+            ("--log", Some(l)) if l == "stdout" => config = config.hooks(StdoutLogger),
+            #[cfg(unix)]
+            ("--unix-socket", Some(path)) => config = config.unix_socket(path),
+            #[cfg(feature = "tls")]
+            ("--tls-cert", Some(path)) => tls_cert = Some(path),
+            #[cfg(feature = "tls")]
+            ("--tls-key", Some(path)) => tls_key = Some(path),
+            _ => return Err(usage()),
+        }
+    }
+    if shards.is_some() && cache_file.is_some() {
+        return Err(usage());
+    }
+    #[cfg(feature = "tls")]
+    match (tls_cert, tls_key) {
+        (Some(cert), Some(key)) => config = config.tls(cert, key),
+        (None, None) => {}
+        _ => return Err(usage()),
+    }
 
-    // Listens to the address.
-    let listener = Arc::new(CancellableTcpListener::bind(ADDR)?);
+    if let Some(shards) = shards {
+        config = config.shards(shards);
+    }
+    let server = Server::new(config)?;
 
     // Installs a Ctrl-C handler.
-    let ctrlc_listner_handle = listener.clone();
+    let handle = server.handle();
     ctrlc::set_handler(move || {
-        ctrlc_listner_handle.cancel().unwrap();
+        handle.shutdown().unwrap();
     })
     .expect("Error setting Ctrl-C handler");
 
-    // Executes the listener.
-    let listener_pool = pool.clone();
-    pool.execute(move || {
-        // Creates the request handler.
-        let handler = Handler::default();
-
-        // For each incoming connection...
-        for (id, stream) in listener.incoming().enumerate() {
-            // send a job to the thread pool.
-            let report_sender = report_sender.clone();
-            let handler = handler.clone();
-            listener_pool.execute(move || {
-                let report = handler.handle_conn(id, stream.unwrap());
-                report_sender.send(report).unwrap();
-            });
+    let handler = server.handler().clone();
+    if let Some(path) = &cache_file {
+        match File::open(path) {
+            Ok(file) => {
+                let loaded = handler.load_cache(BufReader::new(file))?;
+                println!("[cache] loaded {} results from {}", loaded, path.display());
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
         }
-    });
+    }
 
-    // Executes the reporter.
-    pool.execute(move || {
-        let mut stats = Statistics::default();
-        for report in report_receiver {
-            println!("[report] {:?}", report);
-            stats.add_report(report);
-        }
+    // Blocks until the server is cancelled, and all connections are handled.
+    let stat = server.run();
+    println!("[stat] {:?}", stat);
+    if stat.abandoned() > 0 {
+        println!("[shutdown] abandoned {} jobs", stat.abandoned());
+    }
+    println!("[hot keys] {:?}", stat.hot_keys());
+    println!(
+        "[latency] p50 {:?}, p95 {:?}, p99 {:?}",
+        stat.latency().percentile(50.0),
+        stat.latency().percentile(95.0),
+        stat.latency().percentile(99.0)
+    );
 
-        println!("[sending stat]");
-        stat_sender.send(stats).unwrap();
-        println!("[sent stat]");
-    });
+    if let Some(path) = &cache_file {
+        let dumped = handler.dump_cache(BufWriter::new(File::create(path)?))?;
+        println!("[cache] dumped {} results to {}", dumped, path.display());
+    }
 
-    // Blocks until the reporter sends the statistics.
-    let stat = stat_receiver.recv().unwrap();
-    println!("[stat] {:?}", stat);
+    collector_flush();
 
     Ok(())
-    // When the pool is dropped, all worker threads are joined.
 }
@@ -0,0 +1,154 @@
+//! Read-heavy benchmark of `OrderedListSet`, `RwListSet` and `LazyListSet`.
+//!
+//! Run e.g. `cargo run --release --bin list_set -- --threads 8 --keys 1000`. The readers look up
+//! random keys while a writer inserts and removes keys, and the throughput of the lookups of each
+//! set is printed to stdout.
+
+mod common;
+
+use common::Args;
+use cs431_homework::{workload_rng, LazyListSet, OrderedListSet, RwListSet};
+use rand::Rng;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: list_set [OPTIONS]
+
+options:
+  --duration D      how long to run each set, e.g. 500ms, 5s (default: 3s)
+  --threads N       number of readers (default: number of CPUs)
+  --keys N          number of distinct keys, half of which are in the set (default: 1000)
+  --help            show this message";
+
+#[derive(Debug)]
+struct Options {
+    duration: Duration,
+    threads: usize,
+    keys: usize,
+}
+
+impl Options {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut options = Options {
+            duration: Duration::from_secs(3),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            keys: 1000,
+        };
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--duration" => options.duration = args.duration(&option)?,
+                "--threads" => options.threads = args.positive(&option)?,
+                "--keys" => options.keys = args.positive(&option)?,
+                _ => return Err(common::unknown(&option)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The operations of the benchmarked sets.
+trait Set: Default + Sync {
+    fn contains(&self, key: &usize) -> bool;
+    fn insert(&self, key: usize) -> bool;
+    fn remove(&self, key: &usize) -> bool;
+}
+
+impl Set for OrderedListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        self.contains(key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        self.insert(key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        self.remove(key).is_ok()
+    }
+}
+
+impl Set for RwListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        self.contains(key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        self.insert(key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        self.remove(key).is_ok()
+    }
+}
+
+impl Set for LazyListSet<usize> {
+    fn contains(&self, key: &usize) -> bool {
+        self.contains(key)
+    }
+
+    fn insert(&self, key: usize) -> bool {
+        self.insert(key).is_ok()
+    }
+
+    fn remove(&self, key: &usize) -> bool {
+        self.remove(key)
+    }
+}
+
+/// Runs the workload on a new set, and returns the throughput of the lookups in operations per
+/// second.
+fn run<S: Set>(options: &Options) -> f64 {
+    let set = S::default();
+    for key in (0..options.keys).step_by(2) {
+        let _ = set.insert(key);
+    }
+    let stop = AtomicBool::new(false);
+    let lookups = AtomicUsize::new(0);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..options.threads {
+            let _ = s.spawn(|| {
+                let mut rng = workload_rng();
+                let mut n = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    let _ = set.contains(&rng.gen_range(0..options.keys));
+                    n += 1;
+                }
+                let _ = lookups.fetch_add(n, Ordering::Relaxed);
+            });
+        }
+        let _ = s.spawn(|| {
+            let mut rng = workload_rng();
+            while !stop.load(Ordering::Relaxed) {
+                let key = rng.gen_range(0..options.keys);
+                if !set.remove(&key) {
+                    let _ = set.insert(key);
+                }
+            }
+        });
+        thread::sleep(options.duration);
+        stop.store(true, Ordering::Relaxed);
+    });
+    lookups.into_inner() as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let options = common::parse_options(USAGE, Options::parse);
+    common::report_seed();
+
+    println!(
+        "{} readers and a writer, {} keys, {:?} per set",
+        options.threads, options.keys, options.duration
+    );
+    let coupling = run::<OrderedListSet<usize>>(&options);
+    println!("lock coupling: {:.0} lookups/s", coupling);
+    let rw = run::<RwListSet<usize>>(&options);
+    println!("rw coupling:   {:.0} lookups/s ({:.1}x)", rw, rw / coupling);
+    let lazy = run::<LazyListSet<usize>>(&options);
+    println!(
+        "lazy:          {:.0} lookups/s ({:.1}x)",
+        lazy,
+        lazy / coupling
+    );
+}
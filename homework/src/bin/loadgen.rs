@@ -0,0 +1,311 @@
+//! Load generator for `hello_server`.
+//!
+//! Run the server, then e.g. `cargo run --release --bin loadgen -- --connections 32 --duration
+//! 10s --keys 100 --dist zipf:1.1 --keep-alive`. Run with `--help` for the options.
+
+mod common;
+
+use common::Args;
+use cs431_homework::workload_rng;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: loadgen [OPTIONS]
+
+options:
+  --addr ADDR          address of the server (default: localhost:7878)
+  --connections N      number of concurrent connections (default: 8)
+  --duration D         how long to run, e.g. 10s, 500ms, 1m (default: 10s)
+  --keys N             number of distinct keys (default: 100)
+  --dist DIST          key distribution (default: uniform)
+                         uniform    every key is equally likely
+                         zipf:S     the i-th key has probability proportional to 1/i^S
+                         hot:P      key 0 with probability P, uniform otherwise
+  --keep-alive         reuse the connections that the server keeps open
+  --help               show this message";
+
+/// Distribution of the requested keys.
+#[derive(Debug, Clone)]
+enum Dist {
+    Uniform,
+    /// Cumulative probabilities of the keys.
+    Zipf(Vec<f64>),
+    Hot(f64),
+}
+
+impl Dist {
+    fn parse(s: &str, keys: usize) -> Result<Self, String> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => {
+                let param = param
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid parameter `{}`", param))?;
+                (name, Some(param))
+            }
+            None => (s, None),
+        };
+        match (name, param) {
+            ("uniform", None) => Ok(Dist::Uniform),
+            ("zipf", Some(exponent)) => {
+                let weights = (1..=keys).map(|i| 1.0 / (i as f64).powf(exponent));
+                let total = weights.clone().sum::<f64>();
+                let cdf = weights
+                    .scan(0.0, |acc, w| {
+                        *acc += w / total;
+                        Some(*acc)
+                    })
+                    .collect();
+                Ok(Dist::Zipf(cdf))
+            }
+            ("hot", Some(p)) if (0.0..=1.0).contains(&p) => Ok(Dist::Hot(p)),
+            _ => Err(format!("invalid distribution `{}`", s)),
+        }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R, keys: usize) -> usize {
+        match self {
+            Dist::Uniform => rng.gen_range(0..keys),
+            Dist::Zipf(cdf) => {
+                let x = rng.gen::<f64>();
+                cdf.partition_point(|&c| c < x).min(keys - 1)
+            }
+            Dist::Hot(p) => {
+                if rng.gen_bool(*p) {
+                    0
+                } else {
+                    rng.gen_range(0..keys)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    addr: String,
+    connections: usize,
+    duration: Duration,
+    keys: usize,
+    dist: Dist,
+    dist_name: String,
+    keep_alive: bool,
+}
+
+impl Options {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut options = Options {
+            addr: "localhost:7878".to_string(),
+            connections: 8,
+            duration: Duration::from_secs(10),
+            keys: 100,
+            dist: Dist::Uniform,
+            dist_name: "uniform".to_string(),
+            keep_alive: false,
+        };
+        let mut dist = None;
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--addr" => options.addr = args.value(&option)?,
+                "--connections" => options.connections = args.value(&option)?,
+                "--duration" => options.duration = args.duration(&option)?,
+                "--keys" => options.keys = args.value(&option)?,
+                "--dist" => dist = Some(args.value::<String>(&option)?),
+                "--keep-alive" => options.keep_alive = true,
+                _ => return Err(common::unknown(&option)),
+            }
+        }
+        if options.connections == 0 || options.keys == 0 {
+            return Err("need at least one connection and one key".to_string());
+        }
+        if let Some(dist) = dist {
+            options.dist = Dist::parse(&dist, options.keys)?;
+            options.dist_name = dist;
+        }
+        Ok(options)
+    }
+}
+
+/// Results of a connection, or of all of them.
+#[derive(Debug, Default)]
+struct Results {
+    /// Latencies of the completed requests, in microseconds.
+    latencies: Vec<u64>,
+    /// Number of responses of each status code.
+    statuses: BTreeMap<u16, usize>,
+    /// Number of requests that failed with an I/O error.
+    errors: usize,
+    /// Number of connections opened.
+    connects: usize,
+}
+
+impl Results {
+    fn merge(&mut self, other: Results) {
+        self.latencies.extend(other.latencies);
+        for (status, count) in other.statuses {
+            *self.statuses.entry(status).or_default() += count;
+        }
+        self.errors += other.errors;
+        self.connects += other.connects;
+    }
+
+    /// Returns the `p`-th percentile of the sorted latencies.
+    fn percentile(&self, p: f64) -> Duration {
+        let index = ((self.latencies.len() as f64 * p / 100.0).ceil() as usize)
+            .clamp(1, self.latencies.len());
+        Duration::from_micros(self.latencies[index - 1])
+    }
+}
+
+/// Sends a request for `key` and reads the response. Returns the status code, and whether the
+/// server keeps the connection open.
+fn request(
+    stream: &mut BufReader<TcpStream>,
+    key: &str,
+    keep_alive: bool,
+) -> io::Result<(u16, bool)> {
+    // in one write, as the server reads the request at once
+    let req = format!(
+        "GET /{} HTTP/1.1\r\nHost: loadgen\r\nConnection: {}\r\n\r\n",
+        key,
+        if keep_alive { "keep-alive" } else { "close" }
+    );
+    stream.get_mut().write_all(req.as_bytes())?;
+
+    let mut line = String::new();
+    if stream.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed status line"))?;
+
+    let mut content_length = None;
+    let mut close = false;
+    loop {
+        line.clear();
+        if stream.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse::<u64>().ok();
+            } else if name.eq_ignore_ascii_case("connection") {
+                close = value.eq_ignore_ascii_case("close");
+            }
+        }
+    }
+
+    // Without a length, the body ends with the connection.
+    match content_length {
+        Some(len) => {
+            let _ = io::copy(&mut stream.by_ref().take(len), &mut io::sink())?;
+            Ok((status, keep_alive && !close))
+        }
+        None => {
+            let _ = io::copy(stream, &mut io::sink())?;
+            Ok((status, false))
+        }
+    }
+}
+
+/// Sends requests on one connection at a time until `stop` is set.
+fn run_connection(options: &Options, stop: &AtomicBool) -> Results {
+    let mut rng = workload_rng();
+    let mut results = Results::default();
+    let mut stream = None;
+    while !stop.load(Ordering::Relaxed) {
+        let key = format!("k{}", options.dist.sample(&mut rng, options.keys));
+        let start = Instant::now();
+        let conn = match &mut stream {
+            Some(conn) => conn,
+            None => match TcpStream::connect(&options.addr) {
+                Ok(conn) => {
+                    results.connects += 1;
+                    stream.insert(BufReader::new(conn))
+                }
+                Err(_) => {
+                    results.errors += 1;
+                    thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+            },
+        };
+        match request(conn, &key, options.keep_alive) {
+            Ok((status, reusable)) => {
+                results.latencies.push(start.elapsed().as_micros() as u64);
+                *results.statuses.entry(status).or_default() += 1;
+                if !reusable {
+                    stream = None;
+                }
+            }
+            Err(_) => {
+                results.errors += 1;
+                stream = None;
+            }
+        }
+    }
+    results
+}
+
+fn main() {
+    let options = common::parse_options(USAGE, Options::parse);
+    common::report_seed();
+
+    println!(
+        "{} connections to {} for {:?}, {} keys, {}{}",
+        options.connections,
+        options.addr,
+        options.duration,
+        options.keys,
+        options.dist_name,
+        if options.keep_alive {
+            ", keep-alive"
+        } else {
+            ""
+        },
+    );
+
+    let stop = AtomicBool::new(false);
+    let results = Mutex::new(Results::default());
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..options.connections {
+            let _ = s.spawn(|| {
+                let connection = run_connection(&options, &stop);
+                results.lock().unwrap().merge(connection);
+            });
+        }
+        thread::sleep(options.duration);
+        stop.store(true, Ordering::Relaxed);
+    });
+    let elapsed = start.elapsed();
+
+    let mut results = results.into_inner().unwrap();
+    results.latencies.sort_unstable();
+    let completed = results.latencies.len();
+    println!("requests:    {}", completed);
+    println!("errors:      {}", results.errors);
+    println!("connects:    {}", results.connects);
+    println!(
+        "throughput:  {:.1} req/s",
+        completed as f64 / elapsed.as_secs_f64()
+    );
+    for (status, count) in &results.statuses {
+        println!("status {}:  {}", status, count);
+    }
+    if completed > 0 {
+        for p in [50.0, 90.0, 99.0, 99.9] {
+            println!("p{:<5}      {:?}", p, results.percentile(p));
+        }
+        println!("max:         {:?}", results.percentile(100.0));
+    }
+}
@@ -0,0 +1,96 @@
+//! Contended benchmark of the MCS lock against a spin lock.
+//!
+//! Run e.g. `cargo run --release --bin mcs_lock -- --threads 16 --work 100`. Each thread takes a
+//! single lock in a loop to increment a counter, with some work outside of the critical section
+//! in between. The throughput of each lock is printed to stdout. With more threads than CPUs, a
+//! preempted waiter delays those behind it in the queue of the MCS lock, so the spin lock may be
+//! faster.
+
+mod common;
+
+use common::Args;
+use cs431::lock::{Lock, McsLock, RawLock, SpinLock};
+use std::hint;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: mcs_lock [OPTIONS]
+
+options:
+  --duration D      how long to run each lock, e.g. 500ms, 5s (default: 5s)
+  --threads N       number of threads (default: the number of CPUs)
+  --work N          iterations of work between two critical sections (default: 0)
+  --help            show this message";
+
+#[derive(Debug)]
+struct Options {
+    duration: Duration,
+    threads: usize,
+    work: usize,
+}
+
+impl Options {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut options = Options {
+            duration: Duration::from_secs(5),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            work: 0,
+        };
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--duration" => options.duration = args.duration(&option)?,
+                "--threads" => options.threads = args.positive(&option)?,
+                "--work" => options.work = args.value(&option)?,
+                _ => return Err(common::unknown(&option)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Runs the workload on the lock, and returns the throughput in critical sections per second.
+fn run<L: RawLock>(options: &Options) -> f64 {
+    let counter = Lock::<L, usize>::new(0);
+    let stop = AtomicBool::new(false);
+    let ops = AtomicUsize::new(0);
+    let start = Instant::now();
+    thread::scope(|s| {
+        for _ in 0..options.threads {
+            let _ = s.spawn(|| {
+                let mut n = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    *counter.lock() += 1;
+                    for _ in 0..options.work {
+                        hint::spin_loop();
+                    }
+                    n += 1;
+                }
+                let _ = ops.fetch_add(n, Ordering::Relaxed);
+            });
+        }
+        thread::sleep(options.duration);
+        stop.store(true, Ordering::Relaxed);
+    });
+    let elapsed = start.elapsed();
+    let ops = ops.into_inner();
+    assert_eq!(counter.into_inner(), ops);
+    ops as f64 / elapsed.as_secs_f64()
+}
+
+fn main() {
+    let options = common::parse_options(USAGE, Options::parse);
+
+    println!(
+        "{} threads, {} iterations of work between critical sections, {:?} per lock",
+        options.threads, options.work, options.duration
+    );
+    let spin = run::<SpinLock>(&options);
+    println!("spin lock:  {:.0} ops/s", spin);
+    let mcs = run::<McsLock>(&options);
+    println!(
+        "MCS lock:   {:.0} ops/s ({:+.1}%)",
+        mcs,
+        (mcs / spin - 1.0) * 100.0
+    );
+}
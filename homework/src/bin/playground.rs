@@ -0,0 +1,248 @@
+//! Interactive playground for the concurrent data structures.
+//!
+//! Run `cargo run --bin playground` and type `help` for the commands.
+
+mod common;
+
+use common::{parse, parse_duration};
+use crossbeam_epoch as epoch;
+use cs431_homework::{workload_rng, NonblockingMap, OrderedListSet, SplitOrderedList};
+use rand::Rng;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HELP: &str = "commands:
+  new map                create a SplitOrderedList<usize>
+  new set                create an OrderedListSet<usize>
+  insert K [V]           insert key K (with value V for a map)
+  lookup K               look up key K
+  delete K               delete key K
+  threads N              use N threads for `stress`
+  stress DURATION        run random operations on keys 0..KEYS, e.g. `stress 10s`, `stress 500ms`
+  threads N stress D     both of the above
+  stats                  show the statistics of the current structure
+  help                   show this message
+  quit                   exit";
+
+/// Number of distinct keys used by `stress`.
+const KEYS: usize = 1024;
+
+enum Structure {
+    Map(Box<SplitOrderedList<usize>>),
+    Set(OrderedListSet<usize>),
+}
+
+impl Structure {
+    fn name(&self) -> &'static str {
+        match self {
+            Structure::Map(_) => "map",
+            Structure::Set(_) => "set",
+        }
+    }
+
+    fn insert(&self, key: usize, value: usize) -> bool {
+        match self {
+            Structure::Map(map) => map.insert(&key, value, &epoch::pin()).is_ok(),
+            Structure::Set(set) => set.insert(key).is_ok(),
+        }
+    }
+
+    fn lookup(&self, key: usize) -> Option<String> {
+        match self {
+            Structure::Map(map) => map.lookup(&key, &epoch::pin()).map(|v| v.to_string()),
+            Structure::Set(set) => set.contains(&key).then(|| key.to_string()),
+        }
+    }
+
+    fn delete(&self, key: usize) -> Option<String> {
+        match self {
+            Structure::Map(map) => map.delete(&key, &epoch::pin()).ok().map(|v| v.to_string()),
+            Structure::Set(set) => set.remove(&key).ok().map(|k| k.to_string()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Structure::Map(map) => map.range(.., &epoch::pin()).len(),
+            Structure::Set(set) => set.iter().count(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    ops: usize,
+    stress_ops: usize,
+    stress_time: Duration,
+}
+
+struct Playground {
+    structure: Option<Structure>,
+    threads: usize,
+    stats: Stats,
+}
+
+impl Playground {
+    fn structure(&self) -> Result<&Structure, String> {
+        self.structure
+            .as_ref()
+            .ok_or_else(|| "no structure, create one with `new map` or `new set`".to_string())
+    }
+
+    /// Runs a command line. Returns `Ok(false)` if the playground should exit.
+    fn run(&mut self, line: &str) -> Result<bool, String> {
+        let mut args = line.split_whitespace();
+        match args.next() {
+            None => {}
+            Some("help") => println!("{}", HELP),
+            Some("quit") | Some("exit") => return Ok(false),
+            Some("new") => {
+                self.structure = Some(match args.next() {
+                    Some("map") => Structure::Map(Box::default()),
+                    Some("set") => Structure::Set(OrderedListSet::new()),
+                    _ => return Err("usage: new <map|set>".to_string()),
+                });
+                self.stats = Stats::default();
+                println!("created a new {}", self.structure()?.name());
+            }
+            Some("insert") => {
+                let key = parse(args.next(), "key")?;
+                let value = args.next().map_or(Ok(key), |v| parse(Some(v), "value"))?;
+                let inserted = self.structure()?.insert(key, value);
+                self.stats.ops += 1;
+                println!(
+                    "{}",
+                    if inserted {
+                        "inserted"
+                    } else {
+                        "already exists"
+                    }
+                );
+            }
+            Some("lookup") => {
+                let key = parse(args.next(), "key")?;
+                let found = self.structure()?.lookup(key);
+                self.stats.ops += 1;
+                println!("{}", found.unwrap_or_else(|| "not found".to_string()));
+            }
+            Some("delete") => {
+                let key = parse(args.next(), "key")?;
+                let deleted = self.structure()?.delete(key);
+                self.stats.ops += 1;
+                match deleted {
+                    Some(v) => println!("deleted {}", v),
+                    None => println!("not found"),
+                }
+            }
+            Some("threads") => {
+                self.threads = parse(args.next(), "number of threads")?;
+                if self.threads == 0 {
+                    self.threads = 1;
+                    return Err("need at least one thread".to_string());
+                }
+                match args.next() {
+                    Some("stress") => self.stress(args.next())?,
+                    None => println!("using {} threads", self.threads),
+                    Some(_) => return Err("usage: threads N [stress DURATION]".to_string()),
+                }
+            }
+            Some("stress") => self.stress(args.next())?,
+            Some("stats") => {
+                let structure = self.structure()?;
+                println!("structure:   {}", structure.name());
+                println!("items:       {}", structure.len());
+                println!("manual ops:  {}", self.stats.ops);
+                println!("stress ops:  {}", self.stats.stress_ops);
+                if !self.stats.stress_time.is_zero() {
+                    println!(
+                        "throughput:  {:.0} ops/s",
+                        self.stats.stress_ops as f64 / self.stats.stress_time.as_secs_f64()
+                    );
+                }
+            }
+            Some(cmd) => return Err(format!("unknown command `{}`, try `help`", cmd)),
+        }
+        Ok(true)
+    }
+
+    fn stress(&mut self, duration: Option<&str>) -> Result<(), String> {
+        let duration = duration
+            .and_then(parse_duration)
+            .ok_or_else(|| "usage: stress DURATION (e.g. 10s, 500ms)".to_string())?;
+        let structure = self.structure()?;
+        let ops = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+
+        println!(
+            "stressing with {} threads for {:?}...",
+            self.threads, duration
+        );
+        let start = Instant::now();
+        thread::scope(|s| {
+            for _ in 0..self.threads {
+                s.spawn(|| {
+                    let mut rng = workload_rng();
+                    let mut local = 0;
+                    while !stop.load(Ordering::Relaxed) {
+                        let key = rng.gen_range(0..KEYS);
+                        match rng.gen_range(0..3) {
+                            0 => {
+                                let _ = structure.insert(key, key);
+                            }
+                            1 => {
+                                let _ = structure.lookup(key);
+                            }
+                            _ => {
+                                let _ = structure.delete(key);
+                            }
+                        }
+                        local += 1;
+                    }
+                    ops.fetch_add(local, Ordering::Relaxed);
+                });
+            }
+            thread::sleep(duration);
+            stop.store(true, Ordering::Relaxed);
+        });
+        let elapsed = start.elapsed();
+        let ops = ops.into_inner();
+
+        self.stats.stress_ops += ops;
+        self.stats.stress_time += elapsed;
+        println!(
+            "{} ops in {:?} ({:.0} ops/s)",
+            ops,
+            elapsed,
+            ops as f64 / elapsed.as_secs_f64()
+        );
+        Ok(())
+    }
+}
+
+fn main() -> io::Result<()> {
+    common::report_seed();
+    let mut playground = Playground {
+        structure: None,
+        threads: 1,
+        stats: Stats::default(),
+    };
+    println!("CS431 playground, type `help` for the commands");
+
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        match playground.run(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("error: {}", e),
+        }
+    }
+    Ok(())
+}
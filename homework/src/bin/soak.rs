@@ -0,0 +1,414 @@
+//! Long-running soak test of the concurrent data structures.
+//!
+//! Run e.g. `cargo run --release --bin soak -- --duration 4h --interval 1m`. Worker threads run a
+//! mixed workload against all structures. Every thread owns a disjoint set of keys and keeps a
+//! model of them, so each of its lookups has an exact expected answer even while the others
+//! write. Every interval, the workers are paused between two batches, the structures are checked
+//! against the union of the models, and a report is printed to stdout as a line of JSON. The
+//! violations are printed to stderr, and the exit code is 1 if there was any.
+
+mod common;
+
+use common::Args;
+use crossbeam_epoch as epoch;
+use cs431::lockfree::Queue;
+use cs431_homework::hazard_pointer::HAZARDS;
+use cs431_homework::{
+    collector_flush, workload_rng, HpSplitOrderedList, NonblockingMap, OrderedListSet,
+    SplitOrderedList, SplitOrderedSet,
+};
+use rand::Rng;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: soak [OPTIONS]
+
+options:
+  --duration D      how long to run, e.g. 30m, 4h (default: 1h)
+  --interval D      time between two checks and reports (default: 10s)
+  --threads N       number of worker threads (default: number of CPUs)
+  --keys N          number of keys of each thread (default: 1024)
+  --help            show this message";
+
+/// Number of operations of a worker between two chances to pause.
+const BATCH: usize = 256;
+
+/// Allocator that keeps track of the allocated memory.
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static FREES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCS.fetch_add(1, Ordering::Relaxed);
+        let _ = LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = FREES.fetch_add(1, Ordering::Relaxed);
+        let _ = LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+static VIOLATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Reports a violated invariant.
+fn violation(message: String) {
+    eprintln!("[violation] {}", message);
+    let _ = VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[derive(Debug)]
+struct Options {
+    duration: Duration,
+    interval: Duration,
+    threads: usize,
+    keys: usize,
+}
+
+impl Options {
+    fn parse(args: &mut Args) -> Result<Self, String> {
+        let mut options = Options {
+            duration: Duration::from_secs(60 * 60),
+            interval: Duration::from_secs(10),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            keys: 1024,
+        };
+        while let Some(option) = args.next_option() {
+            match option.as_str() {
+                "--duration" => options.duration = args.duration(&option)?,
+                "--interval" => options.interval = args.duration(&option)?,
+                "--threads" => options.threads = args.value(&option)?,
+                "--keys" => options.keys = args.value(&option)?,
+                _ => return Err(common::unknown(&option)),
+            }
+        }
+        if options.threads == 0 || options.keys == 0 || options.interval.is_zero() {
+            return Err("need at least one thread, one key and a positive interval".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// The structures under test.
+#[derive(Debug, Default)]
+struct Structures {
+    split_ordered_list: SplitOrderedList<usize>,
+    split_ordered_set: SplitOrderedSet,
+    hp_split_ordered_list: HpSplitOrderedList<usize>,
+    list_set: OrderedListSet<usize>,
+    queue: Queue<(usize, usize)>,
+}
+
+/// What a thread wrote to the structures, for the keys it owns.
+#[derive(Debug, Default)]
+struct Model {
+    split_ordered_list: BTreeMap<usize, usize>,
+    split_ordered_set: BTreeSet<usize>,
+    hp_split_ordered_list: BTreeMap<usize, usize>,
+    list_set: BTreeSet<usize>,
+    /// Number of items pushed to the queue.
+    pushed: usize,
+    /// Sequence number of the last item popped from each producer.
+    popped: Vec<Option<usize>>,
+}
+
+/// Checks a lookup of an owned key: nobody else writes it, so it must match the model exactly.
+fn check_lookup<T: PartialEq + std::fmt::Debug>(structure: &str, key: usize, got: T, want: T) {
+    if got != want {
+        violation(format!(
+            "{}: lookup({}) returned {:?} instead of {:?}",
+            structure, key, got, want
+        ));
+    }
+}
+
+impl Structures {
+    /// Runs a random operation on a random structure, for a key of `thread`.
+    fn step<R: Rng>(
+        &self,
+        thread: usize,
+        threads: usize,
+        keys: usize,
+        rng: &mut R,
+        model: &mut Model,
+    ) {
+        let key = thread + threads * rng.gen_range(0..keys);
+        let op = rng.gen_range(0..10);
+        let guard = &epoch::pin();
+        match rng.gen_range(0..5) {
+            0 => {
+                let list = &self.split_ordered_list;
+                let value = rng.gen::<usize>();
+                let want = model.split_ordered_list.get(&key).copied();
+                match op {
+                    0..=3 => {
+                        let inserted = list.insert(&key, value, guard).is_ok();
+                        check_lookup("split_ordered_list", key, inserted, want.is_none());
+                        let _ = model.split_ordered_list.entry(key).or_insert(value);
+                    }
+                    4 => {
+                        let old = list.upsert(&key, value, guard).ok().flatten().copied();
+                        check_lookup("split_ordered_list", key, old, want);
+                        let _ = model.split_ordered_list.insert(key, value);
+                    }
+                    5..=7 => {
+                        let deleted = list.delete(&key, guard).ok().copied();
+                        check_lookup("split_ordered_list", key, deleted, want);
+                        let _ = model.split_ordered_list.remove(&key);
+                    }
+                    _ => check_lookup(
+                        "split_ordered_list",
+                        key,
+                        list.lookup(&key, guard).copied(),
+                        want,
+                    ),
+                }
+            }
+            1 => {
+                let set = &self.split_ordered_set;
+                let want = model.split_ordered_set.contains(&key);
+                match op {
+                    0..=3 => {
+                        check_lookup("split_ordered_set", key, set.insert(key, guard), !want);
+                        let _ = model.split_ordered_set.insert(key);
+                    }
+                    4..=7 => {
+                        check_lookup("split_ordered_set", key, set.remove(&key, guard), want);
+                        let _ = model.split_ordered_set.remove(&key);
+                    }
+                    _ => check_lookup("split_ordered_set", key, set.contains(&key, guard), want),
+                }
+            }
+            2 => {
+                let list = &self.hp_split_ordered_list;
+                let value = rng.gen::<usize>();
+                let want = model.hp_split_ordered_list.get(&key).copied();
+                match op {
+                    0..=3 => {
+                        let inserted = list.insert(&key, value).is_ok();
+                        check_lookup("hp_split_ordered_list", key, inserted, want.is_none());
+                        let _ = model.hp_split_ordered_list.entry(key).or_insert(value);
+                    }
+                    4..=7 => {
+                        let deleted = list.delete(&key).ok();
+                        check_lookup("hp_split_ordered_list", key, deleted, want);
+                        let _ = model.hp_split_ordered_list.remove(&key);
+                    }
+                    _ => {
+                        let got = list.lookup(&key, |value| value.copied());
+                        check_lookup("hp_split_ordered_list", key, got, want);
+                    }
+                }
+            }
+            3 => {
+                let set = &self.list_set;
+                let want = model.list_set.contains(&key);
+                match op {
+                    0..=3 => {
+                        check_lookup("list_set", key, set.insert(key).is_ok(), !want);
+                        let _ = model.list_set.insert(key);
+                    }
+                    4..=7 => {
+                        check_lookup("list_set", key, set.remove(&key).is_ok(), want);
+                        let _ = model.list_set.remove(&key);
+                    }
+                    _ => check_lookup("list_set", key, set.contains(&key), want),
+                }
+            }
+            _ => {
+                if op < 5 {
+                    self.queue.push((thread, model.pushed), guard);
+                    model.pushed += 1;
+                } else if let Some((producer, seq)) = self.queue.try_pop(guard) {
+                    // the items of a producer are popped in order
+                    let last = &mut model.popped[producer];
+                    if last.map_or(false, |last| seq <= last) {
+                        violation(format!(
+                            "queue: popped {} of thread {} after {}",
+                            seq,
+                            producer,
+                            last.unwrap()
+                        ));
+                    }
+                    *last = Some(seq);
+                }
+            }
+        }
+    }
+
+    /// Checks the structures against the models while no worker runs. Returns the number of items
+    /// of each structure.
+    fn check(&self, models: &[&Model]) -> Vec<(&'static str, usize)> {
+        let guard = &epoch::pin();
+        let mut items = Vec::new();
+
+        let want = models
+            .iter()
+            .flat_map(|m| m.split_ordered_list.iter().map(|(k, v)| (*k, *v)))
+            .collect::<BTreeMap<_, _>>();
+        let list = &self.split_ordered_list;
+        let got = list
+            .snapshot(guard)
+            .into_iter()
+            .map(|(k, v)| (k, *v))
+            .collect::<BTreeMap<_, _>>();
+        if got != want {
+            violation("split_ordered_list: the items differ from the model".to_string());
+        }
+        if list.len_bounds() != (want.len(), want.len()) {
+            violation(format!(
+                "split_ordered_list: len_bounds {:?} with {} items",
+                list.len_bounds(),
+                want.len()
+            ));
+        }
+        let diagnostics = list.diagnostics(guard);
+        if diagnostics.entries.values().sum::<usize>() != want.len() {
+            violation("split_ordered_list: the bucket entries don't add up".to_string());
+        }
+        items.push(("split_ordered_list", want.len()));
+
+        let want = models
+            .iter()
+            .flat_map(|m| m.split_ordered_set.iter().copied())
+            .collect::<BTreeSet<_>>();
+        let set = &self.split_ordered_set;
+        if set.iter(guard).collect::<BTreeSet<_>>() != want {
+            violation("split_ordered_set: the keys differ from the model".to_string());
+        }
+        if set.len_bounds() != (want.len(), want.len()) {
+            violation(format!(
+                "split_ordered_set: len_bounds {:?} with {} keys",
+                set.len_bounds(),
+                want.len()
+            ));
+        }
+        items.push(("split_ordered_set", want.len()));
+
+        let mut len = 0;
+        for model in models {
+            for (key, value) in &model.hp_split_ordered_list {
+                let got = self.hp_split_ordered_list.lookup(key, |v| v.copied());
+                check_lookup("hp_split_ordered_list", *key, got, Some(*value));
+            }
+            len += model.hp_split_ordered_list.len();
+        }
+        items.push(("hp_split_ordered_list", len));
+
+        let want = models
+            .iter()
+            .flat_map(|m| m.list_set.iter().copied())
+            .collect::<BTreeSet<_>>();
+        // the iterator yields the keys in order
+        if !self.list_set.iter().copied().eq(want.iter().copied()) {
+            violation("list_set: the keys differ from the model".to_string());
+        }
+        items.push(("list_set", want.len()));
+
+        items.push(("queue_pushed", models.iter().map(|m| m.pushed).sum()));
+
+        // the workers hold no shield between two batches
+        let hazards = HAZARDS.all_hazards().len();
+        if hazards != 0 {
+            violation(format!(
+                "hazard_pointer: {} hazards with no reader",
+                hazards
+            ));
+        }
+        items
+    }
+}
+
+fn main() {
+    let options = common::parse_options(USAGE, Options::parse);
+    common::report_seed();
+    eprintln!("[soak] {:?}", options);
+
+    let structures = Structures::default();
+    let models = (0..options.threads)
+        .map(|_| {
+            Mutex::new(Model {
+                popped: vec![None; options.threads],
+                ..Model::default()
+            })
+        })
+        .collect::<Vec<_>>();
+    // The workers run their batches under the read lock, and the checker takes the write lock.
+    let pause = RwLock::new(());
+    let stop = AtomicBool::new(false);
+    let ops = AtomicUsize::new(0);
+
+    let start = Instant::now();
+    thread::scope(|s| {
+        for (t, model) in models.iter().enumerate() {
+            let (structures, options, pause, stop, ops) =
+                (&structures, &options, &pause, &stop, &ops);
+            let _ = s.spawn(move || {
+                let mut rng = workload_rng();
+                while !stop.load(Ordering::Relaxed) {
+                    let _running = pause.read().unwrap();
+                    let mut model = model.lock().unwrap();
+                    for _ in 0..BATCH {
+                        structures.step(t, options.threads, options.keys, &mut rng, &mut model);
+                    }
+                    let _ = ops.fetch_add(BATCH, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let mut last = (Instant::now(), 0);
+        while start.elapsed() < options.duration {
+            thread::sleep(options.interval.min(options.duration - start.elapsed()));
+
+            let paused = pause.write().unwrap();
+            let guards = models.iter().map(|m| m.lock().unwrap()).collect::<Vec<_>>();
+            let items = structures.check(&guards.iter().map(|g| &**g).collect::<Vec<_>>());
+            drop(guards);
+            // nobody is pinned, so all the garbage of the workers can be freed
+            collector_flush();
+
+            let now = Instant::now();
+            let total = ops.load(Ordering::Relaxed);
+            let mut report = format!(
+                "{{\"elapsed_s\":{:.1},\"ops\":{},\"ops_per_s\":{:.0},\"live_bytes\":{},\"allocs\":{},\"frees\":{},\"hazards\":{},\"violations\":{},\"items\":{{",
+                start.elapsed().as_secs_f64(),
+                total,
+                (total - last.1) as f64 / (now - last.0).as_secs_f64(),
+                LIVE_BYTES.load(Ordering::Relaxed),
+                ALLOCS.load(Ordering::Relaxed),
+                FREES.load(Ordering::Relaxed),
+                HAZARDS.all_hazards().len(),
+                VIOLATIONS.load(Ordering::Relaxed),
+            );
+            for (i, (name, len)) in items.iter().enumerate() {
+                let sep = if i == 0 { "" } else { "," };
+                write!(report, "{}\"{}\":{}", sep, name, len).unwrap();
+            }
+            report.push_str("}}");
+            println!("{}", report);
+            last = (now, total);
+            drop(paused);
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+
+    let violations = VIOLATIONS.load(Ordering::Relaxed);
+    eprintln!("[soak] done with {} violations", violations);
+    if violations > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,161 @@
+//! CPU-bound benchmark of `ThreadPool` and `WorkStealingPool`.
+//!
+//! Run e.g. `cargo run --release --bin thread_pool -- --threads 8 --depth 16`. Each pool runs
+//! two job mixes: flat jobs all executed by the main thread, and a binary tree of jobs where each
+//! job executes its two children. The time each pool takes is printed to stdout.
+
+use cs431_homework::hello_server::{ThreadPool, WorkStealingPool};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "usage: thread_pool [OPTIONS]
+
+options:
+  --threads N       number of threads of each pool (default: number of CPUs)
+  --depth N         depth of the tree of jobs, whose 2^(N+1)-1 jobs are also run flat (default: 14)
+  --work N          iterations of the computation of each job (default: 2000)
+  --help            show this message";
+
+#[derive(Debug)]
+struct Options {
+    threads: usize,
+    depth: u32,
+    work: u64,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            depth: 14,
+            work: 2000,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--threads" | "--depth" | "--work" => {
+                    let n = args
+                        .next()
+                        .and_then(|n| n.parse::<u64>().ok())
+                        .filter(|&n| n > 0 || arg == "--depth")
+                        .ok_or_else(|| format!("invalid {}", &arg[2..]))?;
+                    match arg.as_str() {
+                        "--threads" => options.threads = n as usize,
+                        "--depth" if n < 32 => options.depth = n as u32,
+                        "--depth" => return Err("invalid depth".to_string()),
+                        _ => options.work = n,
+                    }
+                }
+                "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// The operations of the benchmarked pools.
+trait Pool: Send + Sync + 'static {
+    fn new(size: usize) -> Self;
+    fn execute<F: FnOnce() + Send + 'static>(&self, f: F);
+    fn join(&self);
+}
+
+impl Pool for ThreadPool {
+    fn new(size: usize) -> Self {
+        ThreadPool::new(size)
+    }
+
+    fn execute<F: FnOnce() + Send + 'static>(&self, f: F) {
+        self.execute(f)
+    }
+
+    fn join(&self) {
+        self.join()
+    }
+}
+
+impl Pool for WorkStealingPool {
+    fn new(size: usize) -> Self {
+        WorkStealingPool::new(size)
+    }
+
+    fn execute<F: FnOnce() + Send + 'static>(&self, f: F) {
+        self.execute(f)
+    }
+
+    fn join(&self) {
+        self.join()
+    }
+}
+
+/// The computation of a job, added to `sink` so that it isn't optimized away.
+fn work(iterations: u64, sink: &AtomicU64) {
+    let mut x = iterations;
+    for i in 0..iterations {
+        x = x.wrapping_mul(6364136223846793005).wrapping_add(i);
+    }
+    let _ = sink.fetch_add(x, Ordering::Relaxed);
+}
+
+fn spawn_tree<P: Pool>(pool: &Arc<P>, depth: u32, iterations: u64, sink: &Arc<AtomicU64>) {
+    let (child_pool, sink) = (Arc::clone(pool), Arc::clone(sink));
+    pool.execute(move || {
+        work(iterations, &sink);
+        if depth > 0 {
+            spawn_tree(&child_pool, depth - 1, iterations, &sink);
+            spawn_tree(&child_pool, depth - 1, iterations, &sink);
+        }
+    });
+}
+
+/// Runs both job mixes on a new pool, and returns how long each took.
+fn run<P: Pool>(options: &Options) -> (Duration, Duration) {
+    let pool = Arc::new(P::new(options.threads));
+    let sink = Arc::new(AtomicU64::new(0));
+
+    let start = Instant::now();
+    for _ in 0..(1u64 << (options.depth + 1)) - 1 {
+        let (sink, iterations) = (Arc::clone(&sink), options.work);
+        pool.execute(move || work(iterations, &sink));
+    }
+    pool.join();
+    let flat = start.elapsed();
+
+    let start = Instant::now();
+    spawn_tree(&pool, options.depth, options.work, &sink);
+    pool.join();
+    (flat, start.elapsed())
+}
+
+fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    println!(
+        "{} threads, {} jobs of {} iterations per mix",
+        options.threads,
+        (1u64 << (options.depth + 1)) - 1,
+        options.work
+    );
+    let (flat, tree) = run::<ThreadPool>(&options);
+    println!("shared channel: flat {:?}, tree {:?}", flat, tree);
+    let (stealing_flat, stealing_tree) = run::<WorkStealingPool>(&options);
+    println!(
+        "work stealing:  flat {:?} ({:.1}x), tree {:?} ({:.1}x)",
+        stealing_flat,
+        flat.as_secs_f64() / stealing_flat.as_secs_f64(),
+        stealing_tree,
+        tree.as_secs_f64() / stealing_tree.as_secs_f64()
+    );
+}
@@ -0,0 +1,29 @@
+//! Teardown of the garbage collectors.
+
+use crossbeam_epoch as epoch;
+
+/// Number of times [`collector_flush`] pins and flushes.
+///
+/// Each flush tries to advance the global epoch and then runs the destructors of a few expired
+/// bags, so it takes a couple of flushes for a bag to expire and many to drain a long queue.
+const FLUSH_ROUNDS: usize = 256;
+
+/// Runs the pending deferred destructors, as far as it is safe.
+///
+/// This flushes the current thread's garbage to the global queue of the default epoch collector,
+/// advances the epoch repeatedly and destroys what has expired, and frees the pointers that the
+/// current thread retired with [`hazard_pointer::retire`](crate::hazard_pointer::retire) and
+/// nobody protects.
+///
+/// A thread that exited has already flushed its garbage, so after the other threads are joined,
+/// this frees everything. Note that `std::thread::scope` doesn't wait for the threads that are not
+/// joined explicitly to exit. Garbage of the threads that are still running, and garbage that a
+/// pinned thread may still see, is left to be collected later. Call it before the process exits,
+/// or between test cases, so that leak checkers such as Miri or Valgrind don't report garbage
+/// that is merely deferred.
+pub fn collector_flush() {
+    for _ in 0..FLUSH_ROUNDS {
+        epoch::pin().flush();
+    }
+    crate::hazard_pointer::collect();
+}
@@ -0,0 +1,299 @@
+//! Chase-Lev work-stealing deque.
+//!
+//! The owner pushes and pops at the back of its [`Worker`], and the other threads steal from the
+//! front through [`Stealer`]s. The orderings follow Lê et al., "Correct and Efficient
+//! Work-Stealing for Weak Memory Models" (PPoPP 2013). The buffer grows when it's full, and the
+//! old buffer is freed through the epoch-based garbage collector, as stealers may still be
+//! reading it.
+//!
+//! ```
+//! use cs431_homework::deque::{Steal, Worker};
+//!
+//! let worker = Worker::new();
+//! let stealer = worker.stealer();
+//! worker.push(1);
+//! worker.push(2);
+//! assert_eq!(stealer.steal(), Steal::Success(1));
+//! assert_eq!(worker.pop(), Some(2));
+//! assert_eq!(stealer.steal(), Steal::Empty);
+//! ```
+//!
+//! With the `check-loom` feature, the indices are loom's atomics, so that the races for the
+//! elements are model checked. The buffer pointer and the garbage collector are not.
+
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, AtomicIsize, Ordering};
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, AtomicIsize, Ordering};
+use std::sync::Arc;
+
+/// Capacity of a new deque.
+const MIN_CAP: usize = 16;
+
+/// Circular buffer, freed explicitly.
+struct Buffer<T> {
+    ptr: *mut MaybeUninit<T>,
+    /// A power of two.
+    cap: usize,
+}
+
+impl<T> Clone for Buffer<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Buffer<T> {}
+
+impl<T> Buffer<T> {
+    fn alloc(cap: usize) -> Self {
+        let slots = (0..cap)
+            .map(|_| MaybeUninit::uninit())
+            .collect::<Box<[MaybeUninit<T>]>>();
+        Self {
+            ptr: Box::into_raw(slots) as *mut MaybeUninit<T>,
+            cap,
+        }
+    }
+
+    /// Frees the buffer without dropping the elements.
+    unsafe fn dealloc(self) {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            self.ptr, self.cap,
+        )));
+    }
+
+    fn at(&self, index: isize) -> *mut MaybeUninit<T> {
+        unsafe { self.ptr.add(index as usize & (self.cap - 1)) }
+    }
+
+    /// Reads the slot of `index`, which may be overwritten concurrently if the read loses a race.
+    unsafe fn read(&self, index: isize) -> MaybeUninit<T> {
+        ptr::read_volatile(self.at(index))
+    }
+
+    unsafe fn write(&self, index: isize, value: T) {
+        ptr::write_volatile(self.at(index), MaybeUninit::new(value));
+    }
+}
+
+struct Inner<T> {
+    /// Index of the first element, incremented by the stealers and the last pop.
+    front: AtomicIsize,
+    /// Index past the last element, only written by the owner.
+    back: AtomicIsize,
+    buffer: Atomic<Buffer<T>>,
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let front = self.front.load(Ordering::Relaxed);
+        let back = self.back.load(Ordering::Relaxed);
+        unsafe {
+            let buffer = self.buffer.load(Ordering::Relaxed, epoch::unprotected());
+            let buffer = buffer.into_owned().into_box();
+            for i in front..back {
+                drop(buffer.read(i).assume_init());
+            }
+            buffer.dealloc();
+        }
+    }
+}
+
+/// Owner of a deque, which pushes and pops at the back. It can be sent to another thread, but
+/// not shared.
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    /// The current buffer, which only the owner replaces.
+    buffer: Cell<Buffer<T>>,
+    _marker: PhantomData<*mut ()>,
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+
+/// Handle stealing from the front of a deque, which can be cloned and shared.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+/// Result of [`Stealer::steal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Steal<T> {
+    /// The deque was empty.
+    Empty,
+    /// An element was stolen.
+    Success(T),
+    /// Lost a race for the element with another thread, so the deque may still have elements.
+    Retry,
+}
+
+impl<T> Steal<T> {
+    /// Returns the stolen element, if any.
+    pub fn success(self) -> Option<T> {
+        match self {
+            Steal::Success(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Default for Worker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Worker<T> {
+    /// Creates an empty deque.
+    pub fn new() -> Self {
+        let buffer = Buffer::alloc(MIN_CAP);
+        Self {
+            inner: Arc::new(Inner {
+                front: AtomicIsize::new(0),
+                back: AtomicIsize::new(0),
+                buffer: Atomic::new(buffer),
+            }),
+            buffer: Cell::new(buffer),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Creates a stealer of the deque.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Returns `true` if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        let back = self.inner.back.load(Ordering::Relaxed);
+        let front = self.inner.front.load(Ordering::SeqCst);
+        back <= front
+    }
+
+    /// Replaces the buffer with one of `cap` slots holding the same elements.
+    fn resize(&self, cap: usize) {
+        let front = self.inner.front.load(Ordering::Relaxed);
+        let back = self.inner.back.load(Ordering::Relaxed);
+        let old = self.buffer.get();
+        let new = Buffer::alloc(cap);
+        for i in front..back {
+            unsafe { ptr::copy_nonoverlapping(old.at(i), new.at(i), 1) };
+        }
+        self.buffer.set(new);
+        let guard = &epoch::pin();
+        let old = self
+            .inner
+            .buffer
+            .swap(Owned::new(new), Ordering::Release, guard);
+        // the stealers that read the old buffer still have copies of the elements
+        unsafe {
+            guard.defer_unchecked(move || old.into_owned().into_box().dealloc());
+        }
+        guard.flush();
+    }
+
+    /// Pushes an element at the back.
+    pub fn push(&self, value: T) {
+        let back = self.inner.back.load(Ordering::Relaxed);
+        let front = self.inner.front.load(Ordering::Acquire);
+        let mut buffer = self.buffer.get();
+        if back - front >= buffer.cap as isize {
+            self.resize(buffer.cap * 2);
+            buffer = self.buffer.get();
+        }
+        unsafe { buffer.write(back, value) };
+        fence(Ordering::Release);
+        self.inner.back.store(back + 1, Ordering::Relaxed);
+    }
+
+    /// Pops the element at the back, i.e. the one pushed last.
+    pub fn pop(&self) -> Option<T> {
+        let back = self.inner.back.load(Ordering::Relaxed) - 1;
+        self.inner.back.store(back, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        let front = self.inner.front.load(Ordering::Relaxed);
+        if front > back {
+            // empty
+            self.inner.back.store(back + 1, Ordering::Relaxed);
+            return None;
+        }
+        let value = unsafe { self.buffer.get().read(back) };
+        if front == back {
+            // the last element, which a stealer may take as well
+            let won = self
+                .inner
+                .front
+                .compare_exchange(front, front + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            self.inner.back.store(back + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        Some(unsafe { value.assume_init() })
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Returns `true` if the deque is empty.
+    pub fn is_empty(&self) -> bool {
+        let front = self.inner.front.load(Ordering::Acquire);
+        fence(Ordering::SeqCst);
+        let back = self.inner.back.load(Ordering::Acquire);
+        back <= front
+    }
+
+    /// Steals the element at the front, i.e. the oldest one.
+    pub fn steal(&self) -> Steal<T> {
+        let front = self.inner.front.load(Ordering::Acquire);
+        fence(Ordering::SeqCst);
+        let guard = &epoch::pin();
+        let back = self.inner.back.load(Ordering::Acquire);
+        if front >= back {
+            return Steal::Empty;
+        }
+        let buffer = self.inner.buffer.load(Ordering::Acquire, guard);
+        let value = unsafe { buffer.deref().read(front) };
+        if self
+            .inner
+            .front
+            .compare_exchange(front, front + 1, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            // the copy belongs to the winner, and isn't dropped as it's `MaybeUninit`
+            return Steal::Retry;
+        }
+        Steal::Success(unsafe { value.assume_init() })
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Worker<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Worker { .. }")
+    }
+}
+
+impl<T> fmt::Debug for Stealer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Stealer { .. }")
+    }
+}
@@ -2,15 +2,21 @@ use core::marker::PhantomData;
 use core::mem::ManuallyDrop;
 use core::ops::Deref;
 use crossbeam_epoch::{pin, Atomic, Guard, Owned};
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use std::cell::RefCell;
 use std::time;
 
+use crate::map::{workload_rng, WorkloadRng};
+
 pub const ELIM_SIZE: usize = 16;
 pub const ELIM_DELAY: time::Duration = time::Duration::from_millis(10);
 
 #[inline]
-pub fn get_random_elim_index() -> usize {
-    thread_rng().gen::<usize>() % ELIM_SIZE
+pub fn get_random_elim_index(size: usize) -> usize {
+    thread_local! {
+        static RNG: RefCell<WorkloadRng> = RefCell::new(workload_rng());
+    }
+    RNG.with(|rng| rng.borrow_mut().gen::<usize>() % size)
 }
 
 /// Concurrent stack types.
@@ -61,19 +67,41 @@ pub trait Stack<T>: Default {
     }
 }
 
+/// Stack whose pushes and pops that fail on the inner stack, i.e. under contention, meet on a
+/// random slot of the elimination array instead, and cancel each other out.
 #[derive(Debug)]
 pub struct ElimStack<T, S: Stack<T>> {
     pub(crate) inner: S,
-    pub(crate) slots: [Atomic<S::PushReq>; ELIM_SIZE],
+    /// A push request, tagged once a pop took it.
+    pub(crate) slots: Box<[Atomic<S::PushReq>]>,
+    /// How long a push waits in a slot for a pop.
+    pub(crate) delay: time::Duration,
     _marker: PhantomData<T>,
 }
 
 impl<T, S: Stack<T>> Default for ElimStack<T, S> {
     fn default() -> Self {
+        Self::with_elim_size(ELIM_SIZE)
+    }
+}
+
+impl<T, S: Stack<T>> ElimStack<T, S> {
+    /// Creates a stack with `size` slots of elimination. The more contended the stack, the more
+    /// slots keep the pushes and pops from contending on the slots too, but the fewer slots, the
+    /// likelier a push and a pop meet. Panics if `size` is 0.
+    pub fn with_elim_size(size: usize) -> Self {
+        assert!(size > 0, "no slot of elimination");
         Self {
             inner: Default::default(),
-            slots: Default::default(),
+            slots: (0..size).map(|_| Atomic::null()).collect(),
+            delay: ELIM_DELAY,
             _marker: PhantomData,
         }
     }
+
+    /// Sets how long a push waits in a slot of elimination for a pop, 10 ms by default.
+    pub fn elim_delay(mut self, delay: time::Duration) -> Self {
+        self.delay = delay;
+        self
+    }
 }
@@ -5,7 +5,7 @@ use core::sync::atomic::Ordering;
 use crossbeam_epoch::{Guard, Owned, Shared};
 use std::thread;
 
-use super::base::{get_random_elim_index, ElimStack, Stack, ELIM_DELAY};
+use super::base::{get_random_elim_index, ElimStack, Stack};
 
 impl<T, S: Stack<T>> Stack<T> for ElimStack<T, S> {
     type PushReq = S::PushReq;
@@ -20,11 +20,35 @@ impl<T, S: Stack<T>> Stack<T> for ElimStack<T, S> {
             Err(req) => req,
         };
 
-        let index = get_random_elim_index();
+        let index = get_random_elim_index(self.slots.len());
         let slot_ref = unsafe { self.slots.get_unchecked(index) };
         let slot = slot_ref.load(Ordering::Acquire, guard);
 
-        unimplemented!()
+        // the slot is taken by another push, or by a pop that didn't clear it yet
+        if !slot.is_null() {
+            return Err(req);
+        }
+        let req = slot_ref
+            .compare_exchange(slot, req, Ordering::Release, Ordering::Relaxed, guard)
+            .map_err(|e| e.new)?;
+
+        thread::sleep(self.delay);
+
+        // withdraws the request, unless a pop took it
+        match slot_ref.compare_exchange(
+            req,
+            Shared::null(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            Ok(_) => Err(unsafe { req.into_owned() }),
+            Err(_) => {
+                // the pop destroys the request, and the push frees the slot
+                slot_ref.store(Shared::null(), Ordering::Relaxed);
+                Ok(())
+            }
+        }
     }
 
     fn try_pop(&self, guard: &Guard) -> Result<Option<T>, ()> {
@@ -32,11 +56,29 @@ impl<T, S: Stack<T>> Stack<T> for ElimStack<T, S> {
             return Ok(result);
         }
 
-        let index = get_random_elim_index();
+        let index = get_random_elim_index(self.slots.len());
         let slot_ref = unsafe { self.slots.get_unchecked(index) };
         let slot = slot_ref.load(Ordering::Acquire, guard);
 
-        unimplemented!()
+        // no push is waiting, or a pop took it already
+        if slot.is_null() || slot.tag() != 0 {
+            return Err(());
+        }
+        slot_ref
+            .compare_exchange(
+                slot,
+                slot.with_tag(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+                guard,
+            )
+            .map_err(|_| ())?;
+
+        Ok(Some(unsafe {
+            let data = ptr::read(slot.deref().deref());
+            guard.defer_destroy(slot);
+            ManuallyDrop::into_inner(data)
+        }))
     }
 
     fn is_empty(&self, guard: &Guard) -> bool {
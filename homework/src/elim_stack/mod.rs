@@ -5,6 +5,7 @@ mod elim;
 mod treiber_stack;
 
 pub use base::Stack;
+pub use treiber_stack::TreiberStack;
 
 /// Elimination-backoff stack based on Treiber's stack.
 pub type ElimStack<T> = base::ElimStack<T, treiber_stack::TreiberStack<T>>;
@@ -13,6 +14,7 @@ pub type ElimStack<T> = base::ElimStack<T, treiber_stack::TreiberStack<T>>;
 mod test {
     use super::*;
     use std::thread::scope;
+    use std::time::Duration;
 
     #[test]
     fn push() {
@@ -31,4 +33,36 @@ mod test {
 
         assert!(stack.pop().is_none());
     }
+
+    #[test]
+    fn elim_size() {
+        const THREADS: usize = 8;
+        const COUNT: usize = 2_000;
+        let stack = ElimStack::with_elim_size(1).elim_delay(Duration::from_micros(50));
+
+        let popped = scope(|scope| {
+            let mut handles = Vec::new();
+            for t in 0..THREADS {
+                let stack = &stack;
+                handles.push(scope.spawn(move || {
+                    let mut popped = Vec::new();
+                    for i in 0..COUNT {
+                        stack.push(t * COUNT + i);
+                        popped.extend(stack.pop());
+                    }
+                    popped
+                }));
+            }
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        // each value is popped once, through the stack or the elimination
+        let mut popped = popped;
+        popped.sort_unstable();
+        assert_eq!(popped, (0..THREADS * COUNT).collect::<Vec<_>>());
+        assert!(stack.pop().is_none());
+    }
 }
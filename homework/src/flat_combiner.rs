@@ -0,0 +1,183 @@
+//! Flat combining, a lock serving the operations of the waiting threads on their behalf.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use crossbeam_utils::Backoff;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Number of times a combiner takes the published requests before releasing the lock, if there
+/// are still new ones.
+const COMBINE_ROUNDS: usize = 4;
+
+/// Operation published by a thread, which lives on the stack of the thread until it's done.
+struct Request<T> {
+    /// Runs the closure `op` points to.
+    call: unsafe fn(*mut (), &mut T),
+    op: *mut (),
+    next: *mut Request<T>,
+    done: AtomicBool,
+}
+
+/// Calls the closure of type `C` that `op` points to.
+unsafe fn call<T, C: FnMut(&mut T)>(op: *mut (), data: &mut T) {
+    (*(op as *mut C))(data)
+}
+
+/// Returns the caller of closures of the type of `op`.
+fn caller<T, C: FnMut(&mut T)>(_: &C) -> unsafe fn(*mut (), &mut T) {
+    call::<T, C>
+}
+
+/// Wrapper of a sequential data structure, serving the operations of concurrent threads with flat
+/// combining, after Hendler et al., "Flat Combining and the Synchronization-Parallelism Tradeoff"
+/// (SPAA 2010).
+///
+/// A thread publishes its operation, and then either waits for it to be done, or takes the lock
+/// and becomes the combiner, which applies all the published operations in a row. Under high
+/// contention, the data structure then stays in the cache of a single thread, and the lock is
+/// taken once per batch rather than once per operation, at the cost of running the operations of
+/// the others.
+///
+/// An operation that panics is resumed on the thread that published it, like with a
+/// [`Mutex`](std::sync::Mutex), but the data structure isn't poisoned.
+///
+/// ```
+/// use cs431_homework::FlatCombiner;
+/// use std::collections::BTreeSet;
+///
+/// let set = FlatCombiner::new(BTreeSet::new());
+/// assert!(set.apply(|set| set.insert(42)));
+/// assert!(set.apply(|set| set.contains(&42)));
+/// ```
+pub struct FlatCombiner<T> {
+    /// Whether a thread is combining.
+    lock: AtomicBool,
+    /// The published requests not taken by a combiner yet, the last one first.
+    pending: AtomicPtr<Request<T>>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Send for FlatCombiner<T> {}
+unsafe impl<T: Send> Sync for FlatCombiner<T> {}
+
+impl<T: Default> Default for FlatCombiner<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> FlatCombiner<T> {
+    /// Wraps the data structure.
+    pub fn new(data: T) -> Self {
+        Self {
+            lock: AtomicBool::new(false),
+            pending: AtomicPtr::new(ptr::null_mut()),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Returns the data structure.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Returns a mutable reference to the data structure, which no operation can be running on.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Applies `op` to the data structure, possibly on another thread, and returns its result.
+    pub fn apply<R, F>(&self, op: F) -> R
+    where
+        F: FnOnce(&mut T) -> R + Send,
+        R: Send,
+    {
+        let mut op = Some(op);
+        let mut result = None;
+        let mut run = |data: &mut T| {
+            let op = op.take().unwrap();
+            result = Some(panic::catch_unwind(AssertUnwindSafe(|| op(data))));
+        };
+        let mut request = Request {
+            call: caller(&run),
+            op: &mut run as *mut _ as *mut (),
+            next: ptr::null_mut(),
+            done: AtomicBool::new(false),
+        };
+        self.publish(&mut request);
+
+        let backoff = Backoff::new();
+        while !request.done.load(Ordering::Acquire) {
+            if !self.lock.load(Ordering::Relaxed) && !self.lock.swap(true, Ordering::Acquire) {
+                // takes the request of this thread, published before
+                unsafe { self.combine() };
+                self.lock.store(false, Ordering::Release);
+                debug_assert!(request.done.load(Ordering::Relaxed));
+                break;
+            }
+            backoff.snooze();
+        }
+
+        match result.unwrap() {
+            Ok(result) => result,
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    fn publish(&self, request: &mut Request<T>) {
+        let request = request as *mut Request<T>;
+        let mut head = self.pending.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*request).next = head };
+            match self.pending.compare_exchange_weak(
+                head,
+                request,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Applies the published requests, in the order they were published.
+    ///
+    /// # Safety
+    ///
+    /// The current thread must hold the lock.
+    unsafe fn combine(&self) {
+        let data = &mut *self.data.get();
+        for _ in 0..COMBINE_ROUNDS {
+            let mut request = self.pending.swap(ptr::null_mut(), Ordering::Acquire);
+            if request.is_null() {
+                return;
+            }
+
+            // reverses the published requests, the first ones first
+            let mut first = ptr::null_mut();
+            while !request.is_null() {
+                let next = (*request).next;
+                (*request).next = first;
+                first = request;
+                request = next;
+            }
+
+            while !first.is_null() {
+                // the request is freed once it's done
+                let next = (*first).next;
+                ((*first).call)((*first).op, data);
+                (*first).done.store(true, Ordering::Release);
+                first = next;
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for FlatCombiner<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FlatCombiner").finish_non_exhaustive()
+    }
+}
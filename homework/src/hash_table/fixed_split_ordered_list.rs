@@ -0,0 +1,251 @@
+//! Split-ordered linked list with a fixed number of buckets.
+
+use core::cmp;
+use core::fmt;
+use core::mem;
+use core::sync::atomic::Ordering;
+use crossbeam_epoch::{self as epoch, unprotected, Atomic, Guard, Owned};
+use cs431::lockfree::list::{Cursor, List, Node};
+
+use super::split_ordered_list::{Iter, Slot, SplitOrderedKey, SplitOrderedList, Value};
+use crate::map::NonblockingMap;
+
+/// Lock-free map from `usize` to `V` with `BUCKETS` buckets, which must be a power of two.
+///
+/// It is the list of [`SplitOrderedList`] with a plain array of bucket pointers instead of the
+/// `GrowableArray`: the buckets are initialized on their first access, but the table never grows
+/// or shrinks. So it needs no counters and no bucket state beyond null or initialized, which makes
+/// it a baseline to measure the cost of resizing against, and a fit for small tables whose size is
+/// known in advance. Chains get long once there are many more items than buckets.
+///
+/// Values can be replaced with `upsert`, like in [`SplitOrderedList`].
+///
+/// A number of buckets that is not a power of two fails the build:
+///
+/// ```compile_fail
+/// let list = cs431_homework::FixedSplitOrderedList::<usize, 3>::new();
+/// ```
+pub struct FixedSplitOrderedList<V, const BUCKETS: usize> {
+    /// Lock-free list sorted by recursive-split order. Sentinel nodes have null values.
+    list: List<SplitOrderedKey, Value<V>>,
+    /// pointers to the sentinels of the buckets, null until initialized
+    buckets: [Atomic<Node<SplitOrderedKey, Value<V>>>; BUCKETS],
+}
+
+impl<V, const BUCKETS: usize> Default for FixedSplitOrderedList<V, BUCKETS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, const BUCKETS: usize> FixedSplitOrderedList<V, BUCKETS> {
+    /// Mask of the bucket of a key. Fails the build if `BUCKETS` is not a power of two.
+    const MASK: usize = {
+        assert!(
+            BUCKETS.is_power_of_two(),
+            "the number of buckets must be a power of two"
+        );
+        BUCKETS - 1
+    };
+
+    /// Creates a new split ordered list.
+    pub fn new() -> Self {
+        // check `BUCKETS` even if the list is never accessed
+        let _ = Self::MASK;
+        let list = List::new();
+        let guard = unsafe { unprotected() };
+        // 0 dummy node
+        list.harris_herlihy_shavit_insert(
+            SplitOrderedList::<V>::get_so_bucket_key(0),
+            Value::null(),
+            guard,
+        );
+        let buckets = [(); BUCKETS].map(|_| Atomic::null());
+        buckets[0].store(list.head(guard).curr(), Ordering::Relaxed);
+        Self { list, buckets }
+    }
+
+    /// Returns the number of buckets.
+    pub const fn buckets(&self) -> usize {
+        BUCKETS
+    }
+
+    #[inline]
+    fn get_parent_bucket(bucket: usize) -> usize {
+        bucket ^ (1 << (mem::size_of::<usize>() * 8 - bucket.leading_zeros() as usize - 1))
+    }
+
+    /// Returns the sentinel of the bucket, initializing it and its ancestors if necessary.
+    fn get_bucket<'g>(
+        &'g self,
+        bucket: usize,
+        guard: &'g Guard,
+    ) -> &'g Node<SplitOrderedKey, Value<V>> {
+        // bucket 0 is always initialized, so the recursion terminates
+        if let Some(sentinel) =
+            unsafe { self.buckets[bucket].load(Ordering::Acquire, guard).as_ref() }
+        {
+            return sentinel;
+        }
+        let parent = self.get_bucket(Self::get_parent_bucket(bucket), guard);
+        let bucket_key = SplitOrderedList::<V>::get_so_bucket_key(bucket);
+        let mut node = Owned::new(Node::new(bucket_key, Value::null()));
+        loop {
+            // sentinels are never deleted, so the cursor never starts from a marked pointer
+            let mut cursor =
+                Cursor::new(parent.next(), parent.next().load(Ordering::Acquire, guard));
+            let found = some_or!(
+                cursor.find_harris_michael(&bucket_key, guard).ok(),
+                continue
+            );
+            // If found, another thread inserted the sentinel and may not have published it yet.
+            if !found {
+                if let Err(n) = cursor.insert(node, guard) {
+                    node = n;
+                    continue;
+                }
+            }
+            // Every initializer publishes the same sentinel.
+            self.buckets[bucket].store(cursor.curr(), Ordering::Release);
+            return unsafe { cursor.curr().deref() };
+        }
+    }
+
+    /// Returns the sentinel of the closest initialized ancestor of the bucket of `key`, or the
+    /// bucket itself. It never initializes a bucket, so it doesn't allocate.
+    fn read_bucket<'g>(
+        &'g self,
+        key: usize,
+        guard: &'g Guard,
+    ) -> &'g Node<SplitOrderedKey, Value<V>> {
+        let mut bucket = key & Self::MASK;
+        loop {
+            let sentinel = self.buckets[bucket].load(Ordering::Acquire, guard);
+            if let Some(sentinel) = unsafe { sentinel.as_ref() } {
+                return sentinel;
+            }
+            bucket = Self::get_parent_bucket(bucket);
+        }
+    }
+
+    /// Moves a cursor from the bucket of `key` to the position of the key. Returns
+    /// `(found, cursor)`.
+    fn find<'g>(
+        &'g self,
+        key: usize,
+        guard: &'g Guard,
+    ) -> (bool, Cursor<'g, SplitOrderedKey, Value<V>>) {
+        let sentinel = self.get_bucket(key & Self::MASK, guard);
+        let so_key = SplitOrderedList::<V>::get_so_data_key(key);
+        loop {
+            let mut cursor = Cursor::new(
+                sentinel.next(),
+                sentinel.next().load(Ordering::Acquire, guard),
+            );
+            if let Ok(found) = cursor.find_harris_michael(&so_key, guard) {
+                return (found, cursor);
+            }
+        }
+    }
+
+    /// Returns an iterator over the items in split order. It is weakly consistent, like
+    /// [`SplitOrderedList::iter`].
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, V> {
+        Iter::new(&self.list, guard)
+    }
+}
+
+impl<V: fmt::Debug, const BUCKETS: usize> fmt::Debug for FixedSplitOrderedList<V, BUCKETS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter(&epoch::pin())).finish()
+    }
+}
+
+impl<V, const BUCKETS: usize> NonblockingMap<usize, V> for FixedSplitOrderedList<V, BUCKETS> {
+    /// Doesn't allocate: it neither initializes buckets nor helps unlinking deleted nodes.
+    fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
+        let so_key = SplitOrderedList::<V>::get_so_data_key(*key);
+        let mut curr = self
+            .read_bucket(*key, guard)
+            .next()
+            .load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.with_tag(0).as_ref() } {
+            let next = node.next().load(Ordering::Acquire, guard);
+            match node.key().cmp(&so_key) {
+                cmp::Ordering::Less => {}
+                // a deleted node may be followed by a new node of the same key
+                cmp::Ordering::Equal if next.tag() != 0 => {}
+                cmp::Ordering::Equal => return node.value().get(guard),
+                cmp::Ordering::Greater => return None,
+            }
+            curr = next;
+        }
+        None
+    }
+
+    fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
+        let mut node = Owned::new(Node::new(
+            SplitOrderedList::<V>::get_so_data_key(*key),
+            Value::new(value),
+        ));
+        loop {
+            let (found, mut cursor) = self.find(*key, guard);
+            if found {
+                return Err(node.into_box().into_value().into_inner());
+            }
+            match cursor.insert(node, guard) {
+                Ok(_) => return Ok(()),
+                Err(n) => node = n,
+            }
+        }
+    }
+
+    fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, ()> {
+        loop {
+            let (found, cursor) = self.find(*key, guard);
+            if !found {
+                return Err(());
+            }
+            if let Ok(v) = cursor.delete(guard) {
+                return Ok(v.freeze(guard));
+            }
+        }
+    }
+
+    /// Replaces the value of an existing key in place.
+    fn upsert<'a>(&'a self, key: &usize, value: V, guard: &'a Guard) -> Result<Option<&'a V>, V> {
+        let mut new = Owned::new(Slot(value));
+        loop {
+            let (found, mut cursor) = self.find(*key, guard);
+            if !found {
+                let node = Owned::new(Node::new(
+                    SplitOrderedList::<V>::get_so_data_key(*key),
+                    Value::from_owned(new),
+                ));
+                match cursor.insert(node, guard) {
+                    Ok(_) => return Ok(None),
+                    Err(node) => {
+                        let value = node.into_box().into_value();
+                        new = unsafe {
+                            value
+                                .0
+                                .swap(epoch::Shared::null(), Ordering::Relaxed, guard)
+                                .into_owned()
+                        };
+                        continue;
+                    }
+                }
+            }
+            let value = unsafe { cursor.curr().deref() }.value();
+            let current = value.0.load(Ordering::Acquire, guard);
+            // a deleted node
+            if current.tag() != 0 {
+                continue;
+            }
+            match value.replace(current, new, guard) {
+                Ok(old) => return Ok(Some(old)),
+                Err(n) => new = n,
+            }
+        }
+    }
+}
@@ -3,10 +3,13 @@
 use core::fmt::Debug;
 use core::marker::PhantomData;
 use core::mem;
-use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicUsize, Ordering};
-use crossbeam_epoch::{self as epoch, unprotected, Atomic, Guard, Owned, Pointer, Shared};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crossbeam_epoch::{
+    self as epoch, unprotected, Atomic, Guard, Owned, Pointable, Pointer, Shared,
+};
 use epoch::CompareExchangeError;
+use std::alloc::{self, Layout};
 
 /// Growable array of `Atomic<T>`.
 ///
@@ -17,7 +20,7 @@ use epoch::CompareExchangeError;
 ///
 /// # Example run
 ///
-/// Suppose `SEGMENT_LOGSIZE = 3` (segment size 8).
+/// Suppose `BITS = 3` (segment size 8).
 ///
 /// When a new `GrowableArray` is created, `root` is initialized with `Atomic::null()`.
 ///
@@ -130,51 +133,114 @@ use epoch::CompareExchangeError;
 /// Instead, it should be handled by the container that the elements actually belong to. For
 /// example in `SplitOrderedList`, destruction of elements are handled by `List`.
 ///
+/// # Usage
+///
+/// The slots are plain `Atomic<T>`s that live as long as the array, so the array itself takes no
+/// ownership of the elements:
+///
+/// ```
+/// use core::sync::atomic::Ordering;
+/// use crossbeam_epoch::{self as epoch, Owned};
+/// use cs431_homework::GrowableArray;
+///
+/// let array = GrowableArray::<String>::new();
+/// let guard = &epoch::pin();
+///
+/// // looking up a missing slot doesn't grow the array
+/// assert!(array.get_if_exists(1 << 20, guard).is_none());
+///
+/// array
+///     .get(1 << 20, guard)
+///     .store(Owned::new("owl".to_string()), Ordering::Release);
+/// let slot = array.get_if_exists(1 << 20, guard).unwrap();
+/// let owl = slot.load(Ordering::Acquire, guard);
+/// assert_eq!(unsafe { owl.deref() }, "owl");
+///
+/// // the elements are the user's to free
+/// slot.store(epoch::Shared::null(), Ordering::Relaxed);
+/// drop(unsafe { owl.into_owned() });
+/// ```
+///
+/// # Fan-out
+///
+/// A segment has `2^BITS` slots, 1024 by default. Fewer bits make smaller segments, so a sparse
+/// array wastes less memory, but a deeper tree, so each access follows more pointers. `BITS` must
+/// be in `2..=20`, which is checked at compile time:
+///
+/// ```compile_fail
+/// let array = cs431_homework::GrowableArray::<usize, 1>::new();
+/// ```
+///
+/// # Trimming
+///
+/// Segments are never freed while the array is alive, even if all their slots are null again.
+/// [`trim`](GrowableArray::trim) frees them, and lowers the tree if the upper indices are no longer
+/// used, so that a long-lived array returns the memory of the indices it no longer uses.
 #[derive(Debug)]
-pub struct GrowableArray<T> {
-    root: Atomic<Segment>,
+pub struct GrowableArray<T, const BITS: usize = 10> {
+    root: Atomic<Segment<BITS>>,
+    /// whether a `trim` is running
+    trimming: AtomicBool,
     _marker: PhantomData<T>,
 }
 
-const SEGMENT_LOGSIZE: usize = 10;
+/// Alignment of the segments. The height of a segment is kept in the tag bits of the pointers to
+/// it, so it bounds the height of the tree.
+const SEGMENT_ALIGN: usize = 64;
 
-struct Segment {
+/// Segment of `2^BITS` slots.
+///
+/// It is unsized as its length can't be written as an array type, so it is allocated through its
+/// own [`Pointable`] implementation.
+#[repr(transparent)]
+struct Segment<const BITS: usize> {
     /// `AtomicUsize` here means `Atomic<T>` or `Atomic<Segment>`.
-    inner: [AtomicUsize; 1 << SEGMENT_LOGSIZE],
+    inner: [AtomicUsize],
 }
 
-impl Segment {
-    fn new() -> Self {
-        Self {
-            inner: unsafe { mem::zeroed() },
-        }
+impl<const BITS: usize> Segment<BITS> {
+    fn layout() -> Layout {
+        Layout::from_size_align(mem::size_of::<AtomicUsize>() << BITS, SEGMENT_ALIGN).unwrap()
     }
 }
 
-impl Deref for Segment {
-    type Target = [AtomicUsize; 1 << SEGMENT_LOGSIZE];
+impl<const BITS: usize> Pointable for Segment<BITS> {
+    const ALIGN: usize = SEGMENT_ALIGN;
+
+    type Init = ();
 
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+    /// Allocates a segment of null slots.
+    unsafe fn init(_: ()) -> usize {
+        let ptr = alloc::alloc_zeroed(Self::layout());
+        if ptr.is_null() {
+            alloc::handle_alloc_error(Self::layout());
+        }
+        ptr as usize
     }
-}
 
-impl DerefMut for Segment {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner
+    unsafe fn deref<'a>(ptr: usize) -> &'a Self {
+        &*(ptr::slice_from_raw_parts(ptr as *const AtomicUsize, 1 << BITS) as *const Self)
+    }
+
+    unsafe fn deref_mut<'a>(ptr: usize) -> &'a mut Self {
+        &mut *(ptr::slice_from_raw_parts_mut(ptr as *mut AtomicUsize, 1 << BITS) as *mut Self)
+    }
+
+    unsafe fn drop(ptr: usize) {
+        alloc::dealloc(ptr as *mut u8, Self::layout());
     }
 }
 
-impl Debug for Segment {
+impl<const BITS: usize> Debug for Segment<BITS> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Segment")
     }
 }
 
-impl<T> Drop for GrowableArray<T> {
+impl<T, const BITS: usize> Drop for GrowableArray<T, BITS> {
     /// Deallocate segments, but not the individual elements.
     fn drop(&mut self) {
-        fn drop_segment(root_atomic: &Atomic<Segment>, guard: &Guard) {
+        fn drop_segment<const BITS: usize>(root_atomic: &Atomic<Segment<BITS>>, guard: &Guard) {
             let root = root_atomic.load(Ordering::Acquire, guard);
             let height = root.tag();
             if height == 0 {
@@ -187,7 +253,7 @@ impl<T> Drop for GrowableArray<T> {
                 return;
             }
             for index in &root.inner {
-                let segment = unsafe { &*((index) as *const _ as *const Atomic<Segment>) };
+                let segment = unsafe { &*((index) as *const _ as *const Atomic<Segment<BITS>>) };
                 drop_segment(segment, guard);
             }
         }
@@ -197,59 +263,309 @@ impl<T> Drop for GrowableArray<T> {
     }
 }
 
-impl<T> Default for GrowableArray<T> {
+impl<T, const BITS: usize> Default for GrowableArray<T, BITS> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<T> GrowableArray<T> {
+impl<T, const BITS: usize> GrowableArray<T, BITS> {
+    /// Maximum height of the tree, so that it covers all `usize` indices. Fails the build if `BITS`
+    /// is out of range.
+    const MAX_HEIGHT: usize = {
+        assert!(
+            BITS >= 2 && BITS <= 20,
+            "the number of bits of a segment must be in 2..=20"
+        );
+        let height = (usize::BITS as usize + BITS - 1) / BITS;
+        assert!(height < SEGMENT_ALIGN);
+        height
+    };
+
     /// Create a new growable array.
     pub fn new() -> Self {
+        // check `BITS` even if the array is never accessed
+        let _ = Self::MAX_HEIGHT;
         Self {
             root: Atomic::null(),
+            trimming: AtomicBool::new(false),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the reference to the `Atomic` pointer at `index` if its segment exists, and `None`
+    /// otherwise. Unlike [`get`](GrowableArray::get), it never allocates, so looking up sparse
+    /// indices doesn't grow the array.
+    ///
+    /// A slot that exists may still be null.
+    pub fn get_if_exists(&self, mut index: usize, guard: &Guard) -> Option<&Atomic<T>> {
+        let mut segment = self.root.load(Ordering::Acquire, guard);
+        if segment.tag() == 0
+            || index
+                .checked_shr((segment.tag() * BITS) as u32)
+                .unwrap_or(0)
+                != 0
+        {
+            return None;
+        }
+
+        loop {
+            let height = segment.tag();
+            let shift = (height - 1) * BITS;
+            let slot = unsafe { &segment.deref().inner[index >> shift] };
+            index &= (1 << shift) - 1;
+            if height == 1 {
+                return Some(unsafe { &*(slot as *const _ as *const Atomic<T>) });
+            }
+
+            let slot = unsafe { &*(slot as *const _ as *const Atomic<Segment<BITS>>) };
+            segment = slot.load(Ordering::Acquire, guard);
+            if segment.is_null() {
+                return None;
+            }
+        }
+    }
+
+    /// Returns an iterator over the indices and the pointers of the non-null elements, in the
+    /// order of the indices. It skips the missing segments, so iterating over a sparse array is
+    /// cheap. Null pointers are skipped even if they are tagged.
+    ///
+    /// The iteration is weakly consistent: an element stored or removed concurrently may or may
+    /// not be yielded, as may the elements of the indices that the array grows to cover during
+    /// the iteration.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> impl Iterator<Item = (usize, Shared<'g, T>)> + 'g
+    where
+        T: 'g,
+    {
+        let root = self.root.load(Ordering::Acquire, guard);
+        Iter {
+            stack: if root.tag() == 0 {
+                Vec::new()
+            } else {
+                vec![(root, 0, 0)]
+            },
+            guard,
             _marker: PhantomData,
         }
     }
 
-    // unsafe { &*(root_atomic as *const _ as *const Atomic<T>) }
+    /// Stores `new` at `index` if the pointer there is `current`, allocating the segments of
+    /// `index` if necessary. Like [`Atomic::compare_exchange`] with `AcqRel` ordering on success
+    /// and `Acquire` on failure, it returns the stored pointer, or the current one and `new`
+    /// back.
+    pub fn compare_exchange<'g, P: Pointer<T>>(
+        &self,
+        index: usize,
+        current: Shared<'_, T>,
+        new: P,
+        guard: &'g Guard,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        self.get(index, guard).compare_exchange(
+            current,
+            new,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            guard,
+        )
+    }
+
+    /// Stores `new` at `index` unless another pointer was stored there. An empty slot holds an
+    /// untagged null pointer, so a tagged null pointer counts as stored. See
+    /// [`compare_exchange`](GrowableArray::compare_exchange).
+    pub fn store_if_null<'g, P: Pointer<T>>(
+        &self,
+        index: usize,
+        new: P,
+        guard: &'g Guard,
+    ) -> Result<Shared<'g, T>, CompareExchangeError<'g, T, P>> {
+        self.compare_exchange(index, Shared::null(), new, guard)
+    }
+
+    /// Detaches the segments whose slots are all null, and removes the root levels that only lead
+    /// to the first segment of the level below. The detached segments are destroyed through
+    /// `guard`. Returns the number of detached segments. Does nothing if another `trim` is
+    /// running.
+    ///
+    /// An empty slot holds an untagged null pointer, as in
+    /// [`store_if_null`](GrowableArray::store_if_null).
+    ///
+    /// Concurrent reads and clears are fine, but a pointer must not be stored in an empty slot
+    /// concurrently with a `trim`: a reference returned by [`get`](GrowableArray::get) before the
+    /// `trim` may point into a detached segment, so that the store is lost.
+    pub fn trim(&self, guard: &Guard) -> usize {
+        if self.trimming.swap(true, Ordering::Acquire) {
+            return 0;
+        }
+        // Only this thread detaches segments, so a segment that it reaches is not destroyed
+        // under it.
+        let mut detached = 0;
+        loop {
+            let root = self.root.load(Ordering::Acquire, guard);
+            let height = root.tag();
+            if height == 0 {
+                break;
+            }
+            let new = if Self::trim_segment(root, &mut detached, guard) {
+                Shared::null()
+            } else if height > 1
+                && unsafe { root.deref() }.inner[1..]
+                    .iter()
+                    .all(|slot| slot.load(Ordering::Acquire) == 0)
+            {
+                // the first child, whose tag already is its height
+                let first = unsafe { &root.deref().inner[0] };
+                let first = unsafe { &*(first as *const _ as *const Atomic<Segment<BITS>>) };
+                first.load(Ordering::Acquire, guard)
+            } else {
+                break;
+            };
+            // A failure means that the tree grew, so it is trimmed again.
+            if self
+                .root
+                .compare_exchange(root, new, Ordering::AcqRel, Ordering::Acquire, guard)
+                .is_ok()
+            {
+                // the remaining children are not destroyed with it
+                unsafe { guard.defer_destroy(root) };
+                detached += 1;
+            }
+        }
+        self.trimming.store(false, Ordering::Release);
+        detached
+    }
+
+    /// Detaches the children of `segment` whose slots are all null, recursively, and counts them
+    /// in `detached`. Returns whether all slots of `segment` are null now.
+    fn trim_segment(
+        segment: Shared<'_, Segment<BITS>>,
+        detached: &mut usize,
+        guard: &Guard,
+    ) -> bool {
+        let height = segment.tag();
+        let mut empty = true;
+        for slot in unsafe { &segment.deref().inner } {
+            if height == 1 {
+                empty &= slot.load(Ordering::Acquire) == 0;
+                continue;
+            }
+            let slot = unsafe { &*(slot as *const _ as *const Atomic<Segment<BITS>>) };
+            let child = slot.load(Ordering::Acquire, guard);
+            if child.is_null() {
+                continue;
+            }
+            if Self::trim_segment(child, detached, guard)
+                && slot
+                    .compare_exchange(
+                        child,
+                        Shared::null(),
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        guard,
+                    )
+                    .is_ok()
+            {
+                unsafe { guard.defer_destroy(child) };
+                *detached += 1;
+            } else {
+                empty = false;
+            }
+        }
+        empty
+    }
+
     /// Returns the reference to the `Atomic` pointer at `index`. Allocates new segments if
     /// necessary.
+    ///
+    /// The elements are always stored in leaf segments (height 1), so the tag bits of an element
+    /// pointer are free for the user.
     pub fn get(&self, mut index: usize, guard: &Guard) -> &Atomic<T> {
-        let guard = &epoch::pin();
-        let mut root_atomic = &self.root;
+        let mut root = self.root.load(Ordering::Acquire, guard);
+        // grow the tree until it covers `index`
+        while root.tag() == 0 || index.checked_shr((root.tag() * BITS) as u32).unwrap_or(0) != 0 {
+            let segment = Owned::<Segment<BITS>>::init(()).with_tag(root.tag() + 1);
+            segment.inner[0].store(root.into_usize(), Ordering::Relaxed);
+            root = match self.root.compare_exchange(
+                root,
+                segment,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(new) => new,
+                Err(CompareExchangeError { current: c, .. }) => c,
+            }
+        }
+
+        let mut segment = root;
         loop {
-            let mut root = root_atomic.load(Ordering::Acquire, guard);
-            while index >= (1 << (root.tag() * SEGMENT_LOGSIZE)) {
-                let segment = Owned::new(Segment::new()).with_tag(root.tag() + 1);
-                unsafe {
-                    let atomic_index =
-                        &*((&segment.inner[0]) as *const _ as *const Atomic<Segment>);
-                    atomic_index.store(root, Ordering::Release);
-                }
-                root = match root_atomic.compare_exchange(
-                    root,
-                    segment,
-                    Ordering::Release,
-                    Ordering::Relaxed,
+            let height = segment.tag();
+            let shift = (height - 1) * BITS;
+            let slot = unsafe { &segment.deref().inner[index >> shift] };
+            index &= (1 << shift) - 1;
+            if height == 1 {
+                return unsafe { &*(slot as *const _ as *const Atomic<T>) };
+            }
+
+            let slot = unsafe { &*(slot as *const _ as *const Atomic<Segment<BITS>>) };
+            let mut child = slot.load(Ordering::Acquire, guard);
+            if child.is_null() {
+                let new = Owned::<Segment<BITS>>::init(()).with_tag(height - 1);
+                child = match slot.compare_exchange(
+                    Shared::null(),
+                    new,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
                     guard,
                 ) {
                     Ok(new) => new,
                     Err(CompareExchangeError { current: c, .. }) => c,
-                }
+                };
             }
-            if root.tag() == 0 && index == 0 {
-                return unsafe { &*(root_atomic as *const _ as *const Atomic<T>) };
+            segment = child;
+        }
+    }
+}
+
+/// Iterator over the non-null elements of a [`GrowableArray`].
+#[derive(Debug)]
+struct Iter<'g, T, const BITS: usize> {
+    /// Segments on the path to the next slot, with the index of their first slot and the position
+    /// of their next slot.
+    stack: Vec<(Shared<'g, Segment<BITS>>, usize, usize)>,
+    guard: &'g Guard,
+    _marker: PhantomData<T>,
+}
+
+impl<'g, T: 'g, const BITS: usize> Iterator for Iter<'g, T, BITS> {
+    type Item = (usize, Shared<'g, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(top) = self.stack.last_mut() {
+            let (segment, base, pos) = *top;
+            if pos == 1 << BITS {
+                let _ = self.stack.pop();
+                continue;
             }
-            let mut index_height = root.tag();
+            top.2 += 1;
 
-            // root is not null
-            unsafe {
-                let atomic =
-                    &(*root.as_raw()).inner[index >> ((index_height - 1) * SEGMENT_LOGSIZE)];
-                root_atomic = &*(atomic as *const _ as *const Atomic<Segment>);
+            let height = segment.tag();
+            let slot = unsafe { &segment.deref().inner[pos] };
+            // The slots beyond `usize::MAX` in the root are never used, so wrapping is fine.
+            let index = base | pos.wrapping_shl(((height - 1) * BITS) as u32);
+            if height == 1 {
+                let slot = unsafe { &*(slot as *const _ as *const Atomic<T>) };
+                let elem = slot.load(Ordering::Acquire, self.guard);
+                if !elem.is_null() {
+                    return Some((index, elem));
+                }
+            } else {
+                let slot = unsafe { &*(slot as *const _ as *const Atomic<Segment<BITS>>) };
+                let child = slot.load(Ordering::Acquire, self.guard);
+                if !child.is_null() {
+                    self.stack.push((child, index, 0));
+                }
             }
-            index %= 1 << ((index_height - 1) * SEGMENT_LOGSIZE);
         }
+        None
     }
 }
@@ -0,0 +1,457 @@
+//! Split-ordered linked list whose nodes are reclaimed with hazard pointers.
+
+use core::cmp;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use crossbeam_epoch::Guard;
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use crate::hazard_pointer::{retire, Shield};
+use crate::map::ConcurrentMap;
+
+/// Lock-free map from `usize` to `V`, like [`SplitOrderedList`], but whose nodes are reclaimed with
+/// the crate's [hazard pointers](crate::hazard_pointer) instead of crossbeam-epoch.
+///
+/// This is the plain algorithm of the paper on top of Michael's hazard pointer version of the
+/// Harris-Michael list: the number of buckets only grows, and values are immutable. Since a
+/// reference to a value is only valid while its node is protected, the map implements
+/// [`ConcurrentMap`] rather than `NonblockingMap`, and the `Guard`s it is passed are ignored.
+///
+/// [`SplitOrderedList`]: super::SplitOrderedList
+#[derive(Debug)]
+pub struct HpSplitOrderedList<V> {
+    /// Segments of the array of pointers to the buckets. Segment `k` holds `2^k` buckets, from the
+    /// bucket `2^k - 1`. Segments are allocated on demand and never freed until the list is
+    /// dropped, and so are the sentinels they point to.
+    segments: [AtomicPtr<AtomicPtr<Node<V>>>; SEGMENTS],
+    /// number of buckets
+    size: AtomicUsize,
+    /// number of items
+    count: AtomicUsize,
+}
+
+/// Number of segments, enough for any bucket index.
+const SEGMENTS: usize = mem::size_of::<usize>() * 8;
+
+/// `size` is doubled when `count > size * LOAD_FACTOR`.
+const LOAD_FACTOR: usize = 2;
+
+/// Split-ordered key, same as in [`super::SplitOrderedList`].
+type SplitOrderedKey = (usize, bool);
+
+#[derive(Debug)]
+struct Node<V> {
+    key: SplitOrderedKey,
+    /// `None` for the sentinels.
+    value: Option<V>,
+    /// Tagged with 1 when the node is logically deleted.
+    next: AtomicPtr<Node<V>>,
+}
+
+#[inline]
+fn tag<T>(ptr: *mut T) -> usize {
+    ptr as usize & 1
+}
+
+#[inline]
+fn with_tag<T>(ptr: *mut T, tag: usize) -> *mut T {
+    (ptr as usize & !1 | tag) as *mut T
+}
+
+/// Shields protecting the node that holds the `prev` pointer of a traversal and its `curr` node.
+struct Shields<V> {
+    prev: Shield<Node<V>>,
+    curr: Shield<Node<V>>,
+}
+
+impl<V> Default for Shields<V> {
+    fn default() -> Self {
+        Self {
+            prev: Shield::default(),
+            curr: Shield::default(),
+        }
+    }
+}
+
+/// Reference to (a part of) a value of an [`HpSplitOrderedList`], which keeps the node of the
+/// value protected until it is dropped.
+///
+/// Like [`std::cell::Ref`], it is narrowed with [`ValueRef::map`], e.g. to return a field of a
+/// cached struct without cloning the struct.
+pub struct ValueRef<'l, T: ?Sized> {
+    /// Protect the node that `value` points into. Either of them does, depending on the traversal
+    /// that found it.
+    _shields: (Shield<()>, Shield<()>),
+    value: NonNull<T>,
+    _marker: PhantomData<&'l T>,
+}
+
+impl<'l, T: ?Sized> ValueRef<'l, T> {
+    /// Narrows the reference to a part of the value, keeping the value protected.
+    ///
+    /// This is an associated function, since `ValueRef` dereferences to the value.
+    pub fn map<U: ?Sized, F: FnOnce(&T) -> &U>(this: Self, f: F) -> ValueRef<'l, U> {
+        let value = NonNull::from(f(&this));
+        ValueRef {
+            _shields: this._shields,
+            value,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for ValueRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the shields protect the node that `value` points into, and the list outlives
+        // `'l`.
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ValueRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for ValueRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// Position of a traversal: `prev` is the pointer to the untagged `curr`. Valid as long as the
+/// `Shields` that found it are alive.
+struct Position<'s, V> {
+    prev: &'s AtomicPtr<Node<V>>,
+    curr: *mut Node<V>,
+}
+
+impl<V> Default for HpSplitOrderedList<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> HpSplitOrderedList<V> {
+    /// Creates a new split ordered list.
+    pub fn new() -> Self {
+        let list = Self {
+            segments: [(); SEGMENTS].map(|_| AtomicPtr::new(ptr::null_mut())),
+            size: AtomicUsize::new(2),
+            count: AtomicUsize::new(0),
+        };
+        let head = Box::into_raw(Box::new(Node {
+            key: Self::get_so_bucket_key(0),
+            value: None,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        list.bucket_slot(0).store(head, Ordering::Release);
+        list
+    }
+
+    /// Returns the slot of `index` in the bucket array, allocating its segment if needed.
+    fn bucket_slot(&self, index: usize) -> &AtomicPtr<Node<V>> {
+        let segment = (mem::size_of::<usize>() * 8) - 1 - (index + 1).leading_zeros() as usize;
+        let offset = index + 1 - (1 << segment);
+        let slots = &self.segments[segment];
+
+        let mut base = slots.load(Ordering::Acquire);
+        if base.is_null() {
+            let new = Box::into_raw(
+                (0..1usize << segment)
+                    .map(|_| AtomicPtr::new(ptr::null_mut::<Node<V>>()))
+                    .collect::<Box<[_]>>(),
+            ) as *mut AtomicPtr<Node<V>>;
+            base = match slots.compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(current) => {
+                    drop(unsafe { Self::segment_from_raw(new, segment) });
+                    current
+                }
+            };
+        }
+        unsafe { &*base.add(offset) }
+    }
+
+    unsafe fn segment_from_raw(
+        base: *mut AtomicPtr<Node<V>>,
+        segment: usize,
+    ) -> Box<[AtomicPtr<Node<V>>]> {
+        Box::from_raw(ptr::slice_from_raw_parts_mut(base, 1 << segment))
+    }
+
+    /// Returns the sentinel of `index`, initializing it and its parents if needed.
+    fn get_bucket(&self, index: usize) -> &Node<V> {
+        let slot = self.bucket_slot(index);
+        let sentinel = slot.load(Ordering::Acquire);
+        if !sentinel.is_null() {
+            return unsafe { &*sentinel };
+        }
+
+        let parent = self.get_bucket(Self::get_parent_bucket(index));
+        let key = Self::get_so_bucket_key(index);
+        let node = Box::into_raw(Box::new(Node {
+            key,
+            value: None,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let shields = Shields::default();
+        let sentinel = loop {
+            let (found, pos) = Self::find(parent, &key, &shields);
+            if found {
+                // sentinels are never freed, so `pos.curr` is good without the shield
+                drop(unsafe { Box::from_raw(node) });
+                break pos.curr;
+            }
+            unsafe { (*node).next.store(pos.curr, Ordering::Relaxed) };
+            if pos
+                .prev
+                .compare_exchange(pos.curr, node, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                break node;
+            }
+        };
+        // If this fails, the winner published the same sentinel.
+        let _ = slot.compare_exchange(
+            ptr::null_mut(),
+            sentinel,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
+        unsafe { &*sentinel }
+    }
+
+    /// Finds `key` in the part of the list after `head`, unlinking and retiring the logically
+    /// deleted nodes on the way. Returns whether the key was found, and where.
+    fn find<'s>(
+        head: &'s Node<V>,
+        key: &SplitOrderedKey,
+        shields: &'s Shields<V>,
+    ) -> (bool, Position<'s, V>) {
+        // Swapping the `&Shield`s rather than the shields keeps the borrows simple.
+        'retry: loop {
+            let (mut prev_shield, mut curr_shield) = (&shields.prev, &shields.curr);
+            let mut prev = &head.next;
+            let mut curr = prev.load(Ordering::Acquire);
+            loop {
+                // protect `curr`, validating that `prev` still points to it
+                let mut protected = curr as *const _;
+                while !curr_shield.try_protect(&mut protected, prev) {
+                    curr = prev.load(Ordering::Acquire);
+                    if tag(curr) != 0 {
+                        // the node of `prev` got deleted
+                        continue 'retry;
+                    }
+                    protected = curr;
+                }
+
+                let curr_ref = match unsafe { curr.as_ref() } {
+                    Some(curr_ref) => curr_ref,
+                    None => return (false, Position { prev, curr }),
+                };
+                let next = curr_ref.next.load(Ordering::Acquire);
+                if tag(next) != 0 {
+                    let next = with_tag(next, 0);
+                    if prev
+                        .compare_exchange(curr, next, Ordering::AcqRel, Ordering::Acquire)
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    unsafe { retire(curr) };
+                    curr = next;
+                    continue;
+                }
+
+                match curr_ref.key.cmp(key) {
+                    cmp::Ordering::Less => {
+                        mem::swap(&mut prev_shield, &mut curr_shield);
+                        prev = &curr_ref.next;
+                        curr = next;
+                    }
+                    cmp::Ordering::Equal => return (true, Position { prev, curr }),
+                    cmp::Ordering::Greater => return (false, Position { prev, curr }),
+                }
+            }
+        }
+    }
+
+    fn find_data<'s>(&'s self, key: usize, shields: &'s Shields<V>) -> (bool, Position<'s, V>) {
+        let size = self.size.load(Ordering::Acquire);
+        let bucket = self.get_bucket(key % size);
+        Self::find(bucket, &Self::get_so_data_key(key), shields)
+    }
+
+    #[inline]
+    fn get_parent_bucket(bucket: usize) -> usize {
+        bucket ^ (1 << (mem::size_of::<usize>() * 8 - bucket.leading_zeros() as usize - 1))
+    }
+
+    #[inline]
+    fn get_so_bucket_key(key: usize) -> SplitOrderedKey {
+        (key.reverse_bits(), false)
+    }
+
+    #[inline]
+    fn get_so_data_key(key: usize) -> SplitOrderedKey {
+        (key.reverse_bits(), true)
+    }
+
+    /// Lookups the given key, calling `f` with its value while the value is protected.
+    pub fn lookup<F, R>(&self, key: &usize, f: F) -> R
+    where
+        F: FnOnce(Option<&V>) -> R,
+    {
+        let shields = Shields::default();
+        let (found, pos) = self.find_data(*key, &shields);
+        if found {
+            f(unsafe { &*pos.curr }.value.as_ref())
+        } else {
+            f(None)
+        }
+    }
+
+    /// Returns a reference to the value of the given key, which keeps the value protected while it
+    /// is alive.
+    pub fn get(&self, key: &usize) -> Option<ValueRef<'_, V>> {
+        let shields = Shields::default();
+        let (found, pos) = self.find_data(*key, &shields);
+        if !found {
+            return None;
+        }
+        let value = NonNull::from(unsafe { &*pos.curr }.value.as_ref()?);
+        Some(ValueRef {
+            _shields: (shields.prev.cast(), shields.curr.cast()),
+            value,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Inserts a key-value pair.
+    pub fn insert(&self, key: &usize, value: V) -> Result<(), V> {
+        let node = Box::into_raw(Box::new(Node {
+            key: Self::get_so_data_key(*key),
+            value: Some(value),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let shields = Shields::default();
+        loop {
+            let (found, pos) = self.find_data(*key, &shields);
+            if found {
+                let node = unsafe { Box::from_raw(node) };
+                return Err(node.value.unwrap());
+            }
+            unsafe { (*node).next.store(pos.curr, Ordering::Relaxed) };
+            if pos
+                .prev
+                .compare_exchange(pos.curr, node, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                break;
+            }
+        }
+
+        let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        let size = self.size.load(Ordering::Relaxed);
+        if count > size.saturating_mul(LOAD_FACTOR) {
+            let _ = self.size.compare_exchange(
+                size,
+                size.saturating_mul(2),
+                Ordering::Release,
+                Ordering::Relaxed,
+            );
+        }
+        Ok(())
+    }
+
+    /// Deletes the given key and returns a clone of its value.
+    pub fn delete(&self, key: &usize) -> Result<V, ()>
+    where
+        V: Clone,
+    {
+        let shields = Shields::default();
+        loop {
+            let (found, pos) = self.find_data(*key, &shields);
+            if !found {
+                return Err(());
+            }
+            let curr = unsafe { &*pos.curr };
+            let next = curr.next.load(Ordering::Acquire);
+            if tag(next) != 0
+                || curr
+                    .next
+                    .compare_exchange(next, with_tag(next, 1), Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+            {
+                continue;
+            }
+
+            let value = curr.value.clone().unwrap();
+            if pos
+                .prev
+                .compare_exchange(pos.curr, next, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                unsafe { retire(pos.curr) };
+            } else {
+                // let a traversal unlink it
+                let _ = self.find_data(*key, &shields);
+            }
+            let _ = self.count.fetch_sub(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+    }
+}
+
+impl<V> Drop for HpSplitOrderedList<V> {
+    fn drop(&mut self) {
+        // the bucket 0 sentinel is the head of the whole list
+        let mut curr = self.bucket_slot(0).load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(with_tag(curr, 0)) };
+            curr = node.next.load(Ordering::Relaxed);
+        }
+        for (segment, slots) in self.segments.iter().enumerate() {
+            let base = slots.load(Ordering::Relaxed);
+            if !base.is_null() {
+                drop(unsafe { Self::segment_from_raw(base, segment) });
+            }
+        }
+    }
+}
+
+unsafe impl<V: Send> Send for HpSplitOrderedList<V> {}
+unsafe impl<V: Send + Sync> Sync for HpSplitOrderedList<V> {}
+
+impl<V: Clone> ConcurrentMap<usize, V> for HpSplitOrderedList<V> {
+    fn lookup<'a, F, R>(&'a self, key: &'a usize, _guard: &'a Guard, f: F) -> R
+    where
+        F: FnOnce(Option<&V>) -> R,
+    {
+        self.lookup(key, f)
+    }
+
+    fn insert<'a>(&'a self, key: &'a usize, value: V, _guard: &'a Guard) -> Result<(), V> {
+        self.insert(key, value)
+    }
+
+    fn delete(&self, key: &usize, _guard: &Guard) -> Result<V, ()> {
+        self.delete(key)
+    }
+}
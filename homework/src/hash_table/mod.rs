@@ -1,7 +1,14 @@
 //! Lock-free hash table Based on https://dl.acm.org/doi/abs/10.1145/1147954.1147958
 
+mod fixed_split_ordered_list;
 mod growable_array;
+mod hp_split_ordered_list;
 mod split_ordered_list;
+mod split_ordered_set;
+mod striped_counter;
 
+pub use fixed_split_ordered_list::FixedSplitOrderedList;
 pub use growable_array::GrowableArray;
-pub use split_ordered_list::SplitOrderedList;
+pub use hp_split_ordered_list::{HpSplitOrderedList, ValueRef};
+pub use split_ordered_list::{Config, Diagnostics, Snapshot, SplitOrderedList};
+pub use split_ordered_set::SplitOrderedSet;
@@ -1,47 +1,463 @@
 //! Split-ordered linked list.
 
+use core::cmp;
 use core::mem;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::ops::{Bound, RangeBounds};
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use crossbeam_utils::Backoff;
 use cs431::lockfree::list::{Cursor, List, Node};
 use epoch::unprotected;
-use std::fmt::Debug;
+use static_assertions::const_assert;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::time::{Duration, Instant};
 
 use super::growable_array::GrowableArray;
+use super::striped_counter::StripedCounter;
 use crate::map::NonblockingMap;
 
-/// Lock-free map from `usize` in range [0, 2^63-1] to `V`.
+/// Lock-free map from `usize` to `V`.
 ///
 /// NOTE: We don't care about hashing in this homework for simplicity.
-#[derive(Debug)]
+///
+/// Under heavy insert/delete churn on a single bucket, lock-free writers may keep failing each
+/// other's CASes on a long chain. When a contended writer observes a chain longer than
+/// `CHAIN_THRESHOLD`, the bucket becomes *hot*: writers to it are serialized by a mini-lock kept
+/// in the tag bits of the bucket's pointer in `buckets`, while readers stay lock-free. The bucket
+/// cools down once its chain gets short again (e.g., after a resize splits it).
+///
+/// The number of buckets is doubled when the table gets crowded and halved when it gets sparse.
+/// Shrinking retires the sentinels of the upper half of the buckets, which costs the deleter that
+/// triggered it O(`size`) work. A retired bucket is initialized again once the table grows back.
+///
+/// After a doubling, each insertion and deletion initializes a few of the new buckets, so that the
+/// first accesses to them don't all pay the recursive initialization. They are all initialized well
+/// before the next doubling. [`SplitOrderedList::initialize_buckets`] initializes them at once,
+/// e.g., on a helper thread.
+///
+/// Values are kept behind an `Atomic`, so they can be replaced in place with `update`,
+/// `compare_exchange_value` and `upsert`. Replaced values are destroyed through the epoch guard.
+///
+/// With [`Config::tombstone_ttl`], a deletion leaves the node in the list as a *tombstone* of the
+/// key instead of unlinking it, which is a single CAS on the value. An insertion of the key revives
+/// the tombstone in place. Once the tombstones outnumber twice those left by the last compaction,
+/// the deleter that notices unlinks the ones older than the TTL in a single pass, which
+/// [`SplitOrderedList::compact`] also does on demand. Under delete-heavy churn, this trades the
+/// marked chains and the helping of the deletions for longer chains until the next compaction.
+///
+/// The alternate `Debug` format (`{:#?}`) shows the items of each initialized bucket.
 pub struct SplitOrderedList<V> {
-    /// Lock-free list sorted by recursive-split order. Use `None` sentinel node value.
-    list: List<usize, Option<V>>,
+    /// Lock-free list sorted by recursive-split order. Sentinel nodes have null values.
+    list: List<SplitOrderedKey, Value<V>>,
     /// array of pointers to the buckets
-    buckets: GrowableArray<Node<usize, Option<V>>>,
+    buckets: GrowableArray<Node<SplitOrderedKey, Value<V>>>,
     /// number of buckets
     size: AtomicUsize,
-    /// number of items
-    count: AtomicUsize,
+    /// number of items, counted before an insertion and after a deletion, so that it bounds the
+    /// number of items from above
+    count: StripedCounter,
+    /// number of items, counted after an insertion and before a deletion, so that it bounds the
+    /// number of items from below
+    committed: StripedCounter,
+    /// the next bucket to be initialized in advance
+    preinit: AtomicUsize,
+    /// number of writes started and finished, so that a snapshot can tell if it raced with one
+    writes_started: AtomicUsize,
+    writes_finished: AtomicUsize,
+    /// number of snapshots holding off the writers
+    freezers: AtomicUsize,
+    /// `size` is doubled when `count > size * load_factor`
+    load_factor: usize,
+    /// `size` is never shrunk below this
+    min_size: usize,
+    /// how long a tombstone is kept, or `None` to unlink the deleted nodes right away
+    tombstone_ttl: Option<Duration>,
+    /// the origin of the tombstone timestamps
+    created: Instant,
+    /// number of tombstones
+    tombstones: StripedCounter,
+    /// `tombstones` above which a deleter compacts the list
+    next_compaction: AtomicUsize,
+    /// whether a compaction is running
+    compacting: AtomicBool,
 }
 
-type SplitOrderedKey = usize;
+/// Sizing parameters of a [`SplitOrderedList`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Average number of items per bucket above which the number of buckets is doubled. The number
+    /// of buckets is halved when the average falls below a quarter of this. Must be positive.
+    pub load_factor: usize,
+    /// Initial number of buckets, rounded up to a power of two. The table never shrinks below it.
+    pub initial_buckets: usize,
+    /// How long a deleted item is kept as a tombstone before a compaction unlinks it, or `None` to
+    /// unlink the deleted items right away. See [`SplitOrderedList`].
+    pub tombstone_ttl: Option<Duration>,
+}
+
+/// Bucket distribution of a [`SplitOrderedList`], returned by [`SplitOrderedList::diagnostics`].
+///
+/// It is taken in a single pass over the list, so under concurrent updates it is only
+/// approximate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// Number of buckets of the list.
+    pub buckets: usize,
+    /// Number of items in each bucket that has some, i.e., with `key % buckets == bucket`, by
+    /// bucket.
+    pub entries: BTreeMap<usize, usize>,
+    /// Length of the chain of each initialized bucket, i.e., the number of items between its
+    /// sentinel and the next one, by bucket. The items of an uninitialized bucket are in the chain
+    /// of its closest initialized ancestor.
+    pub chains: BTreeMap<usize, usize>,
+}
+
+impl Diagnostics {
+    /// Returns the length of the longest chain.
+    pub fn max_chain(&self) -> usize {
+        self.chains.values().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the average length of the chains of the initialized buckets.
+    pub fn avg_chain(&self) -> f64 {
+        if self.chains.is_empty() {
+            return 0.0;
+        }
+        self.chains.values().sum::<usize>() as f64 / self.chains.len() as f64
+    }
+
+    /// Returns the ratio of the initialized buckets.
+    pub fn initialized_ratio(&self) -> f64 {
+        if self.buckets == 0 {
+            return 0.0;
+        }
+        self.chains.len() as f64 / self.buckets as f64
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            load_factor: 2,
+            initial_buckets: 2,
+            tombstone_ttl: None,
+        }
+    }
+}
+
+/// The bit-reversed key and whether it is a data key (`true`) or a bucket sentinel key (`false`).
+/// The flag is kept in a separate word rather than in a bit of the key so that all `usize` keys
+/// are valid. Since `false < true`, the sentinel of a bucket precedes all data keys in it.
+pub(super) type SplitOrderedKey = (usize, bool);
+
+/// Value of a node, which can be replaced in place. Null for the sentinels.
+///
+/// Once the node is deleted, the pointer is tagged so that the value is no longer replaced. This
+/// way, a deleter returns the last value of the node. The value is kept in a [`Slot`], so that the
+/// pointer has a tag bit even if the value is a `u8` or a `()`.
+///
+/// With tombstones, a deleted node keeps a null value and the time of the deletion instead, until
+/// a compaction replaces the null with the address of `REAPED` and unlinks the node. The tag bits
+/// are not used, so that values of any alignment can be tombstoned.
+#[derive(Debug)]
+pub(super) struct Value<V>(pub(super) Atomic<Slot<V>>, AtomicU64);
+
+/// Heap cell of a value, aligned to 2 bytes at least.
+#[derive(Debug)]
+#[repr(align(2))]
+pub(super) struct Slot<V>(pub(super) V);
+
+const_assert!(mem::align_of::<Slot<()>>() >= 2);
+
+/// Its address is the value of a tombstone being unlinked. It is never dereferenced.
+#[repr(align(64))]
+struct Reaped(u8);
+
+static REAPED: Reaped = Reaped(0);
+
+#[inline]
+fn is_reaped<V>(value: Shared<'_, V>) -> bool {
+    ptr::eq(
+        value.as_raw() as *const u8,
+        &REAPED as *const Reaped as *const u8,
+    )
+}
+
+impl<V> Value<V> {
+    pub(super) fn new(value: V) -> Self {
+        Self::from_owned(Owned::new(Slot(value)))
+    }
+
+    pub(super) fn from_owned(value: Owned<Slot<V>>) -> Self {
+        Self(Atomic::from(value), AtomicU64::new(0))
+    }
+
+    pub(super) fn null() -> Self {
+        Self(Atomic::null(), AtomicU64::new(0))
+    }
+
+    /// Returns the current value, or `None` for a sentinel, a deleted node or a tombstone.
+    pub(super) fn get<'g>(&self, guard: &'g Guard) -> Option<&'g V> {
+        let value = self.0.load(Ordering::Acquire, guard);
+        if value.tag() != 0 || is_reaped(value) {
+            return None;
+        }
+        unsafe { value.as_ref() }.map(|slot| &slot.0)
+    }
+
+    /// Turns the node into a tombstone deleted at `now`, and returns the deleted value, which is
+    /// destroyed through `guard`. Otherwise, returns whether the node is being unlinked, in which
+    /// case the key is to be found again.
+    fn tombstone<'g>(&self, now: u64, guard: &'g Guard) -> Result<&'g V, bool> {
+        loop {
+            let current = self.0.load(Ordering::Acquire, guard);
+            if current.is_null() {
+                return Err(false);
+            }
+            if current.tag() != 0 || is_reaped(current) {
+                return Err(true);
+            }
+            self.1.store(now, Ordering::Relaxed);
+            if self
+                .0
+                .compare_exchange(
+                    current,
+                    Shared::null(),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                )
+                .is_ok()
+            {
+                unsafe {
+                    guard.defer_destroy(current);
+                    return Ok(&current.deref().0);
+                }
+            }
+        }
+    }
+
+    /// Revives a tombstone with `new`. Otherwise, returns `new` back and whether the node is being
+    /// unlinked, in which case the key is to be found again. If it isn't, the key is present.
+    fn revive(&self, new: Owned<Slot<V>>, guard: &Guard) -> Result<(), (Owned<Slot<V>>, bool)> {
+        self.0
+            .compare_exchange(
+                Shared::null(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            )
+            .map(|_| ())
+            .map_err(|e| (e.new, e.current.tag() != 0 || is_reaped(e.current)))
+    }
+
+    /// Claims a tombstone deleted at `deadline` or before, for unlinking. Returns whether it did.
+    fn reap(&self, deadline: u64, guard: &Guard) -> bool {
+        let current = self.0.load(Ordering::Acquire, guard);
+        current.is_null()
+            && self.1.load(Ordering::Relaxed) <= deadline
+            && self
+                .0
+                .compare_exchange(
+                    current,
+                    Shared::from(&REAPED as *const Reaped as *const Slot<V>),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+    }
+
+    /// Freezes the value of a node that has just been deleted and returns it.
+    pub(super) fn freeze<'g>(&self, guard: &'g Guard) -> &'g V {
+        let value = self.0.fetch_or(1, Ordering::AcqRel, guard);
+        unsafe { &value.with_tag(0).deref().0 }
+    }
+
+    /// Replaces `current` with `new`, failing if the value has changed or the node is deleted.
+    /// Returns the replaced value.
+    pub(super) fn replace<'g>(
+        &self,
+        current: Shared<'g, Slot<V>>,
+        new: Owned<Slot<V>>,
+        guard: &'g Guard,
+    ) -> Result<&'g V, Owned<Slot<V>>> {
+        match self
+            .0
+            .compare_exchange(current, new, Ordering::AcqRel, Ordering::Acquire, guard)
+        {
+            Ok(_) => unsafe {
+                guard.defer_destroy(current);
+                Ok(&current.deref().0)
+            },
+            Err(e) => Err(e.new),
+        }
+    }
+
+    pub(super) fn into_inner(self) -> V {
+        let value = unsafe { self.0.load(Ordering::Relaxed, unprotected()) };
+        mem::forget(self);
+        unsafe { value.into_owned() }.into_box().0
+    }
+}
+
+impl<V> Drop for Value<V> {
+    fn drop(&mut self) {
+        unsafe {
+            let value = self.0.load(Ordering::Relaxed, unprotected());
+            if !value.is_null() && !is_reaped(value) {
+                drop(value.with_tag(0).into_owned());
+            }
+        }
+    }
+}
+
+/// Tag of a bucket pointer marking the bucket as hot.
+const HOT: usize = 1;
+/// Tag of a bucket pointer held by the writer serialized on a hot bucket, or, on a cell without a
+/// live sentinel, by the thread initializing the bucket.
+const LOCKED: usize = 2;
+/// Tag of a bucket pointer whose sentinel is being removed by a shrink. Such a pointer must not be
+/// dereferenced.
+const RETIRED: usize = 4;
+
+/// Lifecycle of a bucket, decoded from the (pointer, tag) word of its cell in `buckets`.
+///
+/// ```text
+/// Uninitialized ---claim---> Initializing ---publish---> Live <---publish--- Initializing
+///  (null, 0)                (null, LOCKED)         (sentinel, HOT|LOCKED)  (old, RETIRED|LOCKED)
+///                                                        |                        ^
+///                                                        '--shrink--> Retired ----claim
+///                                                                  (old, RETIRED)
+/// ```
+///
+/// Only a bucket in `Live` is dereferenced. A thread that finds the bucket in `Initializing` goes
+/// through the parent bucket instead of waiting.
+enum BucketState<'g, V> {
+    /// The bucket has never been initialized.
+    Uninitialized,
+    /// A thread claimed the initialization and is inserting the sentinel.
+    Initializing,
+    /// The bucket has a live sentinel. The tag has the hot bucket flags.
+    Live(&'g Node<SplitOrderedKey, Value<V>>),
+    /// The bucket was initialized, and then pruned by a shrink.
+    Retired,
+}
+
+impl<'g, V> BucketState<'g, V> {
+    fn decode(raw: Shared<'g, Node<SplitOrderedKey, Value<V>>>) -> Self {
+        match (
+            raw.is_null(),
+            raw.tag() & RETIRED != 0,
+            raw.tag() & LOCKED != 0,
+        ) {
+            (false, false, _) => BucketState::Live(unsafe { raw.with_tag(0).deref() }),
+            (true, _, false) => BucketState::Uninitialized,
+            (_, true, false) => BucketState::Retired,
+            (_, _, true) => BucketState::Initializing,
+        }
+    }
+}
+/// A bucket becomes hot when a contended writer observes a chain longer than this.
+const CHAIN_THRESHOLD: usize = 64;
+/// Number of buckets initialized in advance by each insertion and deletion after a doubling.
+const PREINIT_BATCH: usize = 2;
+/// Number of times a snapshot tries to collect the items without holding off the writers.
+const SNAPSHOT_ATTEMPTS: usize = 4;
+/// minimum number of tombstones that triggers a compaction
+const COMPACTION_MIN: usize = 64;
+
+/// A write to the list, during which no snapshot is taken. Finished when dropped.
+struct WriteSection<'a, V> {
+    list: &'a SplitOrderedList<V>,
+}
+
+impl<V> Drop for WriteSection<'_, V> {
+    fn drop(&mut self) {
+        let _ = self.list.writes_finished.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Mini-lock on a hot bucket. Released when dropped.
+struct BucketLock<'g, V> {
+    list: &'g SplitOrderedList<V>,
+    bucket: usize,
+    guard: &'g Guard,
+}
+
+impl<'g, V> Drop for BucketLock<'g, V> {
+    fn drop(&mut self) {
+        // cool the bucket down if its chain got short
+        let tag = if self
+            .list
+            .chain_len(self.bucket, CHAIN_THRESHOLD / 2, self.guard)
+            <= CHAIN_THRESHOLD / 2
+        {
+            HOT | LOCKED
+        } else {
+            LOCKED
+        };
+        // A shrink may have retired the bucket meanwhile, which already cleared the flags.
+        self.list
+            .update_live_tag(self.bucket, |t| t & !tag, Ordering::Release, self.guard);
+    }
+}
 
 impl<V> Default for SplitOrderedList<V> {
     fn default() -> Self {
+        Self::with_config(Config::default())
+    }
+}
+
+impl<V> SplitOrderedList<V> {
+    /// Creates a new split ordered list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new split ordered list with enough buckets for `capacity` items.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let config = Config::default();
+        Self::with_config(Config {
+            initial_buckets: capacity / config.load_factor,
+            ..config
+        })
+    }
+
+    /// Creates a new split ordered list with the given sizing parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `config.load_factor` is zero, or if tombstones are enabled for values aligned to
+    /// more than 64 bytes.
+    pub fn with_config(config: Config) -> Self {
+        assert!(config.load_factor > 0, "load factor must be positive");
+        assert!(
+            config.tombstone_ttl.is_none()
+                || mem::align_of::<Slot<V>>() <= mem::align_of::<Reaped>(),
+            "values are too aligned for tombstones"
+        );
+        let size = config
+            .initial_buckets
+            .max(2)
+            .checked_next_power_of_two()
+            .unwrap_or(1 << (usize::BITS - 1));
         let list = List::new();
         let buckets = GrowableArray::new();
         let guard = unsafe { &unprotected() };
 
         // 0 dummy node
-        list.harris_herlihy_shavit_insert(0, None, guard);
+        list.harris_herlihy_shavit_insert(Self::get_so_bucket_key(0), Value::null(), guard);
         let mut cursor = list.head(guard);
-        let _ = cursor.find_harris_herlihy_shavit(&0, guard);
+        let _ = cursor.find_harris_herlihy_shavit(&Self::get_so_bucket_key(0), guard);
         let bucket_zero = buckets.get(0, guard);
         bucket_zero.store(cursor.curr(), Ordering::Release);
 
         // 1 dummy node
-        list.harris_herlihy_shavit_insert(Self::get_so_bucket_key(1), None, guard);
+        list.harris_herlihy_shavit_insert(Self::get_so_bucket_key(1), Value::null(), guard);
         let mut cursor = list.head(guard);
         let _ = cursor.find_harris_herlihy_shavit(&Self::get_so_bucket_key(1), guard);
         let bucket_one = buckets.get(1, guard);
@@ -50,89 +466,553 @@ impl<V> Default for SplitOrderedList<V> {
         Self {
             list,
             buckets,
-            size: AtomicUsize::new(2),
-            count: AtomicUsize::new(0),
+            size: AtomicUsize::new(size),
+            count: StripedCounter::new(0),
+            committed: StripedCounter::new(0),
+            preinit: AtomicUsize::new(0),
+            writes_started: AtomicUsize::new(0),
+            writes_finished: AtomicUsize::new(0),
+            freezers: AtomicUsize::new(0),
+            load_factor: config.load_factor,
+            min_size: size,
+            tombstone_ttl: config.tombstone_ttl,
+            created: Instant::now(),
+            tombstones: StripedCounter::new(0),
+            next_compaction: AtomicUsize::new(COMPACTION_MIN),
+            compacting: AtomicBool::new(false),
         }
     }
-}
 
-impl<V> SplitOrderedList<V> {
-    /// `size` is doubled when `count > size * LOAD_FACTOR`.
-    const LOAD_FACTOR: usize = 2;
+    /// Creates a list from `pairs` sorted by key.
+    ///
+    /// The list and the buckets are built directly in split order, instead of inserting the pairs
+    /// one by one, and with enough buckets for the pairs so that the table doesn't grow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not strictly increasing.
+    pub fn bulk_load<I: IntoIterator<Item = (usize, V)>>(pairs: I) -> Self {
+        let pairs = pairs.into_iter().collect::<Vec<_>>();
+        assert!(
+            pairs.windows(2).all(|w| w[0].0 < w[1].0),
+            "keys must be strictly increasing"
+        );
+        let mut list = Self::new();
+        let size = (pairs.len() / list.load_factor + 1)
+            .checked_next_power_of_two()
+            .unwrap_or(1 << (usize::BITS - 1))
+            .max(list.size.load(Ordering::Relaxed));
+        let guard = unsafe { &unprotected() };
 
-    /// Creates a new split ordered list.
-    pub fn new() -> Self {
-        Self::default()
+        // the sentinels except for buckets 0 and 1, and the items, in split order
+        let mut nodes = (2..size)
+            .map(|bucket| (Self::get_so_bucket_key(bucket), None))
+            .chain(
+                pairs
+                    .into_iter()
+                    .map(|(key, value)| (Self::get_so_data_key(key), Some(value))),
+            )
+            .collect::<Vec<_>>();
+        nodes.sort_unstable_by_key(|(so_key, _)| *so_key);
+
+        let count = nodes.len() - (size - 2);
+        let head = list.buckets.get(0, guard).load(Ordering::Relaxed, guard);
+        let mut cursor = Cursor::new(
+            unsafe { head.deref() }.next(),
+            unsafe { head.deref() }
+                .next()
+                .load(Ordering::Relaxed, guard),
+        );
+        for (so_key, value) in nodes {
+            // skip the sentinel of bucket 1
+            if let Some(curr) = unsafe { cursor.curr().as_ref() } {
+                if *curr.key() < so_key {
+                    cursor = Cursor::new(curr.next(), curr.next().load(Ordering::Relaxed, guard));
+                }
+            }
+            let is_data = so_key.1;
+            let value = value.map_or_else(Value::null, Value::new);
+            if cursor
+                .insert(Owned::new(Node::new(so_key, value)), guard)
+                .is_err()
+            {
+                unreachable!("the list is not shared yet");
+            }
+            let node = unsafe { cursor.curr().deref() };
+            if !is_data {
+                list.buckets
+                    .get(Self::get_key(so_key), guard)
+                    .store(cursor.curr(), Ordering::Relaxed);
+            }
+            cursor = Cursor::new(node.next(), node.next().load(Ordering::Relaxed, guard));
+        }
+
+        list.size = AtomicUsize::new(size);
+        list.count = StripedCounter::new(count);
+        list.committed = StripedCounter::new(count);
+        list.preinit = AtomicUsize::new(size);
+        list
     }
 
-    /// Creates a cursor and moves it to the bucket for the given index.  If the bucket doesn't
-    /// exist, recursively initializes the buckets.
-    fn lookup_bucket<'s>(&'s self, index: usize, guard: &'s Guard) -> Cursor<'s, usize, Option<V>> {
-        let size = self.size.load(Ordering::Relaxed);
-        let bucket = index % size;
+    /// Returns `(lower, upper)` bounds of the number of items.
+    ///
+    /// An insertion is counted in `upper` before it takes effect and in `lower` after, and vice
+    /// versa for a deletion. So when the call returns, `lower` is at most the number of items at the
+    /// time it was read, and `upper` is at least the number of items at the time it was read. Both
+    /// are exact if there is no concurrent update.
+    pub fn len_bounds(&self) -> (usize, usize) {
+        // read `lower` first, since it is the one that may transiently be below zero
+        let lower = self.committed.lower().max(0) as usize;
+        let upper = self.count.upper().max(0) as usize;
+        (lower.min(upper), upper)
+    }
 
-        let mut bucket_raw = self.buckets.get(bucket, guard);
-        let node_raw = bucket_raw.load(Ordering::Acquire, guard);
-        if node_raw.is_null() {
-            self.make_bucket(bucket, size, guard);
+    /// Returns the bucket distribution of the list.
+    pub fn diagnostics(&self, guard: &Guard) -> Diagnostics {
+        let size = self.size.load(Ordering::Acquire);
+        // only the buckets seen, since there may be many more buckets than items
+        let mut entries = BTreeMap::new();
+        let mut chains = BTreeMap::new();
+        // the bucket whose chain is being traversed
+        let mut bucket = None;
+        let mut curr = self.list.head(guard).curr();
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let next = node.next().load(Ordering::Acquire, guard);
+            curr = next.with_tag(0);
+            if next.tag() != 0 {
+                continue;
+            }
+            let key = Self::get_key(*node.key());
+            if !node.key().1 {
+                // a sentinel of a bucket being retired doesn't start a chain
+                if key < size {
+                    let _ = chains.insert(key, 0);
+                    bucket = Some(key);
+                }
+                continue;
+            }
+            if node.value().get(guard).is_none() {
+                continue;
+            }
+            *entries.entry(key % size).or_default() += 1;
+            if let Some(len) = bucket.and_then(|bucket| chains.get_mut(&bucket)) {
+                *len += 1;
+            }
+        }
+        Diagnostics {
+            buckets: size,
+            entries,
+            chains,
+        }
+    }
+
+    /// Returns the sizing parameters of the list.
+    pub fn config(&self) -> Config {
+        Config {
+            load_factor: self.load_factor,
+            initial_buckets: self.min_size,
+            tombstone_ttl: self.tombstone_ttl,
+        }
+    }
+
+    /// Returns the items whose keys are in `range`, sorted by key.
+    ///
+    /// The items are stored in bit-reversed key order, so there is no ordered index to walk. If the
+    /// range has no more keys than the list has items, each key is looked up; otherwise, the whole
+    /// list is traversed and filtered. Hence it takes O(min(`range.len()`, `n`) + `m` log `m`) time
+    /// for `n` items and `m` results. Concurrent updates may or may not be reflected.
+    pub fn range<'g, R: RangeBounds<usize>>(
+        &'g self,
+        range: R,
+        guard: &'g Guard,
+    ) -> Vec<(usize, &'g V)> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => Some(start),
+            Bound::Excluded(&start) => start.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => Some(end),
+            Bound::Excluded(&end) => end.checked_sub(1),
+            Bound::Unbounded => Some(usize::MAX),
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if start <= end => (start, end),
+            _ => return Vec::new(),
+        };
+
+        let (_, count) = self.len_bounds();
+        if end - start < count {
+            return (start..=end)
+                .filter_map(|key| self.lookup(&key, guard).map(|value| (key, value)))
+                .collect();
+        }
+
+        let mut items = self
+            .iter(guard)
+            .filter(|(key, _)| (start..=end).contains(key))
+            .collect::<Vec<_>>();
+        items.sort_unstable_by_key(|(key, _)| *key);
+        items
+    }
+
+    /// Replaces the value of `key` with `f(current)`, and returns the replaced value. Returns `None`
+    /// if the key is not in the list.
+    ///
+    /// `f` may be called multiple times if the value is concurrently replaced. The replaced value is
+    /// destroyed through `guard`.
+    pub fn update<'g, F: FnMut(&V) -> V>(
+        &'g self,
+        key: &usize,
+        mut f: F,
+        guard: &'g Guard,
+    ) -> Option<&'g V> {
+        let _write = self.begin_write();
+        let mut new: Option<Owned<Slot<V>>> = None;
+        loop {
+            let (found, cursor) = self.find(key, guard);
+            if !found {
+                return None;
+            }
+            let node = unsafe { cursor.curr().deref() };
+            let current = node.value().0.load(Ordering::Acquire, guard);
+            // a tombstone
+            if current.is_null() {
+                return None;
+            }
+            // deleted, find again
+            if current.tag() != 0 || is_reaped(current) {
+                continue;
+            }
+            let value = f(unsafe { &current.deref().0 });
+            let owned = match new.take() {
+                Some(mut owned) => {
+                    owned.0 = value;
+                    owned
+                }
+                None => Owned::new(Slot(value)),
+            };
+            match node.value().replace(current, owned, guard) {
+                Ok(old) => return Some(old),
+                Err(owned) => new = Some(owned),
+            }
+        }
+    }
+
+    /// Replaces the value of `key` with `new` if it is equal to `expected`, and returns the replaced
+    /// value. Otherwise, returns `new` back.
+    ///
+    /// The replaced value is destroyed through `guard`.
+    pub fn compare_exchange_value<'g>(
+        &'g self,
+        key: &usize,
+        expected: &V,
+        new: V,
+        guard: &'g Guard,
+    ) -> Result<&'g V, V>
+    where
+        V: PartialEq,
+    {
+        let _write = self.begin_write();
+        let mut new = Owned::new(Slot(new));
+        loop {
+            let (found, cursor) = self.find(key, guard);
+            if !found {
+                return Err(new.into_box().0);
+            }
+            let node = unsafe { cursor.curr().deref() };
+            let current = node.value().0.load(Ordering::Acquire, guard);
+            // a tombstone
+            if current.is_null() {
+                return Err(new.into_box().0);
+            }
+            // deleted, find again
+            if current.tag() != 0 || is_reaped(current) {
+                continue;
+            }
+            if unsafe { &current.deref().0 } != expected {
+                return Err(new.into_box().0);
+            }
+            match node.value().replace(current, new, guard) {
+                Ok(old) => return Ok(old),
+                Err(owned) => new = owned,
+            }
+        }
+    }
+
+    /// Deletes all items, keeping the buckets. The deleted nodes are destroyed through `guard`.
+    ///
+    /// Items inserted concurrently may or may not be deleted.
+    pub fn clear(&self, guard: &Guard) {
+        self.retain(|_, _| false, guard);
+    }
+
+    /// Deletes the items for which `f` returns `false`. The deleted nodes are destroyed through
+    /// `guard`.
+    ///
+    /// Each item present during the whole call is visited exactly once. Items inserted, deleted or
+    /// replaced concurrently may or may not be visited.
+    pub fn retain<F: FnMut(usize, &V) -> bool>(&self, mut f: F, guard: &Guard) {
+        let mut curr = self.list.head(guard).curr();
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let mut next = node.next().load(Ordering::Acquire, guard);
+            // skip the sentinels and the deleted nodes
+            if let (true, Some(value)) = (next.tag() == 0, node.value().get(guard)) {
+                if !f(Self::get_key(*node.key()), value) {
+                    let _write = self.begin_write();
+                    self.committed.decrement();
+                    if self.tombstone_ttl.is_some() {
+                        if node.value().tombstone(self.now(), guard).is_ok() {
+                            self.tombstones.increment();
+                            self.on_delete(guard);
+                        } else {
+                            self.committed.increment();
+                        }
+                    } else {
+                        next = node.next().fetch_or(1, Ordering::AcqRel, guard);
+                        if next.tag() == 0 {
+                            let _ = node.value().freeze(guard);
+                            self.on_delete(guard);
+                        } else {
+                            self.committed.increment();
+                        }
+                    }
+                }
+            }
+            curr = next.with_tag(0);
         }
-        self.get_cursor_to_bucket(bucket, bucket_raw, guard)
+
+        self.unlink_marked(guard);
+        self.maybe_compact(guard);
+    }
+
+    /// Physically removes the deleted nodes.
+    fn unlink_marked(&self, guard: &Guard) {
+        loop {
+            let mut cursor = self.list.head(guard);
+            if cursor
+                .find_harris_michael(&(usize::MAX, true), guard)
+                .is_ok()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Returns the timestamp of a tombstone deleted now.
+    fn now(&self) -> u64 {
+        self.created.elapsed().as_nanos() as u64
+    }
+
+    /// Returns the number of tombstones, approximately under concurrent updates.
+    pub fn tombstones(&self) -> usize {
+        self.tombstones.approx()
     }
 
-    fn make_bucket<'s>(&'s self, bucket: usize, size: usize, guard: &'s Guard) {
-        let parent = self.get_parent_bucket(bucket);
-        let parent_raw = self.buckets.get(parent, guard);
-        let node_raw = parent_raw.load(Ordering::Acquire, guard);
-        if node_raw.is_null() {
-            self.make_bucket(parent, size, guard);
+    /// Unlinks the tombstones older than [`Config::tombstone_ttl`] in a single pass, and returns
+    /// their number. Does nothing if tombstones are not enabled, or if another compaction is
+    /// running.
+    ///
+    /// Deleters compact the list when the tombstones pile up, so this is only needed to reclaim
+    /// the tombstones when the deletions stop, e.g., on a timer.
+    pub fn compact(&self, guard: &Guard) -> usize {
+        let ttl = some_or!(self.tombstone_ttl, return 0);
+        if self.compacting.swap(true, Ordering::Acquire) {
+            return 0;
         }
+        let deadline = self
+            .now()
+            .saturating_sub(ttl.as_nanos().try_into().unwrap_or(u64::MAX));
+        let mut reaped = 0;
+        let mut curr = self.list.head(guard).curr();
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let mut next = node.next().load(Ordering::Acquire, guard);
+            // sentinels have null values too
+            if next.tag() == 0 && node.key().1 && node.value().reap(deadline, guard) {
+                // Only the reaper marks a data node, and no one revives it anymore.
+                next = node.next().fetch_or(1, Ordering::AcqRel, guard);
+                self.tombstones.decrement();
+                reaped += 1;
+            }
+            curr = next.with_tag(0);
+        }
+        self.unlink_marked(guard);
+
+        // amortizes the pass over the items
+        let remaining = self.tombstones.approx();
+        self.next_compaction.store(
+            remaining
+                .saturating_mul(2)
+                .max(self.count.approx() / 2)
+                .saturating_add(COMPACTION_MIN),
+            Ordering::Relaxed,
+        );
+        self.compacting.store(false, Ordering::Release);
+        reaped
+    }
 
-        let cursor = self.get_cursor_to_bucket(parent, parent_raw, guard);
-        self.insert_bucket(cursor, bucket, guard);
+    /// Compacts the list if the tombstones piled up since the last compaction.
+    fn maybe_compact(&self, guard: &Guard) {
+        if self.tombstone_ttl.is_some()
+            && self.tombstones.approx() >= self.next_compaction.load(Ordering::Relaxed)
+        {
+            let _ = self.compact(guard);
+        }
     }
 
-    fn insert_bucket<'s>(
+    /// Creates a cursor and moves it to the bucket for the given index.  If the bucket doesn't
+    /// exist, recursively initializes the buckets.
+    fn lookup_bucket<'s>(
         &'s self,
-        mut cursor: Cursor<'s, usize, Option<V>>,
-        bucket: usize,
+        index: usize,
         guard: &'s Guard,
-    ) {
-        let bucket_key = Self::get_so_bucket_key(bucket);
-        let mut node = Owned::new(Node::new(bucket_key, None));
+    ) -> Cursor<'s, SplitOrderedKey, Value<V>> {
         loop {
-            let bucket_atomic = self.buckets.get(bucket, guard);
-            let bucket_raw = bucket_atomic.load(Ordering::Acquire, guard);
-            if !bucket_raw.is_null() {
-                return;
+            let size = self.size.load(Ordering::Relaxed);
+            let sentinel = self.get_bucket(index % size, guard);
+            // Start right after the sentinel so that the cursor never writes to the bucket pointer.
+            let next = sentinel.next().load(Ordering::Acquire, guard);
+            if next.tag() == 0 {
+                return Cursor::new(sentinel.next(), next);
+            }
+            // The sentinel got retired. By now the bucket pointer is tagged `RETIRED`.
+        }
+    }
+
+    /// Returns the sentinel of the bucket, initializing it if necessary. If the bucket is being
+    /// initialized by another thread or retired by a shrink, this may return the sentinel of an
+    /// ancestor bucket instead.
+    fn get_bucket<'g>(
+        &'g self,
+        bucket: usize,
+        guard: &'g Guard,
+    ) -> &'g Node<SplitOrderedKey, Value<V>> {
+        let bucket_raw = self.buckets.get(bucket, guard);
+        loop {
+            let node_raw = bucket_raw.load(Ordering::Acquire, guard);
+            // bucket 0 is always live, so the recursion terminates
+            match BucketState::decode(node_raw) {
+                BucketState::Live(sentinel) => return sentinel,
+                BucketState::Initializing => {
+                    return self.get_bucket(self.get_parent_bucket(bucket), guard)
+                }
+                BucketState::Uninitialized | BucketState::Retired => {}
             }
 
-            if cursor
-                .find_harris_michael(&bucket_key, guard)
-                .unwrap_or(false)
+            let claimed = node_raw.with_tag(node_raw.tag() | LOCKED);
+            if bucket_raw
+                .compare_exchange(
+                    node_raw,
+                    claimed,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_err()
             {
-                return;
+                continue;
             }
-            match cursor.insert(node, guard) {
-                Ok(_) => {
-                    bucket_atomic.store(cursor.curr(), Ordering::Release);
-                    break;
+            let parent = self.get_bucket(self.get_parent_bucket(bucket), guard);
+            match self.insert_bucket(parent, bucket, claimed, guard) {
+                Some(sentinel) => return sentinel,
+                None => {
+                    // give up the claim and retry
+                    let _ = bucket_raw.compare_exchange(
+                        claimed,
+                        node_raw,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                        guard,
+                    );
                 }
-                Err(n) => node = n,
             }
         }
     }
 
-    #[inline]
-    fn get_cursor_to_bucket<'g>(
+    /// Inserts the sentinel of `bucket` after the sentinel of its parent and publishes it in the
+    /// `claimed` cell of the bucket. Returns the sentinel to use for `bucket`, or `None` if the
+    /// parent got retired in the meantime, in which case the claim is still held.
+    fn insert_bucket<'g>(
         &'g self,
+        parent: &'g Node<SplitOrderedKey, Value<V>>,
         bucket: usize,
-        bucket_raw: &'g Atomic<Node<usize, Option<V>>>,
+        claimed: Shared<'g, Node<SplitOrderedKey, Value<V>>>,
         guard: &'g Guard,
-    ) -> Cursor<'g, usize, Option<V>> {
-        let node_raw = bucket_raw.load(Ordering::Acquire, guard);
-        let mut cursor = Cursor::new(bucket_raw, node_raw);
-        let _ = cursor.find_harris_michael(&(Self::get_so_bucket_key(bucket) + 1), guard);
-        cursor
+    ) -> Option<&'g Node<SplitOrderedKey, Value<V>>> {
+        let bucket_atomic = self.buckets.get(bucket, guard);
+        let bucket_key = Self::get_so_bucket_key(bucket);
+        let mut node = Owned::new(Node::new(bucket_key, Value::null()));
+        loop {
+            let next = parent.next().load(Ordering::Acquire, guard);
+            if next.tag() != 0 {
+                return None;
+            }
+            let mut cursor = Cursor::new(parent.next(), next);
+            let found = some_or!(
+                cursor.find_harris_michael(&bucket_key, guard).ok(),
+                continue
+            );
+            if found && cursor.curr() == claimed.with_tag(0) {
+                // The old sentinel of a bucket being retired, which is about to be marked. It
+                // must not be published again, so give up the claim and go through the parent
+                // this time.
+                let _ = bucket_atomic.compare_exchange(
+                    claimed,
+                    claimed.with_tag(claimed.tag() & !LOCKED),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                return Some(parent);
+            }
+            // If found, the sentinel was left unpublished by an initialization that raced with a
+            // shrink.
+            if !found {
+                if let Err(n) = cursor.insert(node, guard) {
+                    node = n;
+                    continue;
+                }
+            }
+            // Only the claimer changes a claimed cell.
+            let published = self
+                .buckets
+                .compare_exchange(bucket, claimed, cursor.curr(), guard);
+            debug_assert!(published.is_ok());
+            return Some(unsafe { cursor.curr().deref() });
+        }
+    }
+
+    /// Retires the buckets in `from..to` after `size` was halved to `from`.
+    ///
+    /// A bucket pointer is first tagged `RETIRED` so that no one dereferences it anymore, and only
+    /// then the sentinel is marked. This way, the sentinel is no longer reachable through
+    /// `buckets` when a traversal unlinks and destroys it.
+    fn retire_buckets(&self, from: usize, to: usize, guard: &Guard) {
+        for bucket in from..to {
+            let bucket_raw = self.buckets.get(bucket, guard);
+            loop {
+                let node_raw = bucket_raw.load(Ordering::Acquire, guard);
+                // A bucket being initialized is left alone, and its sentinel stays in the list.
+                if !matches!(BucketState::decode(node_raw), BucketState::Live(_)) {
+                    break;
+                }
+                // the hot flags go away with the sentinel
+                if bucket_raw
+                    .compare_exchange(
+                        node_raw,
+                        node_raw.with_tag(RETIRED),
+                        Ordering::AcqRel,
+                        Ordering::Relaxed,
+                        guard,
+                    )
+                    .is_ok()
+                {
+                    let sentinel = unsafe { node_raw.with_tag(0).deref() };
+                    let _ = sentinel.next().fetch_or(1, Ordering::AcqRel, guard);
+                    break;
+                }
+            }
+        }
     }
 
     #[inline]
@@ -141,81 +1021,662 @@ impl<V> SplitOrderedList<V> {
     }
 
     #[inline]
-    fn get_so_bucket_key(key: usize) -> SplitOrderedKey {
-        key.reverse_bits()
+    pub(super) fn get_so_bucket_key(key: usize) -> SplitOrderedKey {
+        (key.reverse_bits(), false)
+    }
+
+    #[inline]
+    pub(super) fn get_so_data_key(key: usize) -> SplitOrderedKey {
+        (key.reverse_bits(), true)
     }
 
+    /// Recovers the original key from a split-ordered data key.
     #[inline]
-    fn get_so_data_key(key: usize) -> SplitOrderedKey {
-        key.reverse_bits() | 1
+    pub(super) fn get_key(so_key: SplitOrderedKey) -> usize {
+        so_key.0.reverse_bits()
+    }
+
+    /// Returns an iterator over the items in split order, i.e., in the order of the bit-reversed
+    /// keys.
+    ///
+    /// The iterator is weakly consistent:
+    ///
+    /// - It yields every item that is in the list during the whole iteration exactly once, with
+    ///   its value at some point during the iteration.
+    /// - It may or may not yield the items inserted or deleted concurrently. If a key is deleted and
+    ///   inserted again during the iteration, both the old and the new item may be yielded.
+    /// - It never yields an item that isn't in the list at any point during the iteration.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, V> {
+        Iter::new(&self.list, guard)
+    }
+
+    /// Returns the items at a single point in time during the call.
+    ///
+    /// The items are first collected while the writers proceed, and the collection is kept if no
+    /// write overlapped with it. If writes keep overlapping, the snapshot holds off new writers and
+    /// waits for the running ones to finish, so it must not be taken from within the closure of
+    /// [`update`](Self::update) or [`retain`](Self::retain) on the same list. Readers are never
+    /// blocked.
+    pub fn snapshot<'g>(&'g self, guard: &'g Guard) -> Snapshot<'g, V> {
+        let collect = || {
+            let mut items = self.iter(guard).collect::<Vec<_>>();
+            items.sort_unstable_by_key(|(key, _)| *key);
+            Snapshot { items }
+        };
+
+        let backoff = Backoff::new();
+        for _ in 0..SNAPSHOT_ATTEMPTS {
+            // no write is running once `started` is read
+            let finished = self.writes_finished.load(Ordering::SeqCst);
+            let started = self.writes_started.load(Ordering::SeqCst);
+            if started == finished {
+                let snapshot = collect();
+                if self.writes_started.load(Ordering::SeqCst) == started {
+                    return snapshot;
+                }
+            }
+            backoff.snooze();
+        }
+
+        let _ = self.freezers.fetch_add(1, Ordering::SeqCst);
+        loop {
+            let finished = self.writes_finished.load(Ordering::SeqCst);
+            if self.writes_started.load(Ordering::SeqCst) == finished {
+                break;
+            }
+            backoff.snooze();
+        }
+        let snapshot = collect();
+        let _ = self.freezers.fetch_sub(1, Ordering::SeqCst);
+        snapshot
+    }
+
+    /// Starts a write, waiting for the snapshots holding off the writers.
+    fn begin_write(&self) -> WriteSection<'_, V> {
+        loop {
+            // Either this sees the freezer, or the freezer sees this write started.
+            let _ = self.writes_started.fetch_add(1, Ordering::SeqCst);
+            if self.freezers.load(Ordering::SeqCst) == 0 {
+                return WriteSection { list: self };
+            }
+            let _ = self.writes_finished.fetch_add(1, Ordering::SeqCst);
+            let backoff = Backoff::new();
+            while self.freezers.load(Ordering::SeqCst) != 0 {
+                backoff.snooze();
+            }
+        }
+    }
+
+    /// Returns the sentinel of the closest initialized ancestor of the bucket for `index`, or the
+    /// bucket itself. Unlike `lookup_bucket`, it never initializes a bucket, so it doesn't allocate.
+    fn read_bucket<'g>(
+        &'g self,
+        index: usize,
+        guard: &'g Guard,
+    ) -> &'g Node<SplitOrderedKey, Value<V>> {
+        let mut bucket = index % self.size.load(Ordering::Relaxed);
+        loop {
+            if let Some(bucket_raw) = self.buckets.get_if_exists(bucket, guard) {
+                let node_raw = bucket_raw.load(Ordering::Acquire, guard);
+                if let BucketState::Live(sentinel) = BucketState::decode(node_raw) {
+                    return sentinel;
+                }
+            }
+            // bucket 0 is always initialized
+            bucket = self.get_parent_bucket(bucket);
+        }
     }
 
     /// Moves the bucket cursor returned from `lookup_bucket` to the position of the given key.
-    /// Returns `(size, found, cursor)`
-    fn find<'s>(&'s self, key: &usize, guard: &'s Guard) -> (bool, Cursor<'s, usize, Option<V>>) {
-        let mut bucket_cursor = self.lookup_bucket(*key, guard);
+    /// Returns `(found, cursor)`
+    fn find<'s>(
+        &'s self,
+        key: &usize,
+        guard: &'s Guard,
+    ) -> (bool, Cursor<'s, SplitOrderedKey, Value<V>>) {
+        loop {
+            let mut bucket_cursor = self.lookup_bucket(*key, guard);
+            if let Ok(found) =
+                bucket_cursor.find_harris_michael(&Self::get_so_data_key(*key), guard)
+            {
+                return (found, bucket_cursor);
+            }
+        }
+    }
+
+    /// Counts the data nodes in the chain of `bucket`, stopping once the count exceeds `limit`.
+    fn chain_len(&self, bucket: usize, limit: usize, guard: &Guard) -> usize {
+        let bucket_raw = self.buckets.get(bucket, guard);
+        let node_raw = bucket_raw.load(Ordering::Acquire, guard);
+        let sentinel = match BucketState::decode(node_raw) {
+            BucketState::Live(sentinel) => sentinel,
+            _ => return 0,
+        };
+        let mut curr = sentinel.next().load(Ordering::Acquire, guard);
+        let mut len = 0;
+        while let Some(node) = unsafe { curr.with_tag(0).as_ref() } {
+            // the chain ends at the next sentinel
+            if !node.key().1 || len > limit {
+                break;
+            }
+            len += 1;
+            curr = node.next().load(Ordering::Acquire, guard);
+        }
+        len
+    }
+
+    /// Acquires the mini-lock of `bucket` if it is hot.
+    fn lock_bucket<'g>(&'g self, bucket: usize, guard: &'g Guard) -> Option<BucketLock<'g, V>> {
+        let bucket_raw = self.buckets.get(bucket, guard);
+        let backoff = Backoff::new();
+        loop {
+            let node_raw = bucket_raw.load(Ordering::Relaxed, guard);
+            match BucketState::decode(node_raw) {
+                BucketState::Live(_) if node_raw.tag() & HOT != 0 => {}
+                _ => return None,
+            }
+            if node_raw.tag() & LOCKED == 0
+                && bucket_raw
+                    .compare_exchange(
+                        node_raw,
+                        node_raw.with_tag(node_raw.tag() | LOCKED),
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                        guard,
+                    )
+                    .is_ok()
+            {
+                return Some(BucketLock {
+                    list: self,
+                    bucket,
+                    guard,
+                });
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Called by a writer after its CAS on `bucket` failed. Heats the bucket up if its chain is
+    /// too long.
+    fn on_contention(&self, bucket: usize, guard: &Guard) {
+        if self.chain_len(bucket, CHAIN_THRESHOLD, guard) > CHAIN_THRESHOLD {
+            self.update_live_tag(bucket, |t| t | HOT, Ordering::Relaxed, guard);
+        }
+    }
 
-        let found = bucket_cursor
-            .find_harris_michael(&Self::get_so_data_key(*key), guard)
-            .unwrap_or(false);
+    /// Applies `f` to the tag of `bucket` if it is live. The tag of a cell in any other state is
+    /// not a set of hot bucket flags.
+    fn update_live_tag<F: Fn(usize) -> usize>(
+        &self,
+        bucket: usize,
+        f: F,
+        success: Ordering,
+        guard: &Guard,
+    ) {
+        let bucket_raw = self.buckets.get(bucket, guard);
+        let mut node_raw = bucket_raw.load(Ordering::Relaxed, guard);
+        while let BucketState::Live(_) = BucketState::decode(node_raw) {
+            match bucket_raw.compare_exchange(
+                node_raw,
+                node_raw.with_tag(f(node_raw.tag())),
+                success,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(_) => break,
+                Err(e) => node_raw = e.current,
+            }
+        }
+    }
 
-        (found, bucket_cursor)
+    /// Uncounts a deleted item, halving `size` and retiring the upper half of the buckets if the
+    /// table got too sparse.
+    fn on_delete(&self, guard: &Guard) {
+        self.count.decrement();
+        let count = self.count.approx();
+        let size = self.size.load(Ordering::Relaxed);
+        // a quarter of the growing threshold, so that the table doesn't oscillate
+        if size > self.min_size
+            && count.saturating_mul(4) < size.saturating_mul(self.load_factor)
+            && self
+                .size
+                .compare_exchange(size, size / 2, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.retire_buckets(size / 2, size, guard);
+        }
+        self.preinitialize(PREINIT_BATCH, guard);
     }
 
-    fn assert_valid_key(key: usize) {
-        assert!(key.leading_zeros() != 0);
+    /// Commits a newly inserted item, doubling `size` if the table got too crowded.
+    fn on_insert(&self, guard: &Guard) {
+        self.committed.increment();
+        let prev_count = self.count.approx();
+        let prev_size = self.size.load(Ordering::Relaxed);
+        if prev_count > prev_size.saturating_mul(self.load_factor)
+            && self
+                .size
+                .compare_exchange(
+                    prev_size,
+                    prev_size * 2,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            // the new buckets are to be initialized in advance
+            let _ = self.preinit.fetch_min(prev_size, Ordering::Relaxed);
+        }
+        self.preinitialize(PREINIT_BATCH, guard);
+    }
+
+    /// Initializes up to `n` of the buckets that are not yet initialized in advance.
+    fn preinitialize(&self, n: usize, guard: &Guard) {
+        let mut from = self.preinit.load(Ordering::Relaxed);
+        loop {
+            let size = self.size.load(Ordering::Relaxed);
+            if from >= size {
+                return;
+            }
+            let to = size.min(from.saturating_add(n));
+            match self
+                .preinit
+                .compare_exchange(from, to, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    for bucket in from..to {
+                        let _ = self.get_bucket(bucket, guard);
+                    }
+                    return;
+                }
+                Err(current) => from = current,
+            }
+        }
+    }
+
+    /// Initializes all buckets, so that no operation pays for the initialization of a bucket until
+    /// the next doubling.
+    ///
+    /// Operations initialize the new buckets after a doubling anyway, a few at a time. This can be
+    /// called, e.g., on a helper thread to get it done at once.
+    pub fn initialize_buckets(&self, guard: &Guard) {
+        let size = self.size.load(Ordering::Relaxed);
+        // parents first, so that each initialization is a single insertion
+        for bucket in 0..size {
+            let _ = self.get_bucket(bucket, guard);
+        }
+        let _ = self.preinit.fetch_max(size, Ordering::Relaxed);
+    }
+}
+
+impl<V> Drop for SplitOrderedList<V> {
+    /// Frees every node still in the list, the sentinels and the nodes marked but not yet unlinked
+    /// included, with their values. The array segments are freed by `buckets`.
+    ///
+    /// Nodes and values removed before the drop are owned by the epoch collector, which frees
+    /// them once no thread can see them anymore (see [`collector_flush`](crate::collector_flush)).
+    fn drop(&mut self) {
+        // Nothing else accesses the list, and with an unprotected guard, removed nodes are
+        // destroyed immediately.
+        let guard = unsafe { unprotected() };
+        // unlink the marked nodes
+        while self
+            .list
+            .head(guard)
+            .find_harris_michael(&(usize::MAX, true), guard)
+            .is_err()
+        {}
+        loop {
+            let cursor = self.list.head(guard);
+            if cursor.curr().is_null() {
+                break;
+            }
+            let deleted = cursor.delete(guard);
+            debug_assert!(deleted.is_ok());
+        }
+    }
+}
+
+impl<V: Debug> Debug for SplitOrderedList<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Items of each initialized bucket, in split order.
+        struct Buckets<'a, V>(&'a SplitOrderedList<V>);
+
+        impl<V: Debug> Debug for Buckets<'_, V> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let guard = &epoch::pin();
+                let size = self.0.size.load(Ordering::Acquire);
+                let mut map = f.debug_map();
+                let mut bucket = None;
+                let mut items = Vec::new();
+                let mut curr = self.0.list.head(guard).curr();
+                while let Some(node) = unsafe { curr.as_ref() } {
+                    let next = node.next().load(Ordering::Acquire, guard);
+                    curr = next.with_tag(0);
+                    if next.tag() != 0 {
+                        continue;
+                    }
+                    let key = SplitOrderedList::<V>::get_key(*node.key());
+                    if node.key().1 {
+                        items.extend(node.value().get(guard).map(|value| (key, value)));
+                    } else if key < size {
+                        if let Some(bucket) = bucket.replace(key) {
+                            map.entry(&bucket, &DebugItems(&items));
+                        }
+                        items.clear();
+                    }
+                }
+                if let Some(bucket) = bucket {
+                    map.entry(&bucket, &DebugItems(&items));
+                }
+                map.finish()
+            }
+        }
+
+        struct DebugItems<'a, V>(&'a [(usize, &'a V)]);
+
+        impl<V: Debug> Debug for DebugItems<'_, V> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map()
+                    .entries(self.0.iter().map(|(key, value)| (key, value)))
+                    .finish()
+            }
+        }
+
+        let alternate = f.alternate();
+        let mut s = f.debug_struct("SplitOrderedList");
+        let _ = s
+            .field("size", &self.size.load(Ordering::Relaxed))
+            .field("len_bounds", &self.len_bounds())
+            .field("config", &self.config());
+        if alternate {
+            let _ = s.field("buckets", &Buckets(self));
+        }
+        s.finish()
+    }
+}
+
+impl<V> FromIterator<(usize, V)> for SplitOrderedList<V> {
+    /// Bulk-loads the pairs, keeping the last value of each key.
+    fn from_iter<I: IntoIterator<Item = (usize, V)>>(iter: I) -> Self {
+        let mut pairs = iter.into_iter().collect::<Vec<_>>();
+        pairs.sort_by_key(|(key, _)| *key);
+        // keep the last one, which `dedup_by_key` would drop
+        pairs.reverse();
+        pairs.dedup_by_key(|(key, _)| *key);
+        pairs.reverse();
+        Self::bulk_load(pairs)
+    }
+}
+
+impl<V: Clone> Clone for SplitOrderedList<V> {
+    /// Copies the items seen by a traversal of the list into a fresh list. Updates concurrent to
+    /// the traversal may or may not be copied.
+    fn clone(&self) -> Self {
+        let guard = &epoch::pin();
+        let list = Self::with_config(self.config());
+        for (key, value) in self.iter(guard) {
+            let _ = list.insert(&key, value.clone(), guard);
+        }
+        list
+    }
+}
+
+/// The items of a `SplitOrderedList` at a single point in time, sorted by key. Created by
+/// [`SplitOrderedList::snapshot`].
+///
+/// The values are protected by the guard, so the snapshot stays valid while the list changes.
+#[derive(Debug)]
+pub struct Snapshot<'g, V> {
+    items: Vec<(usize, &'g V)>,
+}
+
+impl<'g, V> Snapshot<'g, V> {
+    /// Returns the number of items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Returns `true` if there were no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the value of `key`.
+    pub fn get(&self, key: &usize) -> Option<&'g V> {
+        self.items
+            .binary_search_by_key(key, |(k, _)| *k)
+            .ok()
+            .map(|index| self.items[index].1)
+    }
+
+    /// Returns an iterator over the items, in increasing order of keys.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &'g V)> + '_ {
+        self.items.iter().copied()
+    }
+}
+
+impl<'g, V> IntoIterator for Snapshot<'g, V> {
+    type Item = (usize, &'g V);
+    type IntoIter = std::vec::IntoIter<(usize, &'g V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+/// Iterator over the items of a `SplitOrderedList`, created by [`SplitOrderedList::iter`], or of a
+/// [`FixedSplitOrderedList`](super::FixedSplitOrderedList).
+#[derive(Debug)]
+pub struct Iter<'g, V> {
+    curr: Shared<'g, Node<SplitOrderedKey, Value<V>>>,
+    guard: &'g Guard,
+}
+
+impl<'g, V> Iter<'g, V> {
+    pub(super) fn new(list: &'g List<SplitOrderedKey, Value<V>>, guard: &'g Guard) -> Self {
+        Self {
+            curr: list.head(guard).curr(),
+            guard,
+        }
+    }
+}
+
+impl<'g, V> Iterator for Iter<'g, V> {
+    type Item = (usize, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.curr.as_ref() }?;
+            let next = node.next().load(Ordering::Acquire, self.guard);
+            self.curr = next.with_tag(0);
+            if next.tag() != 0 {
+                continue;
+            }
+            // sentinels have null values
+            if let Some(value) = node.value().get(self.guard) {
+                return Some((SplitOrderedList::<V>::get_key(*node.key()), value));
+            }
+        }
     }
 }
 
 impl<V> NonblockingMap<usize, V> for SplitOrderedList<V> {
+    /// Doesn't allocate: it neither initializes buckets nor helps unlinking deleted nodes.
     fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
-        Self::assert_valid_key(*key);
-        let (found, cursor) = self.find(key, guard);
-        match found {
-            true => cursor.lookup()?.into(),
-            false => None,
+        let so_key = Self::get_so_data_key(*key);
+        let sentinel = self.read_bucket(*key, guard);
+        let mut curr = sentinel.next().load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.with_tag(0).as_ref() } {
+            let next = node.next().load(Ordering::Acquire, guard);
+            match node.key().cmp(&so_key) {
+                cmp::Ordering::Less => {}
+                // a deleted node may be followed by a new node of the same key
+                cmp::Ordering::Equal if next.tag() != 0 => {}
+                cmp::Ordering::Equal => return node.value().get(guard),
+                cmp::Ordering::Greater => return None,
+            }
+            curr = next;
         }
+        None
     }
 
     fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
-        Self::assert_valid_key(*key);
-        let (found, mut cursor) = self.find(key, guard);
-        if found {
-            return Err(value);
-        }
-
-        let mut node = Owned::new(Node::new(Self::get_so_data_key(*key), Some(value)));
-        match cursor.insert(node, guard) {
-            Ok(_) => {
-                let prev_count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
-                let prev_size = self.size.load(Ordering::Relaxed);
-                if prev_count > prev_size * Self::LOAD_FACTOR {
-                    // we don't care about the results, both way, we win!
-                    let _ = self.size.compare_exchange(
-                        prev_size,
-                        prev_size * 2,
-                        Ordering::Release,
-                        Ordering::Relaxed,
-                    );
+        let write = self.begin_write();
+        let bucket = *key % self.size.load(Ordering::Relaxed);
+        let bucket_lock = self.lock_bucket(bucket, guard);
+
+        let mut node = Owned::new(Node::new(Self::get_so_data_key(*key), Value::new(value)));
+        self.count.increment();
+        loop {
+            let (found, mut cursor) = self.find(key, guard);
+            if found {
+                if self.tombstone_ttl.is_some() {
+                    let curr = unsafe { cursor.curr().deref() };
+                    let new = node
+                        .value()
+                        .0
+                        .swap(Shared::null(), Ordering::Relaxed, guard);
+                    match curr.value().revive(unsafe { new.into_owned() }, guard) {
+                        Ok(()) => {
+                            self.tombstones.decrement();
+                            break;
+                        }
+                        Err((new, again)) => {
+                            node.value().0.store(new, Ordering::Relaxed);
+                            if again {
+                                continue;
+                            }
+                        }
+                    }
+                }
+                self.count.decrement();
+                return Err(node.into_box().into_value().into_inner());
+            }
+
+            match cursor.insert(node, guard) {
+                Ok(_) => break,
+                Err(n) => {
+                    node = n;
+                    if bucket_lock.is_none() {
+                        self.on_contention(bucket, guard);
+                    }
                 }
-                Ok(())
             }
-            Err(n) => Err(n.into_box().into_value().unwrap()),
         }
+        drop(bucket_lock);
+        drop(write);
+
+        self.on_insert(guard);
+        Ok(())
     }
 
     fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, ()> {
-        Self::assert_valid_key(*key);
-        let (found, cursor) = self.find(key, guard);
-        if !found {
-            return Err(());
+        let write = self.begin_write();
+        let bucket = *key % self.size.load(Ordering::Relaxed);
+        let bucket_lock = self.lock_bucket(bucket, guard);
+
+        self.committed.decrement();
+        loop {
+            let (found, cursor) = self.find(key, guard);
+            if !found {
+                self.committed.increment();
+                return Err(());
+            }
+            if self.tombstone_ttl.is_some() {
+                let node = unsafe { cursor.curr().deref() };
+                match node.value().tombstone(self.now(), guard) {
+                    Ok(v) => {
+                        drop(bucket_lock);
+                        drop(write);
+                        self.tombstones.increment();
+                        self.on_delete(guard);
+                        self.maybe_compact(guard);
+                        return Ok(v);
+                    }
+                    Err(true) => continue,
+                    Err(false) => {
+                        self.committed.increment();
+                        return Err(());
+                    }
+                }
+            }
+            match cursor.delete(guard) {
+                Ok(v) => {
+                    drop(bucket_lock);
+                    drop(write);
+                    let v = v.freeze(guard);
+                    self.on_delete(guard);
+                    return Ok(v);
+                }
+                Err(_) => {
+                    if bucket_lock.is_none() {
+                        self.on_contention(bucket, guard);
+                    }
+                }
+            }
         }
-        match cursor.delete(guard) {
-            Ok(v) => {
-                self.count.fetch_sub(1, Ordering::Relaxed);
-                v.as_ref().ok_or(())
+    }
+
+    /// Replaces the value of an existing key in place.
+    fn upsert<'a>(&'a self, key: &usize, value: V, guard: &'a Guard) -> Result<Option<&'a V>, V> {
+        let write = self.begin_write();
+        let bucket = *key % self.size.load(Ordering::Relaxed);
+        let bucket_lock = self.lock_bucket(bucket, guard);
+
+        let mut node = Owned::new(Node::new(Self::get_so_data_key(*key), Value::new(value)));
+        self.count.increment();
+        loop {
+            let (found, mut cursor) = self.find(key, guard);
+            if !found {
+                match cursor.insert(node, guard) {
+                    Ok(_) => {
+                        drop(bucket_lock);
+                        drop(write);
+                        self.on_insert(guard);
+                        return Ok(None);
+                    }
+                    Err(n) => node = n,
+                }
+            } else {
+                let curr_node = unsafe { cursor.curr().deref() };
+                let current = curr_node.value().0.load(Ordering::Acquire, guard);
+                // the value is moved back and forth between `node` and an `Owned`
+                if current.is_null() {
+                    let new = node
+                        .value()
+                        .0
+                        .swap(Shared::null(), Ordering::Relaxed, guard);
+                    match curr_node.value().revive(unsafe { new.into_owned() }, guard) {
+                        Ok(()) => {
+                            drop(bucket_lock);
+                            drop(write);
+                            self.tombstones.decrement();
+                            self.on_insert(guard);
+                            return Ok(None);
+                        }
+                        Err((new, _)) => node.value().0.store(new, Ordering::Relaxed),
+                    }
+                } else if current.tag() == 0 && !is_reaped(current) {
+                    let new = node
+                        .value()
+                        .0
+                        .swap(Shared::null(), Ordering::Relaxed, guard);
+                    match curr_node
+                        .value()
+                        .replace(current, unsafe { new.into_owned() }, guard)
+                    {
+                        Ok(old) => {
+                            self.count.decrement();
+                            return Ok(Some(old));
+                        }
+                        Err(new) => node.value().0.store(new, Ordering::Relaxed),
+                    }
+                }
+            }
+            if bucket_lock.is_none() {
+                self.on_contention(bucket, guard);
             }
-            Err(_) => Err(()),
         }
     }
 }
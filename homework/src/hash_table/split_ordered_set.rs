@@ -0,0 +1,120 @@
+//! Lock-free hash set built on [`SplitOrderedList`].
+
+use core::fmt;
+use crossbeam_epoch::{self as epoch, Guard};
+
+use super::split_ordered_list::{Config, SplitOrderedList};
+use crate::map::NonblockingMap;
+
+/// Lock-free hash set of `usize` keys.
+///
+/// It is a [`SplitOrderedList`] of unit values, so it grows, shrinks and reclaims memory the same
+/// way. The set operations take a snapshot with [`iter`](SplitOrderedSet::iter), which is weakly
+/// consistent: keys inserted or removed concurrently may or may not be taken into account.
+#[derive(Default, Clone)]
+pub struct SplitOrderedSet {
+    list: SplitOrderedList<()>,
+}
+
+impl fmt::Debug for SplitOrderedSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter(&epoch::pin())).finish()
+    }
+}
+
+impl SplitOrderedSet {
+    /// Creates a new set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new set with the given configuration.
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            list: SplitOrderedList::with_config(config),
+        }
+    }
+
+    /// Inserts `key`. Returns `true` if it was not in the set.
+    pub fn insert(&self, key: usize, guard: &Guard) -> bool {
+        self.list.insert(&key, (), guard).is_ok()
+    }
+
+    /// Returns `true` if the set contains `key`.
+    pub fn contains(&self, key: &usize, guard: &Guard) -> bool {
+        self.list.lookup(key, guard).is_some()
+    }
+
+    /// Removes `key`. Returns `true` if it was in the set.
+    pub fn remove(&self, key: &usize, guard: &Guard) -> bool {
+        self.list.delete(key, guard).is_ok()
+    }
+
+    /// Returns `(lower, upper)` bounds of the number of keys. See
+    /// [`SplitOrderedList::len_bounds`].
+    pub fn len_bounds(&self) -> (usize, usize) {
+        self.list.len_bounds()
+    }
+
+    /// Removes all keys.
+    pub fn clear(&self, guard: &Guard) {
+        self.list.clear(guard);
+    }
+
+    /// Removes the keys for which `f` returns `false`.
+    pub fn retain<F: FnMut(usize) -> bool>(&self, mut f: F, guard: &Guard) {
+        self.list.retain(|key, _| f(key), guard);
+    }
+
+    /// Returns a weakly consistent iterator over the keys, in split order. See
+    /// [`SplitOrderedList::iter`].
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> impl Iterator<Item = usize> + 'g {
+        self.list.iter(guard).map(|(key, _)| key)
+    }
+
+    /// Returns a new set of the keys in `self` or in `other`.
+    pub fn union(&self, other: &Self, guard: &Guard) -> Self {
+        self.iter(guard).chain(other.iter(guard)).collect()
+    }
+
+    /// Returns a new set of the keys in both `self` and `other`.
+    pub fn intersection(&self, other: &Self, guard: &Guard) -> Self {
+        self.iter(guard)
+            .filter(|key| other.contains(key, guard))
+            .collect()
+    }
+
+    /// Returns a new set of the keys in `self` but not in `other`.
+    pub fn difference(&self, other: &Self, guard: &Guard) -> Self {
+        self.iter(guard)
+            .filter(|key| !other.contains(key, guard))
+            .collect()
+    }
+
+    /// Returns `true` if every key of `self` is in `other`.
+    pub fn is_subset(&self, other: &Self, guard: &Guard) -> bool {
+        self.iter(guard).all(|key| other.contains(&key, guard))
+    }
+
+    /// Returns `true` if `self` and `other` have no key in common.
+    pub fn is_disjoint(&self, other: &Self, guard: &Guard) -> bool {
+        self.iter(guard).all(|key| !other.contains(&key, guard))
+    }
+}
+
+impl FromIterator<usize> for SplitOrderedSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        Self {
+            list: iter.into_iter().map(|key| (key, ())).collect(),
+        }
+    }
+}
+
+impl Extend<usize> for SplitOrderedSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        let guard = &epoch::pin();
+        for key in iter {
+            let _ = self.insert(key, guard);
+        }
+    }
+}
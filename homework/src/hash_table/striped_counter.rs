@@ -0,0 +1,142 @@
+//! Counter that threads update without contending.
+
+use core::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use crossbeam_utils::CachePadded;
+use std::thread;
+
+/// Number of changes of a stripe between two foldings into the estimate.
+const BATCH: isize = 32;
+
+/// Maximum number of stripes.
+const MAX_STRIPES: usize = 64;
+
+/// Counter whose value is spread over stripes on separate cache lines, one for each thread (or a
+/// few threads if there are many).
+///
+/// A stripe counts its increments and decrements separately. Since both only grow, the sum of the
+/// decrements read before the sum of the increments bounds the value during the read from above,
+/// and the other way around from below.
+///
+/// A thread also folds its stripe into a shared estimate every `BATCH` changes. [`approx`] is the
+/// estimate and the unfolded changes of the current thread's stripe, so it is exact for a single
+/// thread and off by less than `BATCH` for each other stripe.
+///
+/// [`approx`]: StripedCounter::approx
+#[derive(Debug)]
+pub(crate) struct StripedCounter {
+    stripes: Box<[CachePadded<Stripe>]>,
+    /// The initial value and the folded changes of all stripes.
+    estimate: AtomicIsize,
+    initial: usize,
+}
+
+#[derive(Debug, Default)]
+struct Stripe {
+    increments: AtomicUsize,
+    decrements: AtomicUsize,
+    /// `increments - decrements` that is folded into the estimate.
+    folded: AtomicIsize,
+}
+
+impl Stripe {
+    fn unfolded(&self) -> isize {
+        let net = self
+            .increments
+            .load(Ordering::Relaxed)
+            .wrapping_sub(self.decrements.load(Ordering::Relaxed)) as isize;
+        net.wrapping_sub(self.folded.load(Ordering::Relaxed))
+    }
+}
+
+impl StripedCounter {
+    /// Creates a counter of `initial`, with a stripe for each CPU.
+    pub(crate) fn new(initial: usize) -> Self {
+        let stripes = thread::available_parallelism()
+            .map_or(8, |n| n.get())
+            .next_power_of_two()
+            .min(MAX_STRIPES);
+        Self {
+            stripes: (0..stripes).map(|_| CachePadded::default()).collect(),
+            estimate: AtomicIsize::new(initial as isize),
+            initial,
+        }
+    }
+
+    fn stripe(&self) -> &Stripe {
+        thread_local! {
+            static INDEX: usize = {
+                static NEXT: AtomicUsize = AtomicUsize::new(0);
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            };
+        }
+        let index = INDEX.with(|index| *index);
+        &self.stripes[index & (self.stripes.len() - 1)]
+    }
+
+    /// Folds the stripe into the estimate if it changed enough.
+    fn fold(&self, stripe: &Stripe) {
+        let folded = stripe.folded.load(Ordering::Relaxed);
+        let unfolded = stripe.unfolded();
+        if unfolded.abs() >= BATCH
+            && stripe
+                .folded
+                .compare_exchange(
+                    folded,
+                    folded.wrapping_add(unfolded),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            let _ = self.estimate.fetch_add(unfolded, Ordering::Relaxed);
+        }
+    }
+
+    /// Increments the counter.
+    pub(crate) fn increment(&self) {
+        let stripe = self.stripe();
+        let _ = stripe.increments.fetch_add(1, Ordering::SeqCst);
+        self.fold(stripe);
+    }
+
+    /// Decrements the counter.
+    pub(crate) fn decrement(&self) {
+        let stripe = self.stripe();
+        let _ = stripe.decrements.fetch_add(1, Ordering::SeqCst);
+        self.fold(stripe);
+    }
+
+    /// Returns an approximate value, cheaply.
+    pub(crate) fn approx(&self) -> usize {
+        let estimate = self.estimate.load(Ordering::Relaxed);
+        estimate.wrapping_add(self.stripe().unfolded()).max(0) as usize
+    }
+
+    fn sum(&self, read_increments_first: bool) -> isize {
+        let sum = |field: fn(&Stripe) -> &AtomicUsize| {
+            self.stripes.iter().fold(0usize, |sum, s| {
+                sum.wrapping_add(field(s).load(Ordering::SeqCst))
+            })
+        };
+        let (increments, decrements) = if read_increments_first {
+            let increments = sum(|s| &s.increments);
+            (increments, sum(|s| &s.decrements))
+        } else {
+            let decrements = sum(|s| &s.decrements);
+            (sum(|s| &s.increments), decrements)
+        };
+        self.initial
+            .wrapping_add(increments)
+            .wrapping_sub(decrements) as isize
+    }
+
+    /// Returns a value that is at least the value at some point during the call.
+    pub(crate) fn upper(&self) -> isize {
+        self.sum(false)
+    }
+
+    /// Returns a value that is at most the value at some point during the call.
+    pub(crate) fn lower(&self) -> isize {
+        self.sum(true)
+    }
+}
@@ -1,4 +1,5 @@
 use core::marker::PhantomData;
+use core::mem;
 use core::ptr::{self, NonNull};
 use std::collections::HashSet;
 use std::{fmt, thread};
@@ -60,6 +61,15 @@ impl<T> Shield<T> {
         }
         pointer
     }
+
+    /// Converts the shield into one for pointers to `U`, keeping the pointer it protects.
+    pub(crate) fn cast<U>(self) -> Shield<U> {
+        let this = mem::ManuallyDrop::new(self);
+        Shield {
+            slot: this.slot,
+            _marker: PhantomData,
+        }
+    }
 }
 
 impl<T> Default for Shield<T> {
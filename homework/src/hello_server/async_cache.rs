@@ -0,0 +1,165 @@
+//! Key/value cache for asynchronous computations.
+
+use core::convert::Infallible;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+#[derive(Debug)]
+enum Slot<V> {
+    Value(Arc<V>),
+    /// The wakers of the lookups waiting for the computation.
+    Computing(Vec<Waker>),
+}
+
+/// Cache that remembers the result of an asynchronous computation for each key.
+///
+/// Like [`Cache`](super::Cache), the computation of a key runs only once even for concurrent
+/// lookups of the key, but the other lookups await it instead of blocking their thread, and
+/// lookups of other keys never wait for it. The lock of the map is only held between the polls,
+/// never across an `.await`.
+///
+/// A computation that panics, fails, or is cancelled by dropping its future leaves the key
+/// uncomputed, so one of the lookups awaiting it runs its own computation instead.
+#[derive(Debug)]
+pub struct AsyncCache<K, V> {
+    map: Mutex<HashMap<K, Slot<V>>>,
+}
+
+impl<K, V> Default for AsyncCache<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> AsyncCache<K, V> {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self {
+            map: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Locks the map, ignoring poisoning. User code never runs while it's locked, except for the
+    /// `Hash` and `Eq` impls, which panic between the `HashMap` operations.
+    fn lock(&self) -> MutexGuard<'_, HashMap<K, Slot<V>>> {
+        self.map.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Removes the `Computing` slot of the key if the computation panics, fails or is cancelled, and
+/// wakes up the waiters so that one of them retries.
+struct ComputeGuard<'a, K: Eq + Hash, V> {
+    cache: &'a AsyncCache<K, V>,
+    key: &'a K,
+}
+
+impl<K: Eq + Hash, V> Drop for ComputeGuard<'_, K, V> {
+    fn drop(&mut self) {
+        let slot = self.cache.lock().remove(self.key);
+        if let Some(Slot::Computing(wakers)) = slot {
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+/// Future of a lookup waiting for the computation of its key to complete or to be abandoned.
+struct Wait<'a, K, V> {
+    cache: &'a AsyncCache<K, V>,
+    key: &'a K,
+}
+
+impl<K: Eq + Hash, V> Future for Wait<'_, K, V> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match self.cache.lock().get_mut(self.key) {
+            Some(Slot::Computing(wakers)) => {
+                if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            _ => Poll::Ready(()),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> AsyncCache<K, V> {
+    /// Returns the value of the key, or computes it by awaiting the future returned by `f`.
+    ///
+    /// The concurrent lookups of the key await the same computation, so `f` is called only once.
+    pub async fn get_or_insert_with<F, Fut>(&self, key: K, f: F) -> Arc<V>
+    where
+        F: FnOnce(K) -> Fut,
+        Fut: Future<Output = V>,
+    {
+        let result = self
+            .get_or_try_insert_with(key, |key| async { Ok::<_, Infallible>(f(key).await) })
+            .await;
+        match result {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Like [`get_or_insert_with`](AsyncCache::get_or_insert_with), but the computation may fail.
+    /// The error is returned and nothing is cached, so a lookup waiting for the key or a later
+    /// one computes it again.
+    pub async fn get_or_try_insert_with<F, Fut, E>(&self, key: K, f: F) -> Result<Arc<V>, E>
+    where
+        F: FnOnce(K) -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        loop {
+            // in a block so that the lock isn't held across the `.await`
+            let computing = {
+                let mut map = self.lock();
+                match map.get(&key) {
+                    Some(Slot::Value(v)) => return Ok(Arc::clone(v)),
+                    Some(Slot::Computing(_)) => true,
+                    None => {
+                        let _ = map.insert(key.clone(), Slot::Computing(Vec::new()));
+                        false
+                    }
+                }
+            };
+            if !computing {
+                break;
+            }
+            // check again since the computation may have been abandoned
+            Wait {
+                cache: self,
+                key: &key,
+            }
+            .await;
+        }
+
+        // first one to fetch the key
+        let guard = ComputeGuard {
+            cache: self,
+            key: &key,
+        };
+        // on an error or a cancellation, the guard removes the slot
+        let v = Arc::new(f(key.clone()).await?);
+        mem::forget(guard);
+
+        let slot = self.lock().insert(key, Slot::Value(Arc::clone(&v)));
+        if let Some(Slot::Computing(wakers)) = slot {
+            wakers.into_iter().for_each(Waker::wake);
+        }
+        Ok(v)
+    }
+
+    /// Returns the value of the key if it's computed, without awaiting a computation.
+    pub fn peek(&self, key: &K) -> Option<Arc<V>> {
+        match self.lock().get(key)? {
+            Slot::Value(v) => Some(Arc::clone(v)),
+            Slot::Computing(_) => None,
+        }
+    }
+}
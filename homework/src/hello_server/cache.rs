@@ -1,32 +1,814 @@
 //! Thread-safe key/value cache.
 
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use once_cell::sync::OnceCell;
 use std::collections::hash_map::{Entry, HashMap};
 use std::collections::HashSet;
+use std::convert::Infallible;
 use std::default::Default;
-use std::fmt::Debug;
-use std::hash::Hash;
+use std::fmt::{self, Debug, Display};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, BufRead, Write};
+use std::mem;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::rc::Rc;
-use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::str;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::eviction::{EvictionPolicy, Fifo};
+use super::thread_pool::ThreadPool;
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "deterministic")] {
+        use std::collections::hash_map::DefaultHasher;
+
+        /// Hasher picking the shards of the keys. With the `deterministic` feature, it is seeded
+        /// with the [workload seed](crate::workload_seed), so that a key is in the same shard on
+        /// each run.
+        #[derive(Debug, Clone)]
+        struct ShardHasher(u64);
+
+        impl ShardHasher {
+            fn new() -> Self {
+                Self(crate::workload_seed())
+            }
+        }
+
+        impl BuildHasher for ShardHasher {
+            type Hasher = DefaultHasher;
+
+            fn build_hasher(&self) -> DefaultHasher {
+                // the keys of `DefaultHasher::new` are fixed
+                let mut hasher = DefaultHasher::new();
+                hasher.write_u64(self.0);
+                hasher
+            }
+        }
+    } else {
+        /// Hasher picking the shards of the keys.
+        type ShardHasher = std::collections::hash_map::RandomState;
+    }
+}
+
+/// Number of shards of an unbounded cache, unless configured otherwise.
+const DEFAULT_SHARDS: usize = 16;
+
+#[derive(Debug)]
+struct Computed<V> {
+    value: Arc<V>,
+    /// When the value expires, if it has a time-to-live.
+    expires_at: Option<Instant>,
+    /// Weight of the value, 1 without a weigher.
+    weight: usize,
+    /// Whether a background computation is refreshing the expired value. See
+    /// [`Cache::get_or_refresh_with`].
+    refreshing: bool,
+}
+
+impl<V> Clone for Computed<V> {
+    fn clone(&self) -> Self {
+        Self {
+            value: Arc::clone(&self.value),
+            expires_at: self.expires_at,
+            weight: self.weight,
+            refreshing: false,
+        }
+    }
+}
+
+impl<V> Computed<V> {
+    fn is_expired(&self) -> bool {
+        self.expires_at.map_or(false, |at| at <= Instant::now())
+    }
+}
 
 #[derive(Debug)]
 enum CacheEntry<V> {
-    Value(V),
+    Value(Computed<V>),
     Computing(Arc<Condvar>),
 }
 
-impl<V> Default for CacheEntry<V> {
-    fn default() -> Self {
-        Self::Computing(Arc::new(Condvar::new()))
+/// Why an entry was removed from the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RemovalCause {
+    /// The cache held more entries than its capacity.
+    Capacity,
+    /// The time-to-live of the entry elapsed.
+    Expired,
+    /// The entry was removed by [`Cache::invalidate`], [`Cache::invalidate_tag`] or
+    /// [`Cache::invalidate_all`].
+    Explicit,
+}
+
+/// Statistics of a [`Cache`], returned by [`Cache::stats`].
+///
+/// Every lookup is either a hit or a miss. A miss either starts a computation or waits for the
+/// computation of another lookup, which is coalesced.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found a computed value.
+    pub hits: u64,
+    /// Lookups that found no computed value.
+    pub misses: u64,
+    /// Computations started by the misses.
+    pub computations: u64,
+    /// Misses that waited for a computation in flight instead of starting one.
+    pub coalesced: u64,
+    /// Number of computed values in the cache that are not expired.
+    pub entries: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    computations: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl Counters {
+    fn add(counter: &AtomicU64) {
+        let _ = counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that a lookup waits for a computation, unless it already `waited`, and returns
+    /// `true`.
+    fn wait(&self, waited: bool) -> bool {
+        if !waited {
+            Self::add(&self.misses);
+            Self::add(&self.coalesced);
+        }
+        true
+    }
+}
+
+/// Counting semaphore bounding the number of computations running at once. See
+/// [`CacheBuilder::max_concurrent_computations`].
+#[derive(Debug)]
+struct Semaphore {
+    permits: Mutex<usize>,
+    released: Condvar,
+}
+
+impl Semaphore {
+    fn new(permits: usize) -> Self {
+        Self {
+            permits: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits for a permit, which is released when dropped. The lock is never poisoned, as no
+    /// user code runs while it's held.
+    fn acquire(&self) -> Permit<'_> {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.released.wait(permits).unwrap();
+        }
+        *permits -= 1;
+        Permit(self)
+    }
+}
+
+struct Permit<'a>(&'a Semaphore);
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.permits.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Eviction listener, which runs the user's callback on a pool of a single worker, so that the
+/// calls are in the order of the evictions.
+struct Listener<K, V> {
+    notify: Box<dyn Fn(K, Arc<V>, RemovalCause) + Send + Sync>,
+}
+
+impl<K, V> Listener<K, V> {
+    fn send(&self, removed: Vec<(K, Arc<V>)>, cause: RemovalCause) {
+        for (key, value) in removed {
+            (self.notify)(key, value, cause);
+        }
+    }
+}
+
+impl<K, V> Debug for Listener<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Listener")
+    }
+}
+
+type WeighFn<K, V> = dyn Fn(&K, &V) -> usize + Send + Sync;
+
+/// Weight function of the values. See [`CacheBuilder::weigher`].
+struct Weigher<K, V>(Box<WeighFn<K, V>>);
+
+impl<K, V> Debug for Weigher<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Weigher")
+    }
+}
+
+/// Returns the weight of the value with `weigher`, if any.
+fn weigh<K, V>(weigher: &Option<Arc<Weigher<K, V>>>, key: &K, value: &V) -> usize {
+    weigher
+        .as_ref()
+        .map_or(1, |weigher| (weigher.0)(key, value))
+}
+
+/// Entries of a shard.
+#[derive(Debug)]
+struct Entries<K, V, P> {
+    map: HashMap<K, CacheEntry<V>>,
+    /// Bound on the total weight of the computed values of the shard.
+    capacity: Option<usize>,
+    weigher: Option<Arc<Weigher<K, V>>>,
+    /// Total weight of the computed values.
+    weight: usize,
+    /// Tracks the keys of the computed values, if the shard has a capacity.
+    policy: P,
+    /// Keys of the entries carrying each tag. See [`Cache::get_or_insert_with_tags`].
+    tagged: HashMap<String, HashSet<K>>,
+    /// Tags of each tagged entry.
+    tags: HashMap<K, Vec<String>>,
+}
+
+impl<K, V, P> Entries<K, V, P> {
+    fn new(capacity: Option<usize>, weigher: Option<Arc<Weigher<K, V>>>, policy: P) -> Self {
+        Self {
+            map: HashMap::new(),
+            capacity,
+            weigher,
+            weight: 0,
+            policy,
+            tagged: HashMap::new(),
+            tags: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, P: EvictionPolicy<K>> Entries<K, V, P> {
+    /// Returns `true` if the key is being computed by the computation of `condvar`, i.e. it
+    /// wasn't invalidated since the computation started.
+    fn is_computing(&self, key: &K, condvar: &Arc<Condvar>) -> bool {
+        matches!(self.map.get(key), Some(CacheEntry::Computing(c)) if Arc::ptr_eq(c, condvar))
+    }
+
+    /// Tags the entry of the key, which has no tags yet.
+    fn tag(&mut self, key: &K, tags: Vec<String>)
+    where
+        K: Clone,
+    {
+        if tags.is_empty() {
+            return;
+        }
+        for tag in &tags {
+            let _ = self
+                .tagged
+                .entry(tag.clone())
+                .or_insert_with(HashSet::new)
+                .insert(key.clone());
+        }
+        let _ = self.tags.insert(key.clone(), tags);
+    }
+
+    /// Removes the tags of the key, whose entry was removed.
+    fn untag(&mut self, key: &K) {
+        for tag in self.tags.remove(key).into_iter().flatten() {
+            if let Entry::Occupied(mut keys) = self.tagged.entry(tag) {
+                let _ = keys.get_mut().remove(key);
+                if keys.get().is_empty() {
+                    let _ = keys.remove();
+                }
+            }
+        }
+    }
+
+    /// Removes the `Computing` entry of the key, if any, and returns its condvar.
+    fn remove_computing(&mut self, key: &K) -> Option<Arc<Condvar>> {
+        if !matches!(self.map.get(key), Some(CacheEntry::Computing(_))) {
+            return None;
+        }
+        self.untag(key);
+        match self.map.remove(key) {
+            Some(CacheEntry::Computing(condvar)) => Some(condvar),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Removes the computed value of the key, if any. An entry that is being computed is left.
+    fn remove(&mut self, key: &K) -> Option<Arc<V>> {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Like [`remove`](Entries::remove), but also returns the key of the map.
+    fn remove_entry(&mut self, key: &K) -> Option<(K, Arc<V>)> {
+        if !matches!(self.map.get(key), Some(CacheEntry::Value(_))) {
+            return None;
+        }
+        self.untag(key);
+        if self.capacity.is_some() {
+            self.policy.on_remove(key);
+        }
+        match self.map.remove_entry(key) {
+            Some((key, CacheEntry::Value(computed))) => {
+                self.weight -= computed.weight;
+                Some((key, computed.value))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Records a lookup that found the computed value of the key.
+    fn hit(&mut self, key: &K) {
+        if self.capacity.is_some() {
+            self.policy.on_hit(key);
+        }
+    }
+
+    /// Returns `true` if a value of the weight fits in the shard, even if it's empty.
+    fn fits(&self, weight: usize) -> bool {
+        self.capacity.map_or(true, |capacity| weight <= capacity)
+    }
+
+    /// Inserts the computed value of the key, replacing its `Computing` entry, and returns the
+    /// values evicted for it. The value itself is evicted if it doesn't fit.
+    fn insert(&mut self, key: K, computed: Computed<V>) -> Vec<(K, Arc<V>)>
+    where
+        K: Clone,
+    {
+        if !self.fits(computed.weight) {
+            let _ = self.remove_computing(&key);
+            return vec![(key, computed.value)];
+        }
+        self.weight += computed.weight;
+        if self.capacity.is_some() {
+            self.policy.on_insert(&key);
+        }
+        let _ = self.map.insert(key, CacheEntry::Value(computed));
+        self.evict()
+    }
+
+    /// Evicts the victims of the policy while the values weigh more than the capacity, and
+    /// returns them.
+    fn evict(&mut self) -> Vec<(K, Arc<V>)> {
+        let mut evicted = Vec::new();
+        let capacity = some_or!(self.capacity, return evicted);
+        while self.weight > capacity {
+            let victim = some_or!(self.policy.pick_victim(), break);
+            // the policy already stopped tracking the victim
+            if let Some(CacheEntry::Value(c)) = self.map.remove(&victim) {
+                self.weight -= c.weight;
+                self.untag(&victim);
+                evicted.push((victim, c.value));
+            }
+        }
+        evicted
+    }
+
+    /// Removes all expired values.
+    fn remove_expired(&mut self) -> Vec<(K, Arc<V>)>
+    where
+        K: Clone,
+    {
+        let expired = self
+            .map
+            .iter()
+            .filter(|(_, entry)| matches!(entry, CacheEntry::Value(c) if c.is_expired()))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        if expired.is_empty() {
+            return Vec::new();
+        }
+        expired
+            .into_iter()
+            .filter_map(|key| self.remove(&key).map(|value| (key, value)))
+            .collect()
+    }
+
+    /// Removes all entries.
+    fn take(&mut self) -> HashMap<K, CacheEntry<V>> {
+        let map = mem::take(&mut self.map);
+        self.weight = 0;
+        self.tagged.clear();
+        self.tags.clear();
+        if self.capacity.is_some() {
+            for (key, entry) in &map {
+                if let CacheEntry::Value(_) = entry {
+                    self.policy.on_remove(key);
+                }
+            }
+        }
+        map
+    }
+}
+
+/// Locks the entries of a shard, ignoring poisoning. The map is never left half-updated by a
+/// panic, since user code (`f`, the weigher, and the `Hash`, `Eq` and `Clone` impls) only panics
+/// between `HashMap` operations.
+fn lock<K, V, P>(data: &Mutex<Entries<K, V, P>>) -> MutexGuard<'_, Entries<K, V, P>> {
+    data.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Spawns the sweeper of a cache. It's a function pointer so that only
+/// [`CacheBuilder::sweep_interval`] needs the bounds of the sweeper thread.
+struct SpawnSweeper<K, V, P>(SpawnFn<K, V, P>);
+
+type SpawnFn<K, V, P> = fn(&Cache<K, V, P>, Duration) -> Sweeper<K, V, P>;
+
+impl<K, V, P> Debug for SpawnSweeper<K, V, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SpawnSweeper")
+    }
+}
+
+/// Background thread removing the expired values periodically.
+#[derive(Debug)]
+struct Sweeper<K, V, P> {
+    interval: Duration,
+    spawn: SpawnSweeper<K, V, P>,
+    /// Dropped to stop the thread.
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<K, V, P> Sweeper<K, V, P>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + Sync + 'static,
+    P: EvictionPolicy<K> + Send + 'static,
+{
+    fn spawn(cache: &Cache<K, V, P>, interval: Duration) -> Self {
+        let (stop, stopped) = bounded::<()>(0);
+        let shards = Arc::clone(&cache.shards);
+        let listener = cache.listener.clone();
+        let thread = thread::Builder::new()
+            .name("cache-sweeper".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    for shard in shards.iter() {
+                        let expired = lock(shard).remove_expired();
+                        if let Some(listener) = &listener {
+                            listener.send(expired, RemovalCause::Expired);
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn the cache sweeper");
+        Self {
+            interval,
+            spawn: SpawnSweeper(Self::spawn),
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl<K, V, P> Drop for Sweeper<K, V, P> {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns an error of a malformed dump. See [`Cache::dump`].
+fn invalid_dump(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Reads the bytes until `delim`, which is consumed but not returned, or returns `None` at the
+/// end of the reader.
+fn read_until<R: BufRead>(reader: &mut R, delim: u8) -> io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    if reader.read_until(delim, &mut buf)? == 0 {
+        return Ok(None);
+    }
+    if buf.pop() != Some(delim) {
+        return Err(invalid_dump("truncated dump"));
+    }
+    String::from_utf8(buf)
+        .map(Some)
+        .map_err(|_| invalid_dump("non-UTF-8 dump"))
+}
+
+/// Reads a length-prefixed field followed by `delim`, and parses it.
+fn read_field<R: BufRead, T: FromStr>(reader: &mut R, delim: u8) -> io::Result<T> {
+    let len = read_until(reader, b':')?
+        .and_then(|len| len.parse::<usize>().ok())
+        .ok_or_else(|| invalid_dump("invalid field length"))?;
+    let mut buf = vec![0; len + 1];
+    reader.read_exact(&mut buf)?;
+    if buf.pop() != Some(delim) {
+        return Err(invalid_dump("invalid field"));
     }
+    str::from_utf8(&buf)
+        .ok()
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| invalid_dump("invalid field"))
 }
 
 /// Cache that remembers the result for each key.
-#[derive(Debug, Default)]
-pub struct Cache<K, V> {
-    data: Mutex<HashMap<K, CacheEntry<V>>>,
+///
+/// The keys are split into shards by their hash, each with a lock of its own, so that the
+/// lookups of keys in different shards don't contend. See [`CacheBuilder::num_shards`].
+///
+/// A value may be given a time-to-live with
+/// [`get_or_insert_with_ttl`](Cache::get_or_insert_with_ttl). An expired value is removed when its
+/// key is looked up, or by the sweeper thread of [`CacheBuilder::sweep_interval`] if there is one.
+///
+/// A bounded cache evicts the values picked by its [`EvictionPolicy`] `P`, the oldest first by
+/// default. See [`CacheBuilder::with_eviction_policy`].
+#[derive(Debug)]
+pub struct Cache<K, V, P = Fifo<K>> {
+    /// Shared with the sweeper.
+    shards: Arc<[Mutex<Entries<K, V, P>>]>,
+    hasher: ShardHasher,
+    counters: Counters,
+    listener: Option<Arc<Listener<K, V>>>,
+    sweeper: Option<Sweeper<K, V, P>>,
+    /// Shared with the refreshes.
+    semaphore: Option<Arc<Semaphore>>,
+    /// Number of permits of the semaphore.
+    max_computations: Option<usize>,
+    /// Runs the refreshes of [`get_or_refresh_with`](Cache::get_or_refresh_with), created by the
+    /// first one.
+    refresher: OnceCell<ThreadPool>,
+}
+
+impl<K: Clone, V> Default for Cache<K, V> {
+    fn default() -> Self {
+        CacheBuilder::new().build()
+    }
 }
 
-impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
+impl<K, V> Cache<K, V> {
+    /// Returns a builder to configure a cache.
+    pub fn builder() -> CacheBuilder<K, V> {
+        CacheBuilder::new()
+    }
+}
+
+impl<K, V, P> Cache<K, V, P> {
+    /// Passes the removed entries to the eviction listener, if any.
+    fn notify(&self, removed: Vec<(K, Arc<V>)>, cause: RemovalCause) {
+        if let Some(listener) = &self.listener {
+            listener.send(removed, cause);
+        }
+    }
+}
+
+/// Builder of a [`Cache`].
+#[derive(Debug)]
+pub struct CacheBuilder<K, V, P = Fifo<K>> {
+    capacity: Option<usize>,
+    weigher: Option<Arc<Weigher<K, V>>>,
+    num_shards: Option<usize>,
+    listener: Option<Arc<Listener<K, V>>>,
+    sweeper: Option<(Duration, SpawnSweeper<K, V, P>)>,
+    max_computations: Option<usize>,
+    policy: P,
+}
+
+impl<K, V> Default for CacheBuilder<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> CacheBuilder<K, V> {
+    /// Creates a builder of an unbounded cache without a listener.
+    pub fn new() -> Self {
+        Self::with_eviction_policy(Fifo::new())
+    }
+}
+
+impl<K, V, P> CacheBuilder<K, V, P> {
+    /// Creates a builder like [`new`](CacheBuilder::new), but the cache evicts the values picked
+    /// by `policy` once it's bounded by [`max_capacity`](CacheBuilder::max_capacity), e.g.
+    /// [`Lru`](super::Lru) or [`Lfu`](super::Lfu) rather than the default [`Fifo`]. Each shard
+    /// has a clone of `policy`.
+    pub fn with_eviction_policy(policy: P) -> Self {
+        Self {
+            capacity: None,
+            weigher: None,
+            num_shards: None,
+            listener: None,
+            sweeper: None,
+            max_computations: None,
+            policy,
+        }
+    }
+
+    /// Bounds the number of computed values. When a new value exceeds it, the eviction policy
+    /// picks one to evict, the oldest one by default.
+    ///
+    /// The capacity is split evenly among the shards, and each shard evicts its own values. So a
+    /// bounded cache has a single shard unless [`num_shards`](CacheBuilder::num_shards) says
+    /// otherwise, whose policy sees all the keys.
+    pub fn max_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Weighs each computed value with `weigher`, e.g. by its size in bytes, so that
+    /// [`max_capacity`](CacheBuilder::max_capacity) bounds the total weight of the values rather
+    /// than their number. When a new value makes the total exceed the capacity, the victims of the
+    /// eviction policy are evicted until it fits, and a value heavier than the capacity (of its shard) is evicted
+    /// right away.
+    ///
+    /// The weight of a value is computed once, when it's inserted.
+    pub fn weigher<F>(mut self, weigher: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        self.weigher = Some(Arc::new(Weigher(Box::new(weigher))));
+        self
+    }
+
+    /// Splits the cache into `n` shards. The default is 16 for an unbounded cache, and 1 for a
+    /// bounded one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn num_shards(mut self, n: usize) -> Self {
+        assert!(n > 0, "no shard");
+        self.num_shards = Some(n);
+        self
+    }
+
+    /// Runs at most `n` computations at once, e.g. so that a cold cache doesn't start thousands of
+    /// expensive computations at the same time. The lookups of the other keys wait for one of the
+    /// computations to complete before starting theirs, while still holding off the concurrent
+    /// lookups of the same key. The refreshes of [`Cache::get_or_refresh_with`] count as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn max_concurrent_computations(mut self, n: usize) -> Self {
+        assert!(n > 0, "no computation allowed");
+        self.max_computations = Some(n);
+        self
+    }
+
+    /// Calls `listener` with each evicted entry and the cause of its eviction, so that the
+    /// resources held by the values can be released.
+    ///
+    /// The listener runs on a thread of its own rather than on the caller's, one entry at a time
+    /// in the order of the evictions. Dropping the cache (and its clones) waits for the pending
+    /// calls. A panic of the listener is ignored.
+    pub fn eviction_listener<F>(mut self, listener: F) -> Self
+    where
+        K: Send + 'static,
+        V: Send + Sync + 'static,
+        F: Fn(K, Arc<V>, RemovalCause) + Send + Sync + 'static,
+    {
+        let listener = Arc::new(listener);
+        let pool = ThreadPool::new(1);
+        self.listener = Some(Arc::new(Listener {
+            notify: Box::new(move |key, value, cause| {
+                let listener = Arc::clone(&listener);
+                pool.execute(move || {
+                    // the panic is already reported by the panic hook
+                    let _ = catch_unwind(AssertUnwindSafe(|| listener(key, value, cause)));
+                });
+            }),
+        }));
+        self
+    }
+
+    /// Removes the expired values every `interval` on a background thread, and passes them to the
+    /// eviction listener. Otherwise, an expired value is only removed when its key is looked up
+    /// again.
+    ///
+    /// Dropping the cache stops the thread. A clone of the cache has a sweeper of its own.
+    pub fn sweep_interval(mut self, interval: Duration) -> Self
+    where
+        K: Eq + Hash + Clone + Send + 'static,
+        V: Send + Sync + 'static,
+        P: EvictionPolicy<K> + Send + 'static,
+    {
+        self.sweeper = Some((interval, SpawnSweeper(Sweeper::spawn)));
+        self
+    }
+
+    /// Creates the cache.
+    pub fn build(self) -> Cache<K, V, P>
+    where
+        P: Clone,
+    {
+        let capacity = self.capacity;
+        let n = self.num_shards.unwrap_or(match capacity {
+            Some(_) => 1,
+            None => DEFAULT_SHARDS,
+        });
+        let shards = (0..n)
+            .map(|i| {
+                Mutex::new(Entries::new(
+                    capacity.map(|c| c / n + usize::from(i < c % n)),
+                    self.weigher.clone(),
+                    self.policy.clone(),
+                ))
+            })
+            .collect();
+        let mut cache = Cache {
+            shards,
+            hasher: ShardHasher::new(),
+            counters: Counters::default(),
+            listener: self.listener,
+            sweeper: None,
+            semaphore: self.max_computations.map(|n| Arc::new(Semaphore::new(n))),
+            max_computations: self.max_computations,
+            refresher: OnceCell::new(),
+        };
+        if let Some((interval, spawn)) = self.sweeper {
+            cache.sweeper = Some((spawn.0)(&cache, interval));
+        }
+        cache
+    }
+}
+
+/// Removes the `Computing` entry of the key if the computation panics or fails, waking up the
+/// waiters so that one of them retries.
+struct ComputeGuard<'a, K: Eq + Hash, V, P: EvictionPolicy<K>> {
+    cache: &'a Cache<K, V, P>,
+    key: &'a K,
+    condvar: Arc<Condvar>,
+}
+
+impl<K: Eq + Hash, V, P: EvictionPolicy<K>> Drop for ComputeGuard<'_, K, V, P> {
+    fn drop(&mut self) {
+        let mut data = self.cache.lock(self.key);
+        // otherwise, the key was invalidated and the waiters are already woken up
+        if data.is_computing(self.key, &self.condvar) {
+            let _ = data.remove_computing(self.key);
+            self.condvar.notify_all();
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V, P: EvictionPolicy<K> + Clone> Clone for Cache<K, V, P> {
+    /// Copies the computed entries, sharing their values and with the same expiry, one shard at a
+    /// time. Entries that are
+    /// still being computed are not copied. The clone shares the eviction listener, but starts
+    /// with fresh statistics, and has a limit of concurrent computations of its own.
+    fn clone(&self) -> Self {
+        let shards = self
+            .shards
+            .iter()
+            .map(|shard| {
+                let data = lock(shard);
+                let map = data
+                    .map
+                    .iter()
+                    .filter_map(|(key, entry)| match entry {
+                        CacheEntry::Value(c) => Some((key.clone(), CacheEntry::Value(c.clone()))),
+                        CacheEntry::Computing(_) => None,
+                    })
+                    .collect();
+                let mut entries = Entries {
+                    map,
+                    capacity: data.capacity,
+                    weigher: data.weigher.clone(),
+                    weight: data.weight,
+                    policy: data.policy.clone(),
+                    tagged: HashMap::new(),
+                    tags: HashMap::new(),
+                };
+                for (key, tags) in &data.tags {
+                    if entries.map.contains_key(key) {
+                        entries.tag(key, tags.clone());
+                    }
+                }
+                Mutex::new(entries)
+            })
+            .collect();
+        let mut copy = Self {
+            shards,
+            hasher: self.hasher.clone(),
+            counters: Counters::default(),
+            listener: self.listener.clone(),
+            sweeper: None,
+            semaphore: self.max_computations.map(|n| Arc::new(Semaphore::new(n))),
+            max_computations: self.max_computations,
+            refresher: OnceCell::new(),
+        };
+        if let Some(sweeper) = &self.sweeper {
+            copy.sweeper = Some((sweeper.spawn.0)(&copy, sweeper.interval));
+        }
+        copy
+    }
+}
+
+impl<K: Eq + Hash + Clone, V, P: EvictionPolicy<K>> Cache<K, V, P> {
     /// Retrieve the value or insert a new one created by `f`.
     ///
     /// An invocation to this function should not block another invocation with a different key.
@@ -40,34 +822,486 @@ impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
     ///
     /// Hint: the [`Entry`] API may be useful in implementing this function.
     ///
+    /// If `f` panics, the panic is propagated and the key is left uncomputed, so one of the
+    /// invocations waiting for it calls its own `f` instead.
+    ///
+    /// The value is shared by the cache and all invocations that get it, so it's never cloned.
+    ///
     /// [`Entry`]: https://doc.rust-lang.org/stable/std/collections/hash_map/struct.HashMap.html#method.entry
-    pub fn get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> V {
-        let mut data = self.data.lock().unwrap();
-        if let Some(entry) = data.get(&key) {
-            // there has been previouse attempts to fetch this key
+    pub fn get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> Arc<V> {
+        match self.get_or_try_insert(key, None, &[], |key| Ok::<_, Infallible>(f(key))) {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Like [`get_or_insert_with`](Cache::get_or_insert_with), but a value computed by `f` expires
+    /// `ttl` after it's computed. The lookup of an expired value removes it, and computes the key
+    /// again.
+    ///
+    /// The time-to-live only applies to the value computed by this call, so an invocation without
+    /// a time-to-live still respects the time-to-live of the value it finds.
+    pub fn get_or_insert_with_ttl<F>(&self, key: K, ttl: Duration, f: F) -> Arc<V>
+    where
+        F: FnOnce(K) -> V,
+    {
+        match self.get_or_try_insert(key, Some(ttl), &[], |key| Ok::<_, Infallible>(f(key))) {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Like [`get_or_insert_with`](Cache::get_or_insert_with), but `f` may fail.
+    ///
+    /// If `f` returns an error, the error is returned and nothing is cached, so the key is left
+    /// uncomputed as if `f` panicked: one of the invocations waiting for it calls its own `f`, and
+    /// a later invocation may retry.
+    pub fn get_or_try_insert_with<F, E>(&self, key: K, f: F) -> Result<Arc<V>, E>
+    where
+        F: FnOnce(K) -> Result<V, E>,
+    {
+        self.get_or_try_insert(key, None, &[], f)
+    }
+
+    /// Like [`get_or_insert_with`](Cache::get_or_insert_with), but a value computed by `f` is
+    /// tagged with `tags`, so that [`invalidate_tag`](Cache::invalidate_tag) removes it, e.g. to
+    /// remove all the pages of a user when the data of the user changes. The key is tagged from
+    /// the start of the computation, so invalidating a tag also invalidates the computations in
+    /// flight.
+    ///
+    /// Like a time-to-live, the tags only apply to the value computed by this call.
+    pub fn get_or_insert_with_tags<F>(&self, key: K, tags: &[&str], f: F) -> Arc<V>
+    where
+        F: FnOnce(K) -> V,
+    {
+        match self.get_or_try_insert(key, None, tags, |key| Ok::<_, Infallible>(f(key))) {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Returns the value of the key without computing it. If the key is being computed, waits for
+    /// the computation, and returns `None` if it fails. An expired value is removed.
+    pub fn get(&self, key: &K) -> Option<Arc<V>> {
+        let mut data = self.lock(key);
+        let mut waited = false;
+        loop {
+            let entry = match data.map.get(key) {
+                Some(entry) => entry,
+                None => {
+                    if !waited {
+                        Counters::add(&self.counters.misses);
+                    }
+                    return None;
+                }
+            };
             match entry {
-                CacheEntry::Value(v) => v.to_owned(),
-                CacheEntry::Computing(c) => {
-                    let data = Arc::clone(c).wait(data).unwrap();
-                    let v = data.get(&key).unwrap();
-                    match v {
-                        CacheEntry::Value(v) => v.to_owned(),
-                        CacheEntry::Computing(_) => unreachable!(),
+                CacheEntry::Value(c) if !c.is_expired() => {
+                    if !waited {
+                        Counters::add(&self.counters.hits);
+                    }
+                    let value = Arc::clone(&c.value);
+                    data.hit(key);
+                    return Some(value);
+                }
+                CacheEntry::Value(_) => {
+                    if !waited {
+                        Counters::add(&self.counters.misses);
                     }
+                    let expired = data.remove(key).map(|v| (key.clone(), v));
+                    drop(data);
+                    self.notify(expired.into_iter().collect(), RemovalCause::Expired);
+                    return None;
+                }
+                CacheEntry::Computing(c) => {
+                    waited = self.counters.wait(waited);
+                    data = Arc::clone(c)
+                        .wait(data)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// Returns the value of the key if it's computed and not expired. Unlike
+    /// [`get`](Cache::get), it never waits for a computation, and leaves an expired value to be
+    /// removed later. It's not a use of the value for the eviction policy.
+    pub fn peek(&self, key: &K) -> Option<Arc<V>> {
+        let value = match self.lock(key).map.get(key) {
+            Some(CacheEntry::Value(c)) if !c.is_expired() => Some(Arc::clone(&c.value)),
+            _ => None,
+        };
+        Counters::add(match value {
+            Some(_) => &self.counters.hits,
+            None => &self.counters.misses,
+        });
+        value
+    }
+
+    /// Writes the computed values to `writer`, one shard at a time, so that a new cache can be
+    /// warmed up with [`load`](Cache::load). Returns the number of values written. The keys that
+    /// are being computed and the expired values are skipped, and the time-to-live left of each
+    /// value is written with it.
+    ///
+    /// Each value is written on a line of its own, `TTL KLEN:KEY VLEN:VALUE`, where `TTL` is the
+    /// time-to-live left in milliseconds or `-`, and the key and the value are formatted with
+    /// `Display` and prefixed with their length in bytes, so they may contain any character. The
+    /// values of a shard are copied while it's locked, but written after it's unlocked.
+    pub fn dump<W: Write>(&self, mut writer: W) -> io::Result<usize>
+    where
+        K: Display,
+        V: Display,
+    {
+        let mut written = 0;
+        for shard in self.shards.iter() {
+            let now = Instant::now();
+            let values = lock(shard)
+                .map
+                .iter()
+                .filter_map(|(key, entry)| match entry {
+                    CacheEntry::Value(c) if !c.is_expired() => Some((
+                        key.clone(),
+                        Arc::clone(&c.value),
+                        c.expires_at.map(|at| at - now),
+                    )),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            for (key, value, ttl) in values {
+                match ttl {
+                    Some(ttl) => write!(writer, "{} ", ttl.as_millis())?,
+                    None => write!(writer, "- ")?,
                 }
+                let (key, value) = (key.to_string(), value.to_string());
+                writeln!(writer, "{}:{} {}:{}", key.len(), key, value.len(), value)?;
+                written += 1;
             }
-        } else {
-            // first one to ever fetch the key
-            data.insert(key.clone(), Default::default());
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+
+    /// Inserts the values written by [`dump`](Cache::dump) to `reader`, with the time-to-live
+    /// they had left, and returns the number of values inserted. The keys that are already
+    /// computed or being computed are left as they are, and the values the capacity doesn't
+    /// allow are evicted as usual.
+    ///
+    /// Returns an error of kind [`InvalidData`](io::ErrorKind::InvalidData) if the dump is
+    /// malformed, or a key or a value doesn't parse with `FromStr`. The values read before the
+    /// error are still inserted.
+    pub fn load<R: BufRead>(&self, mut reader: R) -> io::Result<usize>
+    where
+        K: FromStr,
+        V: FromStr,
+    {
+        let mut loaded = 0;
+        while let Some(ttl) = read_until(&mut reader, b' ')? {
+            let ttl = match ttl.as_str() {
+                "-" => None,
+                ms => Some(Duration::from_millis(
+                    ms.parse()
+                        .map_err(|_| invalid_dump("invalid time-to-live"))?,
+                )),
+            };
+            let key = read_field::<_, K>(&mut reader, b' ')?;
+            let value = Arc::new(read_field::<_, V>(&mut reader, b'\n')?);
+
+            let mut data = self.lock(&key);
+            if data.map.contains_key(&key) {
+                continue;
+            }
+            let computed = Computed {
+                expires_at: ttl.map(|ttl| Instant::now() + ttl),
+                weight: weigh(&data.weigher, &key, &value),
+                value,
+                refreshing: false,
+            };
+            let evicted = data.insert(key, computed);
             drop(data);
-            let v = f(key.clone());
-            let mut data = self.data.lock().unwrap();
-            let condvar = data.remove(&key).unwrap();
-            data.insert(key, CacheEntry::Value(v.clone()));
-            if let CacheEntry::Computing(condvar) = condvar {
-                condvar.notify_all();
+            self.notify(evicted, RemovalCause::Capacity);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    /// Looks up the key, or computes it with `f` and caches the value for `ttl`, if any, with the
+    /// tags.
+    fn get_or_try_insert<F, E>(
+        &self,
+        key: K,
+        ttl: Option<Duration>,
+        tags: &[&str],
+        f: F,
+    ) -> Result<Arc<V>, E>
+    where
+        F: FnOnce(K) -> Result<V, E>,
+    {
+        let mut data = self.lock(&key);
+        let mut expired = None;
+        let mut waited = false;
+        loop {
+            match data.map.get(&key) {
+                Some(CacheEntry::Value(c)) if !c.is_expired() => {
+                    if !waited {
+                        Counters::add(&self.counters.hits);
+                    }
+                    let value = Arc::clone(&c.value);
+                    data.hit(&key);
+                    return Ok(value);
+                }
+                Some(CacheEntry::Value(_)) => {
+                    expired = data.remove(&key).map(|v| (key.clone(), v));
+                    break;
+                }
+                // wait for the computing thread, and check again since it may have failed
+                Some(CacheEntry::Computing(c)) => {
+                    waited = self.counters.wait(waited);
+                    data = Arc::clone(c)
+                        .wait(data)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                None => break,
+            }
+        }
+        if !waited {
+            Counters::add(&self.counters.misses);
+        }
+        Counters::add(&self.counters.computations);
+
+        // first one to fetch the key
+        let condvar = Arc::new(Condvar::new());
+        data.map
+            .insert(key.clone(), CacheEntry::Computing(Arc::clone(&condvar)));
+        data.tag(&key, tags.iter().map(|tag| tag.to_string()).collect());
+        drop(data);
+        self.notify(expired.into_iter().collect(), RemovalCause::Expired);
+        let guard = ComputeGuard {
+            cache: self,
+            key: &key,
+            condvar,
+        };
+        let permit = self.semaphore.as_ref().map(|semaphore| semaphore.acquire());
+        // on an error, the guard removes the entry
+        let v = Arc::new(f(key.clone())?);
+        drop(permit);
+        let mut data = self.lock(&key);
+        if !data.is_computing(&key, &guard.condvar) {
+            // invalidated while computing, so the value is already stale
+            mem::forget(guard);
+            return Ok(v);
+        }
+        // the guard still removes the entry if the weigher panics
+        let weight = weigh(&data.weigher, &key, &v);
+        let condvar = Arc::clone(&guard.condvar);
+        mem::forget(guard);
+
+        let computed = Computed {
+            value: Arc::clone(&v),
+            expires_at: ttl.map(|ttl| Instant::now() + ttl),
+            weight,
+            refreshing: false,
+        };
+        let evicted = data.insert(key, computed);
+        condvar.notify_all();
+        drop(data);
+        self.notify(evicted, RemovalCause::Capacity);
+        Ok(v)
+    }
+}
+
+impl<K: Eq + Hash, V, P: EvictionPolicy<K>> Cache<K, V, P> {
+    /// Returns the statistics of the lookups so far, and the current number of computed values.
+    /// The counters are read one at a time while the cache may be used, so they are only
+    /// approximately consistent with each other.
+    pub fn stats(&self) -> CacheStats {
+        let entries = self
+            .shards
+            .iter()
+            .map(|shard| {
+                lock(shard)
+                    .map
+                    .values()
+                    .filter(|entry| matches!(entry, CacheEntry::Value(c) if !c.is_expired()))
+                    .count()
+            })
+            .sum();
+        let counters = &self.counters;
+        CacheStats {
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            computations: counters.computations.load(Ordering::Relaxed),
+            coalesced: counters.coalesced.load(Ordering::Relaxed),
+            entries,
+        }
+    }
+
+    /// Returns the index of the shard of the key.
+    fn shard_of(&self, key: &K) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    /// Locks the shard of the key, ignoring poisoning. See [`lock`].
+    fn lock(&self, key: &K) -> MutexGuard<'_, Entries<K, V, P>> {
+        lock(&self.shards[self.shard_of(key)])
+    }
+
+    /// Removes the key from the cache, and returns its value if it was computed.
+    ///
+    /// If the key is being computed, the computation is not cached when it completes, and the
+    /// invocations waiting for it compute the key again, so that no invocation that starts after
+    /// this returns gets a value computed before. The value is also passed to the eviction
+    /// listener with [`RemovalCause::Explicit`].
+    pub fn invalidate(&self, key: &K) -> Option<Arc<V>> {
+        let mut data = self.lock(key);
+        let (key, value) = match data.map.get(key)? {
+            CacheEntry::Value(_) => data.remove_entry(key)?,
+            CacheEntry::Computing(_) => {
+                if let Some(condvar) = data.remove_computing(key) {
+                    condvar.notify_all();
+                }
+                return None;
+            }
+        };
+        drop(data);
+        self.notify(vec![(key, Arc::clone(&value))], RemovalCause::Explicit);
+        Some(value)
+    }
+
+    /// Removes all keys tagged with `tag` from the cache, like [`invalidate`](Cache::invalidate)
+    /// for each key, and returns the number of values removed. The removed values are passed to
+    /// the eviction listener with [`RemovalCause::Explicit`].
+    ///
+    /// All shards are locked at once, so no lookup ever sees some of the keys removed but not the
+    /// others. See [`get_or_insert_with_tags`](Cache::get_or_insert_with_tags).
+    pub fn invalidate_tag(&self, tag: &str) -> usize {
+        let mut shards = self.shards.iter().map(lock).collect::<Vec<_>>();
+        let mut removed = Vec::new();
+        for data in &mut shards {
+            for key in data.tagged.remove(tag).into_iter().flatten() {
+                if let Some(condvar) = data.remove_computing(&key) {
+                    condvar.notify_all();
+                } else if let Some(value) = data.remove(&key) {
+                    removed.push((key, value));
+                }
+            }
+        }
+        drop(shards);
+        let len = removed.len();
+        self.notify(removed, RemovalCause::Explicit);
+        len
+    }
+
+    /// Removes all keys from the cache, like [`invalidate`](Cache::invalidate) for each key, one
+    /// shard at a time. The removed values are passed to the eviction listener with
+    /// [`RemovalCause::Explicit`].
+    pub fn invalidate_all(&self) {
+        for shard in self.shards.iter() {
+            let map = lock(shard).take();
+            let mut removed = Vec::new();
+            for (key, entry) in map {
+                match entry {
+                    CacheEntry::Value(computed) => removed.push((key, computed.value)),
+                    CacheEntry::Computing(condvar) => condvar.notify_all(),
+                }
+            }
+            self.notify(removed, RemovalCause::Explicit);
+        }
+    }
+}
+
+impl<K, V, P> Cache<K, V, P>
+where
+    K: Eq + Hash + Clone + Send + 'static,
+    V: Send + Sync + 'static,
+    P: EvictionPolicy<K> + Send + 'static,
+{
+    /// Like [`get_or_insert_with_ttl`](Cache::get_or_insert_with_ttl), but in stale-while-revalidate
+    /// mode: an expired value is still returned right away, while `f` computes a fresh one in the
+    /// background. Until the fresh value replaces it, the lookups keep getting the expired
+    /// value without starting another computation. The replaced value is passed to the eviction
+    /// listener with [`RemovalCause::Expired`].
+    ///
+    /// Only a missing key is computed by the caller. If the key is invalidated while it's refreshed,
+    /// the fresh value is discarded. If `f` panics, the expired value is kept, and the next lookup
+    /// refreshes it again. The other lookups ([`get`](Cache::get), and the sweeper of
+    /// [`CacheBuilder::sweep_interval`]) still remove an expired value.
+    ///
+    /// The refreshes of the cache share a pool of threads, growing up to
+    /// [`max_concurrent_computations`](CacheBuilder::max_concurrent_computations) threads, or one
+    /// per CPU. Dropping the cache waits for the pending refreshes.
+    pub fn get_or_refresh_with<F>(&self, key: K, ttl: Duration, f: F) -> Arc<V>
+    where
+        F: FnOnce(K) -> V + Send + 'static,
+    {
+        let index = self.shard_of(&key);
+        let mut data = lock(&self.shards[index]);
+        let computed = match data.map.get_mut(&key) {
+            Some(CacheEntry::Value(c)) => c,
+            _ => {
+                drop(data);
+                return self.get_or_insert_with_ttl(key, ttl, f);
             }
-            v
+        };
+        Counters::add(&self.counters.hits);
+        let value = Arc::clone(&computed.value);
+        let stale = !computed.refreshing && computed.is_expired();
+        if stale {
+            computed.refreshing = true;
         }
+        data.hit(&key);
+        if !stale {
+            return value;
+        }
+        let weigher = data.weigher.clone();
+        drop(data);
+
+        let shards = Arc::clone(&self.shards);
+        let listener = self.listener.clone();
+        let semaphore = self.semaphore.clone();
+        let refresh = move || {
+            let permit = semaphore.as_ref().map(|semaphore| semaphore.acquire());
+            let fresh = catch_unwind(AssertUnwindSafe(|| {
+                let fresh = f(key.clone());
+                let weight = weigh(&weigher, &key, &fresh);
+                (Arc::new(fresh), weight)
+            }));
+            drop(permit);
+            let mut data = lock(&shards[index]);
+            let computed = match data.map.get_mut(&key) {
+                Some(CacheEntry::Value(c)) if c.refreshing => c,
+                // invalidated, or computed again after an eviction
+                _ => return,
+            };
+            computed.refreshing = false;
+            // the panic is already reported by the panic hook
+            let (fresh, weight) = ok_or!(fresh, return);
+            let stale = mem::replace(&mut computed.value, fresh);
+            let stale_weight = mem::replace(&mut computed.weight, weight);
+            computed.expires_at = Some(Instant::now() + ttl);
+            data.weight = data.weight - stale_weight + weight;
+            let evicted = if data.fits(weight) {
+                data.evict()
+            } else {
+                let fresh = data.remove(&key).unwrap();
+                vec![(key.clone(), fresh)]
+            };
+            drop(data);
+            if let Some(listener) = listener {
+                listener.send(vec![(key, stale)], RemovalCause::Expired);
+                listener.send(evicted, RemovalCause::Capacity);
+            }
+        };
+        self.refresher
+            .get_or_init(|| {
+                // no more refreshes at once than computations
+                let max_size = self
+                    .max_computations
+                    .unwrap_or_else(|| thread::available_parallelism().map_or(4, |n| n.get()));
+                ThreadPool::builder(1).max_size(max_size).build()
+            })
+            .execute(refresh);
+        value
     }
 }
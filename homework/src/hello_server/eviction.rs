@@ -0,0 +1,202 @@
+//! Eviction policies of a bounded [`Cache`](super::Cache).
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Decides which value a bounded [`Cache`](super::Cache) evicts when it's over capacity.
+///
+/// Each shard of the cache has a policy of its own, cloned from the one given to
+/// [`CacheBuilder::with_eviction_policy`](super::CacheBuilder::with_eviction_policy), and calls
+/// it under the lock of the shard. The policy tracks the keys of the computed values of the
+/// shard: a key is inserted once, may be hit any number of times, and leaves either by
+/// [`pick_victim`](EvictionPolicy::pick_victim) or by [`on_remove`](EvictionPolicy::on_remove)
+/// when the cache removes it otherwise. An unbounded cache never calls its policy.
+pub trait EvictionPolicy<K> {
+    /// Starts tracking the key of a newly computed value.
+    fn on_insert(&mut self, key: &K);
+
+    /// Records a lookup that found the value of the key.
+    fn on_hit(&mut self, key: &K);
+
+    /// Stops tracking the key, whose value was invalidated or expired.
+    fn on_remove(&mut self, key: &K);
+
+    /// Stops tracking the key to evict next, and returns it, or `None` if no key is tracked.
+    fn pick_victim(&mut self) -> Option<K>;
+}
+
+/// Tracked keys ordered by a unique rank, evicting the lowest rank first.
+#[derive(Debug, Clone)]
+struct Ranks<K, R> {
+    ranks: HashMap<K, R>,
+    keys: BTreeMap<R, K>,
+}
+
+impl<K, R> Default for Ranks<K, R> {
+    fn default() -> Self {
+        Self {
+            ranks: HashMap::new(),
+            keys: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, R: Ord + Copy> Ranks<K, R> {
+    fn get(&self, key: &K) -> Option<R> {
+        self.ranks.get(key).copied()
+    }
+
+    fn set(&mut self, key: &K, rank: R) {
+        if let Some(old) = self.ranks.insert(key.clone(), rank) {
+            let _ = self.keys.remove(&old);
+        }
+        let _ = self.keys.insert(rank, key.clone());
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(rank) = self.ranks.remove(key) {
+            let _ = self.keys.remove(&rank);
+        }
+    }
+
+    fn pop_lowest(&mut self) -> Option<K> {
+        let rank = *self.keys.keys().next()?;
+        let key = self.keys.remove(&rank).unwrap();
+        let _ = self.ranks.remove(&key);
+        Some(key)
+    }
+}
+
+/// Evicts the oldest value first, regardless of the lookups. The default policy.
+#[derive(Debug, Clone)]
+pub struct Fifo<K> {
+    ranks: Ranks<K, u64>,
+    /// Number of insertions so far.
+    clock: u64,
+}
+
+impl<K> Default for Fifo<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Fifo<K> {
+    /// Creates the policy.
+    pub fn new() -> Self {
+        Self {
+            ranks: Ranks::default(),
+            clock: 0,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for Fifo<K> {
+    fn on_insert(&mut self, key: &K) {
+        self.clock += 1;
+        self.ranks.set(key, self.clock);
+    }
+
+    fn on_hit(&mut self, _: &K) {}
+
+    fn on_remove(&mut self, key: &K) {
+        self.ranks.remove(key);
+    }
+
+    fn pick_victim(&mut self) -> Option<K> {
+        self.ranks.pop_lowest()
+    }
+}
+
+/// Evicts the least recently used value first.
+#[derive(Debug, Clone)]
+pub struct Lru<K> {
+    ranks: Ranks<K, u64>,
+    /// Number of insertions and hits so far.
+    clock: u64,
+}
+
+impl<K> Default for Lru<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Lru<K> {
+    /// Creates the policy.
+    pub fn new() -> Self {
+        Self {
+            ranks: Ranks::default(),
+            clock: 0,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for Lru<K> {
+    fn on_insert(&mut self, key: &K) {
+        self.clock += 1;
+        self.ranks.set(key, self.clock);
+    }
+
+    fn on_hit(&mut self, key: &K) {
+        if self.ranks.get(key).is_some() {
+            self.on_insert(key);
+        }
+    }
+
+    fn on_remove(&mut self, key: &K) {
+        self.ranks.remove(key);
+    }
+
+    fn pick_victim(&mut self) -> Option<K> {
+        self.ranks.pop_lowest()
+    }
+}
+
+/// Evicts the least frequently used value first, counting the hits since the value was
+/// inserted. Among the values with the fewest hits, the least recently used one goes first.
+#[derive(Debug, Clone)]
+pub struct Lfu<K> {
+    /// Ranked by the number of hits, then by the last use.
+    ranks: Ranks<K, (u64, u64)>,
+    /// Number of insertions and hits so far.
+    clock: u64,
+}
+
+impl<K> Default for Lfu<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Lfu<K> {
+    /// Creates the policy.
+    pub fn new() -> Self {
+        Self {
+            ranks: Ranks::default(),
+            clock: 0,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> EvictionPolicy<K> for Lfu<K> {
+    fn on_insert(&mut self, key: &K) {
+        self.clock += 1;
+        self.ranks.set(key, (0, self.clock));
+    }
+
+    fn on_hit(&mut self, key: &K) {
+        if let Some((hits, _)) = self.ranks.get(key) {
+            self.clock += 1;
+            self.ranks.set(key, (hits + 1, self.clock));
+        }
+    }
+
+    fn on_remove(&mut self, key: &K) {
+        self.ranks.remove(key);
+    }
+
+    fn pick_victim(&mut self) -> Option<K> {
+        self.ranks.pop_lowest()
+    }
+}
@@ -1,30 +1,130 @@
 //! Request handler with a cache.
 
+use core::cell::RefCell;
+use core::fmt;
+use core::str;
 use once_cell::sync::Lazy;
 use regex::bytes::Regex;
-use std::io::prelude::*;
-use std::net::TcpStream;
-use std::sync::Arc;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::cache::Cache;
-use super::statistics::Report;
+use super::cache::{Cache, CacheStats};
+use super::hooks::{HookList, Hooks};
+use super::rate_limit::RateLimiter;
+use super::router::{self, Request, Response, Router};
+use super::statistics::{Report, Served, Statistics};
+use super::stream::{Connection, Io};
+use crate::arena::{ArenaMap, RequestArena};
 
 /// Computes the result for the given key. So expensive, much wow.
-fn very_expensive_computation_that_takes_a_few_seconds(key: String) -> String {
+pub(super) fn very_expensive_computation_that_takes_a_few_seconds(key: String) -> String {
     println!("[handler] doing computation for key: {}", key);
     thread::sleep(Duration::from_secs(3));
     format!("{}🐕", key)
 }
 
+/// Timeouts of the connections of a [`Handler`], so that a slow or idle client can't hold a
+/// thread of the server for long.
+///
+/// A connection that times out in the middle of a request is answered with
+/// `408 Request Timeout`, and closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// How long a connection may wait for the first byte of its next request.
+    pub idle: Duration,
+    /// How long the request line and the headers of a request may take to arrive, from its
+    /// first byte.
+    pub header: Duration,
+    /// How long the whole request, with its body, may take to arrive, from its first byte.
+    pub request: Duration,
+    /// How long a write of a response may block.
+    pub write: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            idle: Duration::from_secs(5),
+            header: Duration::from_secs(10),
+            request: Duration::from_secs(30),
+            write: Duration::from_secs(10),
+        }
+    }
+}
+
+thread_local! {
+    /// Arena of the requests handled by this thread.
+    static ARENA: RefCell<RequestArena> = RefCell::new(RequestArena::new());
+}
+
+/// Settings of a [`Handler`] that may be [reloaded](Handler::reload).
+#[derive(Debug, Clone)]
+struct Settings {
+    router: Arc<Router>,
+    timeouts: Timeouts,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
 /// Hello handler with a cache.
-#[derive(Debug, Default, Clone)]
+///
+/// The requests are dispatched by a [`Router`]. By default, it has the route `GET /:key`
+/// responding with the result of the expensive computation for the key, which is cached.
+///
+/// The routes, the timeouts and the rate limiter are shared by the clones of the handler, and
+/// may be replaced at once by [`reload`](Handler::reload) while it serves connections.
+#[derive(Debug, Clone)]
 pub struct Handler {
     cache: Arc<Cache<String, String>>,
+    /// The router the handler was created with.
+    default_router: Arc<Router>,
+    /// Snapshot of the current settings, which a connection keeps until it's closed.
+    settings: Arc<RwLock<Arc<Settings>>>,
+    stats: Option<Arc<Mutex<Statistics>>>,
+    hooks: HookList,
+}
+
+impl Default for Handler {
+    fn default() -> Self {
+        Self::with_cache(Cache::default())
+    }
 }
 
 impl Handler {
+    /// Creates the default handler, with the route `GET /:key` caching the results in `cache`.
+    pub fn with_cache(cache: Cache<String, String>) -> Self {
+        let cache = Arc::new(cache);
+        let route_cache = Arc::clone(&cache);
+        let router = Router::new().get("/:key", move |request| {
+            let key = request.param("key").unwrap();
+            let result = route_cache.get_or_insert_with(
+                key.to_string(),
+                very_expensive_computation_that_takes_a_few_seconds,
+            );
+            Self::hello(key, result)
+        });
+        Self::with_router(cache, Arc::new(router))
+    }
+
+    fn with_router(cache: Arc<Cache<String, String>>, router: Arc<Router>) -> Self {
+        let settings = Settings {
+            router: Arc::clone(&router),
+            timeouts: Timeouts::default(),
+            limiter: None,
+        };
+        Self {
+            cache,
+            default_router: router,
+            settings: Arc::new(RwLock::new(Arc::new(settings))),
+            stats: None,
+            hooks: HookList::default(),
+        }
+    }
+
     const OK: &'static str = "<!DOCTYPE html>
 <html lang=\"en\">
   <head>
@@ -36,45 +136,442 @@ impl Handler {
   </body>
 </html>";
 
-    const NOT_FOUND: &'static str = "<!DOCTYPE html>
-<html lang=\"en\">
-  <head>
-    <meta charset=\"utf-8\">
-    <title>Hello!</title>
-  </head>
-  <body>
-    <h1>Oops!</h1>
-    <p>Sorry, I don't know what you're asking for.</p>
-  </body>
-</html>";
+    /// Maximum size of the request line and the headers of a request.
+    const MAX_HEAD: usize = 8192;
+
+    /// Maximum size of the body of a request.
+    const MAX_BODY: usize = 1 << 20;
+
+    /// Creates a handler dispatching the requests with `router`. The cache of the handler is
+    /// then unused.
+    pub fn new(router: Router) -> Self {
+        Self::with_router(Arc::default(), Arc::new(router))
+    }
+
+    /// Sets the timeouts of the connections.
+    pub fn with_timeouts(self, timeouts: Timeouts) -> Self {
+        self.with_settings(|settings| settings.timeouts = timeouts)
+    }
+
+    /// Limits the rate of the requests with the limiter. A request over the limit is answered
+    /// with `429 Too Many Requests` and a `Retry-After` header, without being dispatched.
+    pub fn with_rate_limiter(self, limiter: RateLimiter) -> Self {
+        self.with_settings(|settings| settings.limiter = Some(Arc::new(limiter)))
+    }
+
+    /// Changes the settings of this handler only, unlike `reload`.
+    fn with_settings<F: FnOnce(&mut Settings)>(mut self, f: F) -> Self {
+        let mut settings = Settings::clone(&self.settings());
+        f(&mut settings);
+        self.settings = Arc::new(RwLock::new(Arc::new(settings)));
+        self
+    }
+
+    fn settings(&self) -> Arc<Settings> {
+        Arc::clone(&self.settings.read().unwrap())
+    }
+
+    /// Replaces the routes, the timeouts and the rate limiter of the handler and its clones at
+    /// once, e.g. on a configuration change. The routes are the ones the handler was created with
+    /// if `router` is `None`, and the requests aren't limited if `limiter` is `None`.
+    ///
+    /// The connections being served keep the previous settings until they're closed, so that
+    /// none of them is dropped, and the new connections get the new ones.
+    pub fn reload(
+        &self,
+        router: Option<Arc<Router>>,
+        timeouts: Timeouts,
+        limiter: Option<RateLimiter>,
+    ) {
+        let settings = Settings {
+            router: router.unwrap_or_else(|| Arc::clone(&self.default_router)),
+            timeouts,
+            limiter: limiter.map(Arc::new),
+        };
+        *self.settings.write().unwrap() = Arc::new(settings);
+    }
 
-    /// Process the request and generate report.
-    pub fn handle_conn(&self, request_id: usize, mut stream: TcpStream) -> Report {
-        let mut buf = [0; 512];
-        let _ = stream.read(&mut buf).unwrap();
+    /// Serves the statistics as JSON at `GET /stats`, before the routes. See
+    /// [`Statistics::to_json`].
+    pub fn with_stats(mut self, stats: Arc<Mutex<Statistics>>) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Calls `hooks` around the dispatch of each request, after the hooks added before. See
+    /// [`Hooks`].
+    pub fn with_hooks<H: Hooks + 'static>(mut self, hooks: H) -> Self {
+        self.hooks = self.hooks.with(Arc::new(hooks));
+        self
+    }
+
+    /// Replaces the hooks.
+    pub(super) fn with_hook_list(mut self, hooks: HookList) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Returns the page showing the result for the key.
+    pub(super) fn hello<R: fmt::Display>(key: &str, result: R) -> Response {
+        let (head, rest) = Self::OK.split_once("{key}").unwrap();
+        let (middle, tail) = rest.split_once("{result}").unwrap();
+        Response::ok(format!("{}{}{}{}{}", head, key, middle, result, tail))
+            .with_header("Content-Type", "text/html; charset=utf-8")
+    }
+
+    /// Process the requests of the connection and generate report.
+    ///
+    /// The connection is kept alive for the next request as in HTTP/1.1, until the client asks
+    /// to close it, closes it, or leaves it idle for the [idle timeout](Timeouts::idle). The
+    /// request buffer, the parsed headers and the response are allocated in the thread's
+    /// [`RequestArena`], which is reset once each response is written.
+    ///
+    /// The clients without an IP address, e.g. over a Unix domain socket, share a bucket of the
+    /// [rate limiter](Handler::with_rate_limiter).
+    pub fn handle_conn<C: Connection>(&self, request_id: usize, stream: C) -> Report {
+        let client = stream
+            .peer_ip()
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        let settings = self.settings();
+        ARENA.with(|arena| {
+            let mut arena = arena.borrow_mut();
+            let (requests, timed_out) =
+                Self::serve(&mut arena, &stream, true, &settings.timeouts, |request| {
+                    self.hooks.on_request(request);
+                    let start = Instant::now();
+                    let response = self.dispatch(&settings, client, request);
+                    self.hooks.on_response(request, &response, start.elapsed());
+                    response
+                });
+            let report = Report::new(request_id, requests);
+            if timed_out {
+                report.timed_out()
+            } else {
+                report
+            }
+        })
+    }
+
+    /// Responds to the request of `client`, unless it's over the rate limit.
+    fn dispatch(&self, settings: &Settings, client: IpAddr, request: &mut Request<'_>) -> Response {
+        let limited = settings
+            .limiter
+            .as_ref()
+            .map(|limiter| limiter.check(client));
+        if let Some(Err(retry)) = limited {
+            // rounded up to whole seconds
+            let secs = retry.as_secs() + u64::from(retry.subsec_nanos() > 0);
+            return Response::new(429, "").with_header("Retry-After", secs.to_string());
+        }
+        match &self.stats {
+            Some(stats) if request.method() == "GET" && request.path() == "/stats" => {
+                let json = stats.lock().unwrap().to_json();
+                Response::ok(json).with_header("Content-Type", "application/json")
+            }
+            _ => settings.router.dispatch(request),
+        }
+    }
 
+    /// Rejects the connection with `503 Service Unavailable` without reading its requests, e.g.
+    /// at a [`ConnectionLimit`](super::ConnectionLimit), and generates its report.
+    pub fn reject<C: Connection>(&self, request_id: usize, stream: C) -> Report {
+        let start = Instant::now();
+        let response = Response::new(503, "").with_header("Retry-After", "1");
+        if stream
+            .set_write_timeout(Some(self.settings().timeouts.write))
+            .is_ok()
+        {
+            ARENA.with(|arena| {
+                let mut arena = arena.borrow_mut();
+                let mut reader = BufReader::new(Io(&stream));
+                let _ = Self::write_response(&arena, &mut reader, &response, false);
+                arena.reset();
+            });
+        }
+        Report::new(request_id, vec![Served::new(None, 503, start.elapsed())]).rejected()
+    }
+
+    /// Returns the statistics of the cache, shared by the clones of the handler.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Writes the cached results to `writer`. See [`Cache::dump`].
+    pub fn dump_cache<W: Write>(&self, writer: W) -> io::Result<usize> {
+        self.cache.dump(writer)
+    }
+
+    /// Warms up the cache with the results written by [`dump_cache`](Handler::dump_cache). See
+    /// [`Cache::load`].
+    pub fn load_cache<R: BufRead>(&self, reader: R) -> io::Result<usize> {
+        self.cache.load(reader)
+    }
+
+    /// Returns the key of the request in `buf`, if any.
+    pub(super) fn request_key(buf: &[u8]) -> Option<Cow<'_, str>> {
         static REQUEST_REGEX: Lazy<Regex> =
             Lazy::new(|| Regex::new(r"GET /(?P<key>\w+) HTTP/1.1\r\n").unwrap());
-        let key = REQUEST_REGEX
-            .captures(&buf)
+        REQUEST_REGEX
+            .captures(buf)
             .and_then(|cap| cap.name("key"))
-            .map(|key| String::from_utf8_lossy(key.as_bytes()));
+            .map(|key| String::from_utf8_lossy(key.as_bytes()))
+    }
 
-        let resp = if let Some(ref key) = key {
-            let result = self.cache.get_or_insert_with(
-                key.to_string(),
-                very_expensive_computation_that_takes_a_few_seconds,
+    /// Responds to the requests of `stream` with `dispatch`, and returns the record of each
+    /// request, whose key is its path without the leading `/`, or `None` if no route matched it,
+    /// and whether the connection timed out. Unless `keep_alive` is set, only one request is served.
+    ///
+    /// An I/O error, e.g. the client leaving, ends the connection without a response, but a
+    /// timeout in the middle of a request is answered with `408 Request Timeout`.
+    pub(super) fn serve<C: Connection, F: FnMut(&mut Request<'_>) -> Response>(
+        arena: &mut RequestArena,
+        stream: &C,
+        keep_alive: bool,
+        timeouts: &Timeouts,
+        mut dispatch: F,
+    ) -> (Vec<Served>, bool) {
+        let mut requests = Vec::new();
+        if stream.set_write_timeout(Some(timeouts.write)).is_err() {
+            return (requests, false);
+        }
+        let mut reader = BufReader::new(Io(stream));
+        loop {
+            let first = requests.is_empty();
+            let served = Self::respond(
+                arena,
+                &mut reader,
+                keep_alive,
+                first,
+                timeouts,
+                &mut dispatch,
             );
-            format!(
-                "HTTP/1.1 200 OK\r\n\r\n{}",
-                Self::OK.replace("{key}", key).replace("{result}", &result)
-            )
-        } else {
-            format!("HTTP/1.1 404 NOT FOUND\r\n\r\n{}", Self::NOT_FOUND)
+            arena.reset();
+            match served {
+                Ok(Some((served, keep_alive))) => {
+                    requests.push(served);
+                    if !keep_alive {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => return (requests, Self::is_timeout(&e)),
+            }
+        }
+        (requests, false)
+    }
+
+    fn is_timeout(e: &io::Error) -> bool {
+        matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        )
+    }
+
+    /// Sets the read timeout of the stream to the time left before `deadline`, or fails with a
+    /// `TimedOut` error if there is none.
+    fn read_until<C: Connection>(stream: &C, deadline: Instant) -> io::Result<()> {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        stream.set_read_timeout(Some(left))
+    }
+
+    /// Reads the request line and the headers of the next request into the arena, and returns
+    /// them with the time their first byte arrived. Returns `None` if the connection is closed
+    /// before the request starts, or idle for too long after the `first` request. An incomplete
+    /// head, or one larger than [`MAX_HEAD`](Handler::MAX_HEAD), is returned as is.
+    fn read_head<'a, C: Connection>(
+        arena: &'a RequestArena,
+        reader: &mut BufReader<Io<'_, C>>,
+        first: bool,
+        timeouts: &Timeouts,
+    ) -> io::Result<Option<(&'a [u8], Instant)>> {
+        let stream = reader.get_ref().0;
+        let idle = Instant::now() + timeouts.idle;
+        let mut start = None;
+        let buf = arena.buffer(Self::MAX_HEAD);
+        let mut len = 0;
+        while len < buf.len() {
+            let deadline = match start {
+                Some(start) => start + timeouts.header.min(timeouts.request),
+                None => idle,
+            };
+            let read = Self::read_until(stream, deadline);
+            let available = match read.and_then(|()| reader.fill_buf().map(|a| a.len())) {
+                Ok(0) => break,
+                Ok(_) => reader.buffer(),
+                Err(e) if start.is_none() && !first && Self::is_timeout(&e) => return Ok(None),
+                Err(e) if Self::is_timeout(&e) => return Err(io::ErrorKind::TimedOut.into()),
+                Err(e) => return Err(e),
+            };
+            let _ = start.get_or_insert_with(Instant::now);
+            // consumes a line at most, so that the next request is left in the reader
+            let line = available
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(available.len(), |i| i + 1)
+                .min(buf.len() - len);
+            buf[len..len + line].copy_from_slice(&available[..line]);
+            reader.consume(line);
+            len += line;
+            if buf[..len] == *b"\r\n" {
+                // an empty line before the request
+                len = 0;
+            } else if buf[..len].ends_with(b"\r\n\r\n") {
+                break;
+            }
+        }
+        Ok(start.filter(|_| len > 0).map(|start| (&buf[..len], start)))
+    }
+
+    /// Responds to the next request of `reader` with `dispatch`. Returns the record of the
+    /// request, and whether the connection is kept alive for another request, or `None` if the
+    /// connection is closed before the request.
+    fn respond<C: Connection, F: FnOnce(&mut Request<'_>) -> Response>(
+        arena: &RequestArena,
+        reader: &mut BufReader<Io<'_, C>>,
+        keep_alive: bool,
+        first: bool,
+        timeouts: &Timeouts,
+        dispatch: F,
+    ) -> io::Result<Option<(Served, bool)>> {
+        // answers a timeout before failing with it
+        let timed_out = |reader: &mut BufReader<Io<'_, C>>, e: io::Error| {
+            if e.kind() == io::ErrorKind::TimedOut {
+                let _ = Self::write_response(arena, reader, &Response::new(408, ""), false);
+            }
+            e
+        };
+        let head = Self::read_head(arena, reader, first, timeouts);
+        let (buf, start) = match head {
+            Ok(head) => some_or!(head, return Ok(None)),
+            Err(e) => return Err(timed_out(reader, e)),
         };
 
-        stream.write_all(resp.as_bytes()).unwrap();
+        let headers = Self::parse_headers(arena, buf);
+        let mut request_line = Self::request_line(buf).split(' ');
+        let (mut request, version) = match (
+            request_line.next(),
+            request_line.next(),
+            request_line.next(),
+        ) {
+            (Some(method), Some(target), Some(version))
+                if buf.ends_with(b"\r\n\r\n") && version.starts_with("HTTP/1.") =>
+            {
+                (Request::new(method, target, headers, &[]), version)
+            }
+            _ => {
+                Self::write_response(arena, reader, &Response::new(400, ""), false)?;
+                return Ok(Some((Served::new(None, 400, start.elapsed()), false)));
+            }
+        };
+        let connection = request.header("Connection").unwrap_or_default();
+        let keep_alive = keep_alive
+            && if version == "HTTP/1.0" {
+                connection.eq_ignore_ascii_case("keep-alive")
+            } else {
+                !connection.eq_ignore_ascii_case("close")
+            };
+
+        let len = match request.header("Content-Length").map(str::parse::<usize>) {
+            None => 0,
+            Some(Ok(len)) if len <= Self::MAX_BODY => len,
+            Some(Ok(_)) => {
+                Self::write_response(arena, reader, &Response::new(413, ""), false)?;
+                return Ok(Some((Served::new(None, 413, start.elapsed()), false)));
+            }
+            Some(Err(_)) => {
+                Self::write_response(arena, reader, &Response::new(400, ""), false)?;
+                return Ok(Some((Served::new(None, 400, start.elapsed()), false)));
+            }
+        };
+        let body = arena.buffer(len);
+        let mut read = 0;
+        while read < len {
+            let deadline = start + timeouts.request;
+            match Self::read_until(reader.get_ref().0, deadline)
+                .and_then(|()| reader.read(&mut body[read..]))
+            {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if Self::is_timeout(&e) => {
+                    return Err(timed_out(reader, io::ErrorKind::TimedOut.into()))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        request.body = body;
+
+        let response = dispatch(&mut request);
+        let key = match response.status() {
+            404 | 405 | 429 => None,
+            _ => Some(request.path().trim_start_matches('/').to_string()),
+        };
+        Self::write_response(arena, reader, &response, keep_alive)?;
+        let served = Served::new(key, response.status(), start.elapsed());
+        Ok(Some((served, keep_alive)))
+    }
+
+    /// Writes the response, with the headers framing it on the connection.
+    fn write_response<C: Connection>(
+        arena: &RequestArena,
+        reader: &mut BufReader<Io<'_, C>>,
+        response: &Response,
+        keep_alive: bool,
+    ) -> io::Result<()> {
+        struct Headers<'a>(&'a [(String, String)]);
+        impl fmt::Display for Headers<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                for (name, value) in self.0 {
+                    write!(f, "{}: {}\r\n", name, value)?;
+                }
+                Ok(())
+            }
+        }
+
+        let head = arena.alloc_fmt(format_args!(
+            "HTTP/1.1 {} {}\r\n{}Content-Length: {}\r\nConnection: {}\r\n\r\n",
+            response.status(),
+            router::reason(response.status()),
+            Headers(response.headers()),
+            response.content_length(),
+            if keep_alive { "keep-alive" } else { "close" },
+        ));
+        let mut stream = *reader.get_ref();
+        stream.write_all(head.as_bytes())?;
+        let (path, len) = some_or!(
+            response.file_body(),
+            return stream.write_all(response.body())
+        );
+        let copied = io::copy(&mut File::open(path)?.take(len), &mut stream)?;
+        if copied < len {
+            // the file was truncated
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(())
+    }
+
+    /// Returns the request line of the request in `buf`.
+    fn request_line(buf: &[u8]) -> &str {
+        let line = buf.split(|&b| b == b'\r').next().unwrap_or_default();
+        str::from_utf8(line).unwrap_or_default()
+    }
 
-        Report::new(request_id, key.map(String::from))
+    /// Parses the headers of the request in `buf` into a map borrowing from it. Malformed lines
+    /// are skipped.
+    fn parse_headers<'a>(arena: &'a RequestArena, buf: &'a [u8]) -> ArenaMap<'a, &'a str, &'a str> {
+        let mut headers = ArenaMap::new_in(arena);
+        let head = buf.split(|&b| b == 0).next().unwrap_or_default();
+        let head = str::from_utf8(head).unwrap_or_default();
+        let head = head.split("\r\n\r\n").next().unwrap_or_default();
+        // skip the request line
+        for line in head.split("\r\n").skip(1) {
+            if let Some((name, value)) = line.split_once(':') {
+                let _ = headers.insert(name.trim(), value.trim());
+            }
+        }
+        headers
     }
 }
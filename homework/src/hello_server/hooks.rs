@@ -0,0 +1,118 @@
+//! Hooks observing the requests of a handler, e.g. to log them.
+
+use core::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::router::{Request, Response};
+use super::statistics::Report;
+
+/// Observer of the requests dispatched by a [`Handler`](super::Handler), added by
+/// [`Handler::with_hooks`](super::Handler::with_hooks), e.g. for structured logging or tracing.
+///
+/// The hooks are called on the thread serving the connection, so a slow hook delays the
+/// response. A request that can't be parsed isn't dispatched, hence not observed.
+pub trait Hooks: Send + Sync {
+    /// Called before the request is dispatched, even if it's over the rate limit.
+    fn on_request(&self, request: &Request<'_>) {
+        let _ = request;
+    }
+
+    /// Called with the response to the request before it's written, and how long the dispatch
+    /// took.
+    fn on_response(&self, request: &Request<'_>, response: &Response, elapsed: Duration) {
+        let _ = (request, response, elapsed);
+    }
+
+    /// Called by a [`Server`](super::Server) with the report of each connection, once it's
+    /// handled.
+    fn on_report(&self, report: &Report) {
+        let _ = report;
+    }
+}
+
+/// Shared hooks, e.g. to inspect what they recorded.
+impl<H: Hooks + ?Sized> Hooks for Arc<H> {
+    fn on_request(&self, request: &Request<'_>) {
+        (**self).on_request(request)
+    }
+
+    fn on_response(&self, request: &Request<'_>, response: &Response, elapsed: Duration) {
+        (**self).on_response(request, response, elapsed)
+    }
+
+    fn on_report(&self, report: &Report) {
+        (**self).on_report(report)
+    }
+}
+
+/// Hooks printing a line per response on stdout, with the fields as `key=value` pairs, e.g.
+/// `[request] method=GET path=/key status=200 elapsed_us=42`, and a line per connection reported
+/// by a [`Server`](super::Server), e.g. `[report] Report { .. }`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutLogger;
+
+impl Hooks for StdoutLogger {
+    fn on_response(&self, request: &Request<'_>, response: &Response, elapsed: Duration) {
+        let query = request.query().map(|query| format!(" query={}", query));
+        println!(
+            "[request] method={} path={}{} status={} elapsed_us={}",
+            request.method(),
+            request.path(),
+            query.unwrap_or_default(),
+            response.status(),
+            elapsed.as_micros()
+        );
+    }
+
+    fn on_report(&self, report: &Report) {
+        println!("[report] {:?}", report);
+    }
+}
+
+/// Hooks called in the order they were added.
+#[derive(Clone)]
+pub(super) struct HookList(Arc<[Arc<dyn Hooks>]>);
+
+impl Default for HookList {
+    fn default() -> Self {
+        Self(Arc::new([]))
+    }
+}
+
+impl fmt::Debug for HookList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HookList({} hooks)", self.0.len())
+    }
+}
+
+impl HookList {
+    pub(super) fn with(&self, hooks: Arc<dyn Hooks>) -> Self {
+        let mut list = self.0.to_vec();
+        list.push(hooks);
+        Self(list.into())
+    }
+
+    pub(super) fn on_request(&self, request: &Request<'_>) {
+        for hooks in self.0.iter() {
+            hooks.on_request(request);
+        }
+    }
+
+    pub(super) fn on_response(
+        &self,
+        request: &Request<'_>,
+        response: &Response,
+        elapsed: Duration,
+    ) {
+        for hooks in self.0.iter() {
+            hooks.on_response(request, response, elapsed);
+        }
+    }
+
+    pub(super) fn on_report(&self, report: &Report) {
+        for hooks in self.0.iter() {
+            hooks.on_report(report);
+        }
+    }
+}
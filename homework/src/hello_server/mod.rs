@@ -1,13 +1,45 @@
 //! Hello server with a cache.
 
+#[cfg(feature = "async")]
+mod async_cache;
 mod cache;
+mod eviction;
 mod handler;
+mod hooks;
+mod rate_limit;
+mod router;
+mod server;
+mod shard;
+mod static_files;
 mod statistics;
+mod stream;
 mod tcp;
 mod thread_pool;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(unix)]
+mod unix;
+mod work_stealing;
 
-pub use cache::Cache;
-pub use handler::Handler;
-pub use statistics::{Report, Statistics};
-pub use tcp::CancellableTcpListener;
-pub use thread_pool::ThreadPool;
+#[cfg(feature = "async")]
+pub use async_cache::AsyncCache;
+pub use cache::{Cache, CacheBuilder, CacheStats, RemovalCause};
+pub use eviction::{EvictionPolicy, Fifo, Lfu, Lru};
+pub use handler::{Handler, Timeouts};
+pub use hooks::{Hooks, StdoutLogger};
+pub use rate_limit::{Limit, RateLimiter};
+pub use router::{Request, Response, Router};
+pub use server::{Server, ServerConfig, ServerHandle};
+pub use shard::Shards;
+pub use static_files::StaticFiles;
+pub use statistics::{Histogram, Report, Served, Statistics};
+pub use stream::Connection;
+pub use tcp::{CancellableTcpListener, ConnectionLimit, Overflow, Permit};
+pub use thread_pool::{
+    Affinity, Full, Job, JobError, JobHandle, Priority, Scope, ThreadPool, ThreadPoolBuilder,
+};
+#[cfg(feature = "tls")]
+pub use tls::TlsStream;
+#[cfg(unix)]
+pub use unix::{CancellableUnixListener, UnixIncoming};
+pub use work_stealing::WorkStealingPool;
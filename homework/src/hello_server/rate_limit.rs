@@ -0,0 +1,149 @@
+//! Token-bucket rate limiting of the requests, globally and per client.
+
+use core::hash::{Hash, Hasher};
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// Number of shards of the buckets of the clients.
+const SHARDS: usize = 16;
+
+/// Number of clients of a shard above which the full buckets are dropped.
+const MAX_CLIENTS: usize = 1024;
+
+/// Rate of a token bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limit {
+    /// Nanoseconds to refill a token.
+    interval: u64,
+    /// Capacity of the bucket.
+    burst: u64,
+}
+
+impl Limit {
+    /// Creates a limit of `rate` requests per second, with bursts of up to `burst` requests.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` or `burst` is zero.
+    pub fn per_second(rate: u32, burst: u32) -> Self {
+        assert!(rate > 0 && burst > 0, "empty limit");
+        Self {
+            interval: 1_000_000_000 / u64::from(rate),
+            burst: u64::from(burst),
+        }
+    }
+}
+
+/// Token bucket in a single atomic, as in the generic cell rate algorithm: instead of the
+/// tokens, it tracks the time at which the bucket would be full again.
+#[derive(Debug, Default)]
+struct Bucket {
+    /// Nanoseconds since the epoch of the limiter.
+    full_at: AtomicU64,
+}
+
+impl Bucket {
+    /// Takes a token at `now`, or returns how long until one is available.
+    fn acquire(&self, limit: &Limit, now: u64) -> Result<(), Duration> {
+        let mut full_at = self.full_at.load(Ordering::Relaxed);
+        loop {
+            let next = full_at.max(now) + limit.interval;
+            // the bucket is empty once it's `burst` intervals from being full
+            let empty_at = now + limit.burst * limit.interval;
+            if next > empty_at {
+                return Err(Duration::from_nanos(next - empty_at));
+            }
+            match self.full_at.compare_exchange_weak(
+                full_at,
+                next,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(current) => full_at = current,
+            }
+        }
+    }
+
+    fn is_full(&self, now: u64) -> bool {
+        self.full_at.load(Ordering::Relaxed) <= now
+    }
+}
+
+/// Rate limiter of the requests of a [`Handler`](super::Handler), with a bucket shared by all
+/// clients and a bucket per client IP address, both optional.
+///
+/// A request takes a token from the bucket of its client, then from the global one. The buckets
+/// are atomics, so a request only takes the read lock of the shard of its client's bucket, and
+/// the write lock only for a new client. The buckets of the clients that are refilled are dropped
+/// once a shard has many clients.
+#[derive(Debug)]
+pub struct RateLimiter {
+    epoch: Instant,
+    global: Option<(Limit, Bucket)>,
+    per_client: Option<Limit>,
+    clients: Box<[RwLock<HashMap<IpAddr, Bucket>>]>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    /// Creates a limiter without any limit.
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            global: None,
+            per_client: None,
+            clients: (0..SHARDS).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    /// Limits the requests of all clients together.
+    pub fn global(mut self, limit: Limit) -> Self {
+        self.global = Some((limit, Bucket::default()));
+        self
+    }
+
+    /// Limits the requests of each client IP address.
+    pub fn per_client(mut self, limit: Limit) -> Self {
+        self.per_client = Some(limit);
+        self
+    }
+
+    /// Takes a token for a request of `client`, or returns how long until the client may retry.
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        let now = self.epoch.elapsed().as_nanos() as u64;
+        if let Some(limit) = &self.per_client {
+            let mut hasher = DefaultHasher::new();
+            client.hash(&mut hasher);
+            let shard = &self.clients[hasher.finish() as usize % SHARDS];
+            let acquired = shard
+                .read()
+                .unwrap()
+                .get(&client)
+                .map(|bucket| bucket.acquire(limit, now));
+            match acquired {
+                Some(acquired) => acquired?,
+                None => {
+                    let mut clients = shard.write().unwrap();
+                    if clients.len() >= MAX_CLIENTS {
+                        clients.retain(|_, bucket| !bucket.is_full(now));
+                    }
+                    clients.entry(client).or_default().acquire(limit, now)?;
+                }
+            }
+        }
+        if let Some((limit, bucket)) = &self.global {
+            bucket.acquire(limit, now)?;
+        }
+        Ok(())
+    }
+}
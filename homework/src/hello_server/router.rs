@@ -0,0 +1,353 @@
+//! Routing of the requests to the handlers registered for their method and path.
+
+use core::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::arena::ArenaMap;
+
+/// Request parsed by the server, borrowing from the request's
+/// [`RequestArena`](crate::arena::RequestArena).
+#[derive(Debug)]
+pub struct Request<'a> {
+    method: &'a str,
+    path: &'a str,
+    query: Option<&'a str>,
+    headers: ArenaMap<'a, &'a str, &'a str>,
+    pub(super) body: &'a [u8],
+    /// Path parameters, set by the router.
+    params: Vec<(String, &'a str)>,
+}
+
+impl<'a> Request<'a> {
+    /// Creates a request for the target `target`, e.g. `/user/42?verbose`.
+    pub fn new(
+        method: &'a str,
+        target: &'a str,
+        headers: ArenaMap<'a, &'a str, &'a str>,
+        body: &'a [u8],
+    ) -> Self {
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target, None),
+        };
+        Self {
+            method,
+            path,
+            query,
+            headers,
+            body,
+            params: Vec::new(),
+        }
+    }
+
+    /// Returns the method, e.g. `GET`.
+    pub fn method(&self) -> &'a str {
+        self.method
+    }
+
+    /// Returns the path, without the query.
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// Returns the query, i.e. the part of the target after `?`, if any.
+    pub fn query(&self) -> Option<&'a str> {
+        self.query
+    }
+
+    /// Returns the value of the header, whose name is case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns the body.
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// Returns the value of the path parameter `name` of the route that matched the request.
+    pub fn param(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Body of a [`Response`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Body {
+    Bytes(Vec<u8>),
+    /// The first bytes of a file, read in chunks as the response is written.
+    File(PathBuf, u64),
+}
+
+/// Response of a handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Body,
+}
+
+impl Response {
+    /// Creates a response with the status code and the body.
+    pub fn new<B: Into<Vec<u8>>>(status: u16, body: B) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Body::Bytes(body.into()),
+        }
+    }
+
+    /// Creates a `200 OK` response whose body is the first `len` bytes of the file at `path`.
+    /// The file is only read when the response is written, a chunk at a time, so a large file is
+    /// never held in memory. If the file is shorter by then, the connection is closed.
+    pub fn file<P: Into<PathBuf>>(path: P, len: u64) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: Body::File(path.into(), len),
+        }
+    }
+
+    /// Creates a `200 OK` response with the body.
+    pub fn ok<B: Into<Vec<u8>>>(body: B) -> Self {
+        Self::new(200, body)
+    }
+
+    /// Creates a `404 Not Found` response.
+    pub fn not_found() -> Self {
+        Self::new(404, NOT_FOUND).with_header("Content-Type", "text/html; charset=utf-8")
+    }
+
+    /// Adds a header. `Content-Length` and `Connection` are set by the server.
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Returns the status code.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the headers, in the order they were added.
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Returns the body, which is empty for a [file](Response::file) body.
+    pub fn body(&self) -> &[u8] {
+        match &self.body {
+            Body::Bytes(bytes) => bytes,
+            Body::File(..) => &[],
+        }
+    }
+
+    /// Returns the length of the body.
+    pub fn content_length(&self) -> u64 {
+        match &self.body {
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::File(_, len) => *len,
+        }
+    }
+
+    /// Returns the path and the length of the file of a [file](Response::file) body.
+    pub(super) fn file_body(&self) -> Option<(&Path, u64)> {
+        match &self.body {
+            Body::Bytes(_) => None,
+            Body::File(path, len) => Some((path, *len)),
+        }
+    }
+}
+
+/// Returns the reason phrase of the status code.
+pub(super) fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+const NOT_FOUND: &str = "<!DOCTYPE html>
+<html lang=\"en\">
+  <head>
+    <meta charset=\"utf-8\">
+    <title>Hello!</title>
+  </head>
+  <body>
+    <h1>Oops!</h1>
+    <p>Sorry, I don't know what you're asking for.</p>
+  </body>
+</html>";
+
+type HandlerFn = dyn Fn(&Request<'_>) -> Response + Send + Sync;
+
+/// Segment of the pattern of a route.
+#[derive(Debug)]
+enum Segment {
+    Literal(String),
+    /// `:name`, matching any non-empty segment.
+    Param(String),
+    /// `*name`, matching the rest of the path, which may be empty. Only at the end.
+    Rest(String),
+}
+
+struct Route {
+    method: String,
+    segments: Vec<Segment>,
+    handler: Arc<HandlerFn>,
+}
+
+impl fmt::Debug for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Route")
+            .field("method", &self.method)
+            .field("segments", &self.segments)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Route {
+    /// Returns the path parameters if the route matches the path.
+    fn matches<'p>(&self, path: &'p str) -> Option<Vec<(&str, &'p str)>> {
+        let mut params = Vec::new();
+        let mut rest = path.strip_prefix('/')?;
+        for (i, segment) in self.segments.iter().enumerate() {
+            if let Segment::Rest(name) = segment {
+                params.push((name.as_str(), rest));
+                return Some(params);
+            }
+            let (part, next) = match rest.split_once('/') {
+                Some((part, next)) => (part, Some(next)),
+                None => (rest, None),
+            };
+            match segment {
+                Segment::Literal(literal) if literal != part => return None,
+                Segment::Param(_) if part.is_empty() => return None,
+                Segment::Param(name) => params.push((name.as_str(), part)),
+                _ => {}
+            }
+            match next {
+                Some(next) => rest = next,
+                None => return (i + 1 == self.segments.len()).then(|| params),
+            }
+        }
+        None
+    }
+}
+
+/// Table of the handlers of the requests, by method and path pattern.
+///
+/// A pattern is a path whose segments may be parameters: `:name` matches any non-empty segment,
+/// and a final `*name` matches the rest of the path. The values are available with
+/// [`Request::param`]. The routes are tried in the order they were added, and the first one
+/// matching the method and the path handles the request. If a route matches the path but not the
+/// method, the response is `405 Method Not Allowed`, and otherwise `404 Not Found`.
+///
+/// The handlers are shared by all threads of the server, so they must be `Send + Sync`.
+#[derive(Debug, Default)]
+pub struct Router {
+    routes: Vec<Route>,
+}
+
+impl Router {
+    /// Creates a router without any route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a route handling the requests with the method that match the pattern.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern doesn't start with `/`, or has a `*name` segment that is not the
+    /// last one, or a parameter without a name.
+    pub fn route<H>(mut self, method: &str, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(&Request<'_>) -> Response + Send + Sync + 'static,
+    {
+        let pattern = pattern
+            .strip_prefix('/')
+            .unwrap_or_else(|| panic!("pattern `{}` doesn't start with `/`", pattern));
+        let parts = pattern.split('/').collect::<Vec<_>>();
+        let segments = parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                if let Some(name) = part.strip_prefix(':') {
+                    assert!(!name.is_empty(), "unnamed parameter in `/{}`", pattern);
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = part.strip_prefix('*') {
+                    assert!(!name.is_empty(), "unnamed parameter in `/{}`", pattern);
+                    assert!(i + 1 == parts.len(), "`*{}` is not at the end", name);
+                    Segment::Rest(name.to_string())
+                } else {
+                    Segment::Literal(part.to_string())
+                }
+            })
+            .collect();
+        self.routes.push(Route {
+            method: method.to_string(),
+            segments,
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    /// Adds a route handling the `GET` requests that match the pattern.
+    pub fn get<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(&Request<'_>) -> Response + Send + Sync + 'static,
+    {
+        self.route("GET", pattern, handler)
+    }
+
+    /// Adds a route handling the `POST` requests that match the pattern.
+    pub fn post<H>(self, pattern: &str, handler: H) -> Self
+    where
+        H: Fn(&Request<'_>) -> Response + Send + Sync + 'static,
+    {
+        self.route("POST", pattern, handler)
+    }
+
+    /// Responds to the request with the handler of the first route that matches it.
+    pub fn dispatch(&self, request: &mut Request<'_>) -> Response {
+        let mut allowed = Vec::new();
+        for route in &self.routes {
+            let params = some_or!(route.matches(request.path), continue);
+            if route.method != request.method {
+                allowed.push(route.method.as_str());
+                continue;
+            }
+            request.params = params
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect();
+            return (route.handler)(request);
+        }
+        if allowed.is_empty() {
+            return Response::not_found();
+        }
+        Response::new(405, "").with_header("Allow", allowed.join(", "))
+    }
+}
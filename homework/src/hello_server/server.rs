@@ -0,0 +1,468 @@
+//! Hello server, wiring the listener, the thread pool, the handler and the statistics together.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_channel::{unbounded, Sender};
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+#[cfg(any(unix, feature = "tls"))]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use super::cache::Cache;
+use super::handler::{Handler, Timeouts};
+use super::hooks::{HookList, Hooks};
+use super::rate_limit::{Limit, RateLimiter};
+use super::router::Router;
+use super::shard::Shards;
+use super::statistics::{Report, Statistics};
+use super::stream::Connection;
+use super::tcp::{CancellableTcpListener, ConnectionLimit, Overflow};
+use super::thread_pool::ThreadPool;
+#[cfg(feature = "tls")]
+use super::tls::TlsStream;
+#[cfg(unix)]
+use super::unix::CancellableUnixListener;
+
+/// Parameters of a [`Server`].
+///
+/// The number of threads, the timeouts, the rate limits and the routes may be changed while the
+/// server runs, by [`ServerHandle::reload`]. The other parameters only apply to a new server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    addr: String,
+    threads: usize,
+    queue_capacity: usize,
+    max_connections: usize,
+    overflow: Overflow,
+    timeouts: Timeouts,
+    rate_limit: Option<Limit>,
+    rate_limit_per_client: Option<Limit>,
+    router: Option<Arc<Router>>,
+    hooks: HookList,
+    cache_capacity: Option<usize>,
+    shards: Option<usize>,
+    shutdown_timeout: Duration,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+    #[cfg(feature = "tls")]
+    tls: Option<(PathBuf, PathBuf)>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: "localhost:7878".to_string(),
+            threads: 7,
+            queue_capacity: 256,
+            max_connections: 1024,
+            overflow: Overflow::Wait,
+            timeouts: Timeouts::default(),
+            rate_limit: None,
+            rate_limit_per_client: None,
+            router: None,
+            hooks: HookList::default(),
+            cache_capacity: None,
+            shards: None,
+            shutdown_timeout: Duration::from_secs(5),
+            #[cfg(unix)]
+            unix_socket: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Creates the default configuration: listening to `localhost:7878` with 7 threads, a queue
+    /// of 256 connections, at most 1024 connections at a time, the default [`Timeouts`], no rate
+    /// limit, the routes of [`Handler::with_cache`], an unbounded cache, and 5 seconds to finish
+    /// the jobs on exit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the address to listen to, e.g. `127.0.0.1:0` for any free port.
+    pub fn addr<A: Into<String>>(mut self, addr: A) -> Self {
+        self.addr = addr.into();
+        self
+    }
+
+    /// Sets the number of threads of the pool, including the listener.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is less than 2, as the listener takes a thread.
+    pub fn threads(mut self, threads: usize) -> Self {
+        assert!(threads >= 2, "no thread left for the connections");
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the number of connections queued for the pool before the listener waits. See
+    /// [`ThreadPoolBuilder::queue_capacity`](super::ThreadPoolBuilder::queue_capacity).
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity;
+        self
+    }
+
+    /// Sets the number of connections handled at a time. See [`ConnectionLimit`].
+    pub fn max_connections(mut self, max: usize) -> Self {
+        self.max_connections = max;
+        self
+    }
+
+    /// Sets what to do with the connections over the limit.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets the timeouts of the connections.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Limits the rate of the requests of all clients together. See [`RateLimiter::global`].
+    pub fn rate_limit(mut self, limit: Limit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Limits the rate of the requests of each client. See [`RateLimiter::per_client`].
+    pub fn rate_limit_per_client(mut self, limit: Limit) -> Self {
+        self.rate_limit_per_client = Some(limit);
+        self
+    }
+
+    /// Dispatches the requests with `router` instead of the default routes, which use the cache.
+    pub fn router(mut self, router: Router) -> Self {
+        self.router = Some(Arc::new(router));
+        self
+    }
+
+    /// Calls `hooks` around the dispatch of each request, and with the report of each connection.
+    /// See [`Handler::with_hooks`].
+    pub fn hooks<H: Hooks + 'static>(mut self, hooks: H) -> Self {
+        self.hooks = self.hooks.with(Arc::new(hooks));
+        self
+    }
+
+    /// Bounds the cache to `capacity` results.
+    pub fn cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = Some(capacity);
+        self
+    }
+
+    /// Handles the connections with `n` single-threaded [`Shards`] instead of the thread pool.
+    /// The shards have caches of their own, ignoring the other settings of the cache, and don't
+    /// limit the connections.
+    pub fn shards(mut self, n: usize) -> Self {
+        self.shards = Some(n);
+        self
+    }
+
+    /// Sets how long the pool may take to finish its jobs on exit.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Also listens to the Unix domain socket at `path`, with a listener of its own in the pool,
+    /// which takes a thread. Its connections are handled by the same handler and pool as the
+    /// TCP ones, even with [shards](ServerConfig::shards), and count towards the same
+    /// [limit](ServerConfig::max_connections).
+    #[cfg(unix)]
+    pub fn unix_socket<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.unix_socket = Some(path.into());
+        self
+    }
+
+    /// Serves HTTPS rather than HTTP on the TCP address, with the certificate chain of the PEM file
+    /// `cert` and the private key of the PEM file `key`, read by [`Server::new`]. The accepted
+    /// sockets are wrapped in [`TlsStream`]s, and handled like the plain ones. The Unix domain
+    /// socket stays plain, and the [shards](ServerConfig::shards) don't support TLS.
+    #[cfg(feature = "tls")]
+    pub fn tls<P: Into<PathBuf>, Q: Into<PathBuf>>(mut self, cert: P, key: Q) -> Self {
+        self.tls = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// Returns the rate limiter of the limits, if any.
+    fn rate_limiter(&self) -> Option<RateLimiter> {
+        if self.rate_limit.is_none() && self.rate_limit_per_client.is_none() {
+            return None;
+        }
+        let mut limiter = RateLimiter::new();
+        if let Some(limit) = self.rate_limit {
+            limiter = limiter.global(limit);
+        }
+        if let Some(limit) = self.rate_limit_per_client {
+            limiter = limiter.per_client(limit);
+        }
+        Some(limiter)
+    }
+}
+
+/// Handle stopping or reconfiguring a [`Server`] from another thread, e.g. on Ctrl-C.
+#[derive(Debug, Clone)]
+pub struct ServerHandle {
+    tcp: Arc<CancellableTcpListener>,
+    #[cfg(unix)]
+    unix: Option<Arc<CancellableUnixListener>>,
+    pool: Weak<ThreadPool>,
+    handler: Handler,
+}
+
+impl ServerHandle {
+    /// Cancels the listeners of the server, so that it stops accepting connections, and its
+    /// [`run`](Server::run) returns once the connections are handled. The socket file of the
+    /// Unix domain socket, if any, is removed.
+    pub fn shutdown(&self) -> io::Result<()> {
+        #[cfg(unix)]
+        if let Some(unix) = &self.unix {
+            unix.cancel()?;
+        }
+        self.tcp.cancel()
+    }
+
+    /// Applies the number of threads, the timeouts, the rate limits and the routes of `config`
+    /// to the server, without dropping any connection. See [`Handler::reload`]. The rate
+    /// limiter is replaced, so the clients start with full buckets.
+    ///
+    /// The [shards](ServerConfig::shards) are not reconfigured.
+    pub fn reload(&self, config: &ServerConfig) {
+        if let Some(pool) = self.pool.upgrade() {
+            #[cfg(unix)]
+            let threads = config.threads + usize::from(self.unix.is_some());
+            #[cfg(not(unix))]
+            let threads = config.threads;
+            pool.resize(threads, threads);
+        }
+        self.handler.reload(
+            config.router.clone(),
+            config.timeouts,
+            config.rate_limiter(),
+        );
+    }
+}
+
+/// Hello server.
+///
+/// The listener runs in a [`ThreadPool`], along with a job per connection:
+///
+/// - The listener accepts the connections, and executes a job for each of them that handles it
+///   with the [`Handler`] and sends its report to the reporter.
+///
+/// - The reporter, i.e. the thread [running](Server::run) the server, aggregates the reports into
+///   the [`Statistics`], which the handler serves at `/stats`.
+///
+/// The queue of the pool is bounded, so that a flood of connections makes the listener wait for
+/// the workers rather than queue the connections without bound.
+#[derive(Debug)]
+pub struct Server {
+    config: ServerConfig,
+    listener: Arc<CancellableTcpListener>,
+    #[cfg(unix)]
+    unix_listener: Option<Arc<CancellableUnixListener>>,
+    pool: Arc<ThreadPool>,
+    handler: Handler,
+    stats: Arc<Mutex<Statistics>>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+}
+
+impl Server {
+    /// Creates a server listening to the address of the configuration.
+    pub fn new(config: ServerConfig) -> io::Result<Self> {
+        #[cfg(feature = "tls")]
+        let tls = match &config.tls {
+            Some(_) if config.shards.is_some() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "the shards don't support TLS",
+                ))
+            }
+            Some((cert, key)) => Some(TlsStream::load_config(cert, key)?),
+            None => None,
+        };
+        let listener = Arc::new(CancellableTcpListener::bind(config.addr.as_str())?);
+        #[cfg(unix)]
+        let unix_listener = match &config.unix_socket {
+            Some(path) => Some(Arc::new(CancellableUnixListener::bind(path)?)),
+            None => None,
+        };
+        let stats = Arc::new(Mutex::new(Statistics::default()));
+        let cache = match config.cache_capacity {
+            Some(capacity) => Cache::builder().max_capacity(capacity).build(),
+            None => Cache::default(),
+        };
+        let handler = Handler::with_cache(cache)
+            .with_stats(Arc::clone(&stats))
+            .with_hook_list(config.hooks.clone());
+        handler.reload(
+            config.router.clone(),
+            config.timeouts,
+            config.rate_limiter(),
+        );
+        #[cfg(unix)]
+        let threads = config.threads + usize::from(unix_listener.is_some());
+        #[cfg(not(unix))]
+        let threads = config.threads;
+        let pool = Arc::new(
+            ThreadPool::builder(threads)
+                .queue_capacity(config.queue_capacity)
+                .build(),
+        );
+        Ok(Self {
+            config,
+            listener,
+            #[cfg(unix)]
+            unix_listener,
+            pool,
+            handler,
+            stats,
+            #[cfg(feature = "tls")]
+            tls,
+        })
+    }
+
+    /// Returns the address the server listens to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns the listener, whose [`cancel`](CancellableTcpListener::cancel) stops the server.
+    pub fn listener(&self) -> &Arc<CancellableTcpListener> {
+        &self.listener
+    }
+
+    /// Returns a handle stopping or reconfiguring the server.
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            tcp: Arc::clone(&self.listener),
+            #[cfg(unix)]
+            unix: self.unix_listener.clone(),
+            pool: Arc::downgrade(&self.pool),
+            handler: self.handler.clone(),
+        }
+    }
+
+    /// Returns the handler, e.g. to warm up its cache.
+    pub fn handler(&self) -> &Handler {
+        &self.handler
+    }
+
+    /// Serves the connections until the listeners are cancelled, and returns the statistics of
+    /// all connections. The pool is shut down before, so all worker threads are joined and the
+    /// garbage they left can be freed.
+    pub fn run(self) -> Statistics {
+        let Self {
+            config,
+            listener,
+            #[cfg(unix)]
+            unix_listener,
+            pool,
+            handler,
+            stats,
+            #[cfg(feature = "tls")]
+            tls,
+        } = self;
+
+        // The (MPSC) channel of reports between workers and the reporter.
+        let (report_sender, report_receiver) = unbounded();
+        let accept = Accept {
+            pool: Arc::clone(&pool),
+            handler: handler.clone(),
+            limit: ConnectionLimit::new(config.max_connections, config.overflow),
+            next_id: Arc::new(AtomicUsize::new(0)),
+            reports: report_sender,
+        };
+
+        // Executes the listeners.
+        #[cfg(unix)]
+        if let Some(unix_listener) = unix_listener {
+            let accept = accept.clone();
+            pool.execute(move || accept.run(unix_listener.incoming()));
+        }
+        let shards = config.shards;
+        pool.execute(move || {
+            if let Some(shards) = shards {
+                let shards = Shards::new(shards, accept.reports.clone());
+                for stream in listener.incoming() {
+                    let id = accept.next_id.fetch_add(1, Ordering::Relaxed);
+                    // the client may already be gone
+                    let _ = shards.dispatch(id, stream.unwrap());
+                }
+                // When dropped, the shards finish the queued connections and send the last
+                // reports.
+                return;
+            }
+            #[cfg(feature = "tls")]
+            if let Some(tls) = tls {
+                let incoming = listener.incoming().map(move |stream| {
+                    stream.and_then(|stream| TlsStream::new(stream, Arc::clone(&tls)))
+                });
+                return accept.run(incoming);
+            }
+            accept.run(listener.incoming());
+        });
+
+        // Aggregates the reports on this thread until the listeners and the connections are done.
+        for report in report_receiver {
+            config.hooks.on_report(&report);
+            stats.lock().unwrap().add_report(report);
+        }
+        let mut stats = mem::take(&mut *stats.lock().unwrap());
+        if config.shards.is_none() {
+            stats.set_cache_stats(handler.cache_stats());
+        }
+
+        // The listeners are done, so the workers are only finishing their last jobs.
+        match Arc::try_unwrap(pool) {
+            Ok(pool) => {
+                let abandoned = pool.shutdown_with_timeout(config.shutdown_timeout);
+                stats.set_abandoned(abandoned.len());
+            }
+            Err(pool) => drop(pool),
+        }
+        stats
+    }
+}
+
+/// State of a listener of the server, shared by the listeners.
+#[derive(Debug, Clone)]
+struct Accept {
+    pool: Arc<ThreadPool>,
+    handler: Handler,
+    limit: Arc<ConnectionLimit>,
+    next_id: Arc<AtomicUsize>,
+    reports: Sender<Report>,
+}
+
+impl Accept {
+    /// Executes a job handling each incoming connection, until the listener is cancelled.
+    fn run<C, I>(self, incoming: I)
+    where
+        C: Connection + Send + 'static,
+        I: Iterator<Item = io::Result<C>>,
+    {
+        for stream in incoming {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            // take a permit, waiting for one if needed,
+            let permit = self.limit.acquire();
+            // and send a job to the thread pool.
+            let report_sender = self.reports.clone();
+            let handler = self.handler.clone();
+            self.pool.execute(move || {
+                let report = match permit {
+                    Some(_permit) => handler.handle_conn(id, stream.unwrap()),
+                    None => handler.reject(id, stream.unwrap()),
+                };
+                report_sender.send(report).unwrap();
+            });
+        }
+    }
+}
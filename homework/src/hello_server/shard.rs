@@ -0,0 +1,164 @@
+//! Thread-per-core execution of the hello server.
+
+use core::hash::{Hash, Hasher};
+use crossbeam_channel::{bounded, Sender};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::io;
+use std::net::TcpStream;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::handler::{very_expensive_computation_that_takes_a_few_seconds, Handler, Timeouts};
+use super::router::Response;
+use super::statistics::Report;
+use crate::arena::RequestArena;
+
+/// Number of connections queued for a shard before the dispatcher waits.
+const QUEUE: usize = 64;
+
+/// How long the dispatcher waits for a request to route it by its key.
+const PEEK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Single-threaded shards of the server, each owning the cache of its keys.
+///
+/// The dispatcher (the thread accepting the connections) peeks at each request and sends the
+/// connection to the shard of its key over a single-producer single-consumer channel. A shard
+/// owns a plain `HashMap` cache and a [`RequestArena`], so serving its keys takes no
+/// synchronization at all. In exchange, a shard handles one connection at a time, so a cache miss
+/// holds up the other requests of the shard for the whole computation.
+///
+/// Requests without a key, or that don't arrive in time to be routed, go to the shards in turn.
+#[derive(Debug)]
+pub struct Shards {
+    senders: Vec<Sender<(usize, TcpStream)>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// State of a shard, owned by its thread.
+struct Shard {
+    cache: HashMap<String, String>,
+    arena: RequestArena,
+}
+
+impl Shard {
+    /// Serves one request of the connection, which is closed afterwards since the next request
+    /// may be for the key of another shard.
+    fn handle_conn(&mut self, request_id: usize, stream: TcpStream) -> Report {
+        let cache = &mut self.cache;
+        let timeouts = Timeouts::default();
+        let (requests, timed_out) =
+            Handler::serve(&mut self.arena, &stream, false, &timeouts, |request| {
+                let key = request.path().strip_prefix('/').unwrap_or_default();
+                if request.method() != "GET" || key.is_empty() || key.contains('/') {
+                    return Response::not_found();
+                }
+                let result = cache.entry(key.to_string()).or_insert_with_key(|key| {
+                    very_expensive_computation_that_takes_a_few_seconds(key.clone())
+                });
+                Handler::hello(key, result)
+            });
+        let report = Report::new(request_id, requests);
+        if timed_out {
+            report.timed_out()
+        } else {
+            report
+        }
+    }
+}
+
+impl Shards {
+    /// Spawns `n` shards, which send the report of each connection to `reports`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn new(n: usize, reports: Sender<Report>) -> Self {
+        assert!(n > 0, "no shard");
+        let (senders, threads) = (0..n)
+            .map(|i| {
+                let (sender, receiver) = bounded::<(usize, TcpStream)>(QUEUE);
+                let reports = reports.clone();
+                let thread = thread::Builder::new()
+                    .name(format!("shard-{}", i))
+                    .spawn(move || {
+                        let mut shard = Shard {
+                            cache: HashMap::new(),
+                            arena: RequestArena::new(),
+                        };
+                        for (id, stream) in receiver {
+                            // the statistics may be gone during a shutdown
+                            let _ = reports.send(shard.handle_conn(id, stream));
+                        }
+                    })
+                    .expect("failed to spawn a shard");
+                (sender, thread)
+            })
+            .unzip();
+        Self { senders, threads }
+    }
+
+    /// Returns the number of shards.
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Returns `true` if there is no shard, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.senders.is_empty()
+    }
+
+    /// Returns the shard that owns `key`.
+    pub fn shard_of(&self, key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % self.len() as u64) as usize
+    }
+
+    /// Sends the connection `id` to the shard of its key, waiting if the shard is busy.
+    pub fn dispatch(&self, id: usize, stream: TcpStream) -> io::Result<()> {
+        let mut buf = [0; 512];
+        let deadline = Instant::now() + PEEK_TIMEOUT;
+        let mut peeked = 0;
+        // the request line may arrive in several segments
+        while !buf[..peeked].contains(&b'\n') && peeked < buf.len() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            stream.set_read_timeout(Some(deadline - now))?;
+            match stream.peek(&mut buf) {
+                Ok(0) => break,
+                Ok(len) if len == peeked => thread::sleep(Duration::from_millis(1)),
+                Ok(len) => peeked = len,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    break
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        stream.set_read_timeout(None)?;
+        let shard = match Handler::request_key(&buf[..peeked]) {
+            Some(key) => self.shard_of(&key),
+            None => id % self.len(),
+        };
+        self.senders[shard]
+            .send((id, stream))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the shard exited"))
+    }
+}
+
+impl Drop for Shards {
+    /// Lets the shards finish the queued connections, and joins them.
+    fn drop(&mut self) {
+        self.senders.clear();
+        for thread in self.threads.drain(..) {
+            thread.join().unwrap();
+        }
+    }
+}
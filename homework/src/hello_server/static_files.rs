@@ -0,0 +1,211 @@
+//! Static files served from a directory, with a cache of their contents.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::cache::Cache;
+use super::router::{Request, Response};
+
+/// Files up to this size have their contents cached by default.
+const MAX_CACHED_SIZE: u64 = 64 * 1024;
+
+/// Total size of the cached contents by default.
+const CACHE_CAPACITY: usize = 16 * 1024 * 1024;
+
+/// Cached version of a file.
+#[derive(Debug)]
+struct FileEntry {
+    modified: SystemTime,
+    len: u64,
+    etag: String,
+    last_modified: String,
+    /// The contents, for a small file.
+    bytes: Option<Vec<u8>>,
+}
+
+impl FileEntry {
+    fn is_fresh(&self, metadata: &Metadata, modified: SystemTime) -> bool {
+        self.modified == modified && self.len == metadata.len()
+    }
+}
+
+/// Handler of the requests for the files of a directory.
+///
+/// A file is identified by its modification time and its length, in the `ETag` and
+/// `Last-Modified` headers. A conditional request (`If-None-Match` or `If-Modified-Since`) for an
+/// unchanged file is answered with `304 Not Modified`.
+///
+/// The versions of the files are kept in a [`Cache`], with the contents of the small ones, so
+/// that they are served without reading the file again. A large file is streamed from the disk
+/// by its [response](Response::file), and only its version is cached. A version is checked
+/// against the metadata of the file on each request, so a changed file is read again.
+///
+/// To serve the files under `/static/`:
+///
+/// ```no_run
+/// # use cs431_homework::hello_server::{Router, StaticFiles};
+/// let files = StaticFiles::new("public");
+/// let router = Router::new().get("/static/*path", move |request| {
+///     files.serve(request.param("path").unwrap(), request)
+/// });
+/// ```
+#[derive(Debug)]
+pub struct StaticFiles {
+    root: PathBuf,
+    max_cached_size: u64,
+    cache: Cache<PathBuf, FileEntry>,
+}
+
+impl StaticFiles {
+    /// Creates a handler of the files under `root`, caching the contents of the files up to
+    /// 64 KiB, up to 16 MiB in total.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self::with_cache(root, MAX_CACHED_SIZE, CACHE_CAPACITY)
+    }
+
+    /// Creates a handler of the files under `root`, caching the contents of the files up to
+    /// `max_cached_size` bytes, up to `capacity` bytes in total.
+    pub fn with_cache<P: Into<PathBuf>>(root: P, max_cached_size: u64, capacity: usize) -> Self {
+        Self {
+            root: root.into(),
+            max_cached_size,
+            cache: Cache::builder()
+                .max_capacity(capacity.max(1))
+                .weigher(|_, entry: &FileEntry| {
+                    entry.bytes.as_ref().map_or(0, Vec::len) + entry.etag.len()
+                })
+                .build(),
+        }
+    }
+
+    /// Responds to the request for the file at `path`, relative to the root. A path escaping the
+    /// root, e.g. with `..`, is not found.
+    pub fn serve(&self, path: &str, request: &Request<'_>) -> Response {
+        let path = Path::new(path);
+        if !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Response::not_found();
+        }
+        let path = self.root.join(path);
+        let (metadata, modified) =
+            match fs::metadata(&path).and_then(|metadata| Ok((metadata.modified()?, metadata))) {
+                Ok((modified, metadata)) if metadata.is_file() => (metadata, modified),
+                _ => return Response::not_found(),
+            };
+
+        let cached = self
+            .cache
+            .get(&path)
+            .filter(|entry| entry.is_fresh(&metadata, modified));
+        let entry = match cached {
+            Some(entry) => entry,
+            None => {
+                let _ = self.cache.invalidate(&path);
+                let loaded = self.cache.get_or_try_insert_with(path.clone(), |path| {
+                    self.load(&path, &metadata, modified)
+                });
+                match loaded {
+                    Ok(entry) => entry,
+                    Err(_) => return Response::not_found(),
+                }
+            }
+        };
+
+        let not_modified = match request.header("If-None-Match") {
+            Some(tags) => tags
+                .split(',')
+                .any(|tag| tag.trim() == entry.etag || tag.trim() == "*"),
+            None => request.header("If-Modified-Since") == Some(entry.last_modified.as_str()),
+        };
+        let response = if not_modified {
+            Response::new(304, "")
+        } else {
+            let response = match &entry.bytes {
+                Some(bytes) => Response::ok(bytes.clone()),
+                None => Response::file(path.as_path(), entry.len),
+            };
+            response.with_header("Content-Type", content_type(&path))
+        };
+        response
+            .with_header("ETag", entry.etag.as_str())
+            .with_header("Last-Modified", entry.last_modified.as_str())
+    }
+
+    /// Reads the version of the file, and its contents if it's small.
+    fn load(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        modified: SystemTime,
+    ) -> io::Result<FileEntry> {
+        let bytes = if metadata.len() <= self.max_cached_size {
+            Some(fs::read(path)?)
+        } else {
+            None
+        };
+        // if the file changed since the metadata was read, the next request reads it again
+        let len = bytes
+            .as_ref()
+            .map_or(metadata.len(), |bytes| bytes.len() as u64);
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(FileEntry {
+            modified,
+            len,
+            etag: format!("\"{:x}-{:x}\"", len, since_epoch.as_nanos()),
+            last_modified: http_date(since_epoch.as_secs()),
+            bytes,
+        })
+    }
+}
+
+/// Returns the content type of the file by its extension.
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Formats the time in seconds since the Unix epoch as an HTTP date, e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(secs: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // the civil date of the day, from Howard Hinnant's `civil_from_days`, with years starting
+    // in March
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[((days - 719_468) % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
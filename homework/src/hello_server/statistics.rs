@@ -1,18 +1,150 @@
 //! Server statisics
 
-use std::collections::HashMap;
+use core::fmt::{self, Write};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
 
-/// Report for each operation
+use super::cache::CacheStats;
+use crate::metrics::TopK;
+
+/// Request served on a connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Served {
+    key: Option<String>, // None represents invalid request
+    status: u16,
+    latency: Duration,
+}
+
+impl Served {
+    /// Creates the record of a request with the key, the status code of the response, and the
+    /// time from the first byte of the request to the last byte of the response.
+    pub fn new(key: Option<String>, status: u16, latency: Duration) -> Self {
+        Self {
+            key,
+            status,
+            latency,
+        }
+    }
+}
+
+/// Report for each connection
 #[derive(Debug)]
 pub struct Report {
     _id: usize,
-    key: Option<String>, // None represents invalid request
+    requests: Vec<Served>,
+    timed_out: bool,
+    rejected: bool,
 }
 
 impl Report {
-    /// Creates a new report with the given id and key.
-    pub fn new(id: usize, key: Option<String>) -> Self {
-        Report { _id: id, key }
+    /// Creates a new report with the given id and the requests served on the connection.
+    pub fn new(id: usize, requests: Vec<Served>) -> Self {
+        Report {
+            _id: id,
+            requests,
+            timed_out: false,
+            rejected: false,
+        }
+    }
+
+    /// Marks the connection as closed by a timeout.
+    pub fn timed_out(mut self) -> Self {
+        self.timed_out = true;
+        self
+    }
+
+    /// Returns `true` if the connection was closed by a timeout.
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    /// Marks the connection as rejected at the connection limit.
+    pub fn rejected(mut self) -> Self {
+        self.rejected = true;
+        self
+    }
+
+    /// Returns `true` if the connection was rejected at the connection limit.
+    pub fn is_rejected(&self) -> bool {
+        self.rejected
+    }
+
+    /// Returns the number of requests of the connection.
+    pub fn requests(&self) -> usize {
+        self.requests.len()
+    }
+}
+
+/// Number of buckets of a [`Histogram`].
+const BUCKETS: usize = 40;
+
+/// Histogram of latencies in fixed buckets of exponentially growing width.
+///
+/// The first bucket holds the latencies under 1 µs, and bucket `i` those in
+/// `[2^(i-1), 2^i)` µs, up to about 6 days. So a percentile is overestimated by at most twice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    max: Duration,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Histogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Records a latency.
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u128::from(u64::MAX)) as u64;
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)] += 1;
+        self.count += 1;
+        self.max = self.max.max(latency);
+    }
+
+    /// Returns the number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the largest latency recorded.
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Returns an upper bound of the `p`th percentile of the latencies, e.g. the median for 50,
+    /// or `None` if there is none.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is not in `0..=100`.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        assert!((0.0..=100.0).contains(&p), "invalid percentile {}", p);
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((p / 100.0 * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                let bound = Duration::from_micros(1 << i);
+                return Some(bound.min(self.max));
+            }
+        }
+        unreachable!()
     }
 }
 
@@ -20,12 +152,164 @@ impl Report {
 #[derive(Debug, Default)]
 pub struct Statistics {
     hits: HashMap<Option<String>, usize>,
+    connections: usize,
+    requests: usize,
+    timeouts: usize,
+    rejected: usize,
+    latency: Histogram,
+    statuses: BTreeMap<u16, usize>,
+    hot_keys: TopK<String>,
+    cache: Option<CacheStats>,
+    abandoned: usize,
 }
 
 impl Statistics {
     /// Add a report to the statisics.
     pub fn add_report(&mut self, report: Report) {
-        let hits = self.hits.entry(report.key).or_default();
-        *hits += 1;
+        self.connections += 1;
+        self.requests += report.requests.len();
+        if report.timed_out {
+            self.timeouts += 1;
+        }
+        if report.rejected {
+            self.rejected += 1;
+        }
+        for served in report.requests {
+            if let Some(key) = &served.key {
+                self.hot_keys.record(key);
+            }
+            let hits = self.hits.entry(served.key).or_default();
+            *hits += 1;
+            self.latency.record(served.latency);
+            *self.statuses.entry(served.status).or_default() += 1;
+        }
+    }
+
+    /// Returns the number of connections reported.
+    pub fn connections(&self) -> usize {
+        self.connections
+    }
+
+    /// Returns the number of requests of the connections reported.
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// Returns the number of connections closed by a timeout.
+    pub fn timeouts(&self) -> usize {
+        self.timeouts
+    }
+
+    /// Returns the number of connections rejected at the connection limit.
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    /// Returns the average number of requests per connection, or 0 if there is no connection.
+    pub fn requests_per_connection(&self) -> f64 {
+        if self.connections == 0 {
+            return 0.0;
+        }
+        self.requests as f64 / self.connections as f64
+    }
+
+    /// Returns the histogram of the latencies of the requests.
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    /// Returns the number of responses of each status code, by status code.
+    pub fn statuses(&self) -> Vec<(u16, usize)> {
+        self.statuses.iter().map(|(&s, &n)| (s, n)).collect()
+    }
+
+    /// Returns the most requested keys with their approximate hit counts, most frequent first.
+    pub fn hot_keys(&self) -> Vec<(String, u64)> {
+        self.hot_keys.top()
+    }
+
+    /// Records the statistics of the cache that served the requests.
+    pub fn set_cache_stats(&mut self, stats: CacheStats) {
+        self.cache = Some(stats);
+    }
+
+    /// Returns the statistics of the cache, if recorded.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache
+    }
+
+    /// Records the number of connections abandoned without being handled at the shutdown.
+    pub fn set_abandoned(&mut self, abandoned: usize) {
+        self.abandoned = abandoned;
+    }
+
+    /// Returns the number of connections abandoned without being handled at the shutdown.
+    pub fn abandoned(&self) -> usize {
+        self.abandoned
+    }
+
+    /// Returns the statistics as a JSON object, with the latencies in microseconds.
+    pub fn to_json(&self) -> String {
+        let micros = |latency: Option<Duration>| latency.unwrap_or_default().as_micros();
+        let mut json = String::new();
+        // writing to a string doesn't fail
+        let _ = write!(
+            json,
+            "{{\"connections\":{},\"requests\":{},\"timeouts\":{},\"rejected\":{},\
+             \"requests_per_connection\":{},\"latency_us\":{{\"p50\":{},\"p95\":{},\
+             \"p99\":{},\"max\":{}}},\"statuses\":{{",
+            self.connections,
+            self.requests,
+            self.timeouts,
+            self.rejected,
+            self.requests_per_connection(),
+            micros(self.latency.percentile(50.0)),
+            micros(self.latency.percentile(95.0)),
+            micros(self.latency.percentile(99.0)),
+            self.latency.max().as_micros(),
+        );
+        for (i, (status, n)) in self.statuses.iter().enumerate() {
+            let comma = if i > 0 { "," } else { "" };
+            let _ = write!(json, "{}\"{}\":{}", comma, status, n);
+        }
+        json.push_str("},\"hot_keys\":[");
+        for (i, (key, hits)) in self.hot_keys().iter().enumerate() {
+            let comma = if i > 0 { "," } else { "" };
+            let _ = write!(
+                json,
+                "{}{{\"key\":\"{}\",\"hits\":{}}}",
+                comma,
+                JsonStr(key),
+                hits
+            );
+        }
+        json.push(']');
+        if let Some(cache) = &self.cache {
+            let _ = write!(
+                json,
+                ",\"cache\":{{\"hits\":{},\"misses\":{},\"computations\":{},\
+                 \"coalesced\":{},\"entries\":{}}}",
+                cache.hits, cache.misses, cache.computations, cache.coalesced, cache.entries
+            );
+        }
+        json.push('}');
+        json
+    }
+}
+
+/// String escaped for the inside of a JSON string.
+struct JsonStr<'a>(&'a str);
+
+impl fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
     }
 }
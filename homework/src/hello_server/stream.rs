@@ -0,0 +1,101 @@
+//! Connections of the clients, over TCP, TLS or a Unix domain socket.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+/// Connection to a client that a [`Handler`](super::Handler) serves.
+///
+/// Like the standard streams, a connection is read and written through a shared reference, so
+/// that it can be read and written at once.
+pub trait Connection {
+    /// Reads some bytes into `buf`, like [`Read::read`].
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Writes some bytes of `buf`, like [`Write::write`].
+    fn send(&self, buf: &[u8]) -> io::Result<usize>;
+
+    /// Sets the timeout of the reads, or none if `None`.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Sets the timeout of the writes, or none if `None`.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+
+    /// Returns the IP address of the client, if it has one.
+    fn peer_ip(&self) -> Option<IpAddr>;
+}
+
+impl Connection for TcpStream {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buf)
+    }
+
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        self.peer_addr().ok().map(|addr| addr.ip())
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read(&mut &*self, buf)
+    }
+
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        Write::write(&mut &*self, buf)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_write_timeout(self, timeout)
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        None
+    }
+}
+
+/// Reader and writer of a shared connection.
+#[derive(Debug)]
+pub(super) struct Io<'a, C>(pub(super) &'a C);
+
+impl<C> Clone for Io<'_, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for Io<'_, C> {}
+
+impl<C: Connection> Read for Io<'_, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.recv(buf)
+    }
+}
+
+impl<C: Connection> Write for Io<'_, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.send(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
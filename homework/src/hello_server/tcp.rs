@@ -1,9 +1,10 @@
 //! TcpListener that can be cancelled.
 
 use std::io::{self, Write};
-use std::net::ToSocketAddrs;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::net::{TcpListener, TcpStream};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
 
 /// Like `std::net::tcp::TcpListener`, but `cancel`lable.
 #[derive(Debug)]
@@ -34,6 +35,11 @@ impl CancellableTcpListener {
         })
     }
 
+    /// Returns the address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
     /// Signals the listener to stop accepting new connections.
     pub fn cancel(&self) -> io::Result<()> {
         self.is_canceled.store(true, Ordering::Release);
@@ -63,3 +69,84 @@ impl<'a> Iterator for Incoming<'a> {
         }
     }
 }
+
+/// What to do with a connection accepted at the limit of a [`ConnectionLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Waits for a connection to close, without accepting more connections in the meantime, so
+    /// that the clients wait in the backlog of the listener.
+    #[default]
+    Wait,
+    /// Rejects the connection, e.g. with `503 Service Unavailable`.
+    Reject,
+}
+
+/// Cap on the number of connections handled at the same time, so that a flood of clients can't
+/// exhaust the file descriptors or the queue of the thread pool.
+///
+/// The acceptor takes a [`Permit`] for each accepted connection, which is released when the
+/// connection is handled.
+#[derive(Debug)]
+pub struct ConnectionLimit {
+    max: usize,
+    overflow: Overflow,
+    active: Mutex<usize>,
+    released: Condvar,
+}
+
+/// Permit of a connection under a [`ConnectionLimit`], released when dropped.
+#[derive(Debug)]
+pub struct Permit {
+    limit: Arc<ConnectionLimit>,
+}
+
+impl ConnectionLimit {
+    /// Creates a limit of `max` connections.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn new(max: usize, overflow: Overflow) -> Arc<Self> {
+        assert!(max > 0, "no connection allowed");
+        Arc::new(Self {
+            max,
+            overflow,
+            active: Mutex::new(0),
+            released: Condvar::new(),
+        })
+    }
+
+    /// Takes a permit for a new connection. At the limit, waits for a permit to be released, or
+    /// returns `None` if the connection should be rejected.
+    pub fn acquire(self: &Arc<Self>) -> Option<Permit> {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.max {
+            if self.overflow == Overflow::Reject {
+                return None;
+            }
+            active = self.released.wait(active).unwrap();
+        }
+        *active += 1;
+        Some(Permit {
+            limit: Arc::clone(self),
+        })
+    }
+
+    /// Returns the number of connections holding a permit.
+    pub fn active(&self) -> usize {
+        *self.active.lock().unwrap()
+    }
+
+    /// Returns the maximum number of connections.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut active = self.limit.active.lock().unwrap();
+        *active -= 1;
+        self.limit.released.notify_one();
+    }
+}
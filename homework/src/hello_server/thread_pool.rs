@@ -2,44 +2,377 @@
 
 // NOTE: Crossbeam channels are MPMC, which means that you don't need to wrap the receiver in
 // Arc<Mutex<..>>. Just clone the receiver and give it to each worker thread.
-use crossbeam_channel::{unbounded, Sender};
-use std::sync::{Arc, Condvar, Mutex};
+use crossbeam_channel::{
+    bounded, unbounded, Receiver, RecvTimeoutError, Select, Sender, TryRecvError,
+};
+use std::any::Any;
+use std::fmt;
+#[cfg(feature = "async")]
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+#[cfg(feature = "async")]
+use std::sync::Weak;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll, Wake, Waker};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-struct Job(Box<dyn FnOnce() + Send + 'static>);
+/// A job of the pool, returned by [`ThreadPool::shutdown_with_timeout`] if it was abandoned.
+pub struct Job(pub(super) Box<dyn FnOnce() + Send + 'static>);
 
+impl Job {
+    /// Runs the job on the current thread.
+    pub fn run(self) {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Job")
+    }
+}
+
+/// Error of [`ThreadPool::try_execute`] when the queue is full, with the job that was rejected.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Full<F>(pub F);
+
+impl<F> fmt::Debug for Full<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Full(..)")
+    }
+}
+
+impl<F> fmt::Display for Full<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the job queue is full")
+    }
+}
+
+impl<F> std::error::Error for Full<F> {}
+
+/// Error of [`JobHandle::join`] when the job didn't return a value.
+pub enum JobError {
+    /// The job panicked, with the payload of the panic.
+    Panicked(Box<dyn Any + Send + 'static>),
+    /// The job was dropped without running, e.g. abandoned by
+    /// [`ThreadPool::shutdown_with_timeout`].
+    Cancelled,
+}
+
+impl fmt::Debug for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Panicked(_) => f.write_str("Panicked(..)"),
+            JobError::Cancelled => f.write_str("Cancelled"),
+        }
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Panicked(_) => f.write_str("the job panicked"),
+            JobError::Cancelled => f.write_str("the job was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+/// Handle of a job submitted by [`ThreadPool::submit`], which receives the job's return value.
+///
+/// Dropping the handle doesn't cancel the job, and its return value is then dropped.
 #[derive(Debug)]
-struct Worker {
-    _id: usize,
-    thread: Option<thread::JoinHandle<()>>,
+pub struct JobHandle<R> {
+    result: Receiver<thread::Result<R>>,
+    /// Whether `try_join` already returned the result.
+    done: bool,
+}
+
+impl<R> JobHandle<R> {
+    /// Blocks the current thread until the job is done, and returns its return value.
+    pub fn join(self) -> Result<R, JobError> {
+        match self.result.recv() {
+            Ok(result) => result.map_err(JobError::Panicked),
+            Err(_) => Err(JobError::Cancelled),
+        }
+    }
+
+    /// Returns the job's return value if the job is done, or `None` if it's still queued or
+    /// running. Once it returned the result, it returns `None` forever.
+    pub fn try_join(&mut self) -> Option<Result<R, JobError>> {
+        if self.done {
+            return None;
+        }
+        let result = match self.result.try_recv() {
+            Ok(result) => result.map_err(JobError::Panicked),
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(JobError::Cancelled),
+        };
+        self.done = true;
+        Some(result)
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Worker {
+    pub(super) id: usize,
+    pub(super) thread: Option<thread::JoinHandle<()>>,
+    /// The cores the thread is pinned to, if any.
+    pub(super) cores: Vec<usize>,
+}
+
+impl Worker {
+    /// Lets the thread exit on its own, without waiting for it.
+    fn detach(mut self) {
+        drop(self.thread.take());
+    }
 }
 
 impl Drop for Worker {
     /// When dropped, the thread's `JoinHandle` must be `join`ed.  If the worker panics, then this
     /// function should panic too.  NOTE: that the thread is detached if not `join`ed explicitly.
     fn drop(&mut self) {
-        self.thread.take().unwrap().join().unwrap();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+/// Priority of a job, see [`ThreadPool::execute_with_priority`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// For the jobs that must not wait behind the others, e.g. health checks.
+    High,
+    /// The priority of the jobs executed otherwise.
+    #[default]
+    Normal,
+    /// For the jobs that may wait, e.g. long-running ones.
+    Low,
+}
+
+/// Out of every `STARVATION_INTERVAL` jobs taken, one is of low priority and one is of normal
+/// priority, if any is queued, even if higher priority ones are queued.
+const STARVATION_INTERVAL: usize = 8;
+
+/// Receiving side of the queues of the jobs of each priority.
+#[derive(Debug)]
+struct JobQueue {
+    /// Indexed by the priority.
+    receivers: [Receiver<Job>; 3],
+    /// Number of jobs taken so far.
+    taken: AtomicUsize,
+}
+
+impl JobQueue {
+    fn new() -> ([Sender<Job>; 3], Self) {
+        let [(high, high_receiver), (normal, normal_receiver), (low, low_receiver)] =
+            [unbounded(), unbounded(), unbounded()];
+        let queue = Self {
+            receivers: [high_receiver, normal_receiver, low_receiver],
+            taken: AtomicUsize::new(0),
+        };
+        ([high, normal, low], queue)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.receivers.iter().all(Receiver::is_empty)
+    }
+
+    /// Takes a job of the highest priority, unless a lower priority one is due.
+    fn try_recv(&self) -> Result<Job, TryRecvError> {
+        let order = match self.taken.load(Ordering::Relaxed) % STARVATION_INTERVAL {
+            n if n == STARVATION_INTERVAL - 1 => [Priority::Low, Priority::Normal, Priority::High],
+            n if n == STARVATION_INTERVAL / 2 - 1 => {
+                [Priority::Normal, Priority::Low, Priority::High]
+            }
+            _ => [Priority::High, Priority::Normal, Priority::Low],
+        };
+        let mut disconnected = true;
+        for priority in order {
+            match self.receivers[priority as usize].try_recv() {
+                Ok(job) => {
+                    let _ = self.taken.fetch_add(1, Ordering::Relaxed);
+                    return Ok(job);
+                }
+                Err(TryRecvError::Empty) => disconnected = false,
+                Err(TryRecvError::Disconnected) => {}
+            }
+        }
+        Err(if disconnected {
+            TryRecvError::Disconnected
+        } else {
+            TryRecvError::Empty
+        })
+    }
+
+    /// Blocks until a job is queued or all queues are disconnected, or until `timeout` elapses.
+    fn recv(&self, timeout: Option<Duration>) -> Result<Job, RecvTimeoutError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            match self.try_recv() {
+                Ok(job) => return Ok(job),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+            let mut select = Select::new();
+            for receiver in &self.receivers {
+                let _ = select.recv(receiver);
+            }
+            match deadline {
+                // another worker may take the job first, so try again
+                Some(deadline) => {
+                    if select.ready_deadline(deadline).is_err() {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                }
+                None => {
+                    let _ = select.ready();
+                }
+            }
+        }
+    }
+
+    /// Takes all jobs queued, in the order of their priority.
+    fn drain(&self) -> Vec<Job> {
+        self.receivers.iter().flat_map(Receiver::try_iter).collect()
+    }
+}
+
+/// Jobs of a [`Scope`] that are not done yet, and the first panic of one of them.
+#[derive(Debug, Default)]
+struct ScopeState {
+    pending: Mutex<usize>,
+    done_condvar: Condvar,
+    panic: Mutex<Option<Box<dyn Any + Send + 'static>>>,
+}
+
+impl ScopeState {
+    fn wait(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        while *pending > 0 {
+            pending = self.done_condvar.wait(pending).unwrap();
+        }
+    }
+}
+
+/// Marks a job of a scope done when dropped, whether it ran or not.
+struct ScopeJob(Arc<ScopeState>);
+
+impl Drop for ScopeJob {
+    fn drop(&mut self) {
+        let mut pending = self.0.pending.lock().unwrap();
+        *pending -= 1;
+        if *pending == 0 {
+            self.0.done_condvar.notify_all();
+        }
+    }
+}
+
+/// Scope of [`ThreadPool::scope`], whose jobs may borrow the data outliving `'env`.
+#[derive(Debug)]
+pub struct Scope<'env> {
+    pool: &'env ThreadPool,
+    state: Arc<ScopeState>,
+    /// Invariant in `'env`, so that it isn't shortened to borrow shorter-lived data.
+    _marker: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'env> Scope<'env> {
+    /// Execute a new job in the pool, which is done before the scope returns.
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'env,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+        let done = ScopeJob(Arc::clone(&self.state));
+        let job: Box<dyn FnOnce() + Send + 'env> = Box::new(move || {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                let _ = done.0.panic.lock().unwrap().get_or_insert(payload);
+            }
+            drop(done);
+        });
+        // SAFETY: the scope waits for the job to be done or dropped before `'env` ends.
+        let job = unsafe {
+            mem::transmute::<Box<dyn FnOnce() + Send + 'env>, Box<dyn FnOnce() + Send + 'static>>(
+                job,
+            )
+        };
+        self.pool.execute(job);
+    }
+}
+
+type PanicFn = dyn Fn(&(dyn Any + Send)) + Send + Sync;
+
+/// Hook called with the payload of each panic of a job.
+#[derive(Clone)]
+struct PanicHandler(Arc<PanicFn>);
+
+impl fmt::Debug for PanicHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PanicHandler")
     }
 }
 
 /// Internal data structure for tracking the current job status. This is shared by the worker
 /// closures via `Arc` so that the workers can report to the pool that it started/finished a job.
 #[derive(Debug, Default)]
-struct ThreadPoolInner {
+pub(super) struct ThreadPoolInner {
     job_count: Mutex<usize>,
     empty_condvar: Condvar,
+    /// Bound on the number of queued jobs, if any.
+    queue_capacity: Option<usize>,
+    /// Number of jobs queued but not yet taken by a worker, if the queue is bounded.
+    queued: Mutex<usize>,
+    not_full_condvar: Condvar,
+    /// Bounds on the number of workers, and how long an idle worker waits before it retires.
+    /// The bounds change when the pool is [resized](ThreadPool::resize).
+    min_workers: AtomicUsize,
+    max_workers: AtomicUsize,
+    keep_alive: Duration,
+    /// Number of workers that are running.
+    live: AtomicUsize,
+    /// Number of workers waiting for a job.
+    idle: AtomicUsize,
+    /// Number of jobs that panicked.
+    panics: AtomicUsize,
+    panic_handler: Option<PanicHandler>,
 }
 
 impl ThreadPoolInner {
     /// Increment the job count.
-    fn start_job(&self) {
+    pub(super) fn start_job(&self) {
         let mut guard = self.job_count.lock().unwrap();
         *guard += 1;
     }
 
+    /// Runs the job and decrements the job count. A panic of the job is counted and passed to the
+    /// panic handler, if any, instead of unwinding the worker.
+    pub(super) fn run_job(&self, job: Job) {
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| job.run())) {
+            let _ = self.panics.fetch_add(1, Ordering::SeqCst);
+            if let Some(handler) = &self.panic_handler {
+                (handler.0)(&*payload);
+            }
+        }
+        self.finish_job();
+    }
+
+    /// Returns the number of jobs that panicked so far.
+    pub(super) fn panic_count(&self) -> usize {
+        self.panics.load(Ordering::SeqCst)
+    }
+
     /// Decrement the job count.
-    fn finish_job(&self) {
+    pub(super) fn finish_job(&self) {
         let mut guard = self.job_count.lock().unwrap();
         *guard -= 1;
         if *guard == 0 {
@@ -51,69 +384,659 @@ impl ThreadPoolInner {
     ///
     /// NOTE: We can optimize this function by adding another field to `ThreadPoolInner`, but let's
     /// not care about that in this homework.
-    fn wait_empty(&self) {
+    pub(super) fn wait_empty(&self) {
         let guard = self.job_count.lock().unwrap();
         if *guard != 0 {
             let _lock = self.empty_condvar.wait(guard).unwrap();
         }
     }
 
-    pub fn new() -> Self {
+    /// Wait until the job count becomes 0 or the deadline passes, and return `true` in the former
+    /// case.
+    fn wait_empty_until(&self, deadline: Instant) -> bool {
+        let mut guard = self.job_count.lock().unwrap();
+        while *guard != 0 {
+            let timeout = some_or!(
+                deadline.checked_duration_since(Instant::now()),
+                return false
+            );
+            guard = self.empty_condvar.wait_timeout(guard, timeout).unwrap().0;
+        }
+        true
+    }
+
+    /// Take a slot of the queue for a new job. If the queue is full, wait for a slot if `block`,
+    /// and return `false` otherwise.
+    fn reserve(&self, block: bool) -> bool {
+        let capacity = some_or!(self.queue_capacity, return true);
+        let mut guard = self.queued.lock().unwrap();
+        while *guard >= capacity {
+            if !block {
+                return false;
+            }
+            guard = self.not_full_condvar.wait(guard).unwrap();
+        }
+        *guard += 1;
+        true
+    }
+
+    /// Take a slot of the queue for a new job even if it's full, for a job that can't wait, e.g.
+    /// a task woken by a worker.
+    #[cfg(feature = "async")]
+    fn reserve_anyway(&self) {
+        if self.queue_capacity.is_some() {
+            *self.queued.lock().unwrap() += 1;
+        }
+    }
+
+    /// Free the slot of a job taken by a worker.
+    fn release(&self) {
+        if self.queue_capacity.is_some() {
+            *self.queued.lock().unwrap() -= 1;
+            self.not_full_condvar.notify_one();
+        }
+    }
+
+    /// Count a new worker unless there are `max_workers` already, and return `true` if so.
+    fn add_worker(&self) -> bool {
+        self.live
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+                (live < self.max_workers.load(Ordering::SeqCst)).then(|| live + 1)
+            })
+            .is_ok()
+    }
+
+    /// Uncount an idle worker unless there are only `min_workers` left, and return `true` if so.
+    fn retire_worker(&self) -> bool {
+        self.live
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+                (live > self.min_workers.load(Ordering::SeqCst)).then(|| live - 1)
+            })
+            .is_ok()
+    }
+
+    pub(super) fn new(queue_capacity: Option<usize>) -> Self {
         Self {
             job_count: Mutex::new(0),
             empty_condvar: Condvar::new(),
+            queue_capacity,
+            queued: Mutex::new(0),
+            not_full_condvar: Condvar::new(),
+            min_workers: AtomicUsize::new(0),
+            max_workers: AtomicUsize::new(0),
+            keep_alive: Duration::ZERO,
+            live: AtomicUsize::new(0),
+            idle: AtomicUsize::new(0),
+            panics: AtomicUsize::new(0),
+            panic_handler: None,
+        }
+    }
+}
+
+/// Placement of the threads of a [`ThreadPool`] on the CPU cores, see
+/// [`ThreadPoolBuilder::affinity`].
+///
+/// The cores are numbered as by the OS. The threads are only pinned on Linux, and elsewhere run
+/// on any core.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Affinity {
+    /// Pins each thread to one of the cores the pool is built on, in turn.
+    PerCore,
+    /// Pins each thread to one of the cores, in turn.
+    Cores(Vec<usize>),
+    /// Pins all threads to the set of cores, on which the OS schedules them.
+    CoreSet(Vec<usize>),
+}
+
+impl Affinity {
+    /// Returns the cores of the thread with the given id.
+    fn cores_of(&self, id: usize) -> Vec<usize> {
+        match self {
+            Affinity::PerCore => unreachable!("resolved when the pool is built"),
+            Affinity::Cores(cores) if cores.is_empty() => Vec::new(),
+            Affinity::Cores(cores) => vec![cores[id % cores.len()]],
+            Affinity::CoreSet(cores) => cores.clone(),
+        }
+    }
+}
+
+/// Returns the cores the current thread may run on.
+#[cfg(target_os = "linux")]
+fn available_cores() -> io::Result<Vec<usize>> {
+    // SAFETY: the set is plain data, filled by the call.
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((0..libc::CPU_SETSIZE as usize)
+            .filter(|&core| libc::CPU_ISSET(core, &set))
+            .collect())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_cores() -> io::Result<Vec<usize>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Pins the thread to the cores.
+#[cfg(target_os = "linux")]
+fn pin(thread: &thread::JoinHandle<()>, cores: &[usize]) -> io::Result<()> {
+    use std::os::unix::thread::JoinHandleExt;
+
+    if cores.iter().any(|&core| core >= libc::CPU_SETSIZE as usize) {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    // SAFETY: the set is plain data, and the thread isn't joined yet, so its ID is valid.
+    unsafe {
+        let mut set: libc::cpu_set_t = mem::zeroed();
+        for &core in cores {
+            libc::CPU_SET(core, &mut set);
+        }
+        match libc::pthread_setaffinity_np(
+            thread.as_pthread_t(),
+            mem::size_of::<libc::cpu_set_t>(),
+            &set,
+        ) {
+            0 => Ok(()),
+            e => Err(io::Error::from_raw_os_error(e)),
         }
     }
 }
 
+#[cfg(not(target_os = "linux"))]
+fn pin(_: &thread::JoinHandle<()>, _: &[usize]) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// Builder of a [`ThreadPool`].
+#[derive(Debug, Clone)]
+pub struct ThreadPoolBuilder {
+    size: usize,
+    max_size: usize,
+    keep_alive: Duration,
+    queue_capacity: Option<usize>,
+    panic_handler: Option<PanicHandler>,
+    affinity: Option<Affinity>,
+}
+
+impl ThreadPoolBuilder {
+    /// Creates a builder of a pool with `size` threads and an unbounded queue. Panics if the size
+    /// is 0.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0);
+        Self {
+            size,
+            max_size: size,
+            keep_alive: Duration::from_secs(10),
+            queue_capacity: None,
+            panic_handler: None,
+            affinity: None,
+        }
+    }
+
+    /// Pins the threads to the CPU cores when they're spawned, e.g. to measure the effects of
+    /// cache locality. A thread that can't be pinned, e.g. to a core that doesn't exist, runs on
+    /// any core. See [`ThreadPool::worker_cores`] for the cores of each thread.
+    pub fn affinity(mut self, affinity: Affinity) -> Self {
+        self.affinity = Some(affinity);
+        self
+    }
+
+    /// Grows the pool up to `max_size` threads when jobs are executed while all threads are busy.
+    /// A thread above the initial size retires once it's idle for the
+    /// [`keep_alive`](ThreadPoolBuilder::keep_alive) period. Panics if `max_size` is smaller than
+    /// the size.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        assert!(max_size >= self.size);
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets how long a thread above the initial size may be idle before it retires. The default
+    /// is 10 seconds.
+    pub fn keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Queues at most `capacity` jobs that no thread has taken yet. When the queue is full,
+    /// [`execute`](ThreadPool::execute) blocks until a thread takes a job, and
+    /// [`try_execute`](ThreadPool::try_execute) returns the job, so that a flood of jobs applies
+    /// backpressure to the caller instead of growing the queue. Panics if the capacity is 0.
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        assert!(capacity > 0);
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Calls `handler` with the payload of each panic of a job, on the thread that ran the job.
+    ///
+    /// A panic of a job is always caught, and the thread goes on with the next job. Without a
+    /// handler, dropping the pool panics if a job panicked. With one, the handler is supposed to
+    /// report the panics, and dropping the pool doesn't panic.
+    pub fn panic_handler<H>(mut self, handler: H) -> Self
+    where
+        H: Fn(&(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.panic_handler = Some(PanicHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Creates the pool.
+    pub fn build(self) -> ThreadPool {
+        let (senders, queue) = JobQueue::new();
+        let mut inner = ThreadPoolInner::new(self.queue_capacity);
+        inner.min_workers = AtomicUsize::new(self.size);
+        inner.max_workers = AtomicUsize::new(self.max_size);
+        inner.keep_alive = self.keep_alive;
+        inner.panic_handler = self.panic_handler;
+        let pool_inner = Arc::new(inner);
+        let affinity = self.affinity.map(|affinity| match affinity {
+            Affinity::PerCore => Affinity::Cores(available_cores().unwrap_or_default()),
+            affinity => affinity,
+        });
+        let threads = Arc::new(Threads {
+            workers: Mutex::new(Vec::new()),
+            next_id: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            panicked: AtomicBool::new(false),
+            queue,
+            pool_inner: Arc::clone(&pool_inner),
+            affinity,
+        });
+        for _ in 0..self.size {
+            assert!(pool_inner.add_worker());
+            threads.spawn_worker();
+        }
+        ThreadPool {
+            threads,
+            job_senders: Some(Arc::new(senders)),
+            pool_inner,
+        }
+    }
+}
+
+/// The threads of a [`ThreadPool`], shared with them so that a thread can spawn another.
+#[derive(Debug)]
+struct Threads {
+    /// Also has the threads that retired, until they are joined.
+    workers: Mutex<Vec<Worker>>,
+    next_id: AtomicUsize,
+    /// Whether the pool stopped accepting jobs, after which no thread is spawned.
+    closed: AtomicBool,
+    /// Whether a thread joined before the pool is dropped panicked, i.e. the panic handler.
+    panicked: AtomicBool,
+    /// Also takes the queued jobs abandoned by [`ThreadPool::shutdown_with_timeout`].
+    queue: JobQueue,
+    pool_inner: Arc<ThreadPoolInner>,
+    /// Without [`Affinity::PerCore`], which is resolved to the cores.
+    affinity: Option<Affinity>,
+}
+
+impl Threads {
+    fn lock(&self) -> MutexGuard<'_, Vec<Worker>> {
+        self.workers.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Spawns a worker, which is already counted. The workers that retired are joined meanwhile.
+    fn spawn_worker(self: &Arc<Self>) {
+        let mut workers = self.lock();
+        if self.closed.load(Ordering::SeqCst) {
+            let _ = self.pool_inner.live.fetch_sub(1, Ordering::SeqCst);
+            return;
+        }
+        for worker in workers.iter_mut() {
+            if worker.thread.as_ref().map_or(false, |t| t.is_finished()) {
+                // the panic propagates when the pool is dropped
+                if worker.thread.take().unwrap().join().is_err() {
+                    self.panicked.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        workers.retain(|worker| worker.thread.is_some());
+        let threads = Arc::clone(self);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let thread = thread::spawn(move || threads.work());
+        let cores = match &self.affinity {
+            Some(affinity) => {
+                let cores = affinity.cores_of(id);
+                if !cores.is_empty() && pin(&thread, &cores).is_ok() {
+                    cores
+                } else {
+                    Vec::new()
+                }
+            }
+            None => Vec::new(),
+        };
+        workers.push(Worker {
+            id,
+            thread: Some(thread),
+            cores,
+        });
+    }
+
+    /// Spawns a worker if all workers are busy, unless there are enough of them.
+    ///
+    /// It's called by the pool after queuing a job, and by a worker with jobs still queued after
+    /// it took one, so whichever comes last sees that a job is left without an idle worker.
+    fn grow(self: &Arc<Self>) {
+        if self.pool_inner.idle.load(Ordering::SeqCst) == 0 && self.pool_inner.add_worker() {
+            self.spawn_worker();
+        }
+    }
+
+    /// Stops spawning workers, and returns the workers spawned so far.
+    fn close(&self) -> Vec<Worker> {
+        let mut workers = self.lock();
+        self.closed.store(true, Ordering::SeqCst);
+        mem::take(&mut *workers)
+    }
+
+    /// Run the queued jobs until the queue is disconnected, or the worker retires.
+    fn work(self: &Arc<Self>) {
+        let inner = &*self.pool_inner;
+        // uncounts the worker if it exits otherwise, even by a panic of the panic handler
+        struct Exit<'a>(&'a ThreadPoolInner, bool);
+        impl Drop for Exit<'_> {
+            fn drop(&mut self) {
+                if !self.1 {
+                    let _ = self.0.live.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        }
+        let mut exit = Exit(inner, false);
+
+        loop {
+            let _ = inner.idle.fetch_add(1, Ordering::SeqCst);
+            // even in a pool of a fixed size, as it may be resized to fewer workers
+            let job = self.queue.recv(Some(inner.keep_alive));
+            let _ = inner.idle.fetch_sub(1, Ordering::SeqCst);
+            match job {
+                Ok(j) => {
+                    inner.release();
+                    if !self.queue.is_empty() {
+                        self.grow();
+                    }
+                    // the worker goes on with the next job, as if a new one was spawned
+                    inner.run_job(j);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    // a job queued meanwhile may have seen the worker idle
+                    if self.queue.is_empty() && inner.retire_worker() {
+                        exit.1 = true;
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// Future driven by a [`ThreadPool`], which is its own waker.
+#[cfg(feature = "async")]
+struct Task<T> {
+    /// `None` once the future is done.
+    future: Mutex<Option<Pin<Box<dyn Future<Output = T> + Send>>>>,
+    result: Sender<thread::Result<T>>,
+    /// Whether a job polling the future is queued, so that many wakes queue a single job.
+    scheduled: AtomicBool,
+    job_senders: Weak<[Sender<Job>; 3]>,
+    threads: Weak<Threads>,
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + 'static> Task<T> {
+    /// Polls the future once.
+    fn run(self: Arc<Self>) {
+        // a wake while polling queues another job
+        self.scheduled.store(false, Ordering::SeqCst);
+        let mut future = self.future.lock().unwrap();
+        let polled = some_or!(future.as_mut(), return);
+        let waker = Waker::from(Arc::clone(&self));
+        let mut cx = Context::from_waker(&waker);
+        let result = match panic::catch_unwind(AssertUnwindSafe(|| polled.as_mut().poll(&mut cx))) {
+            Ok(Poll::Pending) => return,
+            Ok(Poll::Ready(output)) => Ok(output),
+            Err(payload) => Err(payload),
+        };
+        *future = None;
+        // the handle may be dropped meanwhile
+        let _ = self.result.send(result);
+    }
+}
+
+#[cfg(feature = "async")]
+impl<T: Send + 'static> Wake for Task<T> {
+    fn wake(self: Arc<Self>) {
+        if self.scheduled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        // otherwise the pool is gone, and the task is dropped with its wakers
+        let job_senders = some_or!(self.job_senders.upgrade(), return);
+        let threads = some_or!(self.threads.upgrade(), return);
+        threads.pool_inner.reserve_anyway();
+        threads.pool_inner.start_job();
+        let task = Arc::clone(&self);
+        job_senders[Priority::Normal as usize]
+            .send(Job(Box::new(move || task.run())))
+            .unwrap();
+        threads.grow();
+    }
+}
+
 /// Thread pool.
+///
+/// The pool has a fixed number of threads, unless it's built with
+/// [`ThreadPoolBuilder::max_size`] to grow under load.
 #[derive(Debug)]
 pub struct ThreadPool {
-    _workers: Vec<Worker>,
-    job_sender: Option<Sender<Job>>,
+    threads: Arc<Threads>,
+    /// Indexed by the priority. Shared with the tasks of the futures, which don't keep the
+    /// queues open.
+    job_senders: Option<Arc<[Sender<Job>; 3]>>,
     pool_inner: Arc<ThreadPoolInner>,
 }
 
 impl ThreadPool {
     /// Create a new ThreadPool with `size` threads. Panics if the size is 0.
     pub fn new(size: usize) -> Self {
-        assert!(size > 0);
-        let (sender, reciever) = unbounded::<Job>();
-        let mut workers = Vec::new();
-        let pool_inner = Arc::new(ThreadPoolInner::new());
-
-        for _ in 0..size {
-            let pool_inner = Arc::clone(&pool_inner);
-            let reciever = reciever.clone();
-
-            let handle = thread::spawn(move || {
-                while let Ok(j) = reciever.recv() {
-                    j.0();
-                    pool_inner.finish_job();
-                }
-            });
-            let worker = Worker {
-                _id: workers.len(),
-                thread: Some(handle),
-            };
-            workers.push(worker);
-        }
-        Self {
-            _workers: workers,
-            job_sender: Some(sender),
-            pool_inner,
-        }
+        ThreadPoolBuilder::new(size).build()
     }
 
-    /// Execute a new job in the thread pool.
+    /// Returns a builder of a pool with `size` threads. See [`ThreadPoolBuilder`].
+    pub fn builder(size: usize) -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new(size)
+    }
+
+    /// Create a new ThreadPool with `size` threads, which queues at most `capacity` jobs that no
+    /// thread has taken yet. When the queue is full, [`execute`](ThreadPool::execute) blocks until
+    /// a thread takes a job, and [`try_execute`](ThreadPool::try_execute) returns the job, so that
+    /// a flood of jobs applies backpressure to the caller instead of growing the queue. Panics if
+    /// the size or the capacity is 0.
+    pub fn with_queue_capacity(size: usize, capacity: usize) -> Self {
+        ThreadPoolBuilder::new(size)
+            .queue_capacity(capacity)
+            .build()
+    }
+
+    /// Execute a new job in the thread pool. If the queue is bounded and full, block until there
+    /// is room.
     pub fn execute<F>(&self, f: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        if let Some(job_sender) = &self.job_sender {
+        self.execute_with_priority(f, Priority::Normal)
+    }
+
+    /// Execute a new job in the thread pool like [`execute`](ThreadPool::execute), but ahead of
+    /// the jobs of lower priority that are queued.
+    ///
+    /// So that the jobs of lower priority still progress when higher priority ones keep coming,
+    /// one job out of every few taken by a thread is of low priority, and one is of normal
+    /// priority, if there are such jobs queued.
+    pub fn execute_with_priority<F>(&self, f: F, priority: Priority)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(job_senders) = &self.job_senders {
+            let _ = self.pool_inner.reserve(true);
+            self.pool_inner.start_job();
+            job_senders[priority as usize]
+                .send(Job(Box::new(f)))
+                .unwrap();
+            self.threads.grow();
+        }
+    }
+
+    /// Execute a new job in the thread pool, or return it in `Err` if the queue is bounded and
+    /// full. See [`with_queue_capacity`](ThreadPool::with_queue_capacity).
+    pub fn try_execute<F>(&self, f: F) -> Result<(), Full<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(job_senders) = &self.job_senders {
+            if !self.pool_inner.reserve(false) {
+                return Err(Full(f));
+            }
             self.pool_inner.start_job();
-            job_sender.send(Job(Box::new(f))).unwrap();
+            job_senders[Priority::Normal as usize]
+                .send(Job(Box::new(f)))
+                .unwrap();
+            self.threads.grow();
+        }
+        Ok(())
+    }
+
+    /// Execute a new job in the thread pool like [`execute`](ThreadPool::execute), and return a
+    /// handle of its return value.
+    ///
+    /// A panic of the job is caught and returned by [`JobHandle::join`], so unlike a job executed
+    /// otherwise, it doesn't make the pool panic when dropped.
+    pub fn submit<F, R>(&self, f: F) -> JobHandle<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = bounded(1);
+        self.execute(move || {
+            // the handle may be dropped meanwhile
+            let _ = sender.send(panic::catch_unwind(AssertUnwindSafe(f)));
+        });
+        JobHandle {
+            result: receiver,
+            done: false,
+        }
+    }
+
+    /// Drives the future in the pool, and returns a handle of its output.
+    ///
+    /// The future is polled by a job, and each time it's woken afterwards, by a new job queued
+    /// with the normal priority, even if the queue is full. A panic of the future is caught and
+    /// returned by [`JobHandle::join`] as with [`submit`](ThreadPool::submit). A future woken after
+    /// the pool is dropped is dropped, and its handle returns [`JobError::Cancelled`].
+    ///
+    /// The thread polling the future is blocked until it returns, so the future must not block,
+    /// e.g. on I/O, but rather be woken once it may progress.
+    #[cfg(feature = "async")]
+    pub fn spawn_future<F>(&self, future: F) -> JobHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let (sender, receiver) = bounded(1);
+        if let Some(job_senders) = &self.job_senders {
+            let task = Arc::new(Task {
+                future: Mutex::new(Some(Box::pin(future))),
+                result: sender,
+                scheduled: AtomicBool::new(true),
+                job_senders: Arc::downgrade(job_senders),
+                threads: Arc::downgrade(&self.threads),
+            });
+            self.execute(move || task.run());
+        }
+        JobHandle {
+            result: receiver,
+            done: false,
+        }
+    }
+
+    /// Runs `f` with a scope, whose jobs may borrow the locals of the caller, unlike the ones of
+    /// [`execute`](ThreadPool::execute). Returns once `f` returned and all jobs of the scope are
+    /// done. If `f` or a job panicked, then this function panics too, after all jobs are done.
+    ///
+    /// The calling thread blocks meanwhile, so calling this function in a job of the pool may
+    /// deadlock if all threads of the pool do so.
+    pub fn scope<'env, F, R>(&'env self, f: F) -> R
+    where
+        F: FnOnce(&Scope<'env>) -> R,
+    {
+        let scope = Scope {
+            pool: self,
+            state: Arc::new(ScopeState::default()),
+            _marker: PhantomData,
+        };
+        // waits even if `f` panics
+        struct Wait<'a>(&'a ScopeState);
+        impl Drop for Wait<'_> {
+            fn drop(&mut self) {
+                self.0.wait();
+            }
+        }
+        let wait = Wait(&scope.state);
+        let result = f(&scope);
+        drop(wait);
+        if let Some(payload) = scope.state.panic.lock().unwrap().take() {
+            panic::resume_unwind(payload);
+        }
+        result
+    }
+
+    /// Returns the number of threads running, which is between the bounds given to the
+    /// [`ThreadPoolBuilder`].
+    pub fn size(&self) -> usize {
+        self.pool_inner.live.load(Ordering::SeqCst)
+    }
+
+    /// Returns the ID of each running thread, numbered in the order they were spawned, with the
+    /// cores it's pinned to by the [affinity](ThreadPoolBuilder::affinity) of the pool, or none
+    /// if it isn't pinned.
+    pub fn worker_cores(&self) -> Vec<(usize, Vec<usize>)> {
+        self.threads
+            .lock()
+            .iter()
+            .filter(|worker| worker.thread.as_ref().map_or(false, |t| !t.is_finished()))
+            .map(|worker| (worker.id, worker.cores.clone()))
+            .collect()
+    }
+
+    /// Changes the number of threads to `size`, growing up to `max_size` under load as with
+    /// [`ThreadPoolBuilder::max_size`]. The threads missing are spawned at once, and the threads
+    /// in excess retire once they are idle for the [keep-alive](ThreadPoolBuilder::keep_alive)
+    /// period, so the running jobs are never interrupted. Panics if the size is 0, or if
+    /// `max_size` is smaller than the size.
+    pub fn resize(&self, size: usize, max_size: usize) {
+        assert!(size > 0 && max_size >= size);
+        let inner = &*self.pool_inner;
+        // the bounds are never crossed meanwhile
+        if max_size >= inner.max_workers.load(Ordering::SeqCst) {
+            inner.max_workers.store(max_size, Ordering::SeqCst);
+            inner.min_workers.store(size, Ordering::SeqCst);
+        } else {
+            inner.min_workers.store(size, Ordering::SeqCst);
+            inner.max_workers.store(max_size, Ordering::SeqCst);
         }
+        while inner.live.load(Ordering::SeqCst) < size && inner.add_worker() {
+            self.threads.spawn_worker();
+        }
+    }
+
+    /// Returns the number of jobs that panicked so far. The panics caught by
+    /// [`submit`](ThreadPool::submit) aren't counted.
+    pub fn panic_count(&self) -> usize {
+        self.pool_inner.panic_count()
     }
 
     /// Block the current thread until all jobs in the pool have been executed.  NOTE: This method
@@ -121,17 +1044,45 @@ impl ThreadPool {
     pub fn join(&self) {
         self.pool_inner.wait_empty();
     }
+
+    /// Stops accepting jobs, and lets the workers run the queued jobs until they are all done or
+    /// `timeout` elapses. Returns the jobs that are still queued by then, which are abandoned.
+    ///
+    /// If the jobs are done in time, all worker threads are joined as when the pool is dropped.
+    /// Otherwise, the workers still running a job are detached, and exit once their job is done,
+    /// so this never waits past the deadline.
+    pub fn shutdown_with_timeout(mut self, timeout: Duration) -> Vec<Job> {
+        let deadline = Instant::now() + timeout;
+        drop(self.job_senders.take());
+        if self.pool_inner.wait_empty_until(deadline) {
+            return Vec::new();
+        }
+        // the workers may still take some of the jobs meanwhile
+        let abandoned = self.threads.queue.drain();
+        for worker in self.threads.close() {
+            worker.detach();
+        }
+        abandoned
+    }
 }
 
 impl Drop for ThreadPool {
     /// When dropped, all worker threads' `JoinHandle` must be `join`ed. If the thread panicked,
-    /// then this function should panic too.
+    /// then this function should panic too.  A job that panicked makes this function panic too,
+    /// unless the pool has a [panic handler](ThreadPoolBuilder::panic_handler).
     fn drop(&mut self) {
-        if let Some(job_sender) = self.job_sender.take() {
-            let _ = job_sender;
+        if let Some(job_senders) = self.job_senders.take() {
+            let _ = job_senders;
         }
-        while let Some(worker) = self._workers.pop() {
+        let mut workers = self.threads.close();
+        while let Some(worker) = workers.pop() {
             let _ = worker;
         }
+        if self.threads.panicked.load(Ordering::Relaxed) {
+            panic!("a thread of the pool panicked");
+        }
+        if self.pool_inner.panic_handler.is_none() && self.panic_count() > 0 {
+            panic!("{} jobs of the pool panicked", self.panic_count());
+        }
     }
 }
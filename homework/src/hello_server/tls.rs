@@ -0,0 +1,130 @@
+//! Connections over TLS, with rustls.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rustls::{Certificate, PrivateKey, ServerConfig, ServerConnection, StreamOwned};
+
+use super::stream::Connection;
+
+/// TCP connection to a client over TLS, served by a [`Handler`](super::Handler) like a plain one.
+///
+/// The handshake only happens on the first read or write, so that wrapping an accepted socket
+/// doesn't block the listener. A failed handshake is an error of that read or write.
+#[derive(Debug)]
+pub struct TlsStream {
+    io: Mutex<Session>,
+    /// The socket, to set the timeouts without waiting for a read or a write.
+    tcp: TcpStream,
+}
+
+/// TLS session on a socket.
+#[derive(Debug)]
+struct Session {
+    stream: StreamOwned<ServerConnection, TcpStream>,
+    /// Whether a read or a write failed, after which the session is unusable. Otherwise a write
+    /// would wait for the client to read the record of the error, until the timeout.
+    failed: bool,
+}
+
+impl Session {
+    fn io<R>(&mut self, f: impl FnOnce(&mut Self) -> io::Result<R>) -> io::Result<R> {
+        if self.failed {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the TLS session failed",
+            ));
+        }
+        let result = f(self);
+        self.failed = result.is_err();
+        result
+    }
+}
+
+impl TlsStream {
+    /// Loads the rustls configuration serving the certificate chain of the PEM file `cert`, with
+    /// the private key of the PEM file `key`, in PKCS #8, RSA or SEC1 form.
+    pub fn load_config(cert: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+        let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+        let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))?;
+        if certs.is_empty() {
+            return Err(invalid(format!("no certificate in {}", cert.display())));
+        }
+        let mut keys = BufReader::new(File::open(key)?);
+        let key = loop {
+            match rustls_pemfile::read_one(&mut keys)? {
+                Some(
+                    rustls_pemfile::Item::PKCS8Key(der)
+                    | rustls_pemfile::Item::RSAKey(der)
+                    | rustls_pemfile::Item::ECKey(der),
+                ) => break der,
+                Some(_) => continue,
+                None => return Err(invalid(format!("no private key in {}", key.display()))),
+            }
+        };
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                certs.into_iter().map(Certificate).collect(),
+                PrivateKey(key),
+            )
+            .map_err(|e| invalid(e.to_string()))?;
+        Ok(Arc::new(config))
+    }
+
+    /// Starts a TLS session with the configuration on an accepted socket.
+    pub fn new(tcp: TcpStream, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let conn = ServerConnection::new(config)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        Ok(Self {
+            tcp: tcp.try_clone()?,
+            io: Mutex::new(Session {
+                stream: StreamOwned::new(conn, tcp),
+                failed: false,
+            }),
+        })
+    }
+}
+
+impl Connection for TlsStream {
+    fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.lock().unwrap().io(|io| io.stream.read(buf))
+    }
+
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.io.lock().unwrap().io(|io| {
+            let n = io.stream.write(buf)?;
+            io.stream.flush()?;
+            Ok(n)
+        })
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp.set_read_timeout(timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp.set_write_timeout(timeout)
+    }
+
+    fn peer_ip(&self) -> Option<IpAddr> {
+        self.tcp.peer_addr().ok().map(|addr| addr.ip())
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        // tells the client that the response is complete, if it's still there
+        if let Ok(io) = self.io.get_mut() {
+            if !io.failed {
+                io.stream.conn.send_close_notify();
+                let _ = io.stream.flush();
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! UnixListener that can be cancelled.
+
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Like [`CancellableTcpListener`](super::CancellableTcpListener), but listening to a Unix
+/// domain socket, e.g. for a local reverse proxy.
+///
+/// The socket file is removed when the listener is cancelled, or dropped.
+#[derive(Debug)]
+pub struct CancellableUnixListener {
+    inner: UnixListener,
+    path: PathBuf,
+    is_canceled: AtomicBool,
+}
+
+/// Like `std::os::unix::net::Incoming`, but stops `accept`ing connections if the listener is
+/// `cancel`ed.
+#[derive(Debug)]
+pub struct UnixIncoming<'a> {
+    listener: &'a CancellableUnixListener,
+}
+
+impl CancellableUnixListener {
+    /// Wraps `UnixListener::bind`. A socket file left at `path` by a listener that is gone, e.g.
+    /// a server that crashed, is replaced.
+    pub fn bind<P: AsRef<Path>>(path: P) -> io::Result<CancellableUnixListener> {
+        let path = path.as_ref();
+        let listener = match UnixListener::bind(path) {
+            Err(e)
+                if e.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(path).is_err() =>
+            {
+                fs::remove_file(path)?;
+                UnixListener::bind(path)?
+            }
+            listener => listener?,
+        };
+        Ok(CancellableUnixListener {
+            inner: listener,
+            path: path.to_path_buf(),
+            is_canceled: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns the path of the socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Signals the listener to stop accepting new connections, and removes the socket file.
+    pub fn cancel(&self) -> io::Result<()> {
+        if self.is_canceled.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        // wakes up the listener blocked in `accept`, as `CancellableTcpListener::cancel` does
+        let mut conn = UnixStream::connect(&self.path)?;
+        let _ = conn.write(&[0])?;
+        fs::remove_file(&self.path)
+    }
+
+    /// Returns an iterator over the connections being received on this listener. The returned
+    /// iterator will return `None` if the listener is `cancel`led.
+    pub fn incoming(&self) -> UnixIncoming<'_> {
+        UnixIncoming { listener: self }
+    }
+}
+
+impl Drop for CancellableUnixListener {
+    fn drop(&mut self) {
+        if !*self.is_canceled.get_mut() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Iterator for UnixIncoming<'_> {
+    type Item = io::Result<UnixStream>;
+    /// Returns None if the listener is `cancel()`led.
+    fn next(&mut self) -> Option<io::Result<UnixStream>> {
+        if self.listener.is_canceled.load(Ordering::Acquire) {
+            None
+        } else {
+            Some(self.listener.inner.accept().map(|p| p.0))
+        }
+    }
+}
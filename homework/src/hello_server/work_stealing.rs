@@ -0,0 +1,204 @@
+//! Thread pool with a work-stealing deque per worker.
+
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use super::thread_pool::{Job, ThreadPoolInner, Worker};
+use crate::deque::{self, Steal, Stealer};
+
+/// Source of the ids of the pools, so that a worker thread knows which pool it belongs to.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The deque of the worker running on this thread, if any.
+struct Local {
+    pool: usize,
+    deque: deque::Worker<Job>,
+}
+
+thread_local! {
+    static LOCAL: RefCell<Option<Local>> = RefCell::new(None);
+}
+
+/// State shared by the pool and its workers.
+#[derive(Debug)]
+struct Shared {
+    id: usize,
+    /// Jobs executed by threads other than the workers.
+    injector: Receiver<Job>,
+    stealers: Vec<Stealer<Job>>,
+    /// Number of jobs in the injector or in a deque, incremented before a job is queued.
+    queued: AtomicUsize,
+    shutdown: AtomicBool,
+    sleep: Mutex<()>,
+    wakeup: Condvar,
+    pool_inner: ThreadPoolInner,
+}
+
+impl Shared {
+    /// Wakes up a sleeping worker, if any.
+    fn notify(&self) {
+        let _guard = self.sleep.lock().unwrap();
+        self.wakeup.notify_one();
+    }
+
+    /// Takes a job from the injector, or steals one from the deques of the other workers,
+    /// starting after the worker of `index`.
+    fn steal(&self, index: usize) -> Option<Job> {
+        if let Ok(job) = self.injector.try_recv() {
+            return Some(job);
+        }
+        let n = self.stealers.len();
+        loop {
+            let mut retry = false;
+            for i in 1..n {
+                match self.stealers[(index + i) % n].steal() {
+                    Steal::Success(job) => return Some(job),
+                    Steal::Retry => retry = true,
+                    Steal::Empty => {}
+                }
+            }
+            if !retry {
+                return None;
+            }
+        }
+    }
+
+    /// Runs the jobs of the worker of `index` until the pool shuts down and no job is left.
+    fn run(&self, index: usize) {
+        loop {
+            let local = LOCAL.with(|local| local.borrow().as_ref().unwrap().deque.pop());
+            if let Some(job) = local.or_else(|| self.steal(index)) {
+                let _ = self.queued.fetch_sub(1, Ordering::SeqCst);
+                // the worker goes on with the next job, like the workers of `ThreadPool`
+                self.pool_inner.run_job(job);
+                continue;
+            }
+
+            let guard = self.sleep.lock().unwrap();
+            if self.queued.load(Ordering::SeqCst) > 0 {
+                // a job is being queued, or is in the deque of a worker about to pop it
+                drop(guard);
+                thread::yield_now();
+                continue;
+            }
+            if self.shutdown.load(Ordering::SeqCst) {
+                return;
+            }
+            drop(self.wakeup.wait(guard).unwrap());
+        }
+    }
+}
+
+/// Thread pool where each worker has a deque of its own.
+///
+/// A job executed by a worker, e.g. a job spawning sub-jobs, is pushed to the worker's
+/// [Chase-Lev deque](crate::deque), which the worker pops in LIFO order without synchronizing
+/// with the other workers. A job executed by another thread goes through a shared channel. An idle
+/// worker takes the jobs of the channel, then steals the oldest jobs of the other workers' deques.
+/// So unlike [`ThreadPool`](super::ThreadPool), a mix of jobs spawning more jobs doesn't contend
+/// on a single channel.
+///
+/// Like `ThreadPool`, a worker survives the panic of a job, and dropping the pool waits for all
+/// jobs, and panics if a job panicked.
+#[derive(Debug)]
+pub struct WorkStealingPool {
+    _workers: Vec<Worker>,
+    injector: Sender<Job>,
+    shared: Arc<Shared>,
+}
+
+impl WorkStealingPool {
+    /// Create a new pool with `size` threads. Panics if the size is 0.
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0);
+        let (injector, receiver) = unbounded();
+        let deques = (0..size).map(|_| deque::Worker::new()).collect::<Vec<_>>();
+        let shared = Arc::new(Shared {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            injector: receiver,
+            stealers: deques.iter().map(deque::Worker::stealer).collect(),
+            queued: AtomicUsize::new(0),
+            shutdown: AtomicBool::new(false),
+            sleep: Mutex::new(()),
+            wakeup: Condvar::new(),
+            pool_inner: ThreadPoolInner::new(None),
+        });
+
+        let mut workers = Vec::new();
+        for (index, deque) in deques.into_iter().enumerate() {
+            let shared = Arc::clone(&shared);
+            let handle = thread::spawn(move || {
+                LOCAL.with(|local| {
+                    *local.borrow_mut() = Some(Local {
+                        pool: shared.id,
+                        deque,
+                    })
+                });
+                shared.run(index);
+            });
+            workers.push(Worker {
+                id: index,
+                thread: Some(handle),
+                cores: Vec::new(),
+            });
+        }
+        Self {
+            _workers: workers,
+            injector,
+            shared,
+        }
+    }
+
+    /// Execute a new job in the pool. On a worker of the pool, the job is pushed to the worker's
+    /// deque.
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.pool_inner.start_job();
+        let _ = self.shared.queued.fetch_add(1, Ordering::SeqCst);
+        let job = Job(Box::new(f));
+        let job = LOCAL.with(|local| match &*local.borrow() {
+            Some(local) if local.pool == self.shared.id => {
+                local.deque.push(job);
+                None
+            }
+            _ => Some(job),
+        });
+        if let Some(job) = job {
+            self.injector.send(job).unwrap();
+        }
+        self.shared.notify();
+    }
+
+    /// Block the current thread until all jobs in the pool have been executed.
+    pub fn join(&self) {
+        self.shared.pool_inner.wait_empty();
+    }
+
+    /// Returns the number of jobs that panicked so far.
+    pub fn panic_count(&self) -> usize {
+        self.shared.pool_inner.panic_count()
+    }
+}
+
+impl Drop for WorkStealingPool {
+    /// Waits for the workers to finish all jobs, and joins them. If a job panicked, then this
+    /// function panics too.
+    fn drop(&mut self) {
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        {
+            let _guard = self.shared.sleep.lock().unwrap();
+            self.shared.wakeup.notify_all();
+        }
+        while let Some(worker) = self._workers.pop() {
+            drop(worker);
+        }
+        if self.panic_count() > 0 {
+            panic!("{} jobs of the pool panicked", self.panic_count());
+        }
+    }
+}
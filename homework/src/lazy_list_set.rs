@@ -0,0 +1,186 @@
+use core::cmp;
+use core::sync::atomic::{AtomicBool, Ordering};
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::list_set::lock;
+
+/// The lock and the link of a node, or of the head.
+#[derive(Debug)]
+struct Link<T> {
+    lock: Mutex<()>,
+    /// Whether the node is logically removed. Never set for the head.
+    marked: AtomicBool,
+    next: Atomic<Node<T>>,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    link: Link<T>,
+}
+
+impl<T> Link<T> {
+    fn new(next: Shared<'_, Node<T>>) -> Self {
+        Self {
+            lock: Mutex::new(()),
+            marked: AtomicBool::new(false),
+            next: Atomic::from(next),
+        }
+    }
+}
+
+/// Concurrent sorted singly linked list using lazy synchronization.
+///
+/// Unlike [`OrderedListSet`](crate::OrderedListSet), the traversals take no lock. `contains` and
+/// `iter` only read the links, and `insert` and `remove` lock the two nodes around the key, then
+/// check that they are still adjacent and not removed, and traverse again otherwise. A removed
+/// node is first marked, so that `contains` ignores it, and then unlinked. The nodes are freed
+/// through the epoch-based garbage collector, as traversals may still be reading them.
+///
+/// A panic in `T::cmp` propagates to the caller, but the set stays usable.
+#[derive(Debug)]
+pub struct LazyListSet<T> {
+    head: Link<T>,
+}
+
+impl<T> LazyListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: Link::new(Shared::null()),
+        }
+    }
+
+    /// Returns an iterator over the elements that are not removed, in order. It takes no lock, so
+    /// it is weakly consistent: elements inserted or removed concurrently may or may not be
+    /// yielded.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, T> {
+        Iter {
+            curr: self.head.next.load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<T: Ord> LazyListSet<T> {
+    /// Returns the link before the position of `key`, the node after it, and whether that node
+    /// has the key. The nodes may be removed concurrently.
+    fn find<'g>(&'g self, key: &T, guard: &'g Guard) -> (&'g Link<T>, Shared<'g, Node<T>>, bool) {
+        let mut pred = &self.head;
+        let mut curr = pred.next.load(Ordering::Acquire, guard);
+        while let Some(node) = unsafe { curr.as_ref() } {
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => {
+                    pred = &node.link;
+                    curr = pred.next.load(Ordering::Acquire, guard);
+                }
+                cmp::Ordering::Equal => return (pred, curr, true),
+                cmp::Ordering::Greater => break,
+            }
+        }
+        (pred, curr, false)
+    }
+
+    /// Locks `pred` and `curr`, and returns the locks if `pred` still links to `curr` and neither
+    /// is removed.
+    fn lock_validate<'g>(
+        pred: &'g Link<T>,
+        curr: Shared<'g, Node<T>>,
+        guard: &'g Guard,
+    ) -> Option<(MutexGuard<'g, ()>, Option<MutexGuard<'g, ()>>)> {
+        let pred_lock = lock(&pred.lock);
+        let curr_node = unsafe { curr.as_ref() };
+        let curr_lock = curr_node.map(|node| lock(&node.link.lock));
+        if pred.marked.load(Ordering::Relaxed)
+            || curr_node.map_or(false, |node| node.link.marked.load(Ordering::Relaxed))
+            || pred.next.load(Ordering::Relaxed, guard) != curr
+        {
+            return None;
+        }
+        Some((pred_lock, curr_lock))
+    }
+
+    /// Returns `true` if the set contains the key. It takes no lock.
+    pub fn contains(&self, key: &T) -> bool {
+        let guard = &epoch::pin();
+        let (_, curr, found) = self.find(key, guard);
+        found && !unsafe { curr.deref() }.link.marked.load(Ordering::Acquire)
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let guard = &epoch::pin();
+        loop {
+            let (pred, curr, found) = self.find(&key, guard);
+            let _locks = some_or!(Self::lock_validate(pred, curr, guard), continue);
+            if found {
+                return Err(key);
+            }
+            let node = Owned::new(Node {
+                data: key,
+                link: Link::new(curr),
+            });
+            pred.next.store(node, Ordering::Release);
+            return Ok(());
+        }
+    }
+
+    /// Removes the key from the set. Returns `true` if it was in the set.
+    ///
+    /// The key is dropped once no traversal can read it anymore, so it can't be returned.
+    pub fn remove(&self, key: &T) -> bool {
+        let guard = &epoch::pin();
+        loop {
+            let (pred, curr, found) = self.find(key, guard);
+            let _locks = some_or!(Self::lock_validate(pred, curr, guard), continue);
+            if !found {
+                return false;
+            }
+            let node = unsafe { curr.deref() };
+            node.link.marked.store(true, Ordering::Release);
+            let next = node.link.next.load(Ordering::Relaxed, guard);
+            pred.next.store(next, Ordering::Release);
+            unsafe { guard.defer_destroy(curr) };
+            return true;
+        }
+    }
+}
+
+/// Iterator over the elements of a [`LazyListSet`].
+#[derive(Debug)]
+pub struct Iter<'g, T> {
+    curr: Shared<'g, Node<T>>,
+    guard: &'g Guard,
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.curr.as_ref() }?;
+            self.curr = node.link.next.load(Ordering::Acquire, self.guard);
+            if !node.link.marked.load(Ordering::Acquire) {
+                return Some(&node.data);
+            }
+        }
+    }
+}
+
+impl<T> Drop for LazyListSet<T> {
+    fn drop(&mut self) {
+        let guard = unsafe { epoch::unprotected() };
+        let mut curr = self.head.next.load(Ordering::Relaxed, guard);
+        while !curr.is_null() {
+            let node = unsafe { curr.into_owned() };
+            curr = node.link.next.load(Ordering::Relaxed, guard);
+        }
+    }
+}
+
+impl<T> Default for LazyListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -13,23 +13,44 @@
 mod utils;
 
 mod arc;
+pub mod arena;
 mod art;
 mod bst;
+mod collector;
+pub mod deque;
 mod elim_stack;
+mod flat_combiner;
 mod hash_table;
 pub mod hazard_pointer;
 pub mod hello_server;
+mod lazy_list_set;
 mod linked_list;
 mod list_set;
+pub mod lockfree;
 mod map;
+pub mod metrics;
+mod rw_list_set;
+mod seqlock;
+#[cfg(feature = "async")]
+pub mod timer;
 
 pub use arc::Arc;
 pub use art::{Art, Entry};
 pub use bst::Bst;
-pub use elim_stack::ElimStack;
-pub use hash_table::{GrowableArray, SplitOrderedList};
+pub use collector::collector_flush;
+pub use elim_stack::{ElimStack, Stack, TreiberStack};
+pub use flat_combiner::FlatCombiner;
+pub use hash_table::{
+    Config, Diagnostics, FixedSplitOrderedList, GrowableArray, HpSplitOrderedList, Snapshot,
+    SplitOrderedList, SplitOrderedSet, ValueRef,
+};
+pub use lazy_list_set::LazyListSet;
 pub use linked_list::LinkedList;
-pub use list_set::OrderedListSet;
+pub use list_set::{Cursor, GuardedRef, GuardedRefMut, OrderedListMap, OrderedListSet, WouldBlock};
+pub use lockfree::{ArrayQueue, NmTreeMap, SkipListMap};
 pub use map::{
-    ConcurrentMap, NonblockingConcurrentMap, NonblockingMap, RandGen, SequentialMap, StrStringMap,
+    workload_rng, workload_seed, ConcurrentMap, NonblockingConcurrentMap, NonblockingMap, RandGen,
+    SequentialMap, StrStringMap, WorkloadRng, DEFAULT_SEED,
 };
+pub use rw_list_set::RwListSet;
+pub use seqlock::{NoUninit, SeqLock};
@@ -1,7 +1,10 @@
 use std::cmp;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
+use std::mem;
+use std::ops::{Bound, Deref, DerefMut, RangeBounds};
 use std::ptr;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
 
 #[derive(Debug)]
 struct Node<T> {
@@ -13,9 +16,15 @@ unsafe impl<T: Send> Send for Node<T> {}
 unsafe impl<T: Sync> Sync for Node<T> {}
 
 /// Concurrent sorted singly linked list using lock-coupling.
+///
+/// A panic in `T::cmp` propagates to the caller, but the set stays usable. The locks it held are
+/// still poisoned, which [`is_poisoned`](OrderedListSet::is_poisoned) reports, until
+/// [`heal`](OrderedListSet::heal).
 #[derive(Debug)]
 pub struct OrderedListSet<T> {
     head: Mutex<*mut Node<T>>,
+    /// number of elements, updated under the lock of the link
+    len: AtomicUsize,
 }
 
 unsafe impl<T: Send> Send for OrderedListSet<T> {}
@@ -33,11 +42,41 @@ enum CursorState {
 }
 
 // reference to the `next` field of previous node which points to the current node
-struct Cursor<'l, T> {
+struct RawCursor<'l, T> {
     state: CursorState,
     guard: MutexGuard<'l, *mut Node<T>>,
 }
 
+/// Locks `mutex`, ignoring poisoning.
+///
+/// A lock of the list is only poisoned by a panic in `T::cmp` while searching, or in the predicate
+/// of `retain`, which happen between the modifications of the links. So the links are always
+/// consistent.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Tries to lock `mutex`, ignoring poisoning. See [`lock`].
+fn try_lock<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, WouldBlock> {
+    match mutex.try_lock() {
+        Ok(guard) => Ok(guard),
+        Err(TryLockError::Poisoned(e)) => Ok(e.into_inner()),
+        Err(TryLockError::WouldBlock) => Err(WouldBlock),
+    }
+}
+
+/// Error returned by [`OrderedListSet::try_contains`] when a lock is held by another thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WouldBlock;
+
+impl fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a lock of the list is held by another thread")
+    }
+}
+
+impl std::error::Error for WouldBlock {}
+
 impl<T> Node<T> {
     fn new(data: T, next: *mut Self) -> *mut Self {
         Box::into_raw(Box::new(Self {
@@ -47,7 +86,7 @@ impl<T> Node<T> {
     }
 }
 
-impl<'l, T: Ord> Cursor<'l, T> {
+impl<'l, T> RawCursor<'l, T> {
     fn new(guard: MutexGuard<'l, *mut Node<T>>) -> Self {
         Self {
             state: CursorState::Searching,
@@ -67,19 +106,40 @@ impl<'l, T: Ord> Cursor<'l, T> {
             guard,
         }
     }
-    /// Move the cursor to the position of key in the sorted list. If the key is found in the list,
-    /// return `true`.
-    fn find(mut self, key: &T) -> Cursor<'l, T> {
+
+    /// Move the cursor to the first node for which `cmp` doesn't return `Less`.
+    fn find_by<F: FnMut(&T) -> cmp::Ordering>(mut self, mut cmp: F) -> RawCursor<'l, T> {
         while let Some(node) = unsafe { (*self.guard).as_ref() } {
-            match node.data.cmp(key) {
-                cmp::Ordering::Greater => return Cursor::inserting(self.guard),
-                cmp::Ordering::Equal => return Cursor::found(self.guard),
+            match cmp(&node.data) {
+                cmp::Ordering::Greater => return RawCursor::inserting(self.guard),
+                cmp::Ordering::Equal => return RawCursor::found(self.guard),
                 cmp::Ordering::Less => {
-                    let _guard = std::mem::replace(&mut self.guard, node.next.lock().unwrap());
+                    let _guard = std::mem::replace(&mut self.guard, lock(&node.next));
                 }
             }
         }
-        Cursor::inserting(self.guard)
+        RawCursor::inserting(self.guard)
+    }
+
+    /// Links a new node of `data` before the current node.
+    fn insert_node(&mut self, data: T) {
+        *self.guard = Node::new(data, *self.guard);
+    }
+
+    /// Unlinks the current node, which must exist, and returns its data.
+    fn remove_node(&mut self) -> T {
+        let removed_node = unsafe { Box::from_raw(*self.guard) };
+        let next_guard = lock(&removed_node.next);
+        *self.guard = *next_guard;
+        drop(next_guard);
+        removed_node.data
+    }
+}
+
+impl<'l, T: Ord> RawCursor<'l, T> {
+    /// Move the cursor to the position of key in the sorted list.
+    fn find(self, key: &T) -> RawCursor<'l, T> {
+        self.find_by(|data| data.cmp(key))
     }
 }
 
@@ -88,14 +148,58 @@ impl<T> OrderedListSet<T> {
     pub fn new() -> Self {
         Self {
             head: Mutex::new(ptr::null_mut()),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of elements. It takes no lock, so it may be outdated by the time it
+    /// returns if the set is modified concurrently.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if the set has no element. See [`len`](OrderedListSet::len).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if a lock of the set is poisoned, i.e. a thread panicked while holding it,
+    /// e.g. in `T::cmp` or with a [`Cursor`] or an [`Iter`] alive. The operations ignore the
+    /// poisoning, so the set is still usable, but it may tell that an operation didn't complete.
+    pub fn is_poisoned(&self) -> bool {
+        let mut poisoned = self.head.is_poisoned();
+        let mut guard = lock(&self.head);
+        while let Some(node) = unsafe { (*guard).as_ref() } {
+            poisoned |= node.next.is_poisoned();
+            guard = lock(&node.next);
+        }
+        poisoned
+    }
+
+    /// Clears the poisoning of the locks of the set, so that
+    /// [`is_poisoned`](OrderedListSet::is_poisoned) returns `false`.
+    ///
+    /// A poisoned `Mutex` can't be cleared in place, so each poisoned lock is replaced with a new
+    /// one, which needs exclusive access to the set.
+    pub fn heal(&mut self) {
+        let mut link = &mut self.head;
+        loop {
+            let next = *link.get_mut().unwrap_or_else(PoisonError::into_inner);
+            if link.is_poisoned() {
+                *link = Mutex::new(next);
+            }
+            match unsafe { next.as_mut() } {
+                Some(node) => link = &mut node.next,
+                None => break,
+            }
         }
     }
 }
 
 impl<T: Ord> OrderedListSet<T> {
-    fn find(&self, key: &T) -> Cursor<T> {
-        let guard = self.head.lock().unwrap();
-        let mut cursor = Cursor::new(guard);
+    fn find(&self, key: &T) -> RawCursor<T> {
+        let guard = lock(&self.head);
+        let mut cursor = RawCursor::new(guard);
         cursor.find(key)
     }
 
@@ -104,21 +208,27 @@ impl<T: Ord> OrderedListSet<T> {
         matches!(self.find(key).state, CursorState::Found)
     }
 
+    /// Returns `true` if the set contains the key, or `Err(WouldBlock)` as soon as a lock on the
+    /// way is held by another thread. It never blocks.
+    pub fn try_contains(&self, key: &T) -> Result<bool, WouldBlock> {
+        let mut guard = try_lock(&self.head)?;
+        while let Some(node) = unsafe { (*guard).as_ref() } {
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => guard = try_lock(&node.next)?,
+                cmp::Ordering::Equal => return Ok(true),
+                cmp::Ordering::Greater => break,
+            }
+        }
+        Ok(false)
+    }
+
     /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
     pub fn insert(&self, key: T) -> Result<(), T> {
         let mut cursor = self.find(&key);
         match cursor.state {
             CursorState::Insert => {
-                match unsafe { cursor.guard.as_ref() } {
-                    Some(curr_node) => {
-                        let new_node = Node::new(key, *cursor.guard);
-                        *cursor.guard = new_node;
-                    }
-                    None => {
-                        let node = Node::new(key, ptr::null_mut());
-                        *cursor.guard = node;
-                    }
-                }
+                cursor.insert_node(key);
+                let _ = self.len.fetch_add(1, Ordering::Relaxed);
                 Ok(())
             }
             CursorState::Found => Err(key),
@@ -131,27 +241,114 @@ impl<T: Ord> OrderedListSet<T> {
         let mut cursor = self.find(key);
         match cursor.state {
             CursorState::Insert => Err(()),
-            CursorState::Found => match unsafe { (*cursor.guard).as_ref() } {
-                Some(curr_node) => {
-                    let removed_node = unsafe { Box::from_raw(*cursor.guard) };
-                    let next_guard = curr_node.next.lock().unwrap();
-                    *cursor.guard = *next_guard;
-                    Ok(removed_node.data)
-                }
-                None => Err(()),
-            },
+            CursorState::Found => {
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+                Ok(cursor.remove_node())
+            }
             CursorState::Searching => unreachable!(),
         }
     }
 }
 
+/// A cursor over an [`OrderedListSet`], for several adjacent operations in one traversal.
+///
+/// It points at an element, or at the end of the set, and only moves forward. Like an operation of
+/// the set, it holds the lock of the link to the current element, so it blocks the other operations
+/// on the elements from there until it is dropped.
+///
+/// ```
+/// use cs431_homework::OrderedListSet;
+///
+/// let set = [1, 2, 4].into_iter().collect::<OrderedListSet<_>>();
+/// let mut cursor = set.cursor();
+/// // find 2 then insert 3 after it
+/// assert!(cursor.seek(&2));
+/// cursor.move_next();
+/// assert_eq!(cursor.insert_before(3), Ok(()));
+/// assert_eq!(cursor.current(), Some(&4));
+/// assert_eq!(cursor.remove_current(), Some(4));
+/// assert_eq!(cursor.current(), None);
+/// drop(cursor);
+/// assert_eq!(set.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+/// ```
+pub struct Cursor<'l, T> {
+    raw: RawCursor<'l, T>,
+    /// The element before the current one, if any, which the cursor keeps from being removed.
+    prev: Option<&'l T>,
+    len: &'l AtomicUsize,
+}
+
+impl<'l, T> Cursor<'l, T> {
+    /// Returns the current element, or `None` at the end of the set.
+    pub fn current(&self) -> Option<&T> {
+        unsafe { (*self.raw.guard).as_ref() }.map(|node| &node.data)
+    }
+
+    /// Moves to the next element. Does nothing at the end of the set.
+    pub fn move_next(&mut self) {
+        if let Some(node) = unsafe { (*self.raw.guard).as_ref() } {
+            self.raw.guard = lock(&node.next);
+            self.prev = Some(&node.data);
+        }
+    }
+
+    /// Removes the current element and returns it, moving to the next one. Returns `None` at the
+    /// end of the set.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.raw.guard.is_null() {
+            return None;
+        }
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(self.raw.remove_node())
+    }
+}
+
+impl<'l, T: Ord> Cursor<'l, T> {
+    /// Moves forward to the first element not smaller than `key`, and returns whether it is
+    /// `key`. Doesn't move if the current element is not smaller than `key`.
+    pub fn seek(&mut self, key: &T) -> bool {
+        while let Some(node) = unsafe { (*self.raw.guard).as_ref() } {
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => self.move_next(),
+                cmp::Ordering::Equal => return true,
+                cmp::Ordering::Greater => break,
+            }
+        }
+        false
+    }
+
+    /// Inserts `value` before the current element, staying at the current element. Returns
+    /// `value` back in `Err` if it doesn't go right there, i.e., if it is not between the previous
+    /// element and the current one.
+    pub fn insert_before(&mut self, value: T) -> Result<(), T> {
+        if self.prev.map_or(false, |prev| *prev >= value)
+            || self.current().map_or(false, |curr| *curr <= value)
+        {
+            return Err(value);
+        }
+        self.raw.insert_node(value);
+        let _ = self.len.fetch_add(1, Ordering::Relaxed);
+        self.move_next();
+        Ok(())
+    }
+}
+
+impl<T: Debug> Debug for Cursor<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cursor")
+            .field("prev", &self.prev)
+            .field("current", &self.current())
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub struct Iter<'l, T>(Option<MutexGuard<'l, *mut Node<T>>>);
 
 impl<T> OrderedListSet<T> {
     /// An iterator visiting all elements.
     pub fn iter(&self) -> Iter<T> {
-        Iter(Some(self.head.lock().unwrap()))
+        Iter(Some(lock(&self.head)))
     }
 }
 
@@ -169,27 +366,393 @@ impl<'l, T> Iterator for Iter<'l, T> {
             }
         };
 
-        self.0 = Some(node.next.lock().unwrap());
+        self.0 = Some(lock(&node.next));
 
         Some(&node.data)
     }
 }
 
+impl<T> OrderedListSet<T> {
+    /// Returns a cursor at the first element. See [`Cursor`].
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            raw: RawCursor::new(lock(&self.head)),
+            prev: None,
+            len: &self.len,
+        }
+    }
+
+    /// Removes the elements for which `f` returns `false`, in a single lock-coupling traversal.
+    ///
+    /// If `f` panics, the elements visited so far stay removed, and the set stays usable.
+    pub fn retain<F: FnMut(&T) -> bool>(&self, mut f: F) {
+        let mut cursor = RawCursor::new(lock(&self.head));
+        while let Some(node) = unsafe { (*cursor.guard).as_ref() } {
+            if f(&node.data) {
+                cursor.guard = lock(&node.next);
+            } else {
+                drop(cursor.remove_node());
+                let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Removes the smallest element and returns it, or `None` if the set is empty.
+    ///
+    /// It only locks the head and the first node, so the set can be used as a concurrent pool
+    /// that hands out the smallest element first.
+    pub fn pop_front(&self) -> Option<T> {
+        let mut cursor = RawCursor::new(lock(&self.head));
+        if cursor.guard.is_null() {
+            return None;
+        }
+        let _ = self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(cursor.remove_node())
+    }
+}
+
+impl<T: Ord> OrderedListSet<T> {
+    /// An iterator visiting the elements in `range`, in order.
+    ///
+    /// It seeks to the start of the range with lock coupling like `contains`, and then holds the
+    /// locks like [`iter`](OrderedListSet::iter). It releases the last lock once it reaches the
+    /// end of the range.
+    pub fn range<R: RangeBounds<T>>(&self, range: R) -> Range<'_, T, R> {
+        let cursor = RawCursor::new(lock(&self.head)).find_by(|data| match range.start_bound() {
+            Bound::Included(start) => data.cmp(start),
+            Bound::Excluded(start) if data <= start => cmp::Ordering::Less,
+            _ => cmp::Ordering::Greater,
+        });
+        Range {
+            iter: Iter(Some(cursor.guard)),
+            range,
+        }
+    }
+}
+
+/// An iterator over a range of the elements of an [`OrderedListSet`].
+#[derive(Debug)]
+pub struct Range<'l, T, R> {
+    iter: Iter<'l, T>,
+    range: R,
+}
+
+impl<'l, T: Ord, R: RangeBounds<T>> Iterator for Range<'l, T, R> {
+    type Item = &'l T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let guard = self.iter.0.as_ref()?;
+        let in_range =
+            unsafe { guard.as_ref() }.map_or(false, |node| match self.range.end_bound() {
+                Bound::Included(end) => node.data <= *end,
+                Bound::Excluded(end) => node.data < *end,
+                Bound::Unbounded => true,
+            });
+        if !in_range {
+            // release the lock
+            self.iter.0 = None;
+            return None;
+        }
+        self.iter.next()
+    }
+}
+
+impl<T> OrderedListSet<T> {
+    /// Removes all elements, and returns an iterator over them, in order.
+    ///
+    /// It detaches the whole chain under the lock of the head, and then waits for the operations
+    /// that were already past the head, by taking the locks of the chain hand-over-hand once. The
+    /// iterator takes no lock.
+    pub fn drain(&self) -> IntoIter<T> {
+        let head = mem::replace(&mut *lock(&self.head), ptr::null_mut());
+        let mut len = 0;
+        let mut curr = head;
+        while let Some(node) = unsafe { curr.as_ref() } {
+            // the operations in the chain hold the lock of the link before their node
+            curr = *lock(&node.next);
+            len += 1;
+        }
+        // including the elements that the operations inserted into the chain
+        let _ = self.len.fetch_sub(len, Ordering::Relaxed);
+        IntoIter(head)
+    }
+}
+
+/// An owning iterator over the elements of an [`OrderedListSet`], in order.
+#[derive(Debug)]
+pub struct IntoIter<T>(*mut Node<T>);
+
+unsafe impl<T: Send> Send for IntoIter<T> {}
+unsafe impl<T: Sync> Sync for IntoIter<T> {}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.0.is_null() {
+            return None;
+        }
+        let node = unsafe { Box::from_raw(self.0) };
+        self.0 = node
+            .next
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        Some(node.data)
+    }
+}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+impl<T> IntoIterator for OrderedListSet<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(mem::replace(&mut *lock(&self.head), ptr::null_mut()))
+    }
+}
+
+impl<T: Ord> FromIterator<T> for OrderedListSet<T> {
+    /// Sorts the elements once and links them directly. Of equal elements, the first one is kept.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut elements = iter.into_iter().collect::<Vec<_>>();
+        elements.sort();
+        elements.dedup();
+        let len = elements.len();
+        let mut head = ptr::null_mut();
+        for data in elements.into_iter().rev() {
+            head = Node::new(data, head);
+        }
+        Self {
+            head: Mutex::new(head),
+            len: AtomicUsize::new(len),
+        }
+    }
+}
+
+impl<T: Ord> Extend<T> for OrderedListSet<T> {
+    /// Inserts the elements in a single traversal as long as they are sorted, and goes back to the
+    /// head for each element that is smaller than the previous one. The elements that the set
+    /// already has are dropped.
+    ///
+    /// It takes no lock, as it has the set to itself.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let head: *mut *mut Node<T> = self.head.get_mut().unwrap_or_else(PoisonError::into_inner);
+        // the link to search from, and the element of its node
+        let (mut link, mut prev) = (head, ptr::null::<T>());
+        let mut inserted = 0;
+        for data in iter {
+            unsafe {
+                if prev.as_ref().map_or(false, |prev| *prev >= data) {
+                    link = head;
+                }
+                while let Some(node) = (*link).as_mut() {
+                    if node.data >= data {
+                        break;
+                    }
+                    link = node.next.get_mut().unwrap_or_else(PoisonError::into_inner);
+                }
+                let node = match (*link).as_mut() {
+                    Some(node) if node.data == data => node,
+                    _ => {
+                        *link = Node::new(data, *link);
+                        inserted += 1;
+                        &mut **link
+                    }
+                };
+                prev = &node.data;
+                link = node.next.get_mut().unwrap_or_else(PoisonError::into_inner);
+            }
+        }
+        *self.len.get_mut() += inserted;
+    }
+}
+
 impl<T> Drop for OrderedListSet<T> {
     fn drop(&mut self) {
-        let mut cursor = *self.head.lock().unwrap();
+        let mut cursor = *lock(&self.head);
         while !cursor.is_null() {
             unsafe {
                 // using the Box to effectively drop the node
                 let node = Box::from_raw(cursor);
-                cursor = *node.next.lock().unwrap();
+                cursor = *lock(&node.next);
             }
         }
     }
 }
 
+impl<T: Clone> Clone for OrderedListSet<T> {
+    /// Copies the elements seen by a lock-coupling traversal into a fresh list.
+    fn clone(&self) -> Self {
+        let mut head = ptr::null_mut();
+        let elements = self.iter().cloned().collect::<Vec<_>>();
+        let len = elements.len();
+        // `iter` visits the elements in order, so link the new nodes from the back.
+        for data in elements.into_iter().rev() {
+            head = Node::new(data, head);
+        }
+        Self {
+            head: Mutex::new(head),
+            len: AtomicUsize::new(len),
+        }
+    }
+}
+
 impl<T> Default for OrderedListSet<T> {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// Concurrent sorted singly linked map using lock-coupling.
+///
+/// It is an [`OrderedListSet`] of key-value pairs ordered by their keys. A reference to a value
+/// holds the lock of the link to its node, so it blocks the operations on the keys after it until
+/// it is dropped.
+///
+/// A panic in `K::cmp` propagates to the caller, but the map stays usable.
+#[derive(Debug)]
+pub struct OrderedListMap<K, V> {
+    head: Mutex<*mut Node<(K, V)>>,
+}
+
+unsafe impl<K: Send, V: Send> Send for OrderedListMap<K, V> {}
+unsafe impl<K: Send + Sync, V: Send + Sync> Sync for OrderedListMap<K, V> {}
+
+/// Shared reference to a value of an [`OrderedListMap`], holding the lock of the link to its node.
+#[derive(Debug)]
+pub struct GuardedRef<'l, K, V> {
+    guard: MutexGuard<'l, *mut Node<(K, V)>>,
+}
+
+/// Exclusive reference to a value of an [`OrderedListMap`], holding the lock of the link to its
+/// node.
+#[derive(Debug)]
+pub struct GuardedRefMut<'l, K, V> {
+    guard: MutexGuard<'l, *mut Node<(K, V)>>,
+}
+
+impl<K, V> GuardedRef<'_, K, V> {
+    /// Returns the key of the value.
+    pub fn key(&self) -> &K {
+        unsafe { &(**self.guard).data.0 }
+    }
+}
+
+impl<K, V> Deref for GuardedRef<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        // Nothing reaches the node without the lock.
+        unsafe { &(**self.guard).data.1 }
+    }
+}
+
+impl<K, V> GuardedRefMut<'_, K, V> {
+    /// Returns the key of the value.
+    pub fn key(&self) -> &K {
+        unsafe { &(**self.guard).data.0 }
+    }
+}
+
+impl<K, V> Deref for GuardedRefMut<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        unsafe { &(**self.guard).data.1 }
+    }
+}
+
+impl<K, V> DerefMut for GuardedRefMut<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        unsafe { &mut (**self.guard).data.1 }
+    }
+}
+
+impl<K, V> OrderedListMap<K, V> {
+    /// Creates a new map.
+    pub fn new() -> Self {
+        Self {
+            head: Mutex::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl<K: Ord, V> OrderedListMap<K, V> {
+    fn find(&self, key: &K) -> RawCursor<'_, (K, V)> {
+        RawCursor::new(lock(&self.head)).find_by(|(k, _)| k.cmp(key))
+    }
+
+    /// Returns `true` if the map contains the key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        matches!(self.find(key).state, CursorState::Found)
+    }
+
+    /// Returns a reference to the value of the key.
+    pub fn get(&self, key: &K) -> Option<GuardedRef<'_, K, V>> {
+        let cursor = self.find(key);
+        match cursor.state {
+            CursorState::Found => Some(GuardedRef {
+                guard: cursor.guard,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value of the key.
+    pub fn get_mut(&self, key: &K) -> Option<GuardedRefMut<'_, K, V>> {
+        let cursor = self.find(key);
+        match cursor.state {
+            CursorState::Found => Some(GuardedRefMut {
+                guard: cursor.guard,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Inserts a key-value pair. If the map already has the key, replaces its value and returns
+    /// the previous one.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let mut cursor = self.find(&key);
+        match cursor.state {
+            CursorState::Insert => {
+                cursor.insert_node((key, value));
+                None
+            }
+            CursorState::Found => {
+                Some(mem::replace(unsafe { &mut (**cursor.guard).data.1 }, value))
+            }
+            CursorState::Searching => unreachable!(),
+        }
+    }
+
+    /// Removes the key from the map and returns its value.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let mut cursor = self.find(key);
+        match cursor.state {
+            CursorState::Insert => None,
+            CursorState::Found => Some(cursor.remove_node().1),
+            CursorState::Searching => unreachable!(),
+        }
+    }
+}
+
+impl<K, V> Drop for OrderedListMap<K, V> {
+    fn drop(&mut self) {
+        let mut cursor = *lock(&self.head);
+        while !cursor.is_null() {
+            let node = unsafe { Box::from_raw(cursor) };
+            cursor = *lock(&node.next);
+        }
+    }
+}
+
+impl<K, V> Default for OrderedListMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,233 @@
+//! Bounded MPMC queue on a circular buffer.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_utils::{Backoff, CachePadded};
+
+/// Slot of the buffer, with the position it is ready for.
+struct Slot<T> {
+    /// For the position `pos` of the slot, `pos` if it's empty and ready for the push at `pos`,
+    /// `pos + 1` if it's full and ready for the pop at `pos`.
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Lock-free queue of a fixed capacity, for any number of producers and consumers, after Dmitry
+/// Vyukov's "Bounded MPMC queue".
+///
+/// The pushes and the pops take positions by incrementing the tail and the head, and each slot
+/// has a sequence number telling which position it's ready for, so that a thread only
+/// synchronizes with the others on the slot of its position. A thread that takes a position still
+/// waits for the thread of the previous lap to be done with its slot, so a preempted thread may
+/// make the queue look full or empty to the others until it resumes.
+///
+/// When it's full, [`try_push`](Self::try_push) gives the value back, and
+/// [`force_push`](Self::force_push) replaces the oldest value instead, e.g. for a buffer of the
+/// latest events.
+pub struct ArrayQueue<T> {
+    /// Position of the next pop.
+    head: CachePadded<AtomicUsize>,
+    /// Position of the next push.
+    tail: CachePadded<AtomicUsize>,
+    /// The slot of position `pos` is `buffer[pos % buffer.len()]`.
+    buffer: Box<[Slot<T>]>,
+}
+
+unsafe impl<T: Send> Send for ArrayQueue<T> {}
+unsafe impl<T: Send> Sync for ArrayQueue<T> {}
+
+impl<T> ArrayQueue<T> {
+    /// Creates an empty queue of `capacity` values. Panics if the capacity is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be positive");
+        Self {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            buffer: (0..capacity)
+                .map(|pos| Slot {
+                    seq: AtomicUsize::new(pos),
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the maximum number of values in the queue.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn slot(&self, pos: usize) -> &Slot<T> {
+        &self.buffer[pos % self.buffer.len()]
+    }
+
+    /// Pushes a value at the back, or gives it back if the queue is full.
+    pub fn try_push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            match (seq.wrapping_sub(pos) as isize).cmp(&0) {
+                core::cmp::Ordering::Equal => match self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(tail) => pos = tail,
+                },
+                // the value of the previous lap is still there
+                core::cmp::Ordering::Less => return Err(value),
+                // another push took the position
+                core::cmp::Ordering::Greater => pos = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Pushes a value at the back. If the queue is full, the oldest value is popped to make room
+    /// for it, and returned.
+    pub fn force_push(&self, value: T) -> Option<T> {
+        let backoff = Backoff::new();
+        let cap = self.buffer.len();
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq == pos {
+                if let Err(tail) = self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    pos = tail;
+                    continue;
+                }
+                unsafe { (*slot.value.get()).write(value) };
+                slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                return None;
+            }
+
+            let oldest = pos.wrapping_sub(cap);
+            if seq != oldest.wrapping_add(1) || self.head.load(Ordering::Relaxed) != oldest {
+                // Either another push took the position, or a pop is taking the value of the
+                // previous lap. Both are done soon.
+                backoff.snooze();
+                pos = self.tail.load(Ordering::Relaxed);
+                continue;
+            }
+
+            // The queue is full. Takes the position, so that no other push writes the slot, and
+            // then the oldest value, unless a pop takes it first.
+            if let Err(tail) = self.tail.compare_exchange_weak(
+                pos,
+                pos.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                pos = tail;
+                continue;
+            }
+            let displaced = if self
+                .head
+                .compare_exchange(
+                    oldest,
+                    oldest.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                Some(unsafe { (*slot.value.get()).assume_init_read() })
+            } else {
+                while slot.seq.load(Ordering::Acquire) != pos {
+                    backoff.snooze();
+                }
+                None
+            };
+            unsafe { (*slot.value.get()).write(value) };
+            slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+            return displaced;
+        }
+    }
+
+    /// Pops the value at the front, or returns `None` if the queue is empty.
+    pub fn try_pop(&self) -> Option<T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            match (seq.wrapping_sub(pos.wrapping_add(1)) as isize).cmp(&0) {
+                core::cmp::Ordering::Equal => match self.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.seq
+                            .store(pos.wrapping_add(self.buffer.len()), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(head) => pos = head,
+                },
+                // the push of the position isn't done
+                core::cmp::Ordering::Less => return None,
+                // another pop took the position
+                core::cmp::Ordering::Greater => pos = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Returns the number of values in the queue, as observed at some point.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            // a consistent pair, with no push or pop in between
+            if self.tail.load(Ordering::SeqCst) == tail {
+                return tail.wrapping_sub(head).min(self.buffer.len());
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is observed to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue is observed to be full.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.buffer.len()
+    }
+}
+
+impl<T> Drop for ArrayQueue<T> {
+    fn drop(&mut self) {
+        let head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        let mut pos = head;
+        while pos != tail {
+            let slot = &mut self.buffer[pos % self.buffer.len()];
+            unsafe { slot.value.get_mut().assume_init_drop() };
+            pos = pos.wrapping_add(1);
+        }
+    }
+}
+
+impl<T> fmt::Debug for ArrayQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArrayQueue")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
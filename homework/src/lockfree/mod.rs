@@ -0,0 +1,10 @@
+//! Lock-free data structures.
+
+pub mod array_queue;
+pub mod nm_tree;
+pub mod skiplist;
+pub mod spsc;
+
+pub use array_queue::ArrayQueue;
+pub use nm_tree::NmTreeMap;
+pub use skiplist::SkipListMap;
@@ -0,0 +1,394 @@
+//! Lock-free external binary search tree, ordered by its keys.
+
+use core::sync::atomic::Ordering;
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+
+use crate::map::NonblockingMap;
+
+/// Tag of an edge to a leaf that is being deleted.
+const FLAG: usize = 1;
+
+/// Tag of an edge that is frozen, as its node is being removed.
+const TAG: usize = 2;
+
+/// Key of a node, with the sentinels greater than all the keys of the map.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Key<K> {
+    Fin(K),
+    Inf0,
+    Inf1,
+    Inf2,
+}
+
+/// Node of the tree. A leaf has the key and the value of an entry, and no children. An internal
+/// node has two children, the keys less than its own on the left.
+#[derive(Debug)]
+struct Node<K, V> {
+    key: Key<K>,
+    value: Option<V>,
+    left: Atomic<Node<K, V>>,
+    right: Atomic<Node<K, V>>,
+}
+
+impl<K: Ord, V> Node<K, V> {
+    fn leaf(key: Key<K>, value: Option<V>) -> Self {
+        Self {
+            key,
+            value,
+            left: Atomic::null(),
+            right: Atomic::null(),
+        }
+    }
+
+    fn internal<'g>(key: Key<K>, left: Shared<'g, Self>, right: Shared<'g, Self>) -> Self {
+        Self {
+            key,
+            value: None,
+            left: Atomic::from(left),
+            right: Atomic::from(right),
+        }
+    }
+
+    /// Returns `true` if `key` is on the left of this node.
+    fn goes_left(&self, key: &K) -> bool {
+        match &self.key {
+            Key::Fin(k) => key < k,
+            _ => true,
+        }
+    }
+
+    /// Returns the edge to the child on the side of `key`.
+    fn child(&self, key: &K) -> &Atomic<Self> {
+        if self.goes_left(key) {
+            &self.left
+        } else {
+            &self.right
+        }
+    }
+
+    fn is_key(&self, key: &K) -> bool {
+        matches!(&self.key, Key::Fin(k) if k == key)
+    }
+}
+
+/// Nodes on the path to a key.
+struct SeekRecord<'g, K, V> {
+    /// The last node whose edge to the next node on the path isn't tagged.
+    ancestor: Shared<'g, Node<K, V>>,
+    /// The next node, i.e. the first one that is removed if the leaf is.
+    successor: Shared<'g, Node<K, V>>,
+    parent: Shared<'g, Node<K, V>>,
+    leaf: Shared<'g, Node<K, V>>,
+    /// The edge from the parent to the leaf, with its tags.
+    leaf_edge: Shared<'g, Node<K, V>>,
+}
+
+/// Lock-free external binary search tree map, after Natarajan and Mittal, "Fast Concurrent
+/// Lock-Free Binary Search Trees" (PPoPP 2014).
+///
+/// The entries are in the leaves, and the internal nodes only route the searches. An entry is
+/// deleted by flagging the edge to its leaf, and then removed with its parent by a single CAS on
+/// the edge above them, after the edge to its sibling is tagged so that it no longer changes. The
+/// threads that find a flagged or tagged edge help to remove the leaf, so that no one waits for
+/// a deletion. The removed nodes are destroyed through the epoch-based reclamation of
+/// `crossbeam_epoch`.
+///
+/// Like [`SkipListMap`](super::SkipListMap), and unlike the split-ordered list, it's iterated in
+/// order with [`iter`](Self::iter). The tree isn't balanced, so keys inserted in order make it a
+/// list.
+#[derive(Debug)]
+pub struct NmTreeMap<K, V> {
+    /// The sentinel root, never removed, whose left child is the other sentinel.
+    root: Atomic<Node<K, V>>,
+}
+
+/// Ordered iterator over the entries of an [`NmTreeMap`], returned by [`NmTreeMap::iter`].
+///
+/// It doesn't see a snapshot of the map: a key inserted or deleted while iterating may or may not
+/// be seen, but each key is seen at most once, in ascending order.
+#[derive(Debug)]
+pub struct Iter<'g, K, V> {
+    /// The edges to the subtrees to visit, the next one last.
+    stack: Vec<Shared<'g, Node<K, V>>>,
+    last: Option<&'g K>,
+    guard: &'g Guard,
+}
+
+impl<K: Ord, V> Default for NmTreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> NmTreeMap<K, V> {
+    /// Creates a new, empty map.
+    pub fn new() -> Self {
+        let guard = unsafe { unprotected() };
+        let leaf = |key| Owned::new(Node::leaf(key, None)).into_shared(guard);
+        let s = Owned::new(Node::internal(Key::Inf1, leaf(Key::Inf0), leaf(Key::Inf1)));
+        let root = Node::internal(Key::Inf2, s.into_shared(guard), leaf(Key::Inf2));
+        Self {
+            root: Atomic::new(root),
+        }
+    }
+
+    /// Returns an iterator over the entries, in ascending order of the keys.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            stack: vec![self.root.load(Ordering::Relaxed, guard)],
+            last: None,
+            guard,
+        }
+    }
+
+    /// Finds the path to the leaf where the key is, or would be inserted.
+    fn seek<'g>(&'g self, key: &K, guard: &'g Guard) -> SeekRecord<'g, K, V> {
+        let root = self.root.load(Ordering::Relaxed, guard);
+        let s = unsafe { root.deref() }.left.load(Ordering::Relaxed, guard);
+        let mut parent_edge = unsafe { s.deref() }.left.load(Ordering::Acquire, guard);
+        let mut record = SeekRecord {
+            ancestor: root,
+            successor: s,
+            parent: s,
+            leaf: parent_edge.with_tag(0),
+            leaf_edge: parent_edge,
+        };
+        let mut current_edge = unsafe { record.leaf.deref() }
+            .child(key)
+            .load(Ordering::Acquire, guard);
+        while !current_edge.is_null() {
+            if parent_edge.tag() & TAG == 0 {
+                record.ancestor = record.parent;
+                record.successor = record.leaf;
+            }
+            record.parent = record.leaf;
+            record.leaf = current_edge.with_tag(0);
+            parent_edge = current_edge;
+            current_edge = unsafe { record.leaf.deref() }
+                .child(key)
+                .load(Ordering::Acquire, guard);
+        }
+        record.leaf_edge = parent_edge;
+        record
+    }
+
+    /// Removes the flagged leaf under the parent of the record, which is either the leaf of the
+    /// record or its sibling, by replacing the successor with the sibling. Returns `true` if this
+    /// thread removed it.
+    fn cleanup<'g>(&'g self, key: &K, record: &SeekRecord<'g, K, V>, guard: &'g Guard) -> bool {
+        let ancestor = unsafe { record.ancestor.deref() };
+        let parent = unsafe { record.parent.deref() };
+        let (child_edge, mut sibling_edge) = if parent.goes_left(key) {
+            (&parent.left, &parent.right)
+        } else {
+            (&parent.right, &parent.left)
+        };
+        if child_edge.load(Ordering::Acquire, guard).tag() & FLAG == 0 {
+            // the flagged leaf is the sibling, so the child is the one that stays
+            sibling_edge = child_edge;
+        }
+
+        // The edges of the parent can't change from now on, and the sibling keeps its flag.
+        let sibling = sibling_edge.fetch_or(TAG, Ordering::AcqRel, guard);
+        if ancestor
+            .child(key)
+            .compare_exchange(
+                record.successor,
+                sibling.with_tag(sibling.tag() & FLAG),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+                guard,
+            )
+            .is_err()
+        {
+            return false;
+        }
+        unsafe { self.retire(key, record, sibling.with_tag(0), guard) };
+        true
+    }
+
+    /// Destroys the nodes from the successor to the parent of the record, and the flagged leaves
+    /// under them, which were just removed with the sibling `kept` staying in the tree.
+    ///
+    /// # Safety
+    ///
+    /// The nodes must have been removed by this thread, so that their edges are frozen.
+    unsafe fn retire<'g>(
+        &self,
+        key: &K,
+        record: &SeekRecord<'g, K, V>,
+        kept: Shared<'g, Node<K, V>>,
+        guard: &'g Guard,
+    ) {
+        let mut node = record.successor;
+        loop {
+            let node_ref = node.deref();
+            let left = node_ref.left.load(Ordering::Relaxed, guard).with_tag(0);
+            let right = node_ref.right.load(Ordering::Relaxed, guard).with_tag(0);
+            // Above the parent, the edges on the path are tagged, because the other children are
+            // flagged leaves whose parents were being removed.
+            let (next, leaf) = if node == record.parent {
+                (Shared::null(), if left == kept { right } else { left })
+            } else if node_ref.goes_left(key) {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            guard.defer_destroy(leaf);
+            guard.defer_destroy(node);
+            if next.is_null() {
+                return;
+            }
+            node = next;
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> NonblockingMap<K, V> for NmTreeMap<K, V> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a Guard) -> Option<&'a V> {
+        let record = self.seek(key, guard);
+        let leaf = unsafe { record.leaf.deref() };
+        if leaf.is_key(key) && record.leaf_edge.tag() & FLAG == 0 {
+            leaf.value.as_ref()
+        } else {
+            None
+        }
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &Guard) -> Result<(), V> {
+        let new_leaf =
+            Owned::new(Node::leaf(Key::Fin(key.clone()), Some(value))).into_shared(guard);
+        loop {
+            let record = self.seek(key, guard);
+            let leaf = unsafe { record.leaf.deref() };
+            if leaf.is_key(key) {
+                if record.leaf_edge.tag() & FLAG == 0 {
+                    // the new leaf was never shared
+                    let new_leaf = unsafe { new_leaf.into_owned() }.into_box();
+                    return Err(new_leaf.value.unwrap());
+                }
+                // deleted, but not removed yet
+                let _ = self.cleanup(key, &record, guard);
+                continue;
+            }
+
+            let internal = if leaf.goes_left(key) {
+                Node::internal(leaf.key.clone(), new_leaf, record.leaf)
+            } else {
+                Node::internal(Key::Fin(key.clone()), record.leaf, new_leaf)
+            };
+            match unsafe { record.parent.deref() }
+                .child(key)
+                .compare_exchange(
+                    record.leaf,
+                    Owned::new(internal),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                ) {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    if e.current.with_tag(0) == record.leaf && e.current.tag() != 0 {
+                        let _ = self.cleanup(key, &record, guard);
+                    }
+                }
+            }
+        }
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        // the leaf flagged by this thread
+        let mut deleted = None;
+        loop {
+            let record = self.seek(key, guard);
+            let leaf = match deleted {
+                Some(leaf) => {
+                    // removed by another thread otherwise
+                    if record.leaf == leaf && !self.cleanup(key, &record, guard) {
+                        continue;
+                    }
+                    let leaf: Shared<'a, Node<K, V>> = leaf;
+                    return Ok(unsafe { leaf.deref() }.value.as_ref().unwrap());
+                }
+                None => record.leaf,
+            };
+
+            if !unsafe { leaf.deref() }.is_key(key) {
+                return Err(());
+            }
+            match unsafe { record.parent.deref() }
+                .child(key)
+                .compare_exchange(
+                    leaf,
+                    leaf.with_tag(FLAG),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                    guard,
+                ) {
+                Ok(_) => {
+                    deleted = Some(leaf);
+                    if self.cleanup(key, &record, guard) {
+                        return Ok(unsafe { leaf.deref() }.value.as_ref().unwrap());
+                    }
+                }
+                Err(e) => {
+                    if e.current.with_tag(0) == leaf && e.current.tag() != 0 {
+                        let _ = self.cleanup(key, &record, guard);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<K, V> Drop for NmTreeMap<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut stack = vec![self.root.load(Ordering::Relaxed, guard)];
+            while let Some(node) = stack.pop() {
+                let node = node.with_tag(0).into_owned();
+                for child in [&node.left, &node.right] {
+                    let child = child.load(Ordering::Relaxed, guard);
+                    if !child.is_null() {
+                        stack.push(child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'g, K: Ord, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let edge = self.stack.pop()?;
+            let node = unsafe { edge.with_tag(0).deref() };
+            let right = node.right.load(Ordering::Acquire, self.guard);
+            if !right.is_null() {
+                self.stack.push(right);
+                self.stack
+                    .push(node.left.load(Ordering::Acquire, self.guard));
+                continue;
+            }
+
+            let key = match &node.key {
+                Key::Fin(key) => key,
+                // the sentinels are after all the keys
+                _ => {
+                    self.stack.clear();
+                    return None;
+                }
+            };
+            // A subtree may have moved up the tree while it was being visited, so it may be
+            // visited again.
+            if edge.tag() & FLAG != 0 || self.last.map_or(false, |last| key <= last) {
+                continue;
+            }
+            self.last = Some(key);
+            return Some((key, node.value.as_ref().unwrap()));
+        }
+    }
+}
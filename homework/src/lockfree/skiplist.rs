@@ -0,0 +1,355 @@
+//! Lock-free skiplist map, ordered by its keys.
+
+use core::ops::{Bound, RangeBounds};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use rand::Rng;
+
+use crate::map::NonblockingMap;
+
+/// Number of levels of the skiplist. With a probability of 1/2 for a node to reach the next level,
+/// the searches stay logarithmic up to about 2^16 keys.
+const MAX_HEIGHT: usize = 16;
+
+/// Node of the skiplist, linked at the levels below its height.
+#[derive(Debug)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+
+    /// Successors at each level, the lowest first. The tag of a successor is set if the node is
+    /// deleted at this level, and then it's never changed again.
+    next: Box<[Atomic<Node<K, V>>]>,
+
+    /// Number of levels the node is linked at, plus one while it's being inserted. The node is
+    /// destroyed when it drops to 0, since it's then unreachable.
+    refs: AtomicUsize,
+}
+
+/// Links to the node at each level before which a key is, and the node.
+struct Position<'g, K, V> {
+    preds: [&'g Atomic<Node<K, V>>; MAX_HEIGHT],
+    succs: [Shared<'g, Node<K, V>>; MAX_HEIGHT],
+}
+
+/// Lock-free skiplist map, after "The Art of Multiprocessor Programming" by Herlihy and Shavit.
+///
+/// Unlike the split-ordered list, it keeps the keys in order, so it's iterated in order with
+/// [`iter`](Self::iter), and its keys in a range are found with [`range`](Self::range), in
+/// logarithmic time. A node deleted from the map is destroyed once it's unlinked at all its
+/// levels, through the epoch-based reclamation of `crossbeam_epoch`.
+///
+/// The iterators don't see a snapshot of the map: a key inserted or deleted while iterating may or
+/// may not be seen, but each key is seen at most once, in ascending order.
+#[derive(Debug)]
+pub struct SkipListMap<K, V> {
+    head: [Atomic<Node<K, V>>; MAX_HEIGHT],
+}
+
+/// Ordered iterator over the entries of a [`SkipListMap`], returned by [`SkipListMap::iter`].
+#[derive(Debug)]
+pub struct Iter<'g, K, V> {
+    curr: Shared<'g, Node<K, V>>,
+    guard: &'g Guard,
+}
+
+/// Ordered iterator over the entries of a [`SkipListMap`] whose keys are in a range, returned by
+/// [`SkipListMap::range`].
+#[derive(Debug)]
+pub struct Range<'g, K, V, R> {
+    iter: Iter<'g, K, V>,
+    range: R,
+}
+
+/// Returns a random height, with a probability of 1/2 to reach each next level.
+fn random_height() -> usize {
+    let bits = rand::thread_rng().gen::<u32>() | (1 << (MAX_HEIGHT - 1));
+    bits.trailing_zeros() as usize + 1
+}
+
+impl<K, V> Default for SkipListMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> SkipListMap<K, V> {
+    /// Creates a new, empty map.
+    pub fn new() -> Self {
+        Self {
+            head: Default::default(),
+        }
+    }
+
+    /// Drops a reference of the node, destroying it if it was the last one.
+    ///
+    /// # Safety
+    ///
+    /// The node must not be reachable from the map without the other references.
+    unsafe fn release(&self, node: Shared<'_, Node<K, V>>, guard: &Guard) {
+        if node.deref().refs.fetch_sub(1, Ordering::AcqRel) == 1 {
+            guard.defer_destroy(node);
+        }
+    }
+
+    /// Returns an iterator over the entries, in ascending order of the keys.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> Iter<'g, K, V> {
+        Iter {
+            curr: self.head[0].load(Ordering::Acquire, guard),
+            guard,
+        }
+    }
+}
+
+impl<K: Ord, V> SkipListMap<K, V> {
+    /// Finds the position of the key, unlinking the deleted nodes on the way. Returns whether
+    /// the key is found, i.e. it's the key of the node at the lowest level.
+    fn find<'g>(&'g self, key: &K, guard: &'g Guard) -> (bool, Position<'g, K, V>) {
+        'retry: loop {
+            let mut pos = Position {
+                preds: [&self.head[0]; MAX_HEIGHT],
+                succs: [Shared::null(); MAX_HEIGHT],
+            };
+            let mut pred: &'g [Atomic<Node<K, V>>] = &self.head;
+            for level in (0..MAX_HEIGHT).rev() {
+                let mut curr = pred[level].load(Ordering::Acquire, guard);
+                // `pred` is deleted at this level since it was passed at the level above
+                if curr.tag() != 0 {
+                    continue 'retry;
+                }
+                while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                    let succ = curr_ref.next[level].load(Ordering::Acquire, guard);
+                    if succ.tag() != 0 {
+                        // fails if `pred` is deleted as well, at this level
+                        if pred[level]
+                            .compare_exchange(
+                                curr,
+                                succ.with_tag(0),
+                                Ordering::Release,
+                                Ordering::Relaxed,
+                                guard,
+                            )
+                            .is_err()
+                        {
+                            continue 'retry;
+                        }
+                        unsafe { self.release(curr, guard) };
+                        curr = succ.with_tag(0);
+                        continue;
+                    }
+                    if curr_ref.key >= *key {
+                        break;
+                    }
+                    pred = &curr_ref.next;
+                    curr = succ;
+                }
+                pos.preds[level] = &pred[level];
+                pos.succs[level] = curr;
+            }
+            let found = unsafe { pos.succs[0].as_ref() }.map_or(false, |node| node.key == *key);
+            return (found, pos);
+        }
+    }
+
+    /// Returns the node at the lowest level before which no key satisfies `before`, without
+    /// unlinking the deleted nodes.
+    fn seek<'g, F>(&'g self, before: F, guard: &'g Guard) -> Shared<'g, Node<K, V>>
+    where
+        F: Fn(&K) -> bool,
+    {
+        let mut pred: &'g [Atomic<Node<K, V>>] = &self.head;
+        let mut curr = Shared::null();
+        for level in (0..MAX_HEIGHT).rev() {
+            curr = pred[level].load(Ordering::Acquire, guard).with_tag(0);
+            while let Some(curr_ref) = unsafe { curr.as_ref() } {
+                let succ = curr_ref.next[level].load(Ordering::Acquire, guard);
+                if succ.tag() == 0 {
+                    if !before(&curr_ref.key) {
+                        break;
+                    }
+                    pred = &curr_ref.next;
+                }
+                curr = succ.with_tag(0);
+            }
+        }
+        curr
+    }
+
+    /// Returns an iterator over the entries whose keys are in `range`, in ascending order of the
+    /// keys.
+    pub fn range<'g, R>(&'g self, range: R, guard: &'g Guard) -> Range<'g, K, V, R>
+    where
+        R: RangeBounds<K>,
+    {
+        let curr = match range.start_bound() {
+            Bound::Included(start) => self.seek(|key| key < start, guard),
+            Bound::Excluded(start) => self.seek(|key| key <= start, guard),
+            Bound::Unbounded => self.head[0].load(Ordering::Acquire, guard),
+        };
+        Range {
+            iter: Iter { curr, guard },
+            range,
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> NonblockingMap<K, V> for SkipListMap<K, V> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a Guard) -> Option<&'a V> {
+        let node = unsafe { self.seek(|k| k < key, guard).as_ref()? };
+        if node.key == *key {
+            Some(&node.value)
+        } else {
+            None
+        }
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &Guard) -> Result<(), V> {
+        let height = random_height();
+        let mut node = Owned::new(Node {
+            key: key.clone(),
+            value,
+            next: (0..height).map(|_| Atomic::null()).collect(),
+            // the inserter's and the lowest level's
+            refs: AtomicUsize::new(2),
+        });
+        let (node, mut pos) = loop {
+            let (found, pos) = self.find(key, guard);
+            if found {
+                return Err(node.into_box().value);
+            }
+            node.next[0].store(pos.succs[0], Ordering::Relaxed);
+            match pos.preds[0].compare_exchange(
+                pos.succs[0],
+                node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(node) => break (node, pos),
+                Err(e) => node = e.new,
+            }
+        };
+
+        // The node is in the map from now on. Links it at the upper levels, until it's deleted.
+        let node_ref = unsafe { node.deref() };
+        'levels: for level in 1..height {
+            loop {
+                let next = node_ref.next[level].load(Ordering::Acquire, guard);
+                let succ = pos.succs[level];
+                if next.tag() != 0
+                    || (next != succ
+                        && node_ref.next[level]
+                            .compare_exchange(
+                                next,
+                                succ,
+                                Ordering::Release,
+                                Ordering::Relaxed,
+                                guard,
+                            )
+                            .is_err())
+                {
+                    break 'levels;
+                }
+                let _ = node_ref.refs.fetch_add(1, Ordering::Relaxed);
+                if pos.preds[level]
+                    .compare_exchange(succ, node, Ordering::Release, Ordering::Relaxed, guard)
+                    .is_ok()
+                {
+                    break;
+                }
+                // still referenced by the inserter
+                let _ = node_ref.refs.fetch_sub(1, Ordering::Relaxed);
+                pos = self.find(key, guard).1;
+                if pos.succs[0] != node {
+                    break 'levels;
+                }
+            }
+        }
+
+        // If the node was deleted meanwhile, it may have been linked after the deleter unlinked it.
+        if node_ref.next[0].load(Ordering::Acquire, guard).tag() != 0 {
+            let _ = self.find(key, guard);
+        }
+        unsafe { self.release(node, guard) };
+        Ok(())
+    }
+
+    fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()> {
+        let (found, pos) = self.find(key, guard);
+        if !found {
+            return Err(());
+        }
+        let node_ref = unsafe { pos.succs[0].deref() };
+
+        // marks the upper levels first, so that the node isn't linked at them anymore once it's
+        // deleted at the lowest
+        for next in node_ref.next[1..].iter().rev() {
+            let _ = next.fetch_or(1, Ordering::AcqRel, guard);
+        }
+        if node_ref.next[0].fetch_or(1, Ordering::AcqRel, guard).tag() != 0 {
+            return Err(());
+        }
+
+        // unlinks the node at all the levels
+        let _ = self.find(key, guard);
+        Ok(&node_ref.value)
+    }
+}
+
+impl<K, V> Drop for SkipListMap<K, V> {
+    fn drop(&mut self) {
+        // A deleted node may still be linked at some levels, but not at the lowest one.
+        let mut nodes = Vec::new();
+        unsafe {
+            let guard = unprotected();
+            for (level, head) in self.head.iter().enumerate() {
+                let mut curr = head.load(Ordering::Relaxed, guard);
+                while let Some(curr_ref) = curr.as_ref() {
+                    nodes.push(curr.as_raw());
+                    curr = curr_ref.next[level]
+                        .load(Ordering::Relaxed, guard)
+                        .with_tag(0);
+                }
+            }
+            nodes.sort_unstable();
+            nodes.dedup();
+            for node in nodes {
+                drop(Owned::from_raw(node as *mut Node<K, V>));
+            }
+        }
+    }
+}
+
+impl<'g, K, V> Iterator for Iter<'g, K, V> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = unsafe { self.curr.as_ref()? };
+            let next = node.next[0].load(Ordering::Acquire, self.guard);
+            self.curr = next.with_tag(0);
+            if next.tag() == 0 {
+                return Some((&node.key, &node.value));
+            }
+        }
+    }
+}
+
+impl<'g, K: Ord, V, R: RangeBounds<K>> Iterator for Range<'g, K, V, R> {
+    type Item = (&'g K, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?;
+        let below_end = match self.range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        if below_end {
+            Some((key, value))
+        } else {
+            self.iter.curr = Shared::null();
+            None
+        }
+    }
+}
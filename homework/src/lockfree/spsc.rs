@@ -0,0 +1,245 @@
+//! Single-producer single-consumer ring buffer.
+//!
+//! [`split`] creates a ring buffer and returns its two halves, which can be sent to two threads:
+//!
+//! ```
+//! use cs431_homework::lockfree::spsc;
+//!
+//! let (mut producer, mut consumer) = spsc::split(4);
+//! assert_eq!(producer.push_slice(b"hello"), 4);
+//! let mut buf = [0; 8];
+//! assert_eq!(consumer.pop_slice(&mut buf), 4);
+//! assert_eq!(&buf[..4], b"hell");
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_utils::CachePadded;
+use std::sync::Arc;
+
+/// The buffer, with the positions of its first value and past its last one. A position is in
+/// `0..2 * capacity`, so that a full buffer and an empty one have different positions.
+struct Inner<T> {
+    /// Written by the consumer only.
+    head: CachePadded<AtomicUsize>,
+    /// Written by the producer only.
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Inner<T> {
+    fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Returns the position `n` after `pos`.
+    fn advance(&self, pos: usize, n: usize) -> usize {
+        (pos + n) % (2 * self.capacity())
+    }
+
+    /// Returns the number of values from `head` to `tail`.
+    fn distance(&self, head: usize, tail: usize) -> usize {
+        (tail + 2 * self.capacity() - head) % (2 * self.capacity())
+    }
+
+    fn slot(&self, pos: usize) -> *mut MaybeUninit<T> {
+        self.buffer[pos % self.capacity()].get()
+    }
+
+    /// Returns the slots of the `n` positions from `pos`, which wrap around at most once.
+    fn slots(&self, pos: usize, n: usize) -> [(*mut T, usize); 2] {
+        let index = pos % self.capacity();
+        let first = n.min(self.capacity() - index);
+        let base = self.buffer.as_ptr() as *mut T;
+        unsafe { [(base.add(index), first), (base, n - first)] }
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let mut head = *self.head.get_mut();
+        let tail = *self.tail.get_mut();
+        while head != tail {
+            unsafe { (*self.slot(head)).assume_init_drop() };
+            head = self.advance(head, 1);
+        }
+    }
+}
+
+/// Creates a ring buffer of `capacity` values, and returns its producer and its consumer. Panics
+/// if the capacity is 0.
+///
+/// Both halves are wait-free, and each of them caches the position of the other, so that it only
+/// reads the one the other writes when the buffer looks full, or empty. While the buffer is
+/// neither, the two threads don't share a cache line.
+pub fn split<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "capacity must be positive");
+    let inner = Arc::new(Inner {
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        buffer: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+    });
+    (
+        Producer {
+            inner: Arc::clone(&inner),
+            head: 0,
+            tail: 0,
+        },
+        Consumer {
+            inner,
+            head: 0,
+            tail: 0,
+        },
+    )
+}
+
+/// Pushing half of a ring buffer, created by [`split`].
+pub struct Producer<T> {
+    inner: Arc<Inner<T>>,
+    /// The head as last read.
+    head: usize,
+    tail: usize,
+}
+
+/// Popping half of a ring buffer, created by [`split`].
+pub struct Consumer<T> {
+    inner: Arc<Inner<T>>,
+    head: usize,
+    /// The tail as last read.
+    tail: usize,
+}
+
+unsafe impl<T: Send> Send for Producer<T> {}
+unsafe impl<T: Send> Send for Consumer<T> {}
+
+impl<T> Producer<T> {
+    /// Returns the number of values the buffer holds at most.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Returns the number of free slots, reading the head again if fewer than `wanted` look free.
+    fn free(&mut self, wanted: usize) -> usize {
+        let free = self.capacity() - self.inner.distance(self.head, self.tail);
+        if free >= wanted {
+            return free;
+        }
+        self.head = self.inner.head.load(Ordering::Acquire);
+        self.capacity() - self.inner.distance(self.head, self.tail)
+    }
+
+    /// Returns the number of values in the buffer, as observed now.
+    pub fn len(&mut self) -> usize {
+        self.capacity() - self.free(usize::MAX)
+    }
+
+    /// Returns `true` if the buffer is observed to be empty.
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes a value, or gives it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.free(1) == 0 {
+            return Err(value);
+        }
+        unsafe { (*self.inner.slot(self.tail)).write(value) };
+        self.tail = self.inner.advance(self.tail, 1);
+        self.inner.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T: Copy> Producer<T> {
+    /// Pushes as many values of `values` as fit, in order, and returns how many.
+    pub fn push_slice(&mut self, values: &[T]) -> usize {
+        let n = values.len().min(self.free(values.len()));
+        let mut values = values.as_ptr();
+        for (slots, len) in self.inner.slots(self.tail, n) {
+            unsafe {
+                ptr::copy_nonoverlapping(values, slots, len);
+                values = values.add(len);
+            }
+        }
+        self.tail = self.inner.advance(self.tail, n);
+        self.inner.tail.store(self.tail, Ordering::Release);
+        n
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Returns the number of values the buffer holds at most.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Returns the number of values, reading the tail again if fewer than `wanted` are seen.
+    fn available(&mut self, wanted: usize) -> usize {
+        let available = self.inner.distance(self.head, self.tail);
+        if available >= wanted {
+            return available;
+        }
+        self.tail = self.inner.tail.load(Ordering::Acquire);
+        self.inner.distance(self.head, self.tail)
+    }
+
+    /// Returns the number of values in the buffer, as observed now.
+    pub fn len(&mut self) -> usize {
+        self.available(usize::MAX)
+    }
+
+    /// Returns `true` if the buffer is observed to be empty.
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pops the oldest value, or returns `None` if the buffer is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.available(1) == 0 {
+            return None;
+        }
+        let value = unsafe { (*self.inner.slot(self.head)).assume_init_read() };
+        self.head = self.inner.advance(self.head, 1);
+        self.inner.head.store(self.head, Ordering::Release);
+        Some(value)
+    }
+}
+
+impl<T: Copy> Consumer<T> {
+    /// Pops the oldest values into `buf`, as many as there are up to its length, and returns how
+    /// many.
+    pub fn pop_slice(&mut self, buf: &mut [T]) -> usize {
+        let n = buf.len().min(self.available(buf.len()));
+        let mut buf = buf.as_mut_ptr();
+        for (slots, len) in self.inner.slots(self.head, n) {
+            unsafe {
+                ptr::copy_nonoverlapping(slots, buf, len);
+                buf = buf.add(len);
+            }
+        }
+        self.head = self.inner.advance(self.head, n);
+        self.inner.head.store(self.head, Ordering::Release);
+        n
+    }
+}
+
+impl<T> fmt::Debug for Producer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer")
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Consumer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer")
+            .field("capacity", &self.capacity())
+            .finish_non_exhaustive()
+    }
+}
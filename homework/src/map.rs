@@ -1,18 +1,63 @@
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU64, Ordering};
 use crossbeam_epoch::Guard;
 use cs431::lock::{Lock, RawLock};
-use rand::{distributions::Alphanumeric, rngs::ThreadRng, Rng};
+use once_cell::sync::Lazy;
+use rand::{distributions::Alphanumeric, Rng};
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "deterministic")] {
+        /// Random generator of the workloads.
+        pub type WorkloadRng = rand::rngs::StdRng;
+    } else {
+        /// Random generator of the workloads.
+        pub type WorkloadRng = rand::rngs::ThreadRng;
+    }
+}
+
+/// Seed used by the `deterministic` feature unless `CS431_SEED` is set.
+pub const DEFAULT_SEED: u64 = 0x431;
+
+/// Returns the seed of the workload generators: `CS431_SEED` if it is set, and `DEFAULT_SEED`
+/// otherwise. It is only used with the `deterministic` feature, and the binaries and the test
+/// harnesses report it so that a run can be reproduced.
+pub fn workload_seed() -> u64 {
+    static SEED: Lazy<u64> = Lazy::new(|| {
+        std::env::var("CS431_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or(DEFAULT_SEED)
+    });
+    *SEED
+}
+
+/// Returns a random generator for workloads.
+///
+/// With the `deterministic` feature, the `n`-th generator created by the process is seeded with
+/// `workload_seed() + n`, so a single-threaded run is reproduced exactly. Otherwise, it is the
+/// thread-local generator seeded by the OS.
+pub fn workload_rng() -> WorkloadRng {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "deterministic")] {
+            static STREAM: AtomicU64 = AtomicU64::new(0);
+            let stream = STREAM.fetch_add(1, Ordering::Relaxed);
+            rand::SeedableRng::seed_from_u64(workload_seed().wrapping_add(stream))
+        } else {
+            rand::thread_rng()
+        }
+    }
+}
 
 /// Types that has random generator
 pub trait RandGen {
     /// Randomly generates a value.
-    fn rand_gen(rng: &mut ThreadRng) -> Self;
+    fn rand_gen(rng: &mut WorkloadRng) -> Self;
 }
 
 const KEY_MAX_LENGTH: usize = 4;
 
 impl RandGen for String {
-    fn rand_gen(rng: &mut ThreadRng) -> Self {
+    fn rand_gen(rng: &mut WorkloadRng) -> Self {
         let length = rng.gen::<usize>() % KEY_MAX_LENGTH;
         rng.sample_iter(&Alphanumeric)
             .take(length)
@@ -23,7 +68,7 @@ impl RandGen for String {
 
 impl RandGen for usize {
     /// pick only 16 bits, MSB=0
-    fn rand_gen(rng: &mut ThreadRng) -> Self {
+    fn rand_gen(rng: &mut WorkloadRng) -> Self {
         const MASK: usize = 0x4004004004007777usize;
         rng.gen::<usize>() & MASK
     }
@@ -31,7 +76,7 @@ impl RandGen for usize {
 
 impl RandGen for u32 {
     /// pick only 16 bits
-    fn rand_gen(rng: &mut ThreadRng) -> Self {
+    fn rand_gen(rng: &mut WorkloadRng) -> Self {
         const MASK: u32 = 0x66666666u32;
         rng.gen::<u32>() & MASK
     }
@@ -76,6 +121,14 @@ pub trait NonblockingMap<K: ?Sized, V> {
     /// Unlike stack or queue's pop that can return `Option<V>`, since a `delete`d
     /// value may also be `lookup`ed, we can only return a reference, not full ownership.
     fn delete<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, ()>;
+
+    /// Inserts a key-value pair, atomically replacing the value if the key already exists.
+    /// Returns a reference to the replaced value, if any.
+    ///
+    /// This is optional. Maps that can't replace a value atomically return `Err(value)`.
+    fn upsert<'a>(&'a self, key: &K, value: V, guard: &'a Guard) -> Result<Option<&'a V>, V> {
+        Err(value)
+    }
 }
 
 /// Converts str sequential map into string sequential map
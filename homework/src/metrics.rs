@@ -0,0 +1,149 @@
+//! Approximate traffic metrics.
+
+use core::hash::{Hash, Hasher};
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::collections::hash_map::DefaultHasher;
+use std::sync::Mutex;
+
+/// Concurrent count-min sketch.
+///
+/// Counts are kept in `depth` rows of `width` atomic counters, and an item is counted in one
+/// counter of each row. The estimate of an item is the minimum of its counters, so it never
+/// underestimates, and overestimates by at most `e * total / width` with probability
+/// `1 - e^-depth`.
+#[derive(Debug)]
+pub struct CountMinSketch {
+    width: usize,
+    depth: usize,
+    counters: Box<[AtomicU64]>,
+}
+
+impl CountMinSketch {
+    /// Creates a new sketch with `depth` rows of `width` counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` or `depth` is zero.
+    pub fn new(width: usize, depth: usize) -> Self {
+        assert!(width > 0 && depth > 0, "empty sketch");
+        Self {
+            width,
+            depth,
+            counters: (0..width * depth).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Index of the counter of `item` in each row.
+    fn indices<'a, T: Hash + ?Sized>(&'a self, item: &'a T) -> impl Iterator<Item = usize> + 'a {
+        (0..self.depth).map(move |row| {
+            let mut hasher = DefaultHasher::new();
+            row.hash(&mut hasher);
+            item.hash(&mut hasher);
+            row * self.width + (hasher.finish() as usize) % self.width
+        })
+    }
+
+    /// Counts `item` once and returns its new estimate.
+    pub fn increment<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        self.indices(item)
+            .map(|i| self.counters[i].fetch_add(1, Ordering::Relaxed) + 1)
+            .min()
+            .unwrap()
+    }
+
+    /// Returns the estimated count of `item`.
+    pub fn estimate<T: Hash + ?Sized>(&self, item: &T) -> u64 {
+        self.indices(item)
+            .map(|i| self.counters[i].load(Ordering::Relaxed))
+            .min()
+            .unwrap()
+    }
+
+    /// Resets all counts to zero.
+    pub fn clear(&self) {
+        for counter in self.counters.iter() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Approximate tracker of the `k` most frequent items.
+///
+/// Items are counted in a [`CountMinSketch`], and the `k` items with the largest estimates seen so
+/// far are kept in a small list. Recording an item whose estimate can't make it into the list
+/// doesn't take the lock.
+#[derive(Debug)]
+pub struct TopK<T> {
+    k: usize,
+    sketch: CountMinSketch,
+    /// The smallest estimate in a full `top`, or 0 if it is not full.
+    threshold: AtomicU64,
+    top: Mutex<Vec<(T, u64)>>,
+}
+
+impl<T: Hash + Eq + Clone> Default for TopK<T> {
+    /// Tracks the 10 most frequent items.
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl<T: Hash + Eq + Clone> TopK<T> {
+    /// Creates a new tracker of the `k` most frequent items.
+    pub fn new(k: usize) -> Self {
+        Self::with_sketch(k, CountMinSketch::new(1024, 4))
+    }
+
+    /// Creates a new tracker of the `k` most frequent items, counted in `sketch`.
+    pub fn with_sketch(k: usize, sketch: CountMinSketch) -> Self {
+        Self {
+            k,
+            sketch,
+            threshold: AtomicU64::new(0),
+            top: Mutex::new(Vec::with_capacity(k + 1)),
+        }
+    }
+
+    /// Records an occurrence of `item`.
+    pub fn record(&self, item: &T) {
+        let estimate = self.sketch.increment(item);
+        if estimate <= self.threshold.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut top = self.top.lock().unwrap();
+        if let Some(entry) = top.iter_mut().find(|(t, _)| t == item) {
+            entry.1 = entry.1.max(estimate);
+        } else if top.len() < self.k {
+            top.push((item.clone(), estimate));
+        } else if let Some(min) = top.iter_mut().min_by_key(|(_, count)| *count) {
+            if min.1 < estimate {
+                *min = (item.clone(), estimate);
+            }
+        }
+        if top.len() == self.k {
+            let min = top.iter().map(|(_, count)| *count).min().unwrap_or(0);
+            self.threshold.store(min, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the estimated count of `item`.
+    pub fn estimate(&self, item: &T) -> u64 {
+        self.sketch.estimate(item)
+    }
+
+    /// Returns the tracked items with their estimated counts, most frequent first.
+    pub fn top(&self) -> Vec<(T, u64)> {
+        let mut top = self.top.lock().unwrap().clone();
+        top.sort_by(|a, b| b.1.cmp(&a.1));
+        top
+    }
+
+    /// Forgets all counts.
+    pub fn clear(&self) {
+        let mut top = self.top.lock().unwrap();
+        top.clear();
+        self.sketch.clear();
+        self.threshold.store(0, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,173 @@
+use std::cmp;
+use std::mem;
+use std::ptr;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    next: RwLock<*mut Node<T>>,
+}
+
+unsafe impl<T: Send> Send for Node<T> {}
+unsafe impl<T: Sync> Sync for Node<T> {}
+
+/// Concurrent sorted singly linked list using reader-writer lock-coupling.
+///
+/// Like [`OrderedListSet`](crate::OrderedListSet), but the links are `RwLock`s. `contains` and
+/// `iter` take shared locks hand-over-hand, so readers don't serialize each other. `insert` and
+/// `remove` also search with shared locks, and only take the exclusive locks of the links around
+/// the key, so they only block the readers that go past the key.
+///
+/// A panic in `T::cmp` propagates to the caller, but the set stays usable.
+#[derive(Debug)]
+pub struct RwListSet<T> {
+    head: RwLock<*mut Node<T>>,
+}
+
+unsafe impl<T: Send> Send for RwListSet<T> {}
+unsafe impl<T: Sync> Sync for RwListSet<T> {}
+
+/// Locks `lock` for reading, ignoring poisoning.
+///
+/// A link is only poisoned by a panic in `T::cmp` while searching, which happens before any link
+/// is modified.
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Locks `lock` for writing, ignoring poisoning. See [`read`].
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<T> Node<T> {
+    fn new(data: T, next: *mut Self) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            data,
+            next: RwLock::new(next),
+        }))
+    }
+}
+
+impl<T> RwListSet<T> {
+    /// Creates a new list.
+    pub fn new() -> Self {
+        Self {
+            head: RwLock::new(ptr::null_mut()),
+        }
+    }
+
+    /// An iterator visiting all elements, with shared locks.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(Some(read(&self.head)))
+    }
+}
+
+impl<T: Ord> RwListSet<T> {
+    /// Returns the exclusively locked link to the position of `key`, and whether the node there
+    /// has the key.
+    fn find_mut(&self, key: &T) -> (RwLockWriteGuard<'_, *mut Node<T>>, bool) {
+        // Search with shared locks. `prev` is the lock of the link to the node of `link`, which
+        // keeps that node from being removed.
+        let mut prev = None;
+        let mut link = &self.head;
+        let mut guard = read(link);
+        while let Some(node) = unsafe { (*guard).as_ref() } {
+            if node.data.cmp(key) != cmp::Ordering::Less {
+                break;
+            }
+            let next = read(&node.next);
+            prev = Some(mem::replace(&mut guard, next));
+            link = &node.next;
+        }
+
+        // There is no upgrade, so the link may change in between. Follow it with exclusive locks.
+        drop(guard);
+        let mut guard = write(link);
+        drop(prev);
+        while let Some(node) = unsafe { (*guard).as_ref() } {
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => guard = write(&node.next),
+                cmp::Ordering::Equal => return (guard, true),
+                cmp::Ordering::Greater => break,
+            }
+        }
+        (guard, false)
+    }
+
+    /// Returns `true` if the set contains the key.
+    pub fn contains(&self, key: &T) -> bool {
+        let mut guard = read(&self.head);
+        while let Some(node) = unsafe { (*guard).as_ref() } {
+            match node.data.cmp(key) {
+                cmp::Ordering::Less => guard = read(&node.next),
+                cmp::Ordering::Equal => return true,
+                cmp::Ordering::Greater => break,
+            }
+        }
+        false
+    }
+
+    /// Insert a key to the set. If the set already has the key, return the provided key in `Err`.
+    pub fn insert(&self, key: T) -> Result<(), T> {
+        let (mut guard, found) = self.find_mut(&key);
+        if found {
+            return Err(key);
+        }
+        *guard = Node::new(key, *guard);
+        Ok(())
+    }
+
+    /// Remove the key from the set and return it.
+    pub fn remove(&self, key: &T) -> Result<T, ()> {
+        let (mut guard, found) = self.find_mut(key);
+        if !found {
+            return Err(());
+        }
+        let node = unsafe { Box::from_raw(*guard) };
+        // waits for the readers of the next node
+        let next = write(&node.next);
+        *guard = *next;
+        drop(next);
+        Ok(node.data)
+    }
+}
+
+/// An iterator over the elements of a [`RwListSet`].
+#[derive(Debug)]
+pub struct Iter<'l, T>(Option<RwLockReadGuard<'l, *mut Node<T>>>);
+
+impl<'l, T> Iterator for Iter<'l, T> {
+    type Item = &'l T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let guard = self.0.as_ref()?;
+        let node = match unsafe { guard.as_ref() } {
+            Some(node) => node,
+            None => {
+                // release the lock
+                self.0 = None;
+                return None;
+            }
+        };
+        self.0 = Some(read(&node.next));
+        Some(&node.data)
+    }
+}
+
+impl<T> Drop for RwListSet<T> {
+    fn drop(&mut self) {
+        let mut curr = *self.head.get_mut().unwrap_or_else(PoisonError::into_inner);
+        while !curr.is_null() {
+            let mut node = unsafe { Box::from_raw(curr) };
+            curr = *node.next.get_mut().unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl<T> Default for RwListSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,229 @@
+//! Sequence lock for small `Copy` data.
+//!
+//! ```
+//! use cs431_homework::SeqLock;
+//!
+//! let stats = SeqLock::new([0u64, 0]);
+//! stats.write([1, 10]);
+//! assert_eq!(stats.update(|[count, total]| [count + 1, total + 20]), [1, 10]);
+//! assert_eq!(stats.read(), [2, 30]);
+//! ```
+//!
+//! With the `check-loom` feature, the version and the words are loom's atomics, so that the
+//! retries of the readers are model checked.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, AtomicUsize, Ordering};
+#[cfg(not(feature = "check-loom"))]
+use crossbeam_utils::Backoff;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, AtomicUsize, Ordering};
+
+const WORD: usize = mem::size_of::<usize>();
+
+/// Waits for a write to finish.
+#[cfg(not(feature = "check-loom"))]
+struct Wait(Backoff);
+
+#[cfg(feature = "check-loom")]
+struct Wait;
+
+impl Wait {
+    fn new() -> Self {
+        #[cfg(not(feature = "check-loom"))]
+        return Self(Backoff::new());
+        #[cfg(feature = "check-loom")]
+        return Self;
+    }
+
+    fn snooze(&self) {
+        #[cfg(not(feature = "check-loom"))]
+        self.0.snooze();
+        #[cfg(feature = "check-loom")]
+        loom::hint::spin_loop();
+    }
+}
+
+/// Values with no uninitialized bytes, i.e. no padding, which a [`SeqLock`] copies as integers.
+///
+/// A type with padding is rejected:
+///
+/// ```compile_fail
+/// // the bytes after the `u8` are padding
+/// let lock = cs431_homework::SeqLock::new((1u8, 2u64));
+/// ```
+///
+/// # Safety
+///
+/// Every byte of a value of the type must be initialized. A `#[repr(C)]` struct of such fields,
+/// with the padding made explicit as fields, qualifies.
+pub unsafe trait NoUninit: Copy {}
+
+macro_rules! impl_no_uninit {
+    ($($t:ty)*) => {
+        $(unsafe impl NoUninit for $t {})*
+    };
+}
+
+impl_no_uninit!(() bool char u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64);
+
+unsafe impl<T: NoUninit, const N: usize> NoUninit for [T; N] {}
+
+/// Cell of a `Copy` value read without writing to shared memory, for data read much more often
+/// than written, e.g. hot statistics counters.
+///
+/// A write increments the version before and after it, so that a reader copies the value and
+/// retries if the version was odd, or changed meanwhile. Readers never block the writers, and
+/// the writers take turns. The value is copied word by word with relaxed atomics, as readers may
+/// copy it while it's written, and the copy is only taken as a `T` once it's validated. So a
+/// torn value is never seen, and there's no data race, unlike with plain reads of the value.
+///
+/// Unlike [`cs431::lock::seqlock::SeqLock`], it's safe to use, but only holds [`NoUninit`] values,
+/// which are copied on each read. Their bytes are copied as integers, so they may not have
+/// padding.
+pub struct SeqLock<T> {
+    /// Odd while a write is in progress.
+    version: AtomicUsize,
+    /// The bytes of the value.
+    words: Box<[AtomicUsize]>,
+    _marker: PhantomData<T>,
+}
+
+unsafe impl<T: NoUninit + Send> Send for SeqLock<T> {}
+unsafe impl<T: NoUninit + Send> Sync for SeqLock<T> {}
+
+/// Releases the write lock, even if the update panics.
+struct Unlock<'s, T> {
+    lock: &'s SeqLock<T>,
+    version: usize,
+}
+
+impl<T> Drop for Unlock<'_, T> {
+    fn drop(&mut self) {
+        self.lock
+            .version
+            .store(self.version.wrapping_add(2), Ordering::Release);
+    }
+}
+
+impl<T: NoUninit> SeqLock<T> {
+    /// Creates a cell holding `value`.
+    pub fn new(value: T) -> Self {
+        let words = (0..(mem::size_of::<T>() + WORD - 1) / WORD)
+            .map(|_| AtomicUsize::new(0))
+            .collect();
+        let lock = Self {
+            version: AtomicUsize::new(0),
+            words,
+            _marker: PhantomData,
+        };
+        lock.store(value);
+        lock
+    }
+
+    /// Copies the value into the words.
+    fn store(&self, value: T) {
+        let bytes = &value as *const T as *const u8;
+        for (i, word) in self.words.iter().enumerate() {
+            let mut buf = [0; WORD];
+            let len = WORD.min(mem::size_of::<T>() - i * WORD);
+            unsafe { ptr::copy_nonoverlapping(bytes.add(i * WORD), buf.as_mut_ptr(), len) };
+            word.store(usize::from_ne_bytes(buf), Ordering::Relaxed);
+        }
+    }
+
+    /// Copies the words, which may be torn by a write.
+    fn load(&self) -> MaybeUninit<T> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = value.as_mut_ptr() as *mut u8;
+        for (i, word) in self.words.iter().enumerate() {
+            let buf = word.load(Ordering::Relaxed).to_ne_bytes();
+            let len = WORD.min(mem::size_of::<T>() - i * WORD);
+            unsafe { ptr::copy_nonoverlapping(buf.as_ptr(), bytes.add(i * WORD), len) };
+        }
+        value
+    }
+
+    /// Returns a copy of the value, retrying while it's written.
+    pub fn read(&self) -> T {
+        let wait = Wait::new();
+        loop {
+            let version = self.version.load(Ordering::Acquire);
+            if version & 1 == 0 {
+                let value = self.load();
+                // the copy happens before the version is read again
+                fence(Ordering::Acquire);
+                if self.version.load(Ordering::Relaxed) == version {
+                    return unsafe { value.assume_init() };
+                }
+            }
+            wait.snooze();
+        }
+    }
+
+    /// Takes the write lock, making the version odd.
+    fn lock(&self) -> Unlock<'_, T> {
+        let wait = Wait::new();
+        loop {
+            let version = self.version.load(Ordering::Relaxed);
+            if version & 1 == 0
+                && self
+                    .version
+                    .compare_exchange(
+                        version,
+                        version.wrapping_add(1),
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                    )
+                    .is_ok()
+            {
+                // the version is odd before the value is written
+                fence(Ordering::Release);
+                return Unlock {
+                    lock: self,
+                    version,
+                };
+            }
+            wait.snooze();
+        }
+    }
+
+    /// Replaces the value.
+    pub fn write(&self, value: T) {
+        let _unlock = self.lock();
+        self.store(value);
+    }
+
+    /// Replaces the value with `f` of it, and returns the previous one. The other writers wait
+    /// for `f`, so it should be short.
+    pub fn update<F: FnOnce(T) -> T>(&self, f: F) -> T {
+        let _unlock = self.lock();
+        // no other write meanwhile
+        let value = unsafe { self.load().assume_init() };
+        self.store(f(value));
+        value
+    }
+
+    /// Returns the value.
+    pub fn into_inner(self) -> T {
+        self.read()
+    }
+}
+
+impl<T: NoUninit + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: NoUninit + fmt::Debug> fmt::Debug for SeqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeqLock")
+            .field("value", &self.read())
+            .finish()
+    }
+}
@@ -0,0 +1,224 @@
+//! Timer futures driven by a hashed timer wheel.
+//!
+//! The wheel is a ring of `SLOTS` slots, each covering one `TICK`. A timer with deadline `d` is put
+//! in the slot of the tick `d` falls into, and is fired when the wheel's hand passes that slot for
+//! the last time before `d`. The hand is moved by a single background thread that is spawned on the
+//! first use and sleeps while there are no timers.
+//!
+//! The futures only need a `Waker`, so they can be polled by any executor.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Resolution of the timers.
+const TICK: Duration = Duration::from_millis(1);
+/// Number of slots in the wheel.
+const SLOTS: usize = 512;
+
+/// State shared by a timer future and its entry in the wheel.
+#[derive(Debug, Default)]
+struct TimerState {
+    fired: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    deadline: Instant,
+    state: Arc<TimerState>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    slots: Vec<Vec<Entry>>,
+    /// Number of timers in `slots`.
+    len: usize,
+    /// The last tick whose slot was processed.
+    hand: u64,
+}
+
+#[derive(Debug)]
+struct Wheel {
+    start: Instant,
+    inner: Mutex<Inner>,
+    cond: Condvar,
+}
+
+static WHEEL: Lazy<&'static Wheel> = Lazy::new(|| {
+    let wheel: &'static Wheel = Box::leak(Box::new(Wheel {
+        start: Instant::now(),
+        inner: Mutex::new(Inner {
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+            len: 0,
+            hand: 0,
+        }),
+        cond: Condvar::new(),
+    }));
+    let _ = thread::Builder::new()
+        .name("timer-wheel".into())
+        .spawn(move || wheel.run())
+        .expect("failed to spawn the timer thread");
+    wheel
+});
+
+impl Wheel {
+    /// The tick that `instant` falls into, rounded up.
+    fn tick_of(&self, instant: Instant) -> u64 {
+        let elapsed = instant.saturating_duration_since(self.start).as_nanos();
+        ((elapsed + TICK.as_nanos() - 1) / TICK.as_nanos()) as u64
+    }
+
+    fn register(&self, deadline: Instant, state: Arc<TimerState>) {
+        let mut inner = self.inner.lock().unwrap();
+        let tick = self.tick_of(deadline).max(inner.hand + 1);
+        inner.slots[tick as usize % SLOTS].push(Entry { deadline, state });
+        inner.len += 1;
+        drop(inner);
+        self.cond.notify_one();
+    }
+
+    /// Moves the hand forever, firing the expired timers.
+    fn run(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        loop {
+            if inner.len == 0 {
+                inner = self.cond.wait(inner).unwrap();
+                continue;
+            }
+
+            let now = Instant::now();
+            let next = self.start + Duration::from_nanos(TICK.as_nanos() as u64 * (inner.hand + 1));
+            if now < next {
+                inner = self.cond.wait_timeout(inner, next - now).unwrap().0;
+                continue;
+            }
+
+            // process the passed slots, but each one at most once. A slot is passed once its tick
+            // is over, so that its timers are all expired, and none is skipped for a revolution.
+            let target =
+                (now.saturating_duration_since(self.start).as_nanos() / TICK.as_nanos()) as u64;
+            let from = inner.hand + 1;
+            let from = from.max(target.saturating_sub(SLOTS as u64 - 1));
+            let mut wakers = Vec::new();
+            for tick in from..=target {
+                let slot = &mut inner.slots[tick as usize % SLOTS];
+                let before = slot.len();
+                slot.retain(|entry| {
+                    // the future was dropped
+                    if Arc::strong_count(&entry.state) == 1 {
+                        return false;
+                    }
+                    if entry.deadline > now {
+                        return true;
+                    }
+                    entry.state.fired.store(true, Ordering::Release);
+                    wakers.extend(entry.state.waker.lock().unwrap().take());
+                    false
+                });
+                let removed = before - slot.len();
+                inner.len -= removed;
+            }
+            inner.hand = target;
+
+            drop(inner);
+            for waker in wakers {
+                waker.wake();
+            }
+            inner = self.inner.lock().unwrap();
+        }
+    }
+}
+
+/// Future returned by [`sleep`].
+#[derive(Debug)]
+pub struct Sleep {
+    deadline: Instant,
+    state: Option<Arc<TimerState>>,
+}
+
+/// Returns a future that completes after `dur`.
+pub fn sleep(dur: Duration) -> Sleep {
+    Sleep {
+        deadline: Instant::now() + dur,
+        state: None,
+    }
+}
+
+impl Sleep {
+    /// The instant at which the future completes.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        let deadline = self.deadline;
+        let state = self.state.get_or_insert_with(|| {
+            let state = Arc::new(TimerState::default());
+            WHEEL.register(deadline, state.clone());
+            state
+        });
+        *state.waker.lock().unwrap() = Some(cx.waker().clone());
+        if state.fired.load(Ordering::Acquire) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Error returned by [`Timeout`] when the deadline has elapsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Future returned by [`timeout`].
+#[derive(Debug)]
+pub struct Timeout<F> {
+    future: Pin<Box<F>>,
+    sleep: Sleep,
+}
+
+/// Runs `future` until it completes or `dur` elapses, whichever comes first.
+pub fn timeout<F: Future>(future: F, dur: Duration) -> Timeout<F> {
+    Timeout {
+        future: Box::pin(future),
+        sleep: sleep(dur),
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(Elapsed)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
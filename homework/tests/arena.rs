@@ -0,0 +1,107 @@
+use cs431_homework::arena::{ArenaMap, RequestArena};
+use std::cell::Cell;
+
+#[test]
+fn alloc() {
+    let arena = RequestArena::with_capacity(64);
+    let a = arena.alloc(1u8);
+    let b = arena.alloc(2u64);
+    let c = arena.alloc_slice_copy(&[3u32, 4, 5]);
+    *a += 10;
+    *b += 10;
+    c[0] += 10;
+    assert_eq!((*a, *b), (11, 12));
+    assert_eq!(c, &[13, 4, 5]);
+    assert_eq!(b as *const u64 as usize % 8, 0);
+    assert_eq!(arena.alloc_str("hello"), "hello");
+
+    // more than a chunk
+    let big = arena.buffer(1000);
+    assert!(big.iter().all(|&b| b == 0));
+    big[999] = 1;
+    assert!(arena.capacity() >= 64 + 1000);
+    assert!(arena.allocated() >= 1 + 8 + 12 + 5 + 1000);
+}
+
+#[test]
+fn alloc_fmt() {
+    let arena = RequestArena::new();
+    let s = arena.alloc_fmt(format_args!("{}-{:?}-{:04}", "a", "b", 7));
+    assert_eq!(s, "a-\"b\"-0007");
+    assert_eq!(arena.alloc_fmt(format_args!("")), "");
+}
+
+#[test]
+fn reset() {
+    let mut arena = RequestArena::with_capacity(16);
+    for _ in 0..4 {
+        for i in 0..100 {
+            assert_eq!(*arena.alloc(i), i);
+        }
+        arena.reset();
+        assert_eq!(arena.allocated(), 0);
+    }
+    // the chunks were merged, so the same workload fits in the first chunk now
+    let capacity = arena.capacity();
+    for i in 0..100 {
+        let _ = arena.alloc(i);
+    }
+    assert_eq!(arena.capacity(), capacity);
+}
+
+#[test]
+fn no_drop() {
+    struct Bomb<'a>(&'a Cell<usize>);
+    impl Drop for Bomb<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    let drops = Cell::new(0);
+    let mut arena = RequestArena::new();
+    let _ = arena.alloc(Bomb(&drops));
+    arena.reset();
+    drop(arena);
+    assert_eq!(drops.get(), 0);
+}
+
+#[test]
+fn map() {
+    let arena = RequestArena::new();
+    let mut map = ArenaMap::new_in(&arena);
+    assert!(map.is_empty());
+    assert_eq!(map.get(&0), None);
+    for i in 0..1000 {
+        assert_eq!(map.insert(i, i * 2), None);
+    }
+    assert_eq!(map.len(), 1000);
+    assert_eq!(map.insert(7, 0), Some(14));
+    *map.get_mut(&8).unwrap() = 1;
+    for i in 0..1000 {
+        let expected = match i {
+            7 => 0,
+            8 => 1,
+            _ => i * 2,
+        };
+        assert_eq!(map.get(&i), Some(&expected));
+    }
+    assert!(!map.contains_key(&1000));
+    let mut keys = map.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+    keys.sort_unstable();
+    assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn map_of_borrowed_headers() {
+    let arena = RequestArena::new();
+    let request = arena.alloc_str("Host: localhost\r\nAccept: */*\r\nHost: again");
+    let mut headers = ArenaMap::new_in(&arena);
+    for line in request.split("\r\n") {
+        let (name, value) = line.split_once(": ").unwrap();
+        let _ = headers.insert(name, value);
+    }
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers.get(&"Host"), Some(&"again"));
+    assert_eq!(headers.get(&"Accept"), Some(&"*/*"));
+}
@@ -0,0 +1,154 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cs431_homework::ArrayQueue;
+use std::thread::scope;
+
+#[test]
+fn smoke() {
+    let queue = ArrayQueue::new(3);
+    assert_eq!(queue.capacity(), 3);
+    assert!(queue.is_empty());
+    assert_eq!(queue.try_pop(), None);
+
+    for i in 0..3 {
+        assert_eq!(queue.try_push(i), Ok(()));
+    }
+    assert!(queue.is_full());
+    assert_eq!(queue.try_push(3), Err(3));
+    assert_eq!(queue.try_pop(), Some(0));
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.try_push(3), Ok(()));
+    for i in 1..4 {
+        assert_eq!(queue.try_pop(), Some(i));
+    }
+    assert_eq!(queue.try_pop(), None);
+}
+
+#[test]
+fn force_push() {
+    let queue = ArrayQueue::new(2);
+    assert_eq!(queue.force_push(0), None);
+    assert_eq!(queue.force_push(1), None);
+    assert_eq!(queue.force_push(2), Some(0));
+    assert_eq!(queue.force_push(3), Some(1));
+    assert_eq!(queue.len(), 2);
+    assert_eq!(queue.try_pop(), Some(2));
+    assert_eq!(queue.force_push(4), None);
+    assert_eq!(queue.try_pop(), Some(3));
+    assert_eq!(queue.try_pop(), Some(4));
+    assert!(queue.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn zero_capacity() {
+    let _ = ArrayQueue::<usize>::new(0);
+}
+
+#[test]
+fn drop_values() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Elem;
+    impl Drop for Elem {
+        fn drop(&mut self) {
+            let _ = DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let queue = ArrayQueue::new(4);
+    for _ in 0..6 {
+        drop(queue.force_push(Elem));
+    }
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+    drop(queue.try_pop());
+    drop(queue);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 6);
+}
+
+#[test]
+fn mpmc() {
+    const THREADS: usize = 4;
+    const COUNT: usize = 100_000;
+
+    let queue = ArrayQueue::new(16);
+    let popped = scope(|s| {
+        for t in 0..THREADS {
+            let queue = &queue;
+            let _ = s.spawn(move || {
+                for i in 0..COUNT {
+                    let mut value = t * COUNT + i;
+                    while let Err(v) = queue.try_push(value) {
+                        value = v;
+                        std::thread::yield_now();
+                    }
+                }
+            });
+        }
+        let mut consumers = Vec::new();
+        for _ in 0..THREADS {
+            consumers.push(s.spawn(|| {
+                // the values of each producer are popped in order
+                let mut last = [None; THREADS];
+                let mut popped = Vec::new();
+                while popped.len() < COUNT {
+                    match queue.try_pop() {
+                        Some(v) => {
+                            let producer = v / COUNT;
+                            assert!(last[producer] < Some(v));
+                            last[producer] = Some(v);
+                            popped.push(v);
+                        }
+                        None => std::thread::yield_now(),
+                    }
+                }
+                popped
+            }));
+        }
+        let mut popped = Vec::new();
+        for consumer in consumers {
+            popped.extend(consumer.join().unwrap());
+        }
+        popped
+    });
+
+    let mut popped = popped;
+    popped.sort_unstable();
+    assert_eq!(popped, (0..THREADS * COUNT).collect::<Vec<_>>());
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn force_push_concurrent() {
+    const THREADS: usize = 4;
+    const COUNT: usize = 100_000;
+
+    // every value is either popped, displaced, or left in the queue, exactly once
+    let queue = ArrayQueue::new(8);
+    let taken = scope(|s| {
+        let mut handles = Vec::new();
+        for t in 0..THREADS {
+            let queue = &queue;
+            handles.push(s.spawn(move || {
+                let mut taken = Vec::new();
+                for i in 0..COUNT {
+                    taken.extend(queue.force_push(t * COUNT + i));
+                    if i % 2 == 0 {
+                        taken.extend(queue.try_pop());
+                    }
+                }
+                taken
+            }));
+        }
+        let mut taken = Vec::new();
+        for handle in handles {
+            taken.extend(handle.join().unwrap());
+        }
+        taken
+    });
+
+    let mut taken = taken;
+    while let Some(v) = queue.try_pop() {
+        taken.push(v);
+    }
+    taken.sort_unstable();
+    assert_eq!(taken, (0..THREADS * COUNT).collect::<Vec<_>>());
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "async")]
+
+use core::future::Future;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use cs431_homework::hello_server::AsyncCache;
+use cs431_homework::timer::sleep;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::task::{Wake, Waker};
+use std::thread::{self, scope, Thread};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+/// Waker that counts its wakes.
+#[derive(Default)]
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        let _ = self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn poll_once<F: Future>(future: Pin<&mut F>, waker: &Waker) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(waker))
+}
+
+#[test]
+fn async_cache_sequential() {
+    let cache = AsyncCache::new();
+    assert_eq!(cache.peek(&1), None);
+    let v = block_on(cache.get_or_insert_with(1, |k| async move { k * 10 }));
+    assert_eq!(*v, 10);
+    let again = block_on(cache.get_or_insert_with(1, |_| async { panic!() }));
+    assert!(Arc::ptr_eq(&v, &again));
+    assert_eq!(cache.peek(&1).map(|v| *v), Some(10));
+}
+
+#[test]
+fn async_cache_try_insert() {
+    let cache = AsyncCache::new();
+    let result = block_on(cache.get_or_try_insert_with(1, |_| async { Err("unreachable") }));
+    assert_eq!(result, Err("unreachable"));
+    // the failure is not cached
+    let result = block_on(cache.get_or_try_insert_with(1, |k| async move { Ok::<_, ()>(k) }));
+    assert_eq!(result.map(|v| *v), Ok(1));
+}
+
+#[test]
+fn async_cache_no_duplicate_concurrent() {
+    const THREADS: usize = 8;
+    let cache = AsyncCache::new();
+    let barrier = Barrier::new(THREADS);
+    let num_compute = AtomicUsize::new(0);
+    scope(|s| {
+        for _ in 0..THREADS {
+            let (cache, barrier, num_compute) = (&cache, &barrier, &num_compute);
+            let _ = s.spawn(move || {
+                barrier.wait();
+                for key in 0..16 {
+                    let v = block_on(cache.get_or_insert_with(key, move |k| async move {
+                        let _ = num_compute.fetch_add(1, Ordering::Relaxed);
+                        sleep(Duration::from_millis(5)).await;
+                        k
+                    }));
+                    assert_eq!(*v, key);
+                }
+            });
+        }
+    });
+    assert_eq!(num_compute.load(Ordering::Relaxed), 16);
+}
+
+#[test]
+fn async_cache_cancel() {
+    let cache = AsyncCache::new();
+    let counter = Arc::new(CountingWaker::default());
+    let waker = Waker::from(Arc::clone(&counter));
+
+    let mut computing = Box::pin(cache.get_or_insert_with(1, |_| async {
+        sleep(Duration::from_secs(60)).await;
+        1
+    }));
+    assert!(poll_once(computing.as_mut(), &waker).is_pending());
+    let mut waiting = Box::pin(cache.get_or_insert_with(1, |_| async { 2 }));
+    assert!(poll_once(waiting.as_mut(), &waker).is_pending());
+    assert!(poll_once(waiting.as_mut(), &waker).is_pending());
+    assert_eq!(cache.peek(&1), None);
+
+    // dropping the computation wakes the waiter, which computes the key itself
+    drop(computing);
+    assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+    match poll_once(waiting.as_mut(), &waker) {
+        Poll::Ready(v) => assert_eq!(*v, 2),
+        Poll::Pending => panic!("the waiter should compute the key"),
+    }
+    assert_eq!(cache.peek(&1).map(|v| *v), Some(2));
+}
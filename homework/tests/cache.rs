@@ -1,7 +1,11 @@
-use crossbeam_channel::bounded;
-use cs431_homework::hello_server::Cache;
+use crossbeam_channel::{bounded, unbounded};
+use cs431_homework::hello_server::{
+    Cache, CacheBuilder, CacheStats, EvictionPolicy, Fifo, Lfu, Lru, RemovalCause,
+};
+use std::collections::HashSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Barrier;
+use std::sync::{Barrier, Mutex};
 use std::thread::scope;
 use std::time::Duration;
 
@@ -14,9 +18,781 @@ fn cache_no_duplicate_sequential() {
     cache.get_or_insert_with(1, |_| 1);
     cache.get_or_insert_with(2, |_| 2);
     cache.get_or_insert_with(3, |_| 3);
-    assert_eq!(cache.get_or_insert_with(1, |_| panic!()), 1);
-    assert_eq!(cache.get_or_insert_with(2, |_| panic!()), 2);
-    assert_eq!(cache.get_or_insert_with(3, |_| panic!()), 3);
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 1);
+    assert_eq!(*cache.get_or_insert_with(2, |_| panic!()), 2);
+    assert_eq!(*cache.get_or_insert_with(3, |_| panic!()), 3);
+}
+
+#[test]
+fn cache_shared_value() {
+    /// A value that can't be cloned.
+    #[derive(Debug)]
+    struct Body(Vec<u8>);
+
+    let cache = Cache::default();
+    let body = cache.get_or_insert_with(1, |_| Body(vec![0; 1 << 20]));
+    let again = cache.get_or_insert_with(1, |_| panic!());
+    assert!(std::sync::Arc::ptr_eq(&body, &again));
+    let copy = cache.clone();
+    assert!(std::sync::Arc::ptr_eq(&body, &copy.get(&1).unwrap()));
+    assert_eq!(cache.stats().entries, 1);
+    assert_eq!(body.0.len(), 1 << 20);
+}
+
+#[test]
+fn cache_clone() {
+    let cache = Cache::default();
+    cache.get_or_insert_with(1, |_| 1);
+    cache.get_or_insert_with(2, |_| 2);
+    let copy = cache.clone();
+    cache.get_or_insert_with(3, |_| 3);
+    assert_eq!(*copy.get_or_insert_with(1, |_| panic!()), 1);
+    assert_eq!(*copy.get_or_insert_with(2, |_| panic!()), 2);
+    assert_eq!(*copy.get_or_insert_with(3, |_| 4), 4);
+}
+
+#[test]
+fn cache_get_peek() {
+    let cache = Cache::default();
+    assert_eq!(cache.get(&1).map(|v| *v), None);
+    assert_eq!(cache.peek(&1).map(|v| *v), None);
+    cache.get_or_insert_with(1, |k| k * 10);
+    assert_eq!(cache.get(&1).map(|v| *v), Some(10));
+    assert_eq!(cache.peek(&1).map(|v| *v), Some(10));
+
+    cache.get_or_insert_with_ttl(2, Duration::ZERO, |k| k * 10);
+    assert_eq!(cache.peek(&2).map(|v| *v), None);
+    assert_eq!(cache.get(&2).map(|v| *v), None);
+    // neither computes
+    assert_eq!(cache.get(&3).map(|v| *v), None);
+    assert_eq!(*cache.get_or_insert_with(3, |k| k * 10), 30);
+}
+
+#[test]
+fn cache_get_computing() {
+    let cache = Cache::default();
+    let (started_sender, started_receiver) = bounded(0);
+    let (finish_sender, finish_receiver) = bounded(0);
+    let (cache, started_sender, finish_receiver) = (&cache, &started_sender, &finish_receiver);
+    scope(|s| {
+        for v in [Err(()), Ok(10)] {
+            let computing = s.spawn(move || {
+                cache.get_or_try_insert_with(1, |_| {
+                    started_sender.send(()).unwrap();
+                    finish_receiver.recv().unwrap();
+                    v
+                })
+            });
+            started_receiver.recv().unwrap();
+            let getting = s.spawn(|| cache.get(&1).map(|v| *v));
+            // `peek` doesn't wait
+            assert_eq!(cache.peek(&1).map(|v| *v), None);
+            std::thread::sleep(Duration::from_millis(100));
+            finish_sender.send(()).unwrap();
+            assert_eq!(computing.join().unwrap().map(|v| *v), v);
+            // `get` returns the result of the computation it waited for, if any
+            assert_eq!(getting.join().unwrap(), v.ok());
+        }
+    });
+    assert_eq!(cache.peek(&1).map(|v| *v), Some(10));
+}
+
+#[test]
+fn cache_stats() {
+    let cache = Cache::default();
+    cache.get_or_insert_with(1, |k| k);
+    cache.get_or_insert_with(1, |_| panic!());
+    assert_eq!(cache.get(&1).map(|v| *v), Some(1));
+    assert_eq!(cache.peek(&2).map(|v| *v), None);
+    assert_eq!(cache.get_or_try_insert_with(2, |_| Err(())), Err(()));
+    cache.get_or_insert_with_ttl(3, Duration::ZERO, |k| k);
+    assert_eq!(
+        cache.stats(),
+        CacheStats {
+            hits: 2,
+            misses: 4,
+            computations: 3,
+            coalesced: 0,
+            entries: 1,
+        }
+    );
+    let copy = cache.clone();
+    assert_eq!(copy.get(&1).map(|v| *v), Some(1));
+    assert_eq!((copy.stats().hits, copy.stats().entries), (1, 1));
+    assert_eq!(cache.stats().hits, 2);
+
+    // the waiters of a computation are coalesced
+    let cache = Cache::default();
+    let barrier = Barrier::new(NUM_THREADS);
+    scope(|s| {
+        s.spawn(|| {
+            cache.get_or_insert_with(0, |k| {
+                barrier.wait();
+                std::thread::sleep(Duration::from_millis(100));
+                k
+            })
+        });
+        for _ in 1..NUM_THREADS {
+            s.spawn(|| {
+                barrier.wait();
+                cache.get_or_insert_with(0, |_| panic!())
+            });
+        }
+    });
+    let stats = cache.stats();
+    assert_eq!((stats.misses, stats.computations), (1 + stats.coalesced, 1));
+    assert_eq!(stats.coalesced + stats.hits, NUM_THREADS as u64 - 1);
+}
+
+#[test]
+fn cache_eviction_listener() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .max_capacity(2)
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    cache.get_or_insert_with(1, |k| k * 10);
+    cache.get_or_insert_with(2, |k| k * 10);
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 10);
+    assert!(receiver.try_recv().is_err());
+
+    // the oldest value goes first
+    cache.get_or_insert_with(3, |k| k * 10);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((1, 10, RemovalCause::Capacity))
+    );
+    assert_eq!(*cache.get_or_insert_with(2, |_| panic!()), 20);
+    assert_eq!(*cache.get_or_insert_with(1, |k| k * 100), 100);
+
+    // dropping the cache waits for the listener
+    drop(cache);
+    assert_eq!(
+        receiver.iter().collect::<Vec<_>>(),
+        [(2, 20, RemovalCause::Capacity)]
+    );
+}
+
+#[test]
+fn cache_weigher() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .max_capacity(10)
+        .weigher(|_, v: &String| v.len())
+        .eviction_listener(move |k, v: std::sync::Arc<String>, cause| {
+            sender.send((k, v.len(), cause)).unwrap()
+        })
+        .build();
+    cache.get_or_insert_with(1, |_| "a".repeat(4));
+    cache.get_or_insert_with(2, |_| "b".repeat(4));
+
+    // the oldest values go until the new one fits
+    cache.get_or_insert_with(3, |_| "c".repeat(8));
+    assert!(cache.peek(&1).is_none());
+    assert!(cache.peek(&2).is_none());
+    assert!(cache.peek(&3).is_some());
+
+    // a value heavier than the capacity is returned but not kept
+    assert_eq!(cache.get_or_insert_with(4, |_| "d".repeat(11)).len(), 11);
+    assert!(cache.peek(&4).is_none());
+    assert!(cache.peek(&3).is_some());
+
+    // invalidating frees the weight
+    assert!(cache.invalidate(&3).is_some());
+    cache.get_or_insert_with(5, |_| "e".repeat(10));
+    assert!(cache.peek(&5).is_some());
+
+    drop(cache);
+    assert_eq!(
+        receiver.iter().collect::<Vec<_>>(),
+        [
+            (1, 4, RemovalCause::Capacity),
+            (2, 4, RemovalCause::Capacity),
+            (4, 11, RemovalCause::Capacity),
+            (3, 8, RemovalCause::Explicit),
+        ]
+    );
+}
+
+/// Looks up 1, 2, 1, 3 with a capacity of 2, and returns the keys left.
+fn evict_with<P: EvictionPolicy<usize> + Clone>(policy: P) -> Vec<usize> {
+    let cache = CacheBuilder::with_eviction_policy(policy)
+        .max_capacity(2)
+        .build();
+    for key in [1, 2, 1, 3] {
+        cache.get_or_insert_with(key, |k| k);
+    }
+    (1..=3).filter(|k| cache.peek(k).is_some()).collect()
+}
+
+#[test]
+fn cache_eviction_policy() {
+    assert_eq!(evict_with(Fifo::new()), [2, 3]);
+    assert_eq!(evict_with(Lru::new()), [1, 3]);
+    assert_eq!(evict_with(Lfu::new()), [1, 3]);
+}
+
+#[test]
+fn eviction_policy_lfu() {
+    let mut lfu = Lfu::new();
+    for key in 1..=3 {
+        lfu.on_insert(&key);
+    }
+    lfu.on_hit(&1);
+    lfu.on_hit(&1);
+    lfu.on_hit(&2);
+    lfu.on_hit(&3);
+    lfu.on_hit(&2);
+    // 3 has the fewest hits, then 2 is used later than 1
+    assert_eq!(lfu.pick_victim(), Some(3));
+    lfu.on_remove(&1);
+    assert_eq!(lfu.pick_victim(), Some(2));
+    assert_eq!(lfu.pick_victim(), None);
+
+    // a hit of a key that isn't tracked is ignored
+    lfu.on_hit(&4);
+    assert_eq!(lfu.pick_victim(), None);
+}
+
+#[test]
+fn eviction_policy_lru() {
+    let mut lru = Lru::new();
+    for key in 1..=3 {
+        lru.on_insert(&key);
+    }
+    lru.on_hit(&1);
+    lru.on_remove(&2);
+    assert_eq!(lru.pick_victim(), Some(3));
+    lru.on_insert(&4);
+    assert_eq!(lru.pick_victim(), Some(1));
+    assert_eq!(lru.pick_victim(), Some(4));
+    assert_eq!(lru.pick_victim(), None);
+}
+
+#[test]
+fn cache_dump_load() {
+    let cache = Cache::default();
+    cache.get_or_insert_with("a b".to_string(), |_| "1\n2".to_string());
+    cache.get_or_insert_with_ttl("ttl".to_string(), Duration::from_secs(60), |_| {
+        "3".to_string()
+    });
+    cache.get_or_insert_with_ttl("expired".to_string(), Duration::ZERO, |_| "4".to_string());
+    let mut dump = Vec::new();
+    assert_eq!(cache.dump(&mut dump).unwrap(), 2);
+    let text = String::from_utf8(dump.clone()).unwrap();
+    assert!(text.contains("- 3:a b 3:1\n2\n"));
+    assert!(text.contains(" 3:ttl 1:3\n"));
+
+    // the keys already computed are kept
+    let warm = Cache::<String, String>::default();
+    warm.get_or_insert_with("ttl".to_string(), |_| "kept".to_string());
+    assert_eq!(warm.load(&dump[..]).unwrap(), 1);
+    assert_eq!(
+        warm.peek(&"a b".to_string()).as_deref().map(String::as_str),
+        Some("1\n2")
+    );
+    assert_eq!(
+        warm.peek(&"ttl".to_string()).as_deref().map(String::as_str),
+        Some("kept")
+    );
+    assert_eq!(warm.peek(&"expired".to_string()), None);
+
+    // the values before a malformed one are loaded
+    let numbers = Cache::<usize, usize>::default();
+    let err = numbers.load(&b"- 1:1 2:10\n0 1:2 1:x\n"[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(numbers.peek(&1).map(|v| *v), Some(10));
+    assert_eq!(numbers.peek(&2), None);
+    let err = numbers.load(&b"- 1:3 2:10"[..]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn cache_max_concurrent_computations() {
+    const KEYS: usize = 8;
+    let cache = Cache::builder().max_concurrent_computations(2).build();
+    let running = AtomicUsize::new(0);
+    let max_running = AtomicUsize::new(0);
+    let calls = AtomicUsize::new(0);
+    scope(|s| {
+        for key in 0..KEYS * 2 {
+            let (cache, running, max_running, calls) = (&cache, &running, &max_running, &calls);
+            // two lookups of each key
+            let _ = s.spawn(move || {
+                let v = cache.get_or_insert_with(key % KEYS, |k| {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    k * 10
+                });
+                assert_eq!(*v, key % KEYS * 10);
+            });
+        }
+    });
+    assert!(max_running.into_inner() <= 2);
+    assert_eq!(calls.into_inner(), KEYS);
+}
+
+#[test]
+fn cache_invalidate_tag() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .num_shards(4)
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    for key in 0..8 {
+        let tags: &[&str] = if key % 2 == 0 { &["even"] } else { &["odd"] };
+        cache.get_or_insert_with_tags(key, tags, |k| k * 10);
+    }
+    cache.get_or_insert_with_tags(8, &["even", "big"], |k| k * 10);
+    // the tags only apply to the computation
+    cache.get_or_insert_with_tags(1, &["even"], |_| panic!());
+
+    assert_eq!(cache.invalidate_tag("even"), 5);
+    assert!((0..=8).all(|k| cache.peek(&k).is_some() == (k % 2 == 1)));
+    assert_eq!(cache.invalidate_tag("big"), 0);
+    assert_eq!(cache.invalidate_tag("none"), 0);
+
+    // a key computed again after a removal doesn't keep its old tags
+    assert!(cache.invalidate(&1).is_some());
+    cache.get_or_insert_with(1, |k| k * 100);
+    assert_eq!(cache.invalidate_tag("odd"), 3);
+    assert_eq!(cache.peek(&1).map(|v| *v), Some(100));
+
+    drop(cache);
+    let mut removed = receiver.iter().collect::<Vec<_>>();
+    removed.sort_unstable_by_key(|&(k, _, _)| k);
+    assert!(removed
+        .iter()
+        .all(|&(_, _, cause)| cause == RemovalCause::Explicit));
+    assert_eq!(
+        removed.iter().map(|&(k, _, _)| k).collect::<Vec<_>>(),
+        [0, 1, 2, 3, 4, 5, 6, 7, 8]
+    );
+}
+
+#[test]
+fn cache_invalidate_tag_computing() {
+    let cache = Cache::default();
+    let barrier = Barrier::new(2);
+    scope(|s| {
+        let handle = s.spawn(|| {
+            cache.get_or_insert_with_tags(1, &["user"], |k| {
+                barrier.wait();
+                barrier.wait();
+                k
+            })
+        });
+        barrier.wait();
+        assert_eq!(cache.invalidate_tag("user"), 0);
+        barrier.wait();
+        // the computation in flight is not cached
+        assert_eq!(*handle.join().unwrap(), 1);
+    });
+    assert_eq!(*cache.get_or_insert_with(1, |k| k + 1), 2);
+}
+
+#[test]
+fn cache_ttl() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .max_capacity(2)
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    assert_eq!(
+        *cache.get_or_insert_with_ttl(1, Duration::from_millis(100), |k| k * 10),
+        10
+    );
+    cache.get_or_insert_with(2, |k| k * 10);
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 10);
+    std::thread::sleep(Duration::from_millis(150));
+
+    // the expired value is computed again, without a time-to-live this time
+    assert_eq!(*cache.get_or_insert_with(1, |k| k * 100), 100);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((1, 10, RemovalCause::Expired))
+    );
+    assert_eq!(*cache.get_or_insert_with(2, |_| panic!()), 20);
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 100);
+
+    // the expired key left the eviction order, so 2 is the oldest
+    cache.get_or_insert_with(3, |k| k * 10);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((2, 20, RemovalCause::Capacity))
+    );
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 100);
+}
+
+#[test]
+fn cache_refresh() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    let ttl = Duration::from_millis(50);
+    assert_eq!(*cache.get_or_refresh_with(1, ttl, |k| k * 10), 10);
+    assert_eq!(*cache.get_or_refresh_with(1, ttl, |_| panic!()), 10);
+    std::thread::sleep(ttl);
+
+    // the expired value is served while it's refreshed
+    let (started_sender, started_receiver) = bounded(0);
+    let (finish_sender, finish_receiver) = bounded::<()>(0);
+    assert_eq!(
+        *cache.get_or_refresh_with(1, ttl, move |k| {
+            started_sender.send(()).unwrap();
+            finish_receiver.recv().unwrap();
+            k * 100
+        }),
+        10
+    );
+    started_receiver.recv().unwrap();
+    assert_eq!(*cache.get_or_refresh_with(1, ttl, |_| panic!()), 10);
+    finish_sender.send(()).unwrap();
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((1, 10, RemovalCause::Expired))
+    );
+    assert_eq!(*cache.get_or_refresh_with(1, ttl, |_| panic!()), 100);
+
+    // a panicking refresh keeps the expired value, and a later lookup refreshes it again
+    std::thread::sleep(ttl);
+    let (done_sender, done_receiver) = bounded(0);
+    assert_eq!(
+        *cache.get_or_refresh_with(1, ttl, move |_| {
+            done_sender.send(()).unwrap();
+            panic!("refresh failed")
+        }),
+        100
+    );
+    done_receiver.recv().unwrap();
+    let mut refreshed = false;
+    for _ in 0..100 {
+        match *cache.get_or_refresh_with(1, ttl, |k| k * 1000) {
+            100 => std::thread::sleep(Duration::from_millis(10)),
+            v => {
+                assert_eq!(v, 1000);
+                refreshed = true;
+                break;
+            }
+        }
+    }
+    assert!(refreshed);
+}
+
+/// The refreshes share a pool of threads, and dropping the cache waits for them.
+#[test]
+fn cache_refresh_pool() {
+    let cache = Cache::builder().max_concurrent_computations(2).build();
+    let ttl = Duration::from_millis(10);
+    for key in 0..NUM_KEYS {
+        assert_eq!(*cache.get_or_refresh_with(key, ttl, |k| k), key);
+    }
+    std::thread::sleep(ttl);
+
+    let threads = std::sync::Arc::new(Mutex::new(Vec::new()));
+    for key in 0..NUM_KEYS {
+        let threads = threads.clone();
+        assert_eq!(
+            *cache.get_or_refresh_with(key, ttl, move |k| {
+                threads.lock().unwrap().push(std::thread::current().id());
+                k + 1
+            }),
+            key
+        );
+    }
+    drop(cache);
+    // every refresh ran, on at most 2 threads
+    let threads = threads.lock().unwrap();
+    assert_eq!(threads.len(), NUM_KEYS);
+    assert!(threads.iter().collect::<HashSet<_>>().len() <= 2);
+}
+
+#[test]
+fn cache_sweeper() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .sweep_interval(Duration::from_millis(20))
+        .build();
+    for key in 0..4 {
+        cache.get_or_insert_with_ttl(key, Duration::from_millis(50 * key as u64), |k| k);
+    }
+    cache.get_or_insert_with(4, |k| k);
+
+    // the expired values are removed without being looked up
+    let mut expired = Vec::new();
+    for _ in 0..4 {
+        let (key, value, cause) = receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+        assert_eq!((value, cause), (key, RemovalCause::Expired));
+        expired.push(key);
+    }
+    expired.sort_unstable();
+    assert_eq!(expired, [0, 1, 2, 3]);
+    assert_eq!(*cache.get_or_insert_with(4, |_| panic!()), 4);
+
+    // the clone has its own sweeper
+    let copy = cache.clone();
+    drop(cache);
+    copy.get_or_insert_with_ttl(5, Duration::ZERO, |k| k);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((5, 5, RemovalCause::Expired))
+    );
+    drop(copy);
+    assert!(receiver.iter().next().is_none());
+}
+
+#[test]
+fn cache_invalidate() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .max_capacity(2)
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    cache.get_or_insert_with(1, |k| k * 10);
+    cache.get_or_insert_with(2, |k| k * 10);
+    assert_eq!(cache.invalidate(&1).map(|v| *v), Some(10));
+    assert_eq!(cache.invalidate(&1), None);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((1, 10, RemovalCause::Explicit))
+    );
+    assert_eq!(*cache.get_or_insert_with(1, |k| k * 100), 100);
+
+    // the invalidated key left the eviction order, so 2 is the oldest
+    cache.get_or_insert_with(3, |k| k * 10);
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_secs(3)),
+        Ok((2, 20, RemovalCause::Capacity))
+    );
+
+    cache.invalidate_all();
+    let mut removed = vec![
+        receiver.recv_timeout(Duration::from_secs(3)).unwrap(),
+        receiver.recv_timeout(Duration::from_secs(3)).unwrap(),
+    ];
+    removed.sort_unstable_by_key(|&(k, _, _)| k);
+    assert_eq!(
+        removed,
+        [
+            (1, 100, RemovalCause::Explicit),
+            (3, 30, RemovalCause::Explicit)
+        ]
+    );
+    assert_eq!(*cache.get_or_insert_with(1, |k| k), 1);
+    assert_eq!(*cache.get_or_insert_with(3, |k| k), 3);
+}
+
+#[test]
+fn cache_invalidate_computing() {
+    let cache = Cache::default();
+    let (started_sender, started_receiver) = bounded(0);
+    let (finish_sender, finish_receiver) = bounded(0);
+    scope(|s| {
+        let computing = s.spawn(|| {
+            cache.get_or_insert_with(1, |_| {
+                started_sender.send(()).unwrap();
+                finish_receiver.recv().unwrap();
+                10
+            })
+        });
+        started_receiver.recv().unwrap();
+        let waiting = s.spawn(|| cache.get_or_insert_with(1, |_| 20));
+        std::thread::sleep(Duration::from_millis(100));
+
+        // the waiter computes the key again, and the stale value isn't cached
+        assert_eq!(cache.invalidate(&1), None);
+        assert_eq!(*waiting.join().unwrap(), 20);
+        finish_sender.send(()).unwrap();
+        assert_eq!(*computing.join().unwrap(), 10);
+    });
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 20);
+
+    // same with `invalidate_all` and a failing computation
+    let cache = Cache::default();
+    scope(|s| {
+        let computing = s.spawn(|| {
+            cache.get_or_try_insert_with(1, |_| {
+                started_sender.send(()).unwrap();
+                finish_receiver.recv().unwrap();
+                Err(())
+            })
+        });
+        started_receiver.recv().unwrap();
+        cache.invalidate_all();
+        assert_eq!(*cache.get_or_insert_with(1, |_| 20), 20);
+        finish_sender.send(()).unwrap();
+        assert_eq!(computing.join().unwrap(), Err(()));
+    });
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 20);
+}
+
+#[test]
+fn cache_shards() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .max_capacity(10)
+        .num_shards(4)
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    for key in 0..100 {
+        assert_eq!(*cache.get_or_insert_with(key, |k| k), key);
+    }
+    // each shard keeps a share of the capacity, 10 in total
+    cache.invalidate_all();
+    drop(cache);
+    let (mut evicted, mut invalidated) = (0, Vec::new());
+    for (key, value, cause) in receiver {
+        assert_eq!(key, value);
+        match cause {
+            RemovalCause::Capacity => evicted += 1,
+            RemovalCause::Explicit => invalidated.push(key),
+            cause => panic!("unexpected cause {:?}", cause),
+        }
+    }
+    assert_eq!(evicted, 90);
+    assert_eq!(invalidated.len(), 10);
+}
+
+#[test]
+fn cache_eviction_listener_panic() {
+    let num_calls = std::sync::Arc::new(AtomicUsize::new(0));
+    let listener = {
+        let num_calls = num_calls.clone();
+        move |k: usize, _: std::sync::Arc<usize>, _| {
+            num_calls.fetch_add(1, Ordering::Relaxed);
+            assert!(k % 2 == 1, "listener failed");
+        }
+    };
+    let cache = Cache::builder()
+        .max_capacity(0)
+        .eviction_listener(listener)
+        .build();
+    for key in 0..4 {
+        // a capacity of zero keeps nothing, but still returns the value
+        assert_eq!(*cache.get_or_insert_with(key, |k| k), key);
+    }
+    drop(cache);
+    assert_eq!(num_calls.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn cache_panic_sequential() {
+    let cache = Cache::default();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        cache.get_or_insert_with(1, |_| -> usize { panic!("computation failed") })
+    }));
+    assert!(result.is_err());
+    assert_eq!(*cache.get_or_insert_with(1, |_| 1), 1);
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 1);
+}
+
+#[test]
+fn cache_panic_bounded() {
+    let (sender, receiver) = unbounded();
+    let cache = Cache::builder()
+        .max_capacity(2)
+        .eviction_listener(move |k, v, cause| sender.send((k, *v, cause)).unwrap())
+        .build();
+    cache.get_or_insert_with(1, |k| k);
+    cache.get_or_insert_with(2, |k| k);
+    for key in [1, 3] {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            cache.get_or_insert_with(key + 2, |_| -> usize { panic!("computation failed") })
+        }));
+        assert!(result.is_err());
+    }
+
+    // the failed computations neither take a slot nor evict anything
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 1);
+    assert_eq!(*cache.get_or_insert_with(2, |_| panic!()), 2);
+    drop(cache);
+    assert!(receiver.iter().next().is_none());
+}
+
+#[test]
+fn cache_try_insert() {
+    let cache = Cache::default();
+    assert_eq!(
+        cache.get_or_try_insert_with(1, |_| Err("unreachable")),
+        Err("unreachable")
+    );
+    // the failure is not cached
+    assert_eq!(
+        cache
+            .get_or_try_insert_with(1, |k| Ok::<_, ()>(k * 10))
+            .map(|v| *v),
+        Ok(10)
+    );
+    assert_eq!(
+        cache.get_or_try_insert_with(1, |_| Err(())).map(|v| *v),
+        Ok(10)
+    );
+    assert_eq!(*cache.get_or_insert_with(1, |_| panic!()), 10);
+}
+
+#[test]
+fn cache_try_insert_concurrent() {
+    let cache = Cache::default();
+    let barrier = Barrier::new(NUM_THREADS);
+    let num_compute = AtomicUsize::new(0);
+    scope(|s| {
+        // the first computation fails after the others started waiting for it
+        s.spawn(|| {
+            let result = cache.get_or_try_insert_with(0, |_| {
+                barrier.wait();
+                std::thread::sleep(Duration::from_millis(100));
+                Err("unreachable")
+            });
+            assert_eq!(result, Err("unreachable"));
+        });
+        for _ in 1..NUM_THREADS {
+            s.spawn(|| {
+                barrier.wait();
+                let v = cache.get_or_try_insert_with(0, |_| {
+                    num_compute.fetch_add(1, Ordering::Relaxed);
+                    Ok::<_, &str>(42)
+                });
+                assert_eq!(v.map(|v| *v), Ok(42));
+            });
+        }
+    });
+    assert_eq!(num_compute.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn cache_panic_concurrent() {
+    let cache = Cache::default();
+    let barrier = Barrier::new(NUM_THREADS);
+    let num_compute = AtomicUsize::new(0);
+    scope(|s| {
+        // the first computation panics after the others started waiting for it
+        s.spawn(|| {
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                cache.get_or_insert_with(0, |_| {
+                    barrier.wait();
+                    std::thread::sleep(Duration::from_millis(100));
+                    panic!("computation failed")
+                })
+            }));
+            assert!(result.is_err());
+        });
+        for _ in 1..NUM_THREADS {
+            s.spawn(|| {
+                barrier.wait();
+                let v = cache.get_or_insert_with(0, |_| {
+                    num_compute.fetch_add(1, Ordering::Relaxed);
+                    42
+                });
+                assert_eq!(*v, 42);
+            });
+        }
+    });
+    assert_eq!(num_compute.load(Ordering::Relaxed), 1);
 }
 
 #[test]
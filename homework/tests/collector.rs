@@ -0,0 +1,38 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::{self as epoch, Owned};
+use crossbeam_utils::thread::scope;
+use cs431_homework::collector_flush;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+struct Tracked;
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let _ = DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn flush() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1000;
+
+    let defer = || {
+        for _ in 0..STEPS {
+            let guard = epoch::pin();
+            let garbage = Owned::new(Tracked).into_shared(&guard);
+            unsafe { guard.defer_destroy(garbage) };
+        }
+    };
+    defer();
+    // joins the threads, which flush their garbage when they exit
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|_| defer());
+        }
+    })
+    .unwrap();
+    collector_flush();
+    assert_eq!(DROPS.load(Ordering::Relaxed), (THREADS + 1) * STEPS);
+}
@@ -0,0 +1,167 @@
+mod mock;
+
+#[cfg(not(feature = "check-loom"))]
+mod basic {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use cs431_homework::deque::{Steal, Worker};
+    use std::thread::scope;
+
+    #[test]
+    fn lifo_and_fifo() {
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        assert!(worker.is_empty());
+        assert_eq!(worker.pop(), None);
+        assert_eq!(stealer.steal(), Steal::Empty);
+
+        for i in 0..4 {
+            worker.push(i);
+        }
+        assert!(!stealer.is_empty());
+        assert_eq!(worker.pop(), Some(3));
+        assert_eq!(stealer.steal(), Steal::Success(0));
+        let other = stealer.clone();
+        assert_eq!(other.steal(), Steal::Success(1));
+        assert_eq!(worker.pop(), Some(2));
+        assert_eq!(worker.pop(), None);
+        assert!(stealer.is_empty() && other.is_empty());
+    }
+
+    #[test]
+    fn grow() {
+        const COUNT: usize = 10_000;
+
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        for i in 0..COUNT {
+            worker.push(i);
+        }
+        for i in 0..COUNT / 2 {
+            assert_eq!(stealer.steal(), Steal::Success(i));
+        }
+        for i in (COUNT / 2..COUNT).rev() {
+            assert_eq!(worker.pop(), Some(i));
+        }
+        assert!(worker.is_empty());
+    }
+
+    #[test]
+    fn drop_elements() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        struct Elem;
+        impl Drop for Elem {
+            fn drop(&mut self) {
+                let _ = DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let worker = Worker::new();
+        let stealer = worker.stealer();
+        for _ in 0..100 {
+            worker.push(Elem);
+        }
+        drop(worker.pop());
+        drop(stealer.steal());
+        drop(worker);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+        drop(stealer);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn stress() {
+        const THREADS: usize = 4;
+        const COUNT: usize = 100_000;
+
+        let worker = Worker::new();
+        let remaining = AtomicUsize::new(COUNT);
+        let taken = scope(|s| {
+            let mut handles = Vec::new();
+            for _ in 0..THREADS {
+                let stealer = worker.stealer();
+                let remaining = &remaining;
+                handles.push(s.spawn(move || {
+                    let mut taken = Vec::new();
+                    while remaining.load(Ordering::Relaxed) > 0 {
+                        if let Steal::Success(i) = stealer.steal() {
+                            taken.push(i);
+                            let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+                        }
+                    }
+                    taken
+                }));
+            }
+
+            let mut taken = Vec::new();
+            for i in 0..COUNT {
+                worker.push(i);
+                if i % 3 == 0 {
+                    if let Some(i) = worker.pop() {
+                        taken.push(i);
+                        let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+                    }
+                }
+            }
+            while let Some(i) = worker.pop() {
+                taken.push(i);
+                let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+            }
+            for handle in handles {
+                taken.extend(handle.join().unwrap());
+            }
+            taken
+        });
+
+        let mut taken = taken;
+        taken.sort_unstable();
+        assert_eq!(taken, (0..COUNT).collect::<Vec<_>>());
+    }
+}
+
+mod correctness {
+    use super::mock::model;
+    use super::mock::thread;
+    use cs431_homework::deque::Worker;
+
+    /// The owner and a stealer race for the last element, which exactly one of them gets.
+    #[test]
+    fn pop_steal_last() {
+        model(|| {
+            let worker = Worker::new();
+            let stealer = worker.stealer();
+            worker.push(1);
+
+            let handle = thread::spawn(move || stealer.steal().success());
+            let popped = worker.pop();
+            let stolen = handle.join().unwrap();
+            assert!(popped.is_some() != stolen.is_some());
+        });
+    }
+
+    /// Each element is taken exactly once, by the owner or by the stealer, while the owner pushes.
+    #[test]
+    fn push_pop_steal() {
+        model(|| {
+            let worker = Worker::new();
+            let stealer = worker.stealer();
+            worker.push(0);
+
+            let handle = thread::spawn(move || {
+                let mut stolen = Vec::new();
+                for _ in 0..2 {
+                    stolen.extend(stealer.steal().success());
+                }
+                stolen
+            });
+            worker.push(1);
+            let mut taken = Vec::new();
+            taken.extend(worker.pop());
+            taken.extend(handle.join().unwrap());
+            while let Some(i) = worker.pop() {
+                taken.push(i);
+            }
+            taken.sort_unstable();
+            assert_eq!(taken, [0, 1]);
+        });
+    }
+}
@@ -0,0 +1,121 @@
+use cs431_homework::{FlatCombiner, OrderedListSet};
+use std::collections::BTreeSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::thread::scope;
+use std::time::{Duration, Instant};
+
+#[test]
+fn smoke() {
+    let combiner = FlatCombiner::new(Vec::new());
+    combiner.apply(|v| v.push(1));
+    assert_eq!(combiner.apply(|v| v.len()), 1);
+    assert_eq!(combiner.apply(|v| v.pop()), Some(1));
+    assert_eq!(combiner.into_inner(), []);
+}
+
+#[test]
+fn counter() {
+    const THREADS: usize = 8;
+    const COUNT: usize = 10_000;
+
+    let counter = FlatCombiner::new(0);
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                for _ in 0..COUNT {
+                    counter.apply(|c| *c += 1);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * COUNT);
+}
+
+#[test]
+fn panic_resumed_on_caller() {
+    const THREADS: usize = 4;
+    const COUNT: usize = 1000;
+    /// Each thread panics once per period.
+    const PERIOD: usize = 250;
+
+    let counter = FlatCombiner::new(0);
+    scope(|s| {
+        for t in 0..THREADS {
+            let counter = &counter;
+            let _ = s.spawn(move || {
+                for i in 0..COUNT {
+                    let result = catch_unwind(AssertUnwindSafe(|| {
+                        counter.apply(|c| {
+                            if i % PERIOD == t {
+                                panic!("op {} of thread {}", i, t);
+                            }
+                            *c += 1;
+                        })
+                    }));
+                    // only the ops of this thread panic on it
+                    assert_eq!(result.is_err(), i % PERIOD == t);
+                }
+            });
+        }
+    });
+    assert_eq!(counter.into_inner(), THREADS * (COUNT - COUNT / PERIOD));
+}
+
+/// Runs the same operations on an `OrderedListSet` and a combined `BTreeSet` with many threads on
+/// few keys, and prints how long each took.
+#[test]
+fn compare_ordered_list_set() {
+    const THREADS: usize = 16;
+    const KEYS: usize = 64;
+    const ROUNDS: usize = 50;
+
+    /// Each thread inserts its keys and removes the odd ones, so that the end result is known.
+    fn run(insert: impl Fn(usize) + Sync, remove: impl Fn(usize) + Sync) -> Duration {
+        let start = Instant::now();
+        scope(|s| {
+            for t in 0..THREADS {
+                let (insert, remove) = (&insert, &remove);
+                let _ = s.spawn(move || {
+                    for round in 0..ROUNDS {
+                        for key in (0..KEYS).map(|i| i * THREADS + t) {
+                            if round % 2 == 0 {
+                                insert(key);
+                            } else if key % 2 == 1 || round != ROUNDS - 1 {
+                                remove(key);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        start.elapsed()
+    }
+
+    let list = OrderedListSet::new();
+    let list_time = run(
+        |key| assert_eq!(list.insert(key), Ok(())),
+        |key| assert_eq!(list.remove(&key), Ok(key)),
+    );
+
+    let combined = FlatCombiner::new(BTreeSet::new());
+    let combined_time = run(
+        |key| assert!(combined.apply(|set| set.insert(key))),
+        |key| assert!(combined.apply(|set| set.remove(&key))),
+    );
+
+    let expected = (0..KEYS * THREADS)
+        .filter(|key| key % 2 == 0)
+        .collect::<Vec<_>>();
+    assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+    assert_eq!(
+        combined.into_inner().into_iter().collect::<Vec<_>>(),
+        expected
+    );
+    println!(
+        "{} threads, {} operations: OrderedListSet {:?}, FlatCombiner<BTreeSet> {:?}",
+        THREADS,
+        THREADS * KEYS * ROUNDS,
+        list_time,
+        combined_time
+    );
+}
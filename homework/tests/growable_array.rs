@@ -62,6 +62,50 @@ impl<V> NonblockingMap<u32, V> for ArrayMap<V> {
     }
 }
 
+/// [`ArrayMap`] updating the slots with `GrowableArray::store_if_null` and
+/// `GrowableArray::compare_exchange`.
+#[derive(Debug, Default)]
+struct CasArrayMap<V>(ArrayMap<V>);
+
+impl<V> NonblockingMap<u32, V> for CasArrayMap<V> {
+    fn lookup<'g>(&self, key: &u32, guard: &'g Guard) -> Option<&'g V> {
+        let slot = self.0.array.get(*key as usize, guard);
+        let ptr = slot.load(Ordering::Acquire, guard);
+        unsafe { ptr.as_ref().map(|n| &*n.data) }
+    }
+
+    fn insert(&self, key: &u32, value: V, guard: &Guard) -> Result<(), V> {
+        let node = Owned::new(Node {
+            data: ManuallyDrop::new(value),
+            next: Atomic::null(),
+        });
+        match self.0.array.store_if_null(*key as usize, node, guard) {
+            Ok(n) => {
+                self.0.storage.push_node(unsafe { n.into_owned() });
+                Ok(())
+            }
+            Err(e) => Err(ManuallyDrop::into_inner(e.new.into_box().data)),
+        }
+    }
+
+    fn delete<'g>(&self, key: &u32, guard: &'g Guard) -> Result<&'g V, ()> {
+        let slot = self.0.array.get(*key as usize, guard);
+        let curr = slot.load(Ordering::Relaxed, guard);
+        // no entry
+        if curr.is_null() {
+            return Err(());
+        }
+        match self
+            .0
+            .array
+            .compare_exchange(*key as usize, curr, Shared::null(), guard)
+        {
+            Ok(_) => Ok(unsafe { &*curr.as_ref().unwrap().data }),
+            Err(_) => Err(()), // already removed
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Stack<T> {
     head: Atomic<Node<T>>,
@@ -136,6 +180,25 @@ fn smoke() {
     assert_eq!(list.lookup(&37, &guard), None);
 }
 
+#[test]
+fn tagged_elements() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    let elems = [0, 1, 1 << 10, 1 << 20];
+
+    for (i, &index) in elems.iter().enumerate() {
+        let elem = Owned::new(index).into_shared(&guard).with_tag(1 + i % 7);
+        array.get(index, &guard).store(elem, Ordering::Relaxed);
+    }
+    // growing the array must not look into the tagged elements
+    for (i, &index) in elems.iter().enumerate() {
+        let elem = array.get(index, &guard).load(Ordering::Relaxed, &guard);
+        assert_eq!(elem.tag(), 1 + i % 7);
+        assert_eq!(unsafe { *elem.deref() }, index);
+        unsafe { drop(elem.into_owned()) };
+    }
+}
+
 #[test]
 fn stress_sequential() {
     const STEPS: usize = 4096;
@@ -171,3 +234,326 @@ fn log_concurrent() {
     const STEPS: usize = 4096 * 12;
     map::log_concurrent::<u32, NonblockingConcurrentMap<_, _, ArrayMap<usize>>>(THREADS, STEPS);
 }
+
+#[test]
+fn stress_sequential_cas() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<u32, NonblockingConcurrentMap<_, _, CasArrayMap<usize>>>(
+        STEPS,
+    );
+}
+
+#[test]
+fn insert_concurrent_cas() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 4096 * 4;
+    map::insert_concurrent::<u32, NonblockingConcurrentMap<_, _, CasArrayMap<usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn stress_concurrent_cas() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 64;
+    map::stress_concurrent::<u32, NonblockingConcurrentMap<_, _, CasArrayMap<usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn get_if_exists() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    assert!(array.get_if_exists(0, &guard).is_none());
+
+    let elem = Owned::new(5).into_shared(&guard);
+    array.get(5, &guard).store(elem, Ordering::Relaxed);
+    // the slots of the leaf segment exist, but only the stored one is non-null
+    assert_eq!(
+        array
+            .get_if_exists(5, &guard)
+            .unwrap()
+            .load(Ordering::Relaxed, &guard),
+        elem
+    );
+    assert!(array
+        .get_if_exists(6, &guard)
+        .unwrap()
+        .load(Ordering::Relaxed, &guard)
+        .is_null());
+    // no segment covers the index yet
+    assert!(array.get_if_exists(1 << 20, &guard).is_none());
+    assert!(array.get_if_exists(usize::MAX, &guard).is_none());
+
+    // growing keeps the old slots, and creates only the segments on the path
+    let far = Owned::new(1 << 20).into_shared(&guard);
+    array.get(1 << 20, &guard).store(far, Ordering::Relaxed);
+    assert_eq!(
+        array
+            .get_if_exists(5, &guard)
+            .unwrap()
+            .load(Ordering::Relaxed, &guard),
+        elem
+    );
+    assert!(array.get_if_exists(1 << 19, &guard).is_none());
+    assert_eq!(
+        array
+            .get_if_exists(1 << 20, &guard)
+            .unwrap()
+            .load(Ordering::Relaxed, &guard),
+        far
+    );
+    unsafe {
+        drop(elem.into_owned());
+        drop(far.into_owned());
+    }
+}
+
+#[test]
+fn iter() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    assert_eq!(array.iter(&guard).count(), 0);
+
+    let indices = [3, 0, 1 << 40, 1023, 1024, 1 << 20, usize::MAX, 5];
+    for (i, &index) in indices.iter().enumerate() {
+        let elem = Owned::new(index).into_shared(&guard).with_tag(i % 2);
+        array.get(index, &guard).store(elem, Ordering::Relaxed);
+    }
+    // allocated but null slots, tagged or not, are skipped
+    array
+        .get(6, &guard)
+        .store(Shared::null().with_tag(1), Ordering::Relaxed);
+    let _ = array.get(1 << 30, &guard);
+
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    let elems = array.iter(&guard).collect::<Vec<_>>();
+    assert_eq!(
+        elems.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+        sorted
+    );
+    for (index, elem) in elems {
+        let i = indices.iter().position(|&x| x == index).unwrap();
+        assert_eq!(elem.tag(), i % 2);
+        assert_eq!(unsafe { *elem.deref() }, index);
+        unsafe { drop(elem.into_owned()) };
+    }
+}
+
+#[test]
+fn compare_exchange() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    let index = 1 << 20;
+
+    let first = array.store_if_null(index, Owned::new(1), &guard).unwrap();
+    assert_eq!(
+        array.get(index, &guard).load(Ordering::Relaxed, &guard),
+        first
+    );
+    // a second store fails and hands the pointer back
+    let e = array
+        .store_if_null(index, Owned::new(2), &guard)
+        .unwrap_err();
+    assert_eq!(e.current, first);
+    assert_eq!(*e.new, 2);
+
+    let second = Owned::new(3).into_shared(&guard);
+    assert!(array
+        .compare_exchange(index, Shared::null(), second, &guard)
+        .is_err());
+    assert_eq!(
+        array.compare_exchange(index, first, second, &guard).ok(),
+        Some(second)
+    );
+    assert_eq!(
+        array.get(index, &guard).load(Ordering::Relaxed, &guard),
+        second
+    );
+
+    // a tagged null is not empty
+    assert_eq!(
+        array
+            .compare_exchange(index, second, Shared::null().with_tag(1), &guard)
+            .ok(),
+        Some(Shared::null().with_tag(1))
+    );
+    assert!(array.store_if_null(index, second, &guard).is_err());
+    unsafe {
+        drop(first.into_owned());
+        drop(second.into_owned());
+    }
+}
+
+fn fan_out<const BITS: usize>() {
+    let array = GrowableArray::<usize, BITS>::new();
+    let guard = pin();
+    let indices = [
+        0,
+        1,
+        3,
+        1 << BITS,
+        1000,
+        1 << 33,
+        usize::MAX - 1,
+        usize::MAX,
+    ];
+    for &index in &indices {
+        assert!(array
+            .store_if_null(index, Owned::new(index), &guard)
+            .is_ok());
+    }
+    for &index in &indices {
+        let elem = array
+            .get_if_exists(index, &guard)
+            .unwrap()
+            .load(Ordering::Relaxed, &guard);
+        assert_eq!(unsafe { *elem.deref() }, index);
+    }
+    assert!(array
+        .get_if_exists(5, &guard)
+        .map_or(true, |slot| slot.load(Ordering::Relaxed, &guard).is_null()));
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    let elems = array.iter(&guard).collect::<Vec<_>>();
+    assert_eq!(
+        elems.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+        sorted
+    );
+    for (_, elem) in elems {
+        unsafe { drop(elem.into_owned()) };
+    }
+}
+
+#[test]
+fn fan_out_small() {
+    fan_out::<2>();
+    fan_out::<3>();
+}
+
+#[test]
+fn fan_out_large() {
+    fan_out::<16>();
+    fan_out::<20>();
+}
+
+#[test]
+fn trim() {
+    let array = GrowableArray::<usize, 3>::new();
+    let guard = pin();
+    assert_eq!(array.trim(&guard), 0);
+
+    let indices = [0, 5, 0o70, 0o7000, 0o1234567];
+    for &index in &indices {
+        let elem = Owned::new(index).into_shared(&guard);
+        array.get(index, &guard).store(elem, Ordering::Relaxed);
+    }
+    // Nothing to trim. Tagged nulls count as stored.
+    array
+        .get(0o7777, &guard)
+        .store(Shared::null().with_tag(1), Ordering::Relaxed);
+    assert_eq!(array.trim(&guard), 0);
+    array
+        .get(0o7777, &guard)
+        .store(Shared::null(), Ordering::Relaxed);
+    // the leaf of 0o7777, and its parent
+    assert_eq!(array.trim(&guard), 2);
+
+    let take = |index: usize| {
+        let slot = array.get_if_exists(index, &guard).unwrap();
+        let elem = slot.swap(Shared::null(), Ordering::Relaxed, &guard);
+        assert_eq!(unsafe { *elem.deref() }, index);
+        unsafe { drop(elem.into_owned()) };
+    };
+
+    // The top levels of 0o1234567 go, and the root is lowered to cover up to 0o7777.
+    take(0o1234567);
+    assert_eq!(array.trim(&guard), 6 + 3);
+    assert!(array.get_if_exists(0o1234567, &guard).is_none());
+    assert!(array.get_if_exists(0o7001, &guard).is_some());
+    assert!(array.get_if_exists(0o10000, &guard).is_none());
+
+    take(0o7000);
+    take(0o70);
+    assert_eq!(array.trim(&guard), 3 + 1 + 3);
+    assert!(array.get_if_exists(0o77, &guard).is_none());
+    assert_eq!(
+        array.iter(&guard).map(|(i, _)| i).collect::<Vec<_>>(),
+        vec![0, 5]
+    );
+
+    // the array still grows
+    array
+        .get(0o777, &guard)
+        .store(Owned::new(0o777), Ordering::Relaxed);
+    assert_eq!(
+        array.iter(&guard).map(|(i, _)| i).collect::<Vec<_>>(),
+        vec![0, 5, 0o777]
+    );
+
+    take(0);
+    take(5);
+    take(0o777);
+    assert_eq!(array.trim(&guard), 5);
+    assert!(array.get_if_exists(0, &guard).is_none());
+    assert_eq!(array.trim(&guard), 0);
+}
+
+#[test]
+fn trim_concurrent() {
+    const THREADS: usize = 4;
+    const INDICES: usize = 4096;
+    let array = GrowableArray::<usize, 2>::new();
+    let guard = pin();
+    for index in 0..INDICES {
+        let elem = Owned::new(index).into_shared(&guard);
+        array.get(index, &guard).store(elem, Ordering::Relaxed);
+    }
+    drop(guard);
+
+    let done = core::sync::atomic::AtomicBool::new(false);
+    std::thread::scope(|s| {
+        // readers
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                while !done.load(Ordering::Acquire) {
+                    let guard = pin();
+                    for index in (0..INDICES).step_by(7) {
+                        if let Some(slot) = array.get_if_exists(index, &guard) {
+                            let elem = slot.load(Ordering::Acquire, &guard);
+                            if let Some(elem) = unsafe { elem.as_ref() } {
+                                assert_eq!(*elem, index);
+                            }
+                        }
+                    }
+                    for (index, elem) in array.iter(&guard) {
+                        assert_eq!(unsafe { *elem.deref() }, index);
+                    }
+                }
+            });
+        }
+        // trimmers
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                while !done.load(Ordering::Acquire) {
+                    let _ = array.trim(&pin());
+                }
+            });
+        }
+        // Removes the elements from the top, so that the trims lower the tree. Clearing a slot during
+        // a trim is fine.
+        for index in (0..INDICES).rev() {
+            let guard = pin();
+            let slot = array.get_if_exists(index, &guard).unwrap();
+            let elem = slot.swap(Shared::null(), Ordering::AcqRel, &guard);
+            unsafe { guard.defer_destroy(elem) };
+        }
+        done.store(true, Ordering::Release);
+    });
+    let guard = pin();
+    let _ = array.trim(&guard);
+    assert!(array.get_if_exists(0, &guard).is_none());
+    assert_eq!(array.trim(&guard), 0);
+}
@@ -0,0 +1,186 @@
+use cs431_homework::hello_server::{Handler, Hooks, Request, Response, Statistics, Timeouts};
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread::{scope, sleep};
+use std::time::{Duration, Instant};
+
+/// Sends `requests` on a single connection, and returns the responses up to the connection's
+/// close, with the number of requests the handler reported.
+fn exchange(requests: &str) -> (String, usize) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Handler::default();
+    scope(|s| {
+        let server = s.spawn(|| {
+            let (stream, _) = listener.accept().unwrap();
+            handler.handle_conn(0, stream)
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(requests.as_bytes()).unwrap();
+        let mut responses = String::new();
+        let _ = stream.read_to_string(&mut responses).unwrap();
+
+        let mut stats = Statistics::default();
+        stats.add_report(server.join().unwrap());
+        assert_eq!(stats.connections(), 1);
+        (responses, stats.requests())
+    })
+}
+
+#[test]
+fn handler_keep_alive() {
+    let (responses, requests) = exchange(concat!(
+        "GET / HTTP/1.1\r\n\r\n",
+        "GET /? HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello",
+        "GET / HTTP/1.1\r\nconnection: Close\r\n\r\n",
+        // not read, since the previous request closes the connection
+        "GET / HTTP/1.1\r\n\r\n",
+    ));
+    assert_eq!(requests, 3);
+    assert_eq!(responses.matches("HTTP/1.1 404 Not Found\r\n").count(), 3);
+    assert_eq!(responses.matches("Connection: keep-alive\r\n").count(), 2);
+    assert!(responses.contains("Connection: close\r\n"));
+    // each response is delimited by its length
+    let (head, rest) = responses.split_once("\r\n\r\n").unwrap();
+    let len = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Length: "))
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    assert!(rest[len..].starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
+
+#[test]
+fn handler_http_1_0_closes() {
+    let (responses, requests) = exchange("GET / HTTP/1.0\r\n\r\nGET / HTTP/1.0\r\n\r\n");
+    assert_eq!(requests, 1);
+    assert_eq!(responses.matches("HTTP/1.1 404").count(), 1);
+    assert!(responses.contains("Connection: close\r\n"));
+
+    let (responses, requests) = exchange(concat!(
+        "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n",
+        "GET / HTTP/1.0\r\n\r\n"
+    ));
+    assert_eq!(requests, 2);
+    assert_eq!(responses.matches("HTTP/1.1 404").count(), 2);
+}
+
+#[test]
+fn handler_slow_client_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Handler::default().with_timeouts(Timeouts {
+        idle: Duration::from_millis(500),
+        header: Duration::from_millis(300),
+        ..Timeouts::default()
+    });
+    scope(|s| {
+        let server = s.spawn(|| {
+            let mut stats = Statistics::default();
+            for id in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                stats.add_report(handler.handle_conn(id, stream));
+            }
+            stats
+        });
+
+        // trickles the head of the request, which never ends
+        let start = Instant::now();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        for byte in b"GET / HTTP/1.1\r\nHost: " {
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+            sleep(Duration::from_millis(50));
+        }
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // keeps the connection idle after a request
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).unwrap();
+        assert_eq!(response.matches("HTTP/1.1 ").count(), 1);
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        let stats = server.join().unwrap();
+        assert_eq!(stats.connections(), 2);
+        assert_eq!(stats.requests(), 1);
+        assert_eq!(stats.timeouts(), 1);
+    });
+}
+
+#[test]
+fn handler_stats() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let stats = Arc::new(Mutex::new(Statistics::default()));
+    let handler = Handler::default().with_stats(Arc::clone(&stats));
+    scope(|s| {
+        let server = s.spawn(|| {
+            for id in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let report = handler.handle_conn(id, stream);
+                stats.lock().unwrap().add_report(report);
+            }
+        });
+        for _ in 0..2 {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(
+                    b"GET / HTTP/1.1\r\n\r\nGET /stats HTTP/1.1\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+            let mut responses = String::new();
+            let _ = stream.read_to_string(&mut responses).unwrap();
+            assert!(responses.contains("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n"));
+        }
+        server.join().unwrap();
+    });
+    // the second response shows the first connection
+    let stats = stats.lock().unwrap();
+    assert_eq!(stats.requests(), 4);
+    assert_eq!(stats.statuses(), vec![(200, 2), (404, 2)]);
+    assert!(stats.latency().percentile(99.0).unwrap() < Duration::from_secs(1));
+}
+
+#[test]
+fn handler_hooks() {
+    #[derive(Default)]
+    struct Log(Mutex<Vec<String>>);
+    impl Hooks for Log {
+        fn on_request(&self, request: &Request<'_>) {
+            self.0.lock().unwrap().push(format!("> {}", request.path()));
+        }
+        fn on_response(&self, request: &Request<'_>, response: &Response, _: Duration) {
+            let line = format!("< {} {}", request.path(), response.status());
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let log = Arc::new(Log::default());
+    let handler = Handler::default().with_hooks(Arc::clone(&log));
+    scope(|s| {
+        let server = s.spawn(|| {
+            let (stream, _) = listener.accept().unwrap();
+            handler.handle_conn(0, stream)
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /a/b HTTP/1.1\r\n\r\nbogus\r\n\r\n")
+            .unwrap();
+        let mut responses = String::new();
+        let _ = stream.read_to_string(&mut responses).unwrap();
+        assert!(responses.contains("HTTP/1.1 400 Bad Request\r\n"));
+        let _ = server.join().unwrap();
+    });
+    // the malformed request isn't dispatched
+    assert_eq!(*log.0.lock().unwrap(), ["> /a/b", "< /a/b 404"]);
+}
@@ -0,0 +1,197 @@
+use crossbeam_epoch::pin;
+use rand::distributions::Alphanumeric;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{
+    AtomicBool, AtomicUsize,
+    Ordering::{Acquire, Relaxed, Release},
+};
+use std::thread;
+
+use cs431_homework::{LazyListSet, WorkloadRng};
+
+pub mod map;
+use map::workload_rng;
+
+static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts its drops in `DROPS`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Counted(usize);
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        let _ = DROPS.fetch_add(1, Relaxed);
+    }
+}
+
+#[test]
+fn smoke() {
+    let set = LazyListSet::new();
+    assert_eq!(set.insert(1), Ok(()));
+    assert_eq!(set.insert(3), Ok(()));
+    assert_eq!(set.insert(2), Ok(()));
+    assert_eq!(set.insert(2), Err(2));
+    assert!(set.contains(&2));
+    assert!(set.remove(&2));
+    assert!(!set.remove(&2));
+    assert!(!set.contains(&2));
+    assert_eq!(set.iter(&pin()).copied().collect::<Vec<_>>(), vec![1, 3]);
+    assert!(set.remove(&3));
+    assert!(set.remove(&1));
+    assert_eq!(set.iter(&pin()).count(), 0);
+}
+
+#[test]
+fn drop_elements() {
+    {
+        let set = LazyListSet::new();
+        for i in 0..100 {
+            assert!(set.insert(Counted(i)).is_ok());
+        }
+        for i in (0..100).step_by(2) {
+            assert!(set.remove(&Counted(i)));
+        }
+    }
+    // the keys of the calls, and the removed elements once they are collected
+    while DROPS.load(Relaxed) < 50 + 100 {
+        pin().flush();
+        thread::yield_now();
+    }
+    assert_eq!(DROPS.load(Relaxed), 150);
+}
+
+#[test]
+fn stress_sequential() {
+    const OPS: usize = 4096;
+    let mut rng = workload_rng();
+    let set = LazyListSet::default();
+    let mut hashset = HashSet::<String>::new();
+    for i in 0..OPS {
+        let key = generate_random_string(&mut rng);
+        match rng.gen_range(0..4) {
+            0 => {
+                println!("iteration {}: contains({:?})", i, key);
+                assert_eq!(set.contains(&key), hashset.contains(&key));
+            }
+            1 => {
+                println!("iteration {}: insert({:?})", i, key);
+                assert_eq!(set.insert(key.clone()).is_ok(), hashset.insert(key));
+            }
+            2 => {
+                println!("iteration {}: remove({:?})", i, key);
+                assert_eq!(set.remove(&key), hashset.remove(&key));
+            }
+            _ => {
+                let result = set.iter(&pin()).cloned().collect::<HashSet<_>>();
+                println!("iteration {}: iter() → {:?}", i, result);
+                assert_eq!(result, hashset);
+            }
+        }
+    }
+}
+
+fn generate_random_string(rng: &mut WorkloadRng) -> String {
+    rng.sample_iter(&Alphanumeric)
+        .take(1)
+        .map(|x| x as char)
+        .collect()
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 12;
+
+    #[derive(Debug, Clone)]
+    enum Log {
+        Contains(bool),
+        Insert(bool),
+        Remove(bool),
+    }
+
+    let set = LazyListSet::new();
+    let logs = thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..THREADS {
+            handles.push(s.spawn(|| {
+                let mut rng = workload_rng();
+                let mut logs = Vec::new();
+                for _ in 0..STEPS {
+                    let key = generate_random_string(&mut rng);
+                    let log = match rng.gen_range(0..3) {
+                        0 => Log::Contains(set.contains(&key)),
+                        1 => Log::Insert(set.insert(key.clone()).is_ok()),
+                        _ => Log::Remove(set.remove(&key)),
+                    };
+                    logs.push((key, log));
+                }
+                logs
+            }));
+        }
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let mut per_key = HashMap::<String, (usize, usize, bool)>::new();
+    for (key, log) in logs {
+        let (inserts, removes, contained) = per_key.entry(key).or_default();
+        match log {
+            Log::Insert(true) => *inserts += 1,
+            Log::Remove(true) => *removes += 1,
+            Log::Contains(true) => *contained = true,
+            _ => {}
+        }
+    }
+    for (key, (inserts, removes, contained)) in per_key {
+        assert!(inserts >= removes, "{:?}", key);
+        assert!(inserts > 0 || !contained, "{:?}", key);
+        // what is left in the set
+        assert_eq!(inserts - removes, set.contains(&key) as usize, "{:?}", key);
+    }
+}
+
+#[test]
+fn iter_consistent() {
+    const THREADS: usize = 15;
+    const STEPS: usize = 4096 * 12;
+
+    let set = LazyListSet::new();
+    // pre-fill with even numbers
+    for i in (0..100).step_by(2).rev() {
+        let _ = set.insert(i);
+    }
+    let evens = set.iter(&pin()).copied().collect::<HashSet<_>>();
+
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        // insert or remove odd numbers
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                let mut rng = workload_rng();
+                for _ in 0..STEPS {
+                    let key = 2 * rng.gen_range(0..50) + 1;
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
+                    }
+                }
+                done.store(true, Release);
+            });
+        }
+        s.spawn(|| {
+            while !done.load(Acquire) {
+                let snapshot = set.iter(&pin()).copied().collect::<Vec<_>>();
+                // sorted without duplicates
+                assert!(snapshot.windows(2).all(|k| k[0] < k[1]));
+                // even numbers are not touched
+                let snapshot = snapshot.into_iter().collect::<HashSet<_>>();
+                assert!(evens.is_subset(&snapshot));
+                assert!(evens.iter().all(|k| set.contains(k)));
+            }
+        });
+    });
+}
@@ -1,13 +1,19 @@
 use rand::distributions::Alphanumeric;
 use rand::prelude::*;
+use std::cell::Cell;
+use std::cmp::Ordering as CmpOrdering;
 use std::collections::{HashMap, HashSet};
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::sync::atomic::{
     AtomicBool,
     Ordering::{Acquire, Release},
 };
 use std::thread;
 
-use cs431_homework::OrderedListSet;
+use cs431_homework::{OrderedListMap, OrderedListSet, WorkloadRng, WouldBlock};
+
+pub mod map;
+use map::workload_rng;
 
 #[test]
 fn smoke() {
@@ -22,6 +28,103 @@ fn smoke() {
     assert_eq!(set.remove(&3), Ok(3));
 }
 
+#[test]
+fn clone() {
+    let set = OrderedListSet::new();
+    for i in 0..100 {
+        set.insert(i).unwrap();
+    }
+    let copy = set.clone();
+    for i in (0..100).step_by(2) {
+        assert_eq!(set.remove(&i), Ok(i));
+    }
+    assert_eq!(
+        copy.iter().copied().collect::<Vec<_>>(),
+        (0..100).collect::<Vec<_>>()
+    );
+    assert_eq!(copy.insert(100), Ok(()));
+    assert!(!set.contains(&100));
+}
+
+thread_local! {
+    static ARMED: Cell<bool> = Cell::new(false);
+}
+
+/// Key whose comparison panics while `ARMED`.
+#[derive(Debug, PartialEq, Eq)]
+struct Bomb(usize);
+
+impl PartialOrd for Bomb {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Bomb {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        if ARMED.with(Cell::get) {
+            panic!("comparison failed");
+        }
+        self.0.cmp(&other.0)
+    }
+}
+
+#[test]
+fn panic_in_cmp() {
+    let set = OrderedListSet::new();
+    for i in (0..20).step_by(2) {
+        set.insert(Bomb(i)).unwrap();
+    }
+
+    ARMED.with(|armed| armed.set(true));
+    assert!(catch_unwind(AssertUnwindSafe(|| set.insert(Bomb(11)))).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| set.remove(&Bomb(10)))).is_err());
+    assert!(catch_unwind(AssertUnwindSafe(|| set.contains(&Bomb(4)))).is_err());
+    ARMED.with(|armed| armed.set(false));
+
+    // the poisoned locks are still usable, and the set is unchanged
+    assert_eq!(
+        set.iter().map(|b| b.0).collect::<Vec<_>>(),
+        (0..20).step_by(2).collect::<Vec<_>>()
+    );
+    assert_eq!(set.insert(Bomb(11)), Ok(()));
+    assert_eq!(set.remove(&Bomb(10)), Ok(Bomb(10)));
+    assert!(set.contains(&Bomb(4)));
+}
+
+#[test]
+fn heal() {
+    let mut set = OrderedListSet::new();
+    for i in (0..20).step_by(2) {
+        set.insert(Bomb(i)).unwrap();
+    }
+    assert!(!set.is_poisoned());
+
+    ARMED.with(|armed| armed.set(true));
+    assert!(catch_unwind(AssertUnwindSafe(|| set.insert(Bomb(11)))).is_err());
+    ARMED.with(|armed| armed.set(false));
+    assert!(set.is_poisoned());
+    set.heal();
+    assert!(!set.is_poisoned());
+
+    // a panic while holding a cursor poisons the lock of the head
+    assert!(catch_unwind(AssertUnwindSafe(|| {
+        let _cursor = set.cursor();
+        panic!("with a cursor");
+    }))
+    .is_err());
+    assert!(set.is_poisoned());
+    set.heal();
+    assert!(!set.is_poisoned());
+
+    assert_eq!(set.insert(Bomb(11)), Ok(()));
+    assert_eq!(
+        set.iter().map(|b| b.0).collect::<Vec<_>>(),
+        [0, 2, 4, 6, 8, 10, 11, 12, 14, 16, 18]
+    );
+    assert_eq!(set.len(), 11);
+}
+
 #[test]
 fn parallel_iter_end() {
     let set = OrderedListSet::new();
@@ -60,7 +163,7 @@ fn stress_sequential() {
         Ops::RemoveNone,
         Ops::Iterate,
     ];
-    let mut rng = thread_rng();
+    let mut rng = workload_rng();
     let set = OrderedListSet::default();
     let mut hashset = HashSet::<String>::new();
 
@@ -110,7 +213,7 @@ fn stress_sequential() {
 const THREADS: usize = 16;
 const STEPS: usize = 4096 * 8;
 
-fn generate_random_string(rng: &mut ThreadRng) -> String {
+fn generate_random_string(rng: &mut WorkloadRng) -> String {
     rng.sample_iter(&Alphanumeric)
         .take(1)
         .map(|x| x as char)
@@ -150,7 +253,7 @@ fn stress_concurrent() {
     thread::scope(|s| {
         for _ in 0..THREADS {
             s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 for _ in 0..STEPS {
                     let op = ops.choose(&mut rng).unwrap();
 
@@ -222,7 +325,7 @@ fn log_concurrent() {
         let mut handles = Vec::new();
         for _ in 0..THREADS {
             let handle = s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 let mut logs = Vec::new();
                 for _ in 0..STEPS {
                     let op = ops.choose(&mut rng).unwrap();
@@ -285,7 +388,7 @@ fn iter_consistent() {
         // insert or remove odd numbers
         for _ in 0..THREADS {
             s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 for _ in 0..STEPS {
                     let key = 2 * rng.gen_range(0..50) + 1;
                     if rng.gen() {
@@ -310,3 +413,444 @@ fn iter_consistent() {
         });
     });
 }
+
+#[test]
+fn map_smoke() {
+    let map = OrderedListMap::new();
+    assert_eq!(map.insert(2, "two".to_string()), None);
+    assert_eq!(map.insert(1, "one".to_string()), None);
+    assert_eq!(map.insert(3, "three".to_string()), None);
+    assert_eq!(map.insert(2, "deux".to_string()), Some("two".to_string()));
+    assert!(map.contains_key(&1));
+    assert!(!map.contains_key(&4));
+
+    let value = map.get(&2).unwrap();
+    assert_eq!(*value.key(), 2);
+    assert_eq!(*value, "deux");
+    drop(value);
+    assert!(map.get(&4).is_none());
+
+    map.get_mut(&3).unwrap().push_str("!!");
+    assert_eq!(*map.get(&3).unwrap(), "three!!");
+    assert!(map.get_mut(&4).is_none());
+
+    assert_eq!(map.remove(&1), Some("one".to_string()));
+    assert_eq!(map.remove(&1), None);
+    assert!(!map.contains_key(&1));
+    assert_eq!(map.remove(&3), Some("three!!".to_string()));
+}
+
+#[test]
+fn map_stress_sequential() {
+    const OPS: usize = 4096;
+    let mut rng = workload_rng();
+    let map = OrderedListMap::default();
+    let mut hashmap = HashMap::<String, usize>::new();
+    for i in 0..OPS {
+        let key = generate_random_string(&mut rng);
+        match rng.gen_range(0..4) {
+            0 => {
+                println!("iteration {}: get({:?})", i, key);
+                assert_eq!(map.get(&key).map(|v| *v), hashmap.get(&key).copied());
+            }
+            1 => {
+                println!("iteration {}: insert({:?}, {})", i, key, i);
+                assert_eq!(map.insert(key.clone(), i), hashmap.insert(key, i));
+            }
+            2 => {
+                println!("iteration {}: get_mut({:?})", i, key);
+                if let Some(mut value) = map.get_mut(&key) {
+                    *value += 1;
+                }
+                if let Some(value) = hashmap.get_mut(&key) {
+                    *value += 1;
+                }
+            }
+            _ => {
+                println!("iteration {}: remove({:?})", i, key);
+                assert_eq!(map.remove(&key), hashmap.remove(&key));
+            }
+        }
+    }
+}
+
+#[test]
+fn map_get_mut_concurrent() {
+    const KEYS: usize = 16;
+    let map = OrderedListMap::new();
+    for key in 0..KEYS {
+        assert_eq!(map.insert(key, 0), None);
+    }
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                let mut rng = workload_rng();
+                for _ in 0..STEPS {
+                    let key = rng.gen_range(0..KEYS);
+                    *map.get_mut(&key).unwrap() += 1;
+                    // keys that come and go around the counters
+                    let other = KEYS + rng.gen_range(0..KEYS);
+                    if map.remove(&other).is_none() {
+                        let _ = map.insert(other, 0);
+                    }
+                }
+            });
+        }
+    });
+    let total = (0..KEYS).map(|key| *map.get(&key).unwrap()).sum::<usize>();
+    assert_eq!(total, THREADS * STEPS);
+}
+
+#[test]
+fn range() {
+    let set = OrderedListSet::new();
+    for i in (0..20).step_by(2) {
+        set.insert(i).unwrap();
+    }
+    let range = |r: std::ops::Range<i32>| set.range(r).copied().collect::<Vec<_>>();
+    assert_eq!(range(3..9), vec![4, 6, 8]);
+    assert_eq!(range(4..8), vec![4, 6]);
+    assert_eq!(range(5..5), vec![]);
+    assert_eq!(range(30..40), vec![]);
+    assert_eq!(set.range(..).count(), 10);
+    assert_eq!(set.range(..=4).copied().collect::<Vec<_>>(), vec![0, 2, 4]);
+    assert_eq!(set.range(15..).copied().collect::<Vec<_>>(), vec![16, 18]);
+    use std::ops::Bound::{Excluded, Included};
+    assert_eq!(
+        set.range((Excluded(4), Included(10)))
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![6, 8, 10]
+    );
+
+    // The lock is released at the end of the range, so the rest of the list is writable.
+    let mut iter = set.range(..6);
+    assert_eq!(iter.by_ref().count(), 3);
+    thread::scope(|s| {
+        s.spawn(|| {
+            assert_eq!(set.insert(7), Ok(()));
+            assert_eq!(set.remove(&6), Ok(6));
+        });
+    });
+    drop(iter);
+    assert_eq!(range(5..9), vec![7, 8]);
+}
+
+#[test]
+fn pop_front() {
+    let set = OrderedListSet::new();
+    assert_eq!(set.pop_front(), None);
+    for i in [3, 1, 2] {
+        set.insert(i).unwrap();
+    }
+    assert_eq!(set.pop_front(), Some(1));
+    assert_eq!(set.pop_front(), Some(2));
+    set.insert(0).unwrap();
+    assert_eq!(set.pop_front(), Some(0));
+    assert_eq!(set.pop_front(), Some(3));
+    assert_eq!(set.pop_front(), None);
+}
+
+#[test]
+fn pop_front_concurrent() {
+    const ITEMS: usize = 4096 * 4;
+    let set = OrderedListSet::new();
+    for i in (0..ITEMS).rev() {
+        set.insert(i).unwrap();
+    }
+    let popped = thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..THREADS {
+            handles.push(s.spawn(|| {
+                let mut popped = Vec::new();
+                while let Some(i) = set.pop_front() {
+                    popped.push(i);
+                }
+                popped
+            }));
+        }
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+    // each thread pops in increasing order, and every item exactly once
+    assert!(popped.iter().all(|p| p.windows(2).all(|w| w[0] < w[1])));
+    let mut all = popped.concat();
+    all.sort_unstable();
+    assert_eq!(all, (0..ITEMS).collect::<Vec<_>>());
+}
+
+#[test]
+fn len() {
+    let set = OrderedListSet::new();
+    assert!(set.is_empty());
+    for i in 0..10 {
+        set.insert(i).unwrap();
+    }
+    assert_eq!(set.insert(3), Err(3));
+    assert_eq!(set.len(), 10);
+    assert_eq!(set.remove(&3), Ok(3));
+    assert_eq!(set.remove(&3), Err(()));
+    assert_eq!(set.pop_front(), Some(0));
+    assert_eq!(set.len(), 8);
+    let copy = set.clone();
+    assert_eq!(copy.pop_front(), Some(1));
+    assert_eq!((copy.len(), set.len()), (7, 8));
+
+    // concurrent inserts and removes of disjoint keys
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let set = &set;
+            s.spawn(move || {
+                for i in 0..256 {
+                    let key = 100 + i * THREADS + t;
+                    set.insert(key).unwrap();
+                    if i % 2 == 0 {
+                        assert_eq!(set.remove(&key), Ok(key));
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(set.len(), 8 + THREADS * 128);
+    assert_eq!(set.len(), set.iter().count());
+    while set.pop_front().is_some() {}
+    assert!(set.is_empty());
+}
+
+#[test]
+fn into_iter() {
+    let set = OrderedListSet::new();
+    for i in [5, 1, 3, 2, 4] {
+        set.insert(i.to_string()).unwrap();
+    }
+    assert_eq!(
+        set.into_iter().collect::<Vec<_>>(),
+        ["1", "2", "3", "4", "5"]
+    );
+
+    // the rest is dropped with the iterator
+    let set = OrderedListSet::new();
+    for i in 0..10 {
+        set.insert(i.to_string()).unwrap();
+    }
+    let mut iter = set.into_iter();
+    assert_eq!(iter.next().as_deref(), Some("0"));
+    drop(iter);
+}
+
+#[test]
+fn drain() {
+    let set = OrderedListSet::new();
+    for i in 0..10 {
+        set.insert(i).unwrap();
+    }
+    let drain = set.drain();
+    assert!(set.is_empty());
+    assert_eq!(set.iter().count(), 0);
+    // the set is usable while the drained elements are consumed
+    set.insert(3).unwrap();
+    assert_eq!(drain.collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+    assert_eq!(set.len(), 1);
+
+    // concurrent with writers, every element ends up drained or in the set exactly once
+    let set = OrderedListSet::new();
+    let done = AtomicBool::new(false);
+    let drained = thread::scope(|s| {
+        for t in 0..THREADS {
+            let (set, done) = (&set, &done);
+            s.spawn(move || {
+                for i in 0..STEPS / 8 {
+                    set.insert(i * THREADS + t).unwrap();
+                }
+                done.store(true, Release);
+            });
+        }
+        let mut drained = Vec::new();
+        while !done.load(Acquire) {
+            let chunk = set.drain().collect::<Vec<_>>();
+            assert!(chunk.windows(2).all(|w| w[0] < w[1]));
+            drained.extend(chunk);
+        }
+        drained
+    });
+    let mut all = drained;
+    all.extend(set.drain());
+    all.sort_unstable();
+    assert_eq!(all, (0..THREADS * (STEPS / 8)).collect::<Vec<_>>());
+    assert!(set.is_empty());
+}
+
+#[test]
+fn from_iter_extend() {
+    let set = [5, 3, 8, 3, 1].into_iter().collect::<OrderedListSet<_>>();
+    assert_eq!(set.iter().copied().collect::<Vec<_>>(), vec![1, 3, 5, 8]);
+    assert_eq!(set.len(), 4);
+
+    let mut set = set;
+    // sorted, in one traversal
+    set.extend([0, 2, 3, 4, 9, 10]);
+    assert_eq!(
+        set.iter().copied().collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4, 5, 8, 9, 10]
+    );
+    // unsorted, with duplicates
+    set.extend([7, 6, 6, 11, -1, 7]);
+    assert_eq!(
+        set.iter().copied().collect::<Vec<_>>(),
+        (-1..=11).collect::<Vec<_>>()
+    );
+    assert_eq!(set.len(), 13);
+    assert_eq!(set.insert(12), Ok(()));
+    assert_eq!(set.remove(&-1), Ok(-1));
+
+    let mut rng = workload_rng();
+    let mut set = OrderedListSet::new();
+    let mut hashset = HashSet::new();
+    for _ in 0..64 {
+        let batch = (0..rng.gen_range(0..32))
+            .map(|_| rng.gen_range(0..256))
+            .collect::<Vec<_>>();
+        hashset.extend(batch.iter().copied());
+        set.extend(batch);
+        assert_eq!(set.len(), hashset.len());
+    }
+    let mut expected = hashset.into_iter().collect::<Vec<_>>();
+    expected.sort_unstable();
+    assert_eq!(set.into_iter().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn retain() {
+    let set = (0..100).collect::<OrderedListSet<_>>();
+    set.retain(|i| i % 3 == 0);
+    assert_eq!(
+        set.iter().copied().collect::<Vec<_>>(),
+        (0..100).step_by(3).collect::<Vec<_>>()
+    );
+    assert_eq!(set.len(), 34);
+    set.retain(|_| false);
+    assert!(set.is_empty());
+    assert_eq!(set.iter().count(), 0);
+
+    // a panicking predicate
+    let set = (0..10).collect::<OrderedListSet<_>>();
+    assert!(catch_unwind(AssertUnwindSafe(|| set.retain(|&i| {
+        assert!(i < 5);
+        i % 2 == 0
+    })))
+    .is_err());
+    assert_eq!(
+        set.iter().copied().collect::<Vec<_>>(),
+        vec![0, 2, 4, 5, 6, 7, 8, 9]
+    );
+    assert_eq!(set.len(), 8);
+
+    // concurrent with writers, the even keys are never removed
+    let set = (0..100).collect::<OrderedListSet<_>>();
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                let mut rng = workload_rng();
+                for _ in 0..STEPS / 8 {
+                    let key = 2 * rng.gen_range(0..50) + 1;
+                    let _ = set.insert(key);
+                }
+            });
+        }
+        s.spawn(|| {
+            for _ in 0..64 {
+                set.retain(|i| i % 2 == 0);
+            }
+        });
+    });
+    assert!((0..100).step_by(2).all(|i| set.contains(&i)));
+    set.retain(|i| i % 2 == 0);
+    assert_eq!(set.len(), 50);
+}
+
+#[test]
+fn try_contains() {
+    let set = (0..10).collect::<OrderedListSet<_>>();
+    assert_eq!(set.try_contains(&3), Ok(true));
+    assert_eq!(set.try_contains(&30), Ok(false));
+
+    // an iterator at 5 holds the lock of the link to 5
+    let mut iter = set.iter();
+    assert_eq!(iter.nth(4), Some(&4));
+    thread::scope(|s| {
+        s.spawn(|| {
+            assert_eq!(set.try_contains(&4), Ok(true));
+            assert_eq!(set.try_contains(&5), Err(WouldBlock));
+            assert_eq!(set.try_contains(&30), Err(WouldBlock));
+        });
+    });
+    drop(iter);
+    assert_eq!(set.try_contains(&5), Ok(true));
+    assert_eq!(
+        WouldBlock.to_string(),
+        "a lock of the list is held by another thread"
+    );
+}
+
+#[test]
+fn cursor() {
+    let set = [10, 20, 30].into_iter().collect::<OrderedListSet<_>>();
+    let mut cursor = set.cursor();
+    assert_eq!(cursor.current(), Some(&10));
+    assert_eq!(cursor.insert_before(10), Err(10));
+    assert_eq!(cursor.insert_before(11), Err(11));
+    assert_eq!(cursor.insert_before(5), Ok(()));
+    assert_eq!(cursor.current(), Some(&10));
+
+    assert!(!cursor.seek(&15));
+    assert_eq!(cursor.current(), Some(&20));
+    // doesn't move backwards
+    assert!(!cursor.seek(&10));
+    assert_eq!(cursor.insert_before(9), Err(9));
+    assert_eq!(cursor.insert_before(15), Ok(()));
+    assert!(cursor.seek(&20));
+    assert_eq!(cursor.remove_current(), Some(20));
+    assert_eq!(cursor.current(), Some(&30));
+    assert_eq!(cursor.insert_before(25), Ok(()));
+    cursor.move_next();
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.remove_current(), None);
+    assert_eq!(cursor.insert_before(30), Err(30));
+    assert_eq!(cursor.insert_before(40), Ok(()));
+    assert!(!cursor.seek(&50));
+    drop(cursor);
+
+    assert_eq!(
+        set.iter().copied().collect::<Vec<_>>(),
+        vec![5, 10, 15, 25, 30, 40]
+    );
+    assert_eq!(set.len(), 6);
+}
+
+#[test]
+fn cursor_concurrent() {
+    // each thread finds its own keys and inserts the next one after them
+    let set = (0..THREADS)
+        .map(|t| t * 1000)
+        .collect::<OrderedListSet<_>>();
+    thread::scope(|s| {
+        for t in 0..THREADS {
+            let set = &set;
+            s.spawn(move || {
+                for i in 0..100 {
+                    let mut cursor = set.cursor();
+                    assert!(cursor.seek(&(t * 1000 + i)));
+                    cursor.move_next();
+                    assert_eq!(cursor.insert_before(t * 1000 + i + 1), Ok(()));
+                }
+            });
+        }
+    });
+    let expected = (0..THREADS)
+        .flat_map(|t| t * 1000..=t * 1000 + 100)
+        .collect::<Vec<_>>();
+    assert_eq!(set.iter().copied().collect::<Vec<_>>(), expected);
+    assert_eq!(set.len(), expected.len());
+}
@@ -1,7 +1,7 @@
 use core::fmt;
 use core::hash::Hash;
 use core::marker::PhantomData;
-use cs431_homework::{ConcurrentMap, RandGen, SequentialMap};
+use cs431_homework::{workload_seed, ConcurrentMap, RandGen, SequentialMap, WorkloadRng};
 use std::collections::HashMap;
 
 use rand::prelude::*;
@@ -9,6 +9,15 @@ use rand::prelude::*;
 use crossbeam_epoch::pin;
 use std::thread;
 
+/// Returns a workload generator. With the `deterministic` feature, the seed is printed too, so
+/// that the output of a failed test tells how to reproduce it.
+pub fn workload_rng() -> WorkloadRng {
+    if cfg!(feature = "deterministic") {
+        eprintln!("[deterministic] CS431_SEED={}", workload_seed());
+    }
+    cs431_homework::workload_rng()
+}
+
 pub fn stress_sequential<
     K: fmt::Debug + Clone + Eq + Hash + RandGen,
     M: Default + SequentialMap<K, usize>,
@@ -31,7 +40,7 @@ pub fn stress_sequential<
         Ops::DeleteSome,
         Ops::DeleteNone,
     ];
-    let mut rng = thread_rng();
+    let mut rng = workload_rng();
     let mut map = M::default();
     let mut hashmap = HashMap::<K, usize>::new();
 
@@ -132,7 +141,7 @@ pub fn lookup_concurrent<
 
     let ops = [Ops::LookupSome, Ops::LookupNone];
 
-    let mut rng = thread_rng();
+    let mut rng = workload_rng();
     let map = M::default();
     let mut hashmap = HashMap::<K, usize>::new();
 
@@ -146,7 +155,7 @@ pub fn lookup_concurrent<
     thread::scope(|s| {
         for _ in 0..threads {
             s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 for _ in 0..steps {
                     let op = ops.choose(&mut rng).unwrap();
 
@@ -185,7 +194,7 @@ pub fn insert_concurrent<
     thread::scope(|s| {
         for _ in 0..threads {
             s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 for _ in 0..steps {
                     let key = K::rand_gen(&mut rng);
                     let value = rng.gen::<usize>();
@@ -236,7 +245,7 @@ pub fn stress_concurrent<
     thread::scope(|s| {
         for _ in 0..threads {
             s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 for _ in 0..steps {
                     let op = ops.choose(&mut rng).unwrap();
 
@@ -321,7 +330,7 @@ pub fn log_concurrent<
         let mut handles = Vec::new();
         for _ in 0..threads {
             let handle = s.spawn(|| {
-                let mut rng = thread_rng();
+                let mut rng = workload_rng();
                 let mut logs = Vec::new();
                 for _ in 0..steps {
                     let op = ops.choose(&mut rng).unwrap();
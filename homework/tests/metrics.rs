@@ -0,0 +1,63 @@
+use cs431_homework::metrics::{CountMinSketch, TopK};
+use std::thread::scope;
+
+#[test]
+fn sketch_never_underestimates() {
+    let sketch = CountMinSketch::new(64, 4);
+    for i in 0..1000usize {
+        for _ in 0..i % 7 {
+            sketch.increment(&i);
+        }
+    }
+    for i in 0..1000usize {
+        assert!(sketch.estimate(&i) >= (i % 7) as u64);
+    }
+    sketch.clear();
+    assert_eq!(sketch.estimate(&3usize), 0);
+}
+
+#[test]
+fn sketch_concurrent() {
+    const THREADS: usize = 8;
+    const STEPS: u64 = 10_000;
+
+    let sketch = CountMinSketch::new(1024, 4);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..STEPS {
+                    sketch.increment("key");
+                }
+            });
+        }
+    });
+    assert_eq!(sketch.estimate("key"), THREADS as u64 * STEPS);
+}
+
+#[test]
+fn top_k_finds_heavy_hitters() {
+    const THREADS: usize = 4;
+
+    let top = TopK::new(3);
+    scope(|s| {
+        for t in 0..THREADS {
+            let top = &top;
+            s.spawn(move || {
+                for i in 0..10_000usize {
+                    // keys 0, 1 and 2 dominate the traffic
+                    let key = if i % 2 == 0 {
+                        i / 2 % 3
+                    } else {
+                        3 + t * 10_000 + i
+                    };
+                    top.record(&key);
+                }
+            });
+        }
+    });
+
+    let mut keys = top.top().into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+    keys.sort_unstable();
+    assert_eq!(keys, vec![0, 1, 2]);
+    assert!(top.estimate(&0) >= 4 * 5000 / 3);
+}
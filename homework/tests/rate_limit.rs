@@ -0,0 +1,73 @@
+use cs431_homework::hello_server::{Handler, Limit, RateLimiter, Statistics};
+use std::io::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{scope, sleep};
+use std::time::Duration;
+
+const A: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+const B: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+#[test]
+fn rate_limit_per_client() {
+    let limiter = RateLimiter::new().per_client(Limit::per_second(10, 3));
+    for _ in 0..3 {
+        assert!(limiter.check(A).is_ok());
+    }
+    let retry = limiter.check(A).unwrap_err();
+    assert!(retry > Duration::ZERO && retry <= Duration::from_millis(100));
+    // the other clients have buckets of their own
+    assert!(limiter.check(B).is_ok());
+
+    sleep(retry);
+    assert!(limiter.check(A).is_ok());
+    assert!(limiter.check(A).is_err());
+}
+
+#[test]
+fn rate_limit_global() {
+    let limiter = RateLimiter::new()
+        .global(Limit::per_second(1, 100))
+        .per_client(Limit::per_second(1, 60));
+    let accepted = AtomicUsize::new(0);
+    scope(|s| {
+        for client in [A, B] {
+            let (limiter, accepted) = (&limiter, &accepted);
+            let _ = s.spawn(move || {
+                for _ in 0..100 {
+                    if limiter.check(client).is_ok() {
+                        let _ = accepted.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+    // each client takes at most 60 tokens, out of 100 for both
+    assert_eq!(accepted.load(Ordering::Relaxed), 100);
+}
+
+#[test]
+fn rate_limit_handler() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Handler::default()
+        .with_rate_limiter(RateLimiter::new().per_client(Limit::per_second(1, 2)));
+    scope(|s| {
+        let server = s.spawn(|| {
+            let (stream, _) = listener.accept().unwrap();
+            handler.handle_conn(0, stream)
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut responses = String::new();
+        let _ = stream.read_to_string(&mut responses).unwrap();
+        assert_eq!(responses.matches("HTTP/1.1 404 Not Found\r\n").count(), 2);
+        assert!(responses.contains("HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\n"));
+
+        let mut stats = Statistics::default();
+        stats.add_report(server.join().unwrap());
+        assert_eq!(stats.requests(), 3);
+    });
+}
@@ -0,0 +1,128 @@
+use cs431_homework::arena::{ArenaMap, RequestArena};
+use cs431_homework::hello_server::{Handler, Request, Response, Router};
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::scope;
+
+fn dispatch(router: &Router, method: &str, target: &str) -> Response {
+    let arena = RequestArena::new();
+    let mut request = Request::new(method, target, ArenaMap::new_in(&arena), &[]);
+    router.dispatch(&mut request)
+}
+
+fn body(response: &Response) -> &str {
+    std::str::from_utf8(response.body()).unwrap()
+}
+
+#[test]
+fn router_params() {
+    let router = Router::new()
+        .get("/", |_| Response::ok("root"))
+        .get("/user/me", |_| Response::ok("me"))
+        .get("/user/:id", |request| {
+            Response::ok(format!("user {}", request.param("id").unwrap()))
+        })
+        .get("/user/:id/post/:post", |request| {
+            Response::ok(format!(
+                "post {} of {}",
+                request.param("post").unwrap(),
+                request.param("id").unwrap()
+            ))
+        })
+        .get("/static/*path", |request| {
+            Response::ok(format!("file {:?}", request.param("path").unwrap()))
+        })
+        .post("/user/:id", |request| {
+            Response::new(201, format!("{:?}", request.query()))
+        });
+
+    assert_eq!(body(&dispatch(&router, "GET", "/")), "root");
+    // the first matching route wins
+    assert_eq!(body(&dispatch(&router, "GET", "/user/me")), "me");
+    assert_eq!(body(&dispatch(&router, "GET", "/user/42")), "user 42");
+    assert_eq!(body(&dispatch(&router, "GET", "/user/42?x=1")), "user 42");
+    assert_eq!(
+        body(&dispatch(&router, "GET", "/user/42/post/7")),
+        "post 7 of 42"
+    );
+    assert_eq!(
+        body(&dispatch(&router, "GET", "/static/css/a.css")),
+        "file \"css/a.css\""
+    );
+    assert_eq!(body(&dispatch(&router, "GET", "/static/")), "file \"\"");
+
+    let response = dispatch(&router, "POST", "/user/42?x=1");
+    assert_eq!(response.status(), 201);
+    assert_eq!(body(&response), "Some(\"x=1\")");
+
+    for target in [
+        "/user",
+        "/user/",
+        "/user/42/",
+        "/user/42/post",
+        "/nope",
+        "user/42",
+    ] {
+        assert_eq!(dispatch(&router, "GET", target).status(), 404, "{}", target);
+    }
+    let response = dispatch(&router, "DELETE", "/user/42");
+    assert_eq!(response.status(), 405);
+    assert_eq!(
+        response.headers(),
+        [("Allow".to_string(), "GET, POST".to_string())]
+    );
+}
+
+#[test]
+#[should_panic]
+fn router_rest_not_at_end() {
+    let _ = Router::new().get("/*path/x", |_| Response::ok(""));
+}
+
+/// The router of a handler is shared by the threads serving the connections.
+#[test]
+fn router_handler() {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let route_hits = hits.clone();
+    let handler = Handler::new(
+        Router::new()
+            .get("/hits", move |_| {
+                let hits = route_hits.fetch_add(1, Ordering::Relaxed) + 1;
+                Response::ok(hits.to_string()).with_header("Content-Type", "text/plain")
+            })
+            .post("/echo", |request| Response::ok(request.body())),
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    scope(|s| {
+        for _ in 0..4 {
+            let (listener, handler) = (&listener, handler.clone());
+            let _ = s.spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let _ = handler.handle_conn(0, stream);
+            });
+        }
+        for _ in 0..3 {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /hits HTTP/1.1\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            let _ = stream.read_to_string(&mut response).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n"));
+        }
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(
+                b"POST /echo HTTP/1.1\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            )
+            .unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).unwrap();
+        assert!(response.ends_with("\r\n\r\nhello"));
+    });
+    assert_eq!(hits.load(Ordering::Relaxed), 3);
+}
@@ -0,0 +1,196 @@
+use rand::distributions::Alphanumeric;
+use rand::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering::{Acquire, Release},
+};
+use std::sync::Barrier;
+use std::thread;
+
+use cs431_homework::{RwListSet, WorkloadRng};
+
+pub mod map;
+use map::workload_rng;
+
+#[test]
+fn smoke() {
+    let set = RwListSet::new();
+    assert_eq!(set.insert(2), Ok(()));
+    assert_eq!(set.insert(1), Ok(()));
+    assert_eq!(set.insert(3), Ok(()));
+    assert_eq!(set.insert(2), Err(2));
+    assert!(set.contains(&2));
+    assert_eq!(set.remove(&2), Ok(2));
+    assert_eq!(set.remove(&2), Err(()));
+    assert!(!set.contains(&2));
+    assert_eq!(set.iter().copied().collect::<Vec<_>>(), vec![1, 3]);
+}
+
+#[test]
+fn parallel_readers() {
+    const READERS: usize = 8;
+    let set = RwListSet::new();
+    for i in 0..100 {
+        set.insert(i).unwrap();
+    }
+    // Every reader holds a shared lock of the list while the others traverse it, which would
+    // deadlock with exclusive locks.
+    let barrier = Barrier::new(READERS);
+    thread::scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|| {
+                let mut iter = set.iter();
+                assert_eq!(iter.next(), Some(&0));
+                let _ = barrier.wait();
+                assert!(set.contains(&99));
+                assert_eq!(set.iter().count(), 100);
+                let _ = barrier.wait();
+                drop(iter);
+            });
+        }
+    });
+
+    // writers wait for the readers of their links
+    let iter = set.iter().skip(50);
+    thread::scope(|s| {
+        s.spawn(|| {
+            assert!(set.contains(&99));
+            assert_eq!(set.remove(&10), Ok(10));
+        });
+    });
+    drop(iter);
+    assert_eq!(set.remove(&60), Ok(60));
+}
+
+#[test]
+fn stress_sequential() {
+    const OPS: usize = 4096;
+    let mut rng = workload_rng();
+    let set = RwListSet::default();
+    let mut hashset = HashSet::<String>::new();
+    for i in 0..OPS {
+        let key = generate_random_string(&mut rng);
+        match rng.gen_range(0..4) {
+            0 => {
+                println!("iteration {}: contains({:?})", i, key);
+                assert_eq!(set.contains(&key), hashset.contains(&key));
+            }
+            1 => {
+                println!("iteration {}: insert({:?})", i, key);
+                assert_eq!(set.insert(key.clone()).is_ok(), hashset.insert(key));
+            }
+            2 => {
+                println!("iteration {}: remove({:?})", i, key);
+                assert_eq!(set.remove(&key).is_ok(), hashset.remove(&key));
+            }
+            _ => {
+                let result = set.iter().cloned().collect::<HashSet<_>>();
+                println!("iteration {}: iter() → {:?}", i, result);
+                assert_eq!(result, hashset);
+            }
+        }
+    }
+}
+
+fn generate_random_string(rng: &mut WorkloadRng) -> String {
+    rng.sample_iter(&Alphanumeric)
+        .take(1)
+        .map(|x| x as char)
+        .collect()
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 12;
+
+    #[derive(Debug, Clone)]
+    enum Log {
+        Contains(bool),
+        Insert(bool),
+        Remove(bool),
+    }
+
+    let set = RwListSet::new();
+    let logs = thread::scope(|s| {
+        let mut handles = Vec::new();
+        for _ in 0..THREADS {
+            handles.push(s.spawn(|| {
+                let mut rng = workload_rng();
+                let mut logs = Vec::new();
+                for _ in 0..STEPS {
+                    let key = generate_random_string(&mut rng);
+                    let log = match rng.gen_range(0..3) {
+                        0 => Log::Contains(set.contains(&key)),
+                        1 => Log::Insert(set.insert(key.clone()).is_ok()),
+                        _ => Log::Remove(set.remove(&key).is_ok()),
+                    };
+                    logs.push((key, log));
+                }
+                logs
+            }));
+        }
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let mut per_key = HashMap::<String, (usize, usize, bool)>::new();
+    for (key, log) in logs {
+        let (inserts, removes, contained) = per_key.entry(key).or_default();
+        match log {
+            Log::Insert(true) => *inserts += 1,
+            Log::Remove(true) => *removes += 1,
+            Log::Contains(true) => *contained = true,
+            _ => {}
+        }
+    }
+    for (key, (inserts, removes, contained)) in per_key {
+        assert!(inserts >= removes, "{:?}", key);
+        assert!(inserts > 0 || !contained, "{:?}", key);
+        assert_eq!(inserts - removes, set.contains(&key) as usize, "{:?}", key);
+    }
+}
+
+#[test]
+fn iter_consistent() {
+    const THREADS: usize = 15;
+    const STEPS: usize = 4096 * 12;
+
+    let set = RwListSet::new();
+    for i in (0..100).step_by(2).rev() {
+        let _ = set.insert(i);
+    }
+    let evens = set.iter().copied().collect::<HashSet<_>>();
+
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                let mut rng = workload_rng();
+                for _ in 0..STEPS {
+                    let key = 2 * rng.gen_range(0..50) + 1;
+                    if rng.gen() {
+                        let _ = set.insert(key);
+                    } else {
+                        let _ = set.remove(&key);
+                    }
+                }
+                done.store(true, Release);
+            });
+        }
+        // several iterators at once
+        for _ in 0..2 {
+            s.spawn(|| {
+                while !done.load(Acquire) {
+                    let snapshot = set.iter().copied().collect::<Vec<_>>();
+                    assert!(snapshot.windows(2).all(|k| k[0] < k[1]));
+                    let snapshot = snapshot.into_iter().collect::<HashSet<_>>();
+                    assert!(evens.is_subset(&snapshot));
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,157 @@
+mod mock;
+
+use cs431_homework::NoUninit;
+
+/// Value whose padding is made explicit, so that it has no uninitialized bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+struct Tagged {
+    tag: u8,
+    _pad: [u8; 7],
+    value: u64,
+}
+
+unsafe impl NoUninit for Tagged {}
+
+#[cfg(not(feature = "check-loom"))]
+mod basic {
+    use super::Tagged;
+    use cs431_homework::SeqLock;
+    use std::thread::scope;
+
+    #[test]
+    fn smoke() {
+        let lock = SeqLock::new(1u64);
+        assert_eq!(lock.read(), 1);
+        lock.write(2);
+        assert_eq!(lock.update(|v| v * 10), 2);
+        assert_eq!(lock.into_inner(), 20);
+
+        // neither a multiple of a word, nor aligned to one
+        let bytes = SeqLock::new([1u8, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(bytes.read(), [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+        bytes.write([11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(bytes.read(), [11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1]);
+
+        let unit = SeqLock::new(());
+        unit.write(());
+        unit.read();
+
+        let tagged = SeqLock::new(Tagged::default());
+        let value = Tagged {
+            tag: 1,
+            value: 2,
+            ..Tagged::default()
+        };
+        tagged.write(value);
+        assert_eq!(tagged.read(), value);
+    }
+
+    /// Readers never see a value written in part.
+    #[test]
+    fn no_torn_reads() {
+        const READERS: usize = 4;
+        const WRITES: u64 = 100_000;
+
+        let lock = SeqLock::new([0u64; 4]);
+        scope(|s| {
+            for _ in 0..READERS {
+                let _ = s.spawn(|| loop {
+                    let value = lock.read();
+                    assert!(value.iter().all(|&v| v == value[0]), "{:?}", value);
+                    if value[0] == WRITES {
+                        break;
+                    }
+                });
+            }
+            for i in 1..=WRITES {
+                lock.write([i; 4]);
+            }
+        });
+    }
+
+    #[test]
+    fn update_concurrent() {
+        const THREADS: u64 = 8;
+        const COUNT: u64 = 10_000;
+
+        // a count and a sum, always updated together
+        let lock = SeqLock::new([0u64, 0]);
+        scope(|s| {
+            for t in 0..THREADS {
+                let lock = &lock;
+                let _ = s.spawn(move || {
+                    for _ in 0..COUNT {
+                        let _ = lock.update(|[count, sum]| [count + 1, sum + t]);
+                        let [count, sum] = lock.read();
+                        assert!(sum <= count * (THREADS - 1));
+                    }
+                });
+            }
+        });
+        assert_eq!(
+            lock.into_inner(),
+            [THREADS * COUNT, COUNT * THREADS * (THREADS - 1) / 2]
+        );
+    }
+}
+
+mod correctness {
+    use super::mock::model;
+    use super::mock::thread;
+    use super::Tagged;
+    use cs431_homework::SeqLock;
+    use std::sync::Arc;
+
+    /// A reader sees either value, but never a mix of them.
+    #[test]
+    fn read_write() {
+        model(|| {
+            let lock = Arc::new(SeqLock::new([0usize; 2]));
+            let writer = {
+                let lock = lock.clone();
+                thread::spawn(move || lock.write([1; 2]))
+            };
+            let value = lock.read();
+            assert!(value == [0; 2] || value == [1; 2], "{:?}", value);
+            writer.join().unwrap();
+            assert_eq!(lock.read(), [1; 2]);
+        });
+    }
+
+    /// A struct is copied whole, with its explicit padding.
+    #[test]
+    fn read_write_struct() {
+        model(|| {
+            let lock = Arc::new(SeqLock::new(Tagged::default()));
+            let value = Tagged {
+                tag: 1,
+                value: 2,
+                ..Tagged::default()
+            };
+            let writer = {
+                let lock = lock.clone();
+                thread::spawn(move || lock.write(value))
+            };
+            let read = lock.read();
+            assert!(read == Tagged::default() || read == value, "{:?}", read);
+            writer.join().unwrap();
+            assert_eq!(lock.read(), value);
+        });
+    }
+
+    /// Concurrent updates don't overwrite each other.
+    #[test]
+    fn update_update() {
+        model(|| {
+            let lock = Arc::new(SeqLock::new(0usize));
+            let other = {
+                let lock = lock.clone();
+                thread::spawn(move || lock.update(|v| v + 1))
+            };
+            let previous = lock.update(|v| v + 1);
+            assert!(previous + other.join().unwrap() == 1);
+            assert_eq!(lock.read(), 2);
+        });
+    }
+}
@@ -0,0 +1,160 @@
+use cs431_homework::hello_server::{Hooks, Limit, Report, Response, Router, Server, ServerConfig};
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+/// Hooks counting the reported connections.
+#[derive(Default)]
+struct Reports(AtomicUsize);
+
+impl Hooks for Reports {
+    fn on_report(&self, _: &Report) {
+        let _ = self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn server_run() {
+    let reports = Arc::new(Reports::default());
+    let server = Server::new(
+        ServerConfig::new()
+            .addr("127.0.0.1:0")
+            .threads(2)
+            .cache_capacity(16)
+            .hooks(Arc::clone(&reports)),
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    let listener = server.listener().clone();
+    let handle = thread::spawn(move || server.run());
+
+    for target in ["/", "/stats"] {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nConnection: close\r\n\r\n",
+            target
+        )
+        .unwrap();
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response).unwrap();
+        if target == "/stats" {
+            // the first connection may not be reported yet
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(response.contains("{\"connections\":"));
+        } else {
+            assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        }
+    }
+
+    listener.cancel().unwrap();
+    let stats = handle.join().unwrap();
+    // with the connection waking up the listener
+    assert!(stats.connections() >= 2);
+    assert_eq!(stats.statuses()[0], (200, 1));
+    assert_eq!(stats.cache_stats().unwrap().entries, 0);
+    assert_eq!(reports.0.load(Ordering::Relaxed), stats.connections());
+    assert_eq!(stats.abandoned(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn server_unix_socket() {
+    use std::os::unix::net::UnixStream;
+
+    let path = std::env::temp_dir().join(format!("cs431-server-{}.sock", std::process::id()));
+    // a socket file left by a server that is gone
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+    let server = Server::new(
+        ServerConfig::new()
+            .addr("127.0.0.1:0")
+            .threads(2)
+            .unix_socket(&path),
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    let shutdown = server.handle();
+    let handle = thread::spawn(move || server.run());
+
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\n\r\nGET /x/y HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).unwrap();
+    assert_eq!(response.matches("HTTP/1.1 404 Not Found\r\n").count(), 2);
+    // and TCP at the same time
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    shutdown.shutdown().unwrap();
+    let stats = handle.join().unwrap();
+    // with the connections waking up the listeners
+    assert!(stats.requests() >= 3);
+    assert!(!path.exists());
+}
+
+#[test]
+fn server_reload() {
+    let version = |body: &'static str| Router::new().get("/version", move |_| Response::ok(body));
+    let server = Server::new(
+        ServerConfig::new()
+            .addr("127.0.0.1:0")
+            .threads(2)
+            .router(version("one")),
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    let handle = server.handle();
+    let server = thread::spawn(move || server.run());
+
+    let get = |stream: &mut TcpStream| {
+        stream.write_all(b"GET /version HTTP/1.1\r\n\r\n").unwrap();
+        // the head and the body may arrive apart
+        let mut response = String::new();
+        let mut buf = [0; 1024];
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            response.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+            if let Some((head, body)) = response.split_once("\r\n\r\n") {
+                let len = head
+                    .split("\r\n")
+                    .find_map(|line| line.strip_prefix("Content-Length: "))
+                    .unwrap();
+                if body.len() == len.parse().unwrap() {
+                    return response;
+                }
+            }
+        }
+    };
+    let mut old = TcpStream::connect(addr).unwrap();
+    assert!(get(&mut old).ends_with("\r\n\r\none"));
+
+    handle.reload(
+        &ServerConfig::new()
+            .threads(3)
+            .router(version("two"))
+            .rate_limit(Limit::per_second(1, 1)),
+    );
+    // the established connection is kept, with the previous configuration
+    assert!(get(&mut old).ends_with("\r\n\r\none"));
+    assert!(get(&mut old).ends_with("\r\n\r\none"));
+    // while a new one gets the new configuration, on the thread added meanwhile
+    let mut new = TcpStream::connect(addr).unwrap();
+    assert!(get(&mut new).ends_with("\r\n\r\ntwo"));
+    assert!(get(&mut new).starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+    drop((old, new));
+
+    handle.shutdown().unwrap();
+    let stats = server.join().unwrap();
+    // and a 400 for the connection waking up the listener
+    let statuses = stats.statuses();
+    assert!(statuses.contains(&(200, 4)) && statuses.contains(&(429, 1)));
+}
@@ -0,0 +1,52 @@
+use crossbeam_channel::unbounded;
+use cs431_homework::hello_server::{CancellableTcpListener, Shards};
+use std::io::prelude::*;
+use std::net::TcpStream;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::thread::scope;
+use std::time::{Duration, Instant};
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET /{} HTTP/1.1\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn shards() {
+    let mut port = 24567;
+    let (addr, listener) = loop {
+        let addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), port));
+        if let Ok(listener) = CancellableTcpListener::bind(&addr) {
+            break (addr, listener);
+        }
+        port += 1;
+    };
+
+    let (report_sender, report_receiver) = unbounded();
+    let shards = Shards::new(4, report_sender);
+    assert_eq!(shards.len(), 4);
+    assert_eq!(shards.shard_of("a"), shards.shard_of("a"));
+    scope(|s| {
+        let _ = s.spawn(|| {
+            for (id, stream) in listener.incoming().enumerate() {
+                shards.dispatch(id, stream.unwrap()).unwrap();
+            }
+        });
+
+        assert!(get(addr, "a").contains("Result for key \"a\" is \"a🐕\""));
+        // the shard of the key has it cached
+        let start = Instant::now();
+        assert!(get(addr, "a").contains("a🐕"));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(get(addr, "").starts_with("HTTP/1.1 404"));
+
+        listener.cancel().unwrap();
+    });
+    drop(shards);
+    // the three requests, and the connection made by `cancel`
+    let reports = report_receiver.iter().count();
+    assert_eq!(reports, 4);
+}
@@ -0,0 +1,221 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch as epoch;
+use cs431_homework::{collector_flush, NonblockingConcurrentMap, NonblockingMap, SkipListMap};
+use std::thread::scope;
+
+pub mod map;
+
+#[test]
+pub fn smoke() {
+    let map = SkipListMap::<usize, usize>::new();
+
+    let guard = epoch::pin();
+
+    assert_eq!(map.insert(&37, 37, &guard), Ok(()));
+    assert_eq!(map.lookup(&42, &guard), None);
+    assert_eq!(map.lookup(&37, &guard), Some(&37));
+
+    assert_eq!(map.insert(&42, 42, &guard), Ok(()));
+    assert_eq!(map.insert(&42, 43, &guard), Err(43));
+    assert_eq!(map.lookup(&42, &guard), Some(&42));
+    assert_eq!(map.lookup(&37, &guard), Some(&37));
+
+    assert_eq!(map.delete(&37, &guard), Ok(&37));
+    assert_eq!(map.lookup(&42, &guard), Some(&42));
+    assert_eq!(map.lookup(&37, &guard), None);
+
+    assert_eq!(map.delete(&37, &guard), Err(()));
+    assert_eq!(map.insert(&37, 38, &guard), Ok(()));
+    assert_eq!(map.lookup(&37, &guard), Some(&38));
+}
+
+#[test]
+fn iter_ordered() {
+    let map = SkipListMap::new();
+    let guard = &epoch::pin();
+    for key in (0..1000).rev().step_by(2) {
+        assert!(map.insert(&key.to_string(), key, guard).is_ok());
+    }
+    assert!(map.delete(&"1".to_string(), guard).is_ok());
+
+    let mut expected = (0..1000)
+        .rev()
+        .step_by(2)
+        .filter(|&key| key != 1)
+        .map(|key| (key.to_string(), key))
+        .collect::<Vec<_>>();
+    expected.sort();
+    let entries = map
+        .iter(guard)
+        .map(|(key, value)| (key.clone(), *value))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, expected);
+}
+
+#[test]
+fn range() {
+    let map = SkipListMap::new();
+    let guard = &epoch::pin();
+    for key in (0..100).map(|key| key * 10) {
+        assert!(map.insert(&key, key, guard).is_ok());
+    }
+    let keys = |range: &mut dyn Iterator<Item = (&usize, &usize)>| {
+        range.map(|(key, _)| *key).collect::<Vec<_>>()
+    };
+
+    assert_eq!(keys(&mut map.range(15..45, guard)), [20, 30, 40]);
+    assert_eq!(keys(&mut map.range(20..=40, guard)), [20, 30, 40]);
+    assert_eq!(keys(&mut map.range(..20, guard)), [0, 10]);
+    assert_eq!(keys(&mut map.range(975.., guard)), [980, 990]);
+    assert_eq!(keys(&mut map.range(41..49, guard)), []);
+    assert_eq!(map.range(.., guard).count(), 100);
+
+    use core::ops::Bound::{Excluded, Included};
+    assert_eq!(
+        keys(&mut map.range((Excluded(20), Included(40)), guard)),
+        [30, 40]
+    );
+}
+
+#[test]
+fn range_concurrent() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+
+    // the even keys stay in the map, the odd ones are inserted and deleted meanwhile
+    let map = SkipListMap::new();
+    for key in (0..KEYS).step_by(2) {
+        assert!(map.insert(&key, key, &epoch::pin()).is_ok());
+    }
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            let _ = s.spawn(move || {
+                for round in 0..16 {
+                    for key in (1..KEYS).step_by(2).skip(t).step_by(THREADS) {
+                        let guard = &epoch::pin();
+                        if round % 2 == 0 {
+                            assert!(map.insert(&key, key, guard).is_ok());
+                        } else {
+                            assert!(map.delete(&key, guard).is_ok());
+                        }
+                    }
+                }
+            });
+        }
+        for _ in 0..64 {
+            let guard = &epoch::pin();
+            let keys = map
+                .range(KEYS / 4..KEYS / 2, guard)
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+            assert!(keys.windows(2).all(|w| w[0] < w[1]), "{:?}", keys);
+            let even = keys.iter().filter(|&&key| key % 2 == 0).count();
+            assert_eq!(even, KEYS / 8);
+        }
+    });
+    assert_eq!(map.iter(&epoch::pin()).count(), KEYS / 2);
+}
+
+#[test]
+fn drop_frees_all() {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug)]
+    struct Tracked;
+
+    impl Tracked {
+        fn new() -> Self {
+            let _ = CREATED.fetch_add(1, Ordering::Relaxed);
+            Self
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            let _ = DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+
+    let map = SkipListMap::new();
+    scope(|s| {
+        // joined, so that the threads have flushed their garbage
+        let mut handles = Vec::new();
+        for _ in 0..THREADS {
+            handles.push(s.spawn(|| {
+                for round in 0..8 {
+                    for key in 0..KEYS {
+                        let guard = &epoch::pin();
+                        if round % 2 == 0 {
+                            let _ = map.insert(&key, Tracked::new(), guard);
+                        } else {
+                            let _ = map.delete(&key, guard);
+                        }
+                    }
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    });
+    for key in 0..KEYS / 2 {
+        let _ = map.insert(&key, Tracked::new(), &epoch::pin());
+    }
+    drop(map);
+
+    collector_flush();
+    assert_eq!(
+        DROPPED.load(Ordering::Relaxed),
+        CREATED.load(Ordering::Relaxed)
+    );
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        String,
+        NonblockingConcurrentMap<_, _, SkipListMap<String, usize>>,
+    >(STEPS);
+}
+
+#[test]
+fn lookup_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 4096;
+    map::lookup_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipListMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn insert_concurrent() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 4096 * 4;
+    map::insert_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipListMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 64;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipListMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 16;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, SkipListMap<usize, usize>>>(
+        THREADS, STEPS,
+    );
+}
@@ -1,8 +1,43 @@
-use crossbeam_epoch as epoch;
-use cs431_homework::{NonblockingConcurrentMap, NonblockingMap, SplitOrderedList};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch::{self as epoch, Guard};
+use cs431_homework::hazard_pointer::collect;
+use cs431_homework::{
+    Config, FixedSplitOrderedList, HpSplitOrderedList, NonblockingConcurrentMap, NonblockingMap,
+    SplitOrderedList, SplitOrderedSet, ValueRef,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::thread::scope;
+use std::time::Duration;
 
 pub mod map;
 
+/// Counts the allocations of each thread.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCS: Cell<usize> = Cell::new(0);
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCS.try_with(|allocs| allocs.set(allocs.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocs() -> usize {
+    ALLOCS.with(Cell::get)
+}
+
 #[test]
 pub fn smoke() {
     let list = SplitOrderedList::<usize>::new();
@@ -39,6 +74,28 @@ pub fn fire() {
     assert_eq!(list.insert(&5, 5, &guard), Ok(()));
     assert_eq!(list.insert(&7, 7, &guard), Ok(()));
     assert_eq!(list.lookup(&7, &guard), Some(&7));
+    assert_eq!(list.delete(&7, &guard), Ok(&7));
+}
+
+#[test]
+pub fn full_key_range() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+
+    let keys = [0, 1, 1 << 63, (1 << 63) + 1, usize::MAX - 1, usize::MAX];
+    for key in keys {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    for key in keys {
+        assert_eq!(list.lookup(&key, &guard), Some(&key));
+    }
+    assert_eq!(list.delete(&usize::MAX, &guard), Ok(&usize::MAX));
+    assert_eq!(list.lookup(&usize::MAX, &guard), None);
+    assert_eq!(
+        list.lookup(&(usize::MAX - 1), &guard),
+        Some(&(usize::MAX - 1))
+    );
+    assert_eq!(list.lookup(&(1 << 63), &guard), Some(&(1 << 63)));
 }
 
 #[test]
@@ -59,6 +116,895 @@ pub fn buckets() {
     assert_eq!(list.insert(&7, 7, &guard), Ok(()));
 }
 
+#[test]
+fn clone() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for key in 0..1024 {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+
+    let copy = list.clone();
+    for key in (0..1024).step_by(2) {
+        assert_eq!(list.delete(&key, &guard), Ok(&key));
+    }
+    for key in 0..1024 {
+        assert_eq!(copy.lookup(&key, &guard), Some(&key));
+    }
+}
+
+#[test]
+fn upsert() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+
+    assert_eq!(list.upsert(&37, 37, &guard), Ok(None));
+    assert_eq!(list.lookup(&37, &guard), Some(&37));
+    assert_eq!(list.upsert(&37, 42, &guard), Ok(Some(&37)));
+    assert_eq!(list.lookup(&37, &guard), Some(&42));
+    assert_eq!(list.insert(&37, 0, &guard), Err(0));
+
+    assert_eq!(list.delete(&37, &guard), Ok(&42));
+    assert_eq!(list.lookup(&37, &guard), None);
+    assert_eq!(list.upsert(&37, 1, &guard), Ok(None));
+    assert_eq!(list.lookup(&37, &guard), Some(&1));
+}
+
+#[test]
+fn upsert_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 256;
+    const STEPS: usize = 16;
+
+    let list = SplitOrderedList::<usize>::new();
+    let inserted = AtomicUsize::new(0);
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            let inserted = &inserted;
+            s.spawn(move || {
+                for step in 0..STEPS {
+                    let guard = epoch::pin();
+                    for key in 0..KEYS {
+                        if list
+                            .upsert(&key, t * STEPS + step, &guard)
+                            .unwrap()
+                            .is_none()
+                        {
+                            inserted.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    // each key is inserted once and replaced otherwise
+    assert_eq!(inserted.into_inner(), KEYS);
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert!(list.delete(&key, &guard).is_ok());
+        assert_eq!(list.delete(&key, &guard), Err(()));
+    }
+}
+
+#[test]
+fn upsert_delete_u8() {
+    // a `u8` has no spare bit of its own to freeze a deleted value with
+    const THREADS: usize = 4;
+    const KEYS: usize = 16;
+    const STEPS: usize = 256;
+
+    let list = SplitOrderedList::<u8>::new();
+    let put = AtomicUsize::new(0);
+    let returned = AtomicUsize::new(0);
+    scope(|s| {
+        for t in 0..THREADS {
+            let (list, put, returned) = (&list, &put, &returned);
+            s.spawn(move || {
+                for step in 0..STEPS {
+                    let guard = epoch::pin();
+                    for key in 0..KEYS {
+                        let old = if (t + step + key) % 2 == 0 {
+                            let value = ((t * 31 + step * 7 + key) % 255 + 1) as u8;
+                            let _ = put.fetch_add(usize::from(value), Ordering::Relaxed);
+                            list.upsert(&key, value, &guard).unwrap()
+                        } else {
+                            list.delete(&key, &guard).ok()
+                        };
+                        let old = old.map_or(0, |&v| usize::from(v));
+                        let _ = returned.fetch_add(old, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    // each value is returned once by the upsert replacing it or the delete, or is still there
+    let guard = epoch::pin();
+    let left = (0..KEYS)
+        .map(|key| list.lookup(&key, &guard).map_or(0, |&v| usize::from(v)))
+        .sum::<usize>();
+    assert_eq!(put.into_inner(), returned.into_inner() + left);
+}
+
+#[test]
+fn hot_bucket_churn() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 64;
+    const STEPS: usize = 64;
+    // all keys fall into bucket 0, so the chain grows way beyond the hot threshold
+    let key = |t: usize, i: usize| (t * KEYS + i) << 32;
+
+    let list = SplitOrderedList::<usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move || {
+                for _ in 0..STEPS {
+                    let guard = epoch::pin();
+                    for i in 0..KEYS {
+                        assert_eq!(list.insert(&key(t, i), i, &guard), Ok(()));
+                    }
+                    for i in 0..KEYS {
+                        assert_eq!(list.lookup(&key(t, i), &guard), Some(&i));
+                    }
+                    for i in 0..KEYS {
+                        assert_eq!(list.delete(&key(t, i), &guard), Ok(&i));
+                    }
+                }
+            });
+        }
+    });
+
+    let guard = epoch::pin();
+    for t in 0..THREADS {
+        for i in 0..KEYS {
+            assert_eq!(list.lookup(&key(t, i), &guard), None);
+        }
+    }
+}
+
+#[test]
+fn shrink_and_regrow() {
+    const KEYS: usize = 4096;
+
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for round in 0..4 {
+        for i in 0..KEYS {
+            assert_eq!(list.insert(&i, i + round, &guard), Ok(()));
+        }
+        for i in 0..KEYS {
+            assert_eq!(list.lookup(&i, &guard), Some(&(i + round)));
+        }
+        // deleting every other key first leaves lookups going through retired buckets
+        for i in (0..KEYS).step_by(2) {
+            assert_eq!(list.delete(&i, &guard), Ok(&(i + round)));
+        }
+        for i in 0..KEYS {
+            let expected = if i % 2 == 0 { None } else { Some(i + round) };
+            assert_eq!(list.lookup(&i, &guard).copied(), expected);
+        }
+        for i in (1..KEYS).step_by(2) {
+            assert_eq!(list.delete(&i, &guard), Ok(&(i + round)));
+        }
+        for i in 0..KEYS {
+            assert_eq!(list.lookup(&i, &guard), None);
+        }
+    }
+}
+
+#[test]
+fn shrink_grow_race() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 1024;
+    const STEPS: usize = 16;
+
+    let list = SplitOrderedList::<usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move || {
+                // threads are out of phase, so some grow the table while others shrink it
+                for step in 0..STEPS {
+                    let keys = KEYS >> ((t + step) % 4);
+                    let guard = epoch::pin();
+                    for i in 0..keys {
+                        let key = i * THREADS + t;
+                        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                    }
+                    for i in 0..keys {
+                        let key = i * THREADS + t;
+                        assert_eq!(list.lookup(&key, &guard), Some(&key));
+                    }
+                    for i in 0..keys {
+                        let key = i * THREADS + t;
+                        assert_eq!(list.delete(&key, &guard), Ok(&key));
+                        assert_eq!(list.lookup(&key, &guard), None);
+                    }
+                }
+            });
+        }
+    });
+
+    let guard = epoch::pin();
+    for key in 0..KEYS * THREADS {
+        assert_eq!(list.lookup(&key, &guard), None);
+    }
+}
+
+#[test]
+fn with_config() {
+    let list = SplitOrderedList::<usize>::with_capacity(0);
+    assert_eq!(list.config(), Config::default());
+
+    let list = SplitOrderedList::<usize>::with_capacity(3000);
+    assert_eq!(list.config().initial_buckets, 2048);
+
+    for config in [
+        Config {
+            load_factor: 1,
+            initial_buckets: 1000,
+            ..Config::default()
+        },
+        Config {
+            load_factor: 16,
+            initial_buckets: 0,
+            ..Config::default()
+        },
+    ] {
+        let list = SplitOrderedList::<usize>::with_config(config);
+        assert_eq!(list.config().load_factor, config.load_factor);
+        assert!(list.config().initial_buckets.is_power_of_two());
+
+        let guard = epoch::pin();
+        for i in 0..4096 {
+            assert_eq!(list.insert(&i, i, &guard), Ok(()));
+        }
+        for i in 0..4096 {
+            assert_eq!(list.lookup(&i, &guard), Some(&i));
+        }
+        for i in 0..4096 {
+            assert_eq!(list.delete(&i, &guard), Ok(&i));
+        }
+        assert_eq!(list.clone().config(), list.config());
+    }
+}
+
+#[test]
+fn clear() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i, &guard), Ok(()));
+    }
+    list.clear(&guard);
+    for i in 0..1024 {
+        assert_eq!(list.lookup(&i, &guard), None);
+    }
+    // the list is still usable
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i + 1, &guard), Ok(()));
+    }
+    for i in 0..1024 {
+        assert_eq!(list.lookup(&i, &guard), Some(&(i + 1)));
+    }
+}
+
+#[test]
+fn clear_concurrent() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+
+    let list = SplitOrderedList::<usize>::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move || {
+                for _ in 0..16 {
+                    let guard = epoch::pin();
+                    for i in 0..KEYS {
+                        let key = i * THREADS + t;
+                        let _ = list.insert(&key, key, &guard);
+                    }
+                    list.clear(&guard);
+                }
+            });
+        }
+    });
+
+    let guard = epoch::pin();
+    list.clear(&guard);
+    for key in 0..KEYS * THREADS {
+        assert_eq!(list.lookup(&key, &guard), None);
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+}
+
+#[test]
+fn retain() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i * 10, &guard), Ok(()));
+    }
+    let mut visited = 0;
+    list.retain(
+        |k, v| {
+            assert_eq!(*v, k * 10);
+            visited += 1;
+            k % 3 == 0
+        },
+        &guard,
+    );
+    assert_eq!(visited, 1024);
+    for i in 0..1024 {
+        let expected = if i % 3 == 0 { Some(i * 10) } else { None };
+        assert_eq!(list.lookup(&i, &guard).copied(), expected);
+    }
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i, &guard).is_ok(), i % 3 != 0);
+    }
+}
+
+#[test]
+fn retain_concurrent() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+
+    let list = SplitOrderedList::<usize>::new();
+    scope(|s| {
+        // writers own disjoint odd keys, the sweeper deletes even keys only
+        for t in 0..THREADS {
+            let list = &list;
+            s.spawn(move || {
+                for _ in 0..16 {
+                    let guard = epoch::pin();
+                    for i in 0..KEYS {
+                        let key = 2 * (i * THREADS + t) + 1;
+                        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                        assert_eq!(list.lookup(&key, &guard), Some(&key));
+                    }
+                    for i in 0..KEYS {
+                        let key = 2 * (i * THREADS + t) + 1;
+                        assert_eq!(list.delete(&key, &guard), Ok(&key));
+                    }
+                }
+            });
+        }
+        let list = &list;
+        s.spawn(move || {
+            for _ in 0..64 {
+                let guard = epoch::pin();
+                for i in 0..KEYS {
+                    let _ = list.insert(&(2 * i), i, &guard);
+                }
+                list.retain(|k, _| k % 2 == 1, &guard);
+            }
+        });
+    });
+
+    let guard = epoch::pin();
+    for key in 0..2 * KEYS * THREADS {
+        assert_eq!(list.lookup(&key, &guard), None);
+    }
+}
+
+#[test]
+fn range() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for i in (0..1024).step_by(3) {
+        assert_eq!(list.insert(&i, i * 10, &guard), Ok(()));
+    }
+    assert_eq!(list.insert(&usize::MAX, 0, &guard), Ok(()));
+
+    let keys = |items: Vec<(usize, &usize)>| items.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+    // short ranges are looked up, long ones are filtered from a traversal
+    assert_eq!(keys(list.range(10..20, &guard)), vec![12, 15, 18]);
+    assert_eq!(keys(list.range(10..=21, &guard)), vec![12, 15, 18, 21]);
+    assert_eq!(
+        keys(list.range(..1000, &guard)),
+        (0..1000).step_by(3).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        keys(list.range(1020.., &guard)),
+        vec![1020, 1023, usize::MAX]
+    );
+    assert_eq!(keys(list.range(.., &guard)).len(), 1024 / 3 + 2);
+    assert_eq!(list.range(1000..1002, &guard), vec![]);
+    assert_eq!(list.range(3..=3, &guard), vec![(3, &30)]);
+}
+
+#[test]
+fn lookup_does_not_allocate() {
+    const KEYS: usize = 4096;
+
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    // grow the table without initializing most of the buckets
+    for i in 0..KEYS {
+        assert_eq!(list.insert(&(i << 20), i, &guard), Ok(()));
+    }
+
+    let before = allocs();
+    for i in 0..KEYS {
+        assert_eq!(list.lookup(&(i << 20), &guard), Some(&i));
+        assert_eq!(list.lookup(&(i * 7 + 1), &guard), None);
+    }
+    assert_eq!(allocs(), before);
+
+    // retired buckets are not initialized again either
+    for i in 0..KEYS - 1 {
+        assert_eq!(list.delete(&(i << 20), &guard), Ok(&i));
+    }
+    let before = allocs();
+    for i in 0..KEYS {
+        let _ = list.lookup(&(i * 7 + 1), &guard);
+    }
+    assert_eq!(list.lookup(&((KEYS - 1) << 20), &guard), Some(&(KEYS - 1)));
+    assert_eq!(allocs(), before);
+}
+
+#[test]
+fn update() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    assert_eq!(list.update(&1, |v| v + 1, &guard), None);
+    assert_eq!(list.insert(&1, 10, &guard), Ok(()));
+    assert_eq!(list.update(&1, |v| v + 1, &guard), Some(&10));
+    assert_eq!(list.lookup(&1, &guard), Some(&11));
+    assert_eq!(list.delete(&1, &guard), Ok(&11));
+    assert_eq!(list.update(&1, |v| v + 1, &guard), None);
+}
+
+#[test]
+fn update_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 16;
+    const STEPS: usize = 1024;
+
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, 0, &guard), Ok(()));
+    }
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..STEPS {
+                    let guard = epoch::pin();
+                    for key in 0..KEYS {
+                        assert!(list.update(&key, |v| v + 1, &guard).is_some());
+                    }
+                }
+            });
+        }
+    });
+    for key in 0..KEYS {
+        assert_eq!(list.lookup(&key, &guard), Some(&(THREADS * STEPS)));
+    }
+}
+
+#[test]
+fn compare_exchange_value() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 1024;
+
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    assert_eq!(list.compare_exchange_value(&1, &0, 1, &guard), Err(1));
+    assert_eq!(list.insert(&1, 0, &guard), Ok(()));
+    assert_eq!(list.compare_exchange_value(&1, &5, 1, &guard), Err(1));
+    assert_eq!(list.compare_exchange_value(&1, &0, 1, &guard), Ok(&0));
+    assert_eq!(list.lookup(&1, &guard), Some(&1));
+
+    // a CAS-loop counter
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..STEPS {
+                    let guard = epoch::pin();
+                    loop {
+                        let current = *list.lookup(&1, &guard).unwrap();
+                        if list
+                            .compare_exchange_value(&1, &current, current + 1, &guard)
+                            .is_ok()
+                        {
+                            break;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    assert_eq!(list.lookup(&1, &guard), Some(&(1 + THREADS * STEPS)));
+}
+
+#[test]
+fn iter() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    for i in 0..KEYS {
+        assert_eq!(list.insert(&(2 * i), i, &guard), Ok(()));
+    }
+    let mut items = list.iter(&guard).map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+    items.sort_unstable();
+    assert_eq!(items, (0..KEYS).map(|i| (2 * i, i)).collect::<Vec<_>>());
+
+    let done = AtomicUsize::new(0);
+    scope(|s| {
+        // churn the odd keys
+        for t in 0..THREADS {
+            let list = &list;
+            let done = &done;
+            s.spawn(move || {
+                for _ in 0..16 {
+                    let guard = epoch::pin();
+                    for i in 0..KEYS / THREADS {
+                        let key = 2 * (i * THREADS + t) + 1;
+                        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                    }
+                    for i in 0..KEYS / THREADS {
+                        let key = 2 * (i * THREADS + t) + 1;
+                        assert_eq!(list.delete(&key, &guard), Ok(&key));
+                    }
+                }
+                done.fetch_add(1, Ordering::Release);
+            });
+        }
+        // the even keys are present during every iteration, so each is yielded exactly once
+        s.spawn(|| {
+            while done.load(Ordering::Acquire) < THREADS {
+                let guard = epoch::pin();
+                let mut evens = Vec::new();
+                for (key, value) in list.iter(&guard) {
+                    if key % 2 == 0 {
+                        assert_eq!(key, 2 * value);
+                        evens.push(key);
+                    } else {
+                        assert_eq!(key, *value);
+                    }
+                }
+                evens.sort_unstable();
+                assert_eq!(evens, (0..KEYS).map(|i| 2 * i).collect::<Vec<_>>());
+            }
+        });
+    });
+}
+
+#[test]
+fn len_bounds() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 4096;
+
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    assert_eq!(list.len_bounds(), (0, 0));
+    for i in 0..KEYS {
+        assert_eq!(list.insert(&i, i, &guard), Ok(()));
+        assert_eq!(list.len_bounds(), (i + 1, i + 1));
+    }
+    assert_eq!(list.insert(&0, 0, &guard), Err(0));
+    assert_eq!(list.delete(&KEYS, &guard), Err(()));
+    assert_eq!(list.upsert(&0, 1, &guard), Ok(Some(&0)));
+    assert_eq!(list.len_bounds(), (KEYS, KEYS));
+    list.retain(|k, _| k % 2 == 0, &guard);
+    assert_eq!(list.len_bounds(), (KEYS / 2, KEYS / 2));
+    list.clear(&guard);
+    assert_eq!(list.len_bounds(), (0, 0));
+
+    // the number of items is always between `inserted - deleting` and `inserting - deleted`
+    let inserting = AtomicUsize::new(0);
+    let inserted = AtomicUsize::new(0);
+    let deleting = AtomicUsize::new(0);
+    let deleted = AtomicUsize::new(0);
+    scope(|s| {
+        for t in 0..THREADS {
+            let counters = (&inserting, &inserted, &deleting, &deleted);
+            let list = &list;
+            s.spawn(move || {
+                let (inserting, inserted, deleting, deleted) = counters;
+                let guard = epoch::pin();
+                for round in 0..4 {
+                    for i in 0..KEYS / THREADS {
+                        let key = i * THREADS + t;
+                        inserting.fetch_add(1, Ordering::SeqCst);
+                        assert_eq!(list.insert(&key, round, &guard), Ok(()));
+                        inserted.fetch_add(1, Ordering::SeqCst);
+                    }
+                    for i in 0..KEYS / THREADS {
+                        let key = i * THREADS + t;
+                        deleting.fetch_add(1, Ordering::SeqCst);
+                        assert_eq!(list.delete(&key, &guard), Ok(&round));
+                        deleted.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+        s.spawn(|| loop {
+            let min = inserted.load(Ordering::SeqCst);
+            let max = deleted.load(Ordering::SeqCst);
+            let (lower, upper) = list.len_bounds();
+            let min = min.saturating_sub(deleting.load(Ordering::SeqCst));
+            let max = inserting.load(Ordering::SeqCst) - max;
+            assert!(lower <= upper);
+            assert!(lower <= max, "lower bound {} > {}", lower, max);
+            assert!(upper >= min, "upper bound {} < {}", upper, min);
+            if deleted.load(Ordering::SeqCst) == 4 * KEYS {
+                break;
+            }
+        });
+    });
+    assert_eq!(list.len_bounds(), (0, 0));
+}
+
+#[test]
+fn diagnostics() {
+    let list = SplitOrderedList::<usize>::with_config(Config {
+        load_factor: 4,
+        initial_buckets: 4,
+        ..Config::default()
+    });
+    let guard = epoch::pin();
+    let diagnostics = list.diagnostics(&guard);
+    assert_eq!(diagnostics.buckets, 4);
+    assert!(diagnostics.entries.is_empty());
+    assert_eq!(diagnostics.chains, BTreeMap::from([(0, 0), (1, 0)]));
+    assert_eq!(diagnostics.max_chain(), 0);
+    assert_eq!(diagnostics.initialized_ratio(), 0.5);
+
+    // 0, 4, 8 in bucket 0 and 3 in bucket 3
+    for key in [0, 4, 8, 3] {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    // the insertions initialized the other buckets in advance
+    let diagnostics = list.diagnostics(&guard);
+    assert_eq!(diagnostics.entries, BTreeMap::from([(0, 3), (3, 1)]));
+    assert_eq!(
+        diagnostics.chains,
+        BTreeMap::from([(0, 3), (1, 0), (2, 0), (3, 1)])
+    );
+    assert_eq!(diagnostics.max_chain(), 3);
+    assert_eq!(diagnostics.avg_chain(), 1.0);
+    assert_eq!(diagnostics.initialized_ratio(), 1.0);
+
+    // the alternate format shows the items of each bucket
+    assert!(!format!("{:?}", list).contains("buckets: {"));
+    let pretty = format!("{:#?}", list);
+    assert!(pretty.contains("buckets: {"));
+    assert!(
+        pretty.contains("3: {\n            3: 3,\n        },"),
+        "{}",
+        pretty
+    );
+    assert_eq!(list.delete(&4, &guard), Ok(&4));
+    let diagnostics = list.diagnostics(&guard);
+    assert_eq!(diagnostics.entries, BTreeMap::from([(0, 2), (3, 1)]));
+    assert_eq!(diagnostics.chains[&0], 2);
+
+    // only the buckets seen are counted
+    let list = SplitOrderedList::<usize>::with_config(Config {
+        initial_buckets: 1 << 40,
+        ..Config::default()
+    });
+    assert_eq!(list.insert(&3, 3, &guard), Ok(()));
+    let diagnostics = list.diagnostics(&guard);
+    assert_eq!(diagnostics.buckets, 1 << 40);
+    assert_eq!(diagnostics.entries, BTreeMap::from([(3, 1)]));
+    assert_eq!(diagnostics.max_chain(), 1);
+}
+
+#[test]
+fn preinitialize_buckets() {
+    let list = SplitOrderedList::<usize>::new();
+    let guard = epoch::pin();
+    // all keys are in bucket 0, so no operation initializes the other buckets on its own
+    for i in 0..4096 {
+        assert_eq!(list.insert(&(i << 20), i, &guard), Ok(()));
+    }
+    let diagnostics = list.diagnostics(&guard);
+    assert_eq!(diagnostics.chains.len(), 2048);
+    assert_eq!(diagnostics.initialized_ratio(), 1.0);
+
+    let list = SplitOrderedList::<usize>::with_config(Config {
+        load_factor: 2,
+        initial_buckets: 1024,
+        ..Config::default()
+    });
+    assert_eq!(list.diagnostics(&guard).initialized_ratio(), 2.0 / 1024.0);
+    list.initialize_buckets(&guard);
+    assert_eq!(list.diagnostics(&guard).initialized_ratio(), 1.0);
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i, &guard), Ok(()));
+        assert_eq!(list.lookup(&i, &guard), Some(&i));
+    }
+}
+
+#[test]
+fn bulk_load() {
+    const N: usize = 10_000;
+    let list = SplitOrderedList::bulk_load((0..N).map(|i| (3 * i, i)));
+    let guard = epoch::pin();
+    assert_eq!(list.len_bounds(), (N, N));
+    let diagnostics = list.diagnostics(&guard);
+    assert_eq!(diagnostics.entries.values().sum::<usize>(), N);
+    assert_eq!(diagnostics.initialized_ratio(), 1.0);
+    // no doubling is needed
+    assert!(diagnostics.avg_chain() <= 2.0);
+    for i in 0..N {
+        assert_eq!(list.lookup(&(3 * i), &guard), Some(&i));
+        assert_eq!(list.lookup(&(3 * i + 1), &guard), None);
+    }
+    assert_eq!(list.insert(&1, 1, &guard), Ok(()));
+    assert_eq!(list.delete(&0, &guard), Ok(&0));
+    assert_eq!(list.len_bounds(), (N, N));
+
+    let empty = SplitOrderedList::<usize>::bulk_load(None);
+    assert_eq!(empty.len_bounds(), (0, 0));
+    assert_eq!(empty.lookup(&0, &guard), None);
+}
+
+#[test]
+#[should_panic(expected = "strictly increasing")]
+fn bulk_load_unsorted() {
+    let _ = SplitOrderedList::bulk_load(vec![(1, 1), (1, 2)]);
+}
+
+#[test]
+fn from_iter() {
+    let list = vec![(5, "a"), (1, "b"), (5, "c"), (3, "d")]
+        .into_iter()
+        .collect::<SplitOrderedList<_>>();
+    let guard = epoch::pin();
+    assert_eq!(list.len_bounds(), (3, 3));
+    assert_eq!(list.lookup(&1, &guard), Some(&"b"));
+    assert_eq!(list.lookup(&3, &guard), Some(&"d"));
+    // the last value wins, as with repeated upserts
+    assert_eq!(list.lookup(&5, &guard), Some(&"c"));
+}
+
+#[test]
+fn set() {
+    let set = SplitOrderedSet::new();
+    let guard = epoch::pin();
+    assert!(set.insert(1, &guard));
+    assert!(!set.insert(1, &guard));
+    assert!(set.insert(2, &guard));
+    assert!(set.contains(&1, &guard));
+    assert!(!set.contains(&3, &guard));
+    assert!(set.remove(&1, &guard));
+    assert!(!set.remove(&1, &guard));
+    assert_eq!(set.len_bounds(), (1, 1));
+    assert_eq!(format!("{:?}", set), "{2}");
+
+    let mut set = (0..100).collect::<SplitOrderedSet>();
+    set.extend(100..200);
+    set.retain(|key| key % 2 == 0, &guard);
+    let mut keys = set.iter(&guard).collect::<Vec<_>>();
+    keys.sort_unstable();
+    assert_eq!(keys, (0..200).step_by(2).collect::<Vec<_>>());
+    set.clear(&guard);
+    assert_eq!(set.len_bounds(), (0, 0));
+}
+
+#[test]
+fn set_operations() {
+    let guard = epoch::pin();
+    let evens = (0..20).step_by(2).collect::<SplitOrderedSet>();
+    let threes = (0..20).step_by(3).collect::<SplitOrderedSet>();
+    let sorted = |set: SplitOrderedSet| {
+        let mut keys = set.iter(&epoch::pin()).collect::<Vec<_>>();
+        keys.sort_unstable();
+        keys
+    };
+    assert_eq!(
+        sorted(evens.union(&threes, &guard)),
+        [0, 2, 3, 4, 6, 8, 9, 10, 12, 14, 15, 16, 18]
+    );
+    assert_eq!(sorted(evens.intersection(&threes, &guard)), [0, 6, 12, 18]);
+    assert_eq!(
+        sorted(evens.difference(&threes, &guard)),
+        [2, 4, 8, 10, 14, 16]
+    );
+    let sixes = evens.intersection(&threes, &guard);
+    assert!(sixes.is_subset(&evens, &guard));
+    assert!(!evens.is_subset(&sixes, &guard));
+    assert!(!evens.is_disjoint(&threes, &guard));
+    assert!(evens
+        .difference(&threes, &guard)
+        .is_disjoint(&threes, &guard));
+}
+
+#[test]
+fn set_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 4096;
+
+    let set = SplitOrderedSet::new();
+    let inserted = AtomicUsize::new(0);
+    scope(|s| {
+        for _ in 0..THREADS {
+            let _ = s.spawn(|| {
+                let guard = &mut epoch::pin();
+                for key in 0..STEPS {
+                    if set.insert(key, guard) {
+                        let _ = inserted.fetch_add(1, Ordering::Relaxed);
+                    }
+                    guard.repin();
+                }
+            });
+        }
+    });
+    // each key is inserted by exactly one thread
+    assert_eq!(inserted.into_inner(), STEPS);
+    assert_eq!(set.len_bounds(), (STEPS, STEPS));
+}
+
+#[test]
+fn snapshot() {
+    let list = (0..100)
+        .map(|i| (i, 2 * i))
+        .collect::<SplitOrderedList<_>>();
+    let guard = epoch::pin();
+    let snapshot = list.snapshot(&guard);
+    // changes after the snapshot are not seen
+    assert_eq!(list.delete(&0, &guard), Ok(&0));
+    assert_eq!(list.update(&1, |v| v + 1, &guard), Some(&2));
+    assert_eq!(list.insert(&100, 200, &guard), Ok(()));
+    assert_eq!(snapshot.len(), 100);
+    assert_eq!(snapshot.get(&0), Some(&0));
+    assert_eq!(snapshot.get(&1), Some(&2));
+    assert_eq!(snapshot.get(&100), None);
+    assert!(snapshot
+        .iter()
+        .map(|(k, v)| (k, *v))
+        .eq((0..100).map(|i| (i, 2 * i))));
+    assert_eq!(list.snapshot(&guard).get(&1), Some(&3));
+    assert!(SplitOrderedList::<usize>::new().snapshot(&guard).is_empty());
+}
+
+#[test]
+fn snapshot_concurrent() {
+    const KEYS: usize = 20_000;
+
+    // Keys are inserted and deleted in increasing order, so the keys at any point are a range.
+    let list = SplitOrderedList::<usize>::new();
+    let done = AtomicUsize::new(0);
+    scope(|s| {
+        let _ = s.spawn(|| {
+            let guard = &mut epoch::pin();
+            for key in 0..KEYS {
+                assert_eq!(list.insert(&key, key, guard), Ok(()));
+                guard.repin();
+            }
+        });
+        let _ = s.spawn(|| {
+            let guard = &mut epoch::pin();
+            for key in 0..KEYS {
+                while list.delete(&key, guard).is_err() {
+                    guard.repin();
+                }
+            }
+            let _ = done.fetch_add(1, Ordering::SeqCst);
+        });
+        let _ = s.spawn(|| {
+            while done.load(Ordering::SeqCst) == 0 {
+                let guard = epoch::pin();
+                let snapshot = list.snapshot(&guard);
+                let keys = snapshot.iter().map(|(k, _)| k).collect::<Vec<_>>();
+                if let (Some(first), Some(last)) = (keys.first(), keys.last()) {
+                    assert_eq!(keys.len(), last - first + 1, "not a range: {:?}", keys);
+                }
+            }
+        });
+    });
+}
+
 #[test]
 fn stress_sequential() {
     const STEPS: usize = 4096;
@@ -103,3 +1049,268 @@ fn log_concurrent() {
         THREADS, STEPS,
     );
 }
+
+#[test]
+fn hp_smoke() {
+    let list = HpSplitOrderedList::<usize>::new();
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i * 10), Ok(()));
+    }
+    assert_eq!(list.insert(&7, 0), Err(0));
+    for i in 0..1024 {
+        assert_eq!(list.lookup(&i, |v| v.copied()), Some(i * 10));
+    }
+    for i in (0..1024).step_by(2) {
+        assert_eq!(list.delete(&i), Ok(i * 10));
+    }
+    for i in 0..1024 {
+        let expected = if i % 2 == 0 { None } else { Some(i * 10) };
+        assert_eq!(list.lookup(&i, |v| v.copied()), expected);
+    }
+    assert_eq!(list.delete(&0), Err(()));
+}
+
+#[test]
+fn hp_stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<usize, HpSplitOrderedList<usize>>(STEPS);
+}
+
+#[test]
+fn hp_lookup_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 4096;
+    map::lookup_concurrent::<usize, HpSplitOrderedList<usize>>(THREADS, STEPS);
+}
+
+#[test]
+fn hp_insert_concurrent() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 4096 * 4;
+    map::insert_concurrent::<usize, HpSplitOrderedList<usize>>(THREADS, STEPS);
+}
+
+#[test]
+fn hp_stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 64;
+    map::stress_concurrent::<usize, HpSplitOrderedList<usize>>(THREADS, STEPS);
+}
+
+#[test]
+fn hp_log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 16;
+    map::log_concurrent::<usize, HpSplitOrderedList<usize>>(THREADS, STEPS);
+}
+
+#[test]
+fn hp_value_ref() {
+    #[derive(Debug, Clone)]
+    struct Entry {
+        name: String,
+        hits: usize,
+    }
+
+    let list = HpSplitOrderedList::new();
+    for i in 0..64 {
+        let entry = Entry {
+            name: format!("entry {}", i),
+            hits: i,
+        };
+        assert!(list.insert(&i, entry).is_ok());
+    }
+    assert!(list.get(&64).is_none());
+    assert_eq!(list.get(&3).unwrap().hits, 3);
+
+    let name = ValueRef::map(list.get(&7).unwrap(), |entry| entry.name.as_str());
+    assert_eq!(&*name, "entry 7");
+    assert_eq!(name.to_string(), "entry 7");
+
+    // the value stays protected after it is deleted and the retired nodes are collected
+    let hits = ValueRef::map(list.get(&42).unwrap(), |entry| &entry.hits);
+    assert_eq!(list.delete(&42).unwrap().hits, 42);
+    collect();
+    assert!(list.get(&42).is_none());
+    assert_eq!(&*name, "entry 7");
+    assert_eq!(*hits, 42);
+}
+
+#[test]
+fn fixed_smoke() {
+    let list = FixedSplitOrderedList::<usize, 8>::new();
+    let guard = &epoch::pin();
+    assert_eq!(list.buckets(), 8);
+    for i in 0..256 {
+        assert_eq!(list.insert(&i, i * 10, guard), Ok(()));
+    }
+    assert_eq!(list.insert(&7, 0, guard), Err(0));
+    for i in 0..256 {
+        assert_eq!(list.lookup(&i, guard), Some(&(i * 10)));
+    }
+    assert_eq!(list.upsert(&7, 70, guard), Ok(Some(&70)));
+    assert_eq!(list.upsert(&300, 3000, guard), Ok(None));
+    assert_eq!(list.lookup(&7, guard), Some(&70));
+    for i in (0..256).step_by(2) {
+        assert_eq!(list.delete(&i, guard), Ok(&(i * 10)));
+    }
+    assert_eq!(list.delete(&0, guard), Err(()));
+
+    let mut items = list.iter(guard).map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+    items.sort_unstable();
+    let expected = (1..256)
+        .step_by(2)
+        .map(|i| (i, i * 10))
+        .chain([(300, 3000)])
+        .collect::<Vec<_>>();
+    assert_eq!(items, expected);
+}
+
+#[test]
+fn fixed_stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        usize,
+        NonblockingConcurrentMap<_, _, FixedSplitOrderedList<usize, 64>>,
+    >(STEPS);
+}
+
+#[test]
+fn fixed_stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 64;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, FixedSplitOrderedList<usize, 64>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn fixed_log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 16;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, FixedSplitOrderedList<usize, 1024>>>(
+        THREADS, STEPS,
+    );
+}
+
+#[test]
+fn tombstones() {
+    let guard = epoch::pin();
+    let list = SplitOrderedList::<usize>::with_config(Config {
+        tombstone_ttl: Some(Duration::from_secs(3600)),
+        ..Config::default()
+    });
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i * 10, &guard), Ok(()));
+    }
+    for i in (0..1024).step_by(2) {
+        assert_eq!(list.delete(&i, &guard), Ok(&(i * 10)));
+        assert_eq!(list.delete(&i, &guard), Err(()));
+    }
+    assert_eq!(list.tombstones(), 512);
+    assert_eq!(list.len_bounds(), (512, 512));
+    for i in 0..1024 {
+        let expected = if i % 2 == 0 { None } else { Some(i * 10) };
+        assert_eq!(list.lookup(&i, &guard).copied(), expected);
+    }
+    assert_eq!(list.iter(&guard).count(), 512);
+    assert!(list.iter(&guard).all(|(key, _)| key % 2 == 1));
+    // nothing is old enough
+    assert_eq!(list.compact(&guard), 0);
+
+    // revived in place
+    assert_eq!(list.insert(&0, 1, &guard), Ok(()));
+    assert_eq!(list.insert(&0, 2, &guard), Err(2));
+    assert_eq!(list.upsert(&2, 3, &guard), Ok(None));
+    assert_eq!(list.update(&4, |v| v + 1, &guard), None);
+    assert_eq!(list.tombstones(), 510);
+    assert_eq!(list.lookup(&0, &guard), Some(&1));
+    assert_eq!(list.lookup(&2, &guard), Some(&3));
+    assert_eq!(list.len_bounds(), (514, 514));
+
+    list.retain(|key, _| key >= 512, &guard);
+    assert_eq!(list.len_bounds(), (256, 256));
+    assert_eq!(list.tombstones(), 768);
+
+    let list = SplitOrderedList::<usize>::with_config(Config {
+        tombstone_ttl: Some(Duration::ZERO),
+        ..Config::default()
+    });
+    for i in 0..32 {
+        assert_eq!(list.insert(&i, i, &guard), Ok(()));
+    }
+    for i in 0..32 {
+        assert_eq!(list.delete(&i, &guard), Ok(&i));
+    }
+    // below the threshold of the deleters
+    assert_eq!(list.tombstones(), 32);
+    assert_eq!(list.compact(&guard), 32);
+    assert_eq!(list.tombstones(), 0);
+    assert_eq!(list.iter(&guard).count(), 0);
+    assert_eq!(list.insert(&7, 7, &guard), Ok(()));
+    assert_eq!(list.lookup(&7, &guard), Some(&7));
+    assert_eq!(list.delete(&7, &guard), Ok(&7));
+
+    // the deleters compact the list
+    for i in 0..1024 {
+        assert_eq!(list.insert(&i, i, &guard), Ok(()));
+        assert_eq!(list.delete(&i, &guard), Ok(&i));
+    }
+    assert!(list.tombstones() < 64 * 2, "{}", list.tombstones());
+}
+
+/// A [`SplitOrderedList`] whose tombstones are unlinked as soon as possible.
+#[derive(Debug)]
+struct Tombstoned(SplitOrderedList<usize>);
+
+impl Default for Tombstoned {
+    fn default() -> Self {
+        Self(SplitOrderedList::with_config(Config {
+            tombstone_ttl: Some(Duration::ZERO),
+            ..Config::default()
+        }))
+    }
+}
+
+impl NonblockingMap<usize, usize> for Tombstoned {
+    fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a usize> {
+        self.0.lookup(key, guard)
+    }
+
+    fn insert(&self, key: &usize, value: usize, guard: &Guard) -> Result<(), usize> {
+        self.0.insert(key, value, guard)
+    }
+
+    fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a usize, ()> {
+        self.0.delete(key, guard)
+    }
+
+    fn upsert<'a>(
+        &'a self,
+        key: &usize,
+        value: usize,
+        guard: &'a Guard,
+    ) -> Result<Option<&'a usize>, usize> {
+        self.0.upsert(key, value, guard)
+    }
+}
+
+#[test]
+fn tombstone_stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<usize, NonblockingConcurrentMap<_, _, Tombstoned>>(STEPS);
+}
+
+#[test]
+fn tombstone_stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 64;
+    map::stress_concurrent::<usize, NonblockingConcurrentMap<_, _, Tombstoned>>(THREADS, STEPS);
+}
+
+#[test]
+fn tombstone_log_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 16;
+    map::log_concurrent::<usize, NonblockingConcurrentMap<_, _, Tombstoned>>(THREADS, STEPS);
+}
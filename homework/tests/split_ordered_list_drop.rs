@@ -0,0 +1,60 @@
+//! Checks that a dropped `SplitOrderedList` frees everything, in its own process so that no other
+//! test holds back the epoch collector. Run under Miri or ASan to check the nodes and the segments
+//! as well.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crossbeam_epoch as epoch;
+use cs431_homework::{collector_flush, NonblockingMap, SplitOrderedList};
+
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+struct Tracked(usize);
+
+impl Tracked {
+    fn new(value: usize) -> Self {
+        let _ = CREATED.fetch_add(1, Ordering::Relaxed);
+        Self(value)
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let _ = DROPPED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn drop_frees_all() {
+    const KEYS: usize = 4096;
+
+    let list = SplitOrderedList::new();
+    {
+        let guard = &epoch::pin();
+        for key in 0..KEYS {
+            assert!(list.insert(&key, Tracked::new(key), guard).is_ok());
+        }
+        // replaced values
+        for key in 0..KEYS / 2 {
+            assert!(list.upsert(&key, Tracked::new(key + 1), guard).is_ok());
+        }
+        // failed insertions give the value back
+        assert!(list.insert(&0, Tracked::new(0), guard).is_err());
+        // deleted nodes, and retired sentinels once it shrinks
+        for key in KEYS / 4..KEYS {
+            assert!(list.delete(&key, guard).is_ok());
+        }
+    }
+    let live = KEYS / 4;
+    let dropped = DROPPED.load(Ordering::Relaxed);
+    drop(list);
+    // what is still in the list is freed right away
+    assert!(DROPPED.load(Ordering::Relaxed) >= dropped + live);
+
+    collector_flush();
+    assert_eq!(
+        DROPPED.load(Ordering::Relaxed),
+        CREATED.load(Ordering::Relaxed)
+    );
+}
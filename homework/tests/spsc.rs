@@ -0,0 +1,126 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use cs431_homework::lockfree::spsc;
+use std::thread::{scope, yield_now};
+
+#[test]
+fn smoke() {
+    let (mut producer, mut consumer) = spsc::split(2);
+    assert_eq!(producer.capacity(), 2);
+    assert!(consumer.is_empty());
+    assert_eq!(consumer.pop(), None);
+
+    assert_eq!(producer.push(0), Ok(()));
+    assert_eq!(producer.push(1), Ok(()));
+    assert_eq!(producer.push(2), Err(2));
+    assert_eq!(consumer.len(), 2);
+    assert_eq!(consumer.pop(), Some(0));
+    assert_eq!(producer.push(2), Ok(()));
+    assert_eq!(producer.len(), 2);
+    assert_eq!(consumer.pop(), Some(1));
+    assert_eq!(consumer.pop(), Some(2));
+    assert_eq!(consumer.pop(), None);
+    assert!(producer.is_empty());
+}
+
+#[test]
+fn slices_wrap_around() {
+    let (mut producer, mut consumer) = spsc::split(5);
+    let mut buf = [0; 8];
+    for round in 0..10 {
+        let data = [round, round + 1, round + 2];
+        assert_eq!(producer.push_slice(&data), 3);
+        assert_eq!(consumer.pop_slice(&mut buf), 3);
+        assert_eq!(buf[..3], data);
+    }
+    assert_eq!(producer.push_slice(&[1, 2, 3, 4, 5, 6]), 5);
+    assert_eq!(producer.push_slice(&[7]), 0);
+    assert_eq!(consumer.pop_slice(&mut buf[..2]), 2);
+    assert_eq!(producer.push_slice(&[6, 7, 8]), 2);
+    assert_eq!(consumer.pop_slice(&mut buf), 5);
+    assert_eq!(buf[..5], [3, 4, 5, 6, 7]);
+    assert_eq!(consumer.pop_slice(&mut buf), 0);
+}
+
+#[test]
+#[should_panic]
+fn zero_capacity() {
+    let _ = spsc::split::<u8>(0);
+}
+
+#[test]
+fn drop_values() {
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Elem;
+    impl Drop for Elem {
+        fn drop(&mut self) {
+            let _ = DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let (mut producer, mut consumer) = spsc::split(4);
+    for _ in 0..3 {
+        assert!(producer.push(Elem).is_ok());
+    }
+    drop(consumer.pop());
+    drop(producer);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    drop(consumer);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn stream_bytes() {
+    const LEN: usize = 1 << 20;
+
+    let data = (0..LEN).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+    let (mut producer, mut consumer) = spsc::split(1000);
+    let received = scope(|s| {
+        let _ = s.spawn(|| {
+            let mut sent = 0;
+            while sent < LEN {
+                let end = (sent + 777).min(LEN);
+                match producer.push_slice(&data[sent..end]) {
+                    0 => yield_now(),
+                    n => sent += n,
+                }
+            }
+        });
+        let mut received = Vec::with_capacity(LEN);
+        let mut buf = [0; 512];
+        while received.len() < LEN {
+            match consumer.pop_slice(&mut buf) {
+                0 => yield_now(),
+                n => received.extend_from_slice(&buf[..n]),
+            }
+        }
+        received
+    });
+    assert_eq!(received, data);
+}
+
+#[test]
+fn push_pop_concurrent() {
+    const COUNT: usize = 100_000;
+
+    let (mut producer, mut consumer) = spsc::split(16);
+    scope(|s| {
+        let _ = s.spawn(move || {
+            for i in 0..COUNT {
+                let mut value = Box::new(i);
+                while let Err(v) = producer.push(value) {
+                    value = v;
+                    yield_now();
+                }
+            }
+        });
+        for i in 0..COUNT {
+            let value = loop {
+                match consumer.pop() {
+                    Some(value) => break value,
+                    None => yield_now(),
+                }
+            };
+            assert_eq!(*value, i);
+        }
+    });
+}
@@ -0,0 +1,126 @@
+use cs431_homework::arena::{ArenaMap, RequestArena};
+use cs431_homework::hello_server::{Handler, Request, Response, Router, StaticFiles};
+use std::fs;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread::scope;
+
+/// Creates an empty directory for the files of a test.
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cs431-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn get(files: &StaticFiles, path: &str, headers: &[(&str, &str)]) -> Response {
+    let arena = RequestArena::new();
+    let mut map = ArenaMap::new_in(&arena);
+    for &(name, value) in headers {
+        let _ = map.insert(name, value);
+    }
+    let request = Request::new("GET", path, map, &[]);
+    files.serve(path, &request)
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> &'a str {
+    response
+        .headers()
+        .iter()
+        .find(|(header, _)| header == name)
+        .map(|(_, value)| value.as_str())
+        .unwrap()
+}
+
+#[test]
+fn static_files_conditional() {
+    let dir = dir("conditional");
+    fs::create_dir(dir.join("css")).unwrap();
+    fs::write(dir.join("css/a.css"), "body {}").unwrap();
+    let files = StaticFiles::new(&dir);
+
+    let response = get(&files, "css/a.css", &[]);
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"body {}");
+    assert_eq!(header(&response, "Content-Type"), "text/css; charset=utf-8");
+    let etag = header(&response, "ETag").to_string();
+    let last_modified = header(&response, "Last-Modified").to_string();
+    assert!(last_modified.ends_with(" GMT"));
+
+    let response = get(&files, "css/a.css", &[("If-None-Match", &etag)]);
+    assert_eq!(response.status(), 304);
+    assert!(response.body().is_empty());
+    let response = get(
+        &files,
+        "css/a.css",
+        &[("If-Modified-Since", &last_modified)],
+    );
+    assert_eq!(response.status(), 304);
+    let response = get(&files, "css/a.css", &[("If-None-Match", "\"other\"")]);
+    assert_eq!(response.status(), 200);
+
+    // a changed file is read again
+    fs::write(dir.join("css/a.css"), "body { margin: 0 }").unwrap();
+    let response = get(&files, "css/a.css", &[("If-None-Match", &etag)]);
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.body(), b"body { margin: 0 }");
+    assert_ne!(header(&response, "ETag"), etag);
+
+    for path in [
+        "missing",
+        "css",
+        "../a.css",
+        "/etc/passwd",
+        "css/../css/a.css",
+    ] {
+        assert_eq!(get(&files, path, &[]).status(), 404, "{}", path);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn static_files_streamed() {
+    let dir = dir("streamed");
+    let contents = (0..100_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    fs::write(dir.join("large.bin"), &contents).unwrap();
+    let files = StaticFiles::with_cache(&dir, 1024, 1 << 20);
+
+    // only the version of a large file is cached
+    let response = get(&files, "large.bin", &[]);
+    assert_eq!(response.status(), 200);
+    assert!(response.body().is_empty());
+    assert_eq!(response.content_length(), contents.len() as u64);
+    let etag = header(&response, "ETag").to_string();
+    assert_eq!(
+        get(&files, "large.bin", &[("If-None-Match", &etag)]).status(),
+        304
+    );
+
+    let router = Router::new().get("/static/*path", move |request| {
+        files.serve(request.param("path").unwrap(), request)
+    });
+    let handler = Handler::new(router);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    scope(|s| {
+        let server = s.spawn(|| {
+            let (stream, _) = listener.accept().unwrap();
+            handler.handle_conn(0, stream)
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /static/large.bin HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response).unwrap();
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let head = std::str::from_utf8(&response[..end]).unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Type: application/octet-stream\r\n"));
+        assert!(head.contains("Content-Length: 100000\r\n"));
+        assert_eq!(&response[end..], &contents[..]);
+        assert_eq!(server.join().unwrap().requests(), 1);
+    });
+    fs::remove_dir_all(&dir).unwrap();
+}
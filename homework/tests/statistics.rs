@@ -0,0 +1,42 @@
+use cs431_homework::hello_server::{Histogram, Report, Served, Statistics};
+use std::time::Duration;
+
+#[test]
+fn statistics_histogram() {
+    let mut histogram = Histogram::new();
+    assert_eq!(histogram.percentile(50.0), None);
+    for ms in 1..=100 {
+        histogram.record(Duration::from_millis(ms));
+    }
+    assert_eq!(histogram.count(), 100);
+    assert_eq!(histogram.max(), Duration::from_millis(100));
+    for (p, exact) in [(0.0, 1), (50.0, 50), (95.0, 95), (99.0, 99), (100.0, 100)] {
+        let bound = histogram.percentile(p).unwrap();
+        let exact = Duration::from_millis(exact);
+        assert!(exact <= bound && bound <= exact * 2, "p{}: {:?}", p, bound);
+    }
+}
+
+#[test]
+fn statistics_json() {
+    let mut stats = Statistics::default();
+    stats.add_report(Report::new(
+        0,
+        vec![
+            Served::new(Some("a\"b".to_string()), 200, Duration::from_micros(3)),
+            Served::new(None, 404, Duration::from_micros(3)),
+        ],
+    ));
+    stats.add_report(Report::new(1, Vec::new()).timed_out());
+    assert_eq!(stats.statuses(), vec![(200, 1), (404, 1)]);
+    assert_eq!(
+        stats.to_json(),
+        concat!(
+            "{\"connections\":2,\"requests\":2,\"timeouts\":1,\"rejected\":0,",
+            "\"requests_per_connection\":1,",
+            "\"latency_us\":{\"p50\":3,\"p95\":3,\"p99\":3,\"max\":3},",
+            "\"statuses\":{\"200\":1,\"404\":1},",
+            "\"hot_keys\":[{\"key\":\"a\\\"b\",\"hits\":1}]}"
+        )
+    );
+}
@@ -1,9 +1,11 @@
 use crossbeam_channel::bounded;
-use cs431_homework::hello_server::CancellableTcpListener;
+use cs431_homework::hello_server::{
+    CancellableTcpListener, ConnectionLimit, Handler, Overflow, Statistics,
+};
 use std::io::prelude::*;
 use std::net::TcpStream;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
-use std::thread::scope;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpListener};
+use std::thread::{scope, sleep};
 use std::time::Duration;
 
 #[test]
@@ -35,3 +37,52 @@ fn cancellable_listener_cancel() {
         done_receiver.recv_timeout(Duration::from_secs(3)).unwrap();
     });
 }
+
+#[test]
+fn connection_limit_wait() {
+    let limit = ConnectionLimit::new(2, Overflow::Wait);
+    let first = limit.acquire().unwrap();
+    let _second = limit.acquire().unwrap();
+    assert_eq!(limit.active(), 2);
+    scope(|s| {
+        let waiting = s.spawn(|| limit.acquire().is_some());
+        sleep(Duration::from_millis(100));
+        assert!(!waiting.is_finished());
+        drop(first);
+        assert!(waiting.join().unwrap());
+    });
+    assert_eq!(limit.active(), 1);
+}
+
+#[test]
+fn connection_limit_reject() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let limit = ConnectionLimit::new(1, Overflow::Reject);
+    let handler = Handler::default();
+    scope(|s| {
+        let server = s.spawn(|| {
+            let mut stats = Statistics::default();
+            let mut permits = Vec::new();
+            for id in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                match limit.acquire() {
+                    // the first connection is kept open
+                    Some(permit) => permits.push((permit, stream)),
+                    None => stats.add_report(handler.reject(id, stream)),
+                }
+            }
+            stats
+        });
+        let _first = TcpStream::connect(addr).unwrap();
+        let mut second = TcpStream::connect(addr).unwrap();
+        let mut response = String::new();
+        let _ = second.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\nRetry-After: 1\r\n"));
+
+        let stats = server.join().unwrap();
+        assert_eq!(stats.rejected(), 1);
+        assert_eq!(stats.statuses(), vec![(503, 1)]);
+    });
+    assert_eq!(limit.active(), 0);
+}
@@ -1,5 +1,5 @@
-use crossbeam_channel::bounded;
-use cs431_homework::hello_server::ThreadPool;
+use crossbeam_channel::{bounded, unbounded};
+use cs431_homework::hello_server::{Affinity, JobError, Priority, ThreadPool};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Barrier};
 use std::thread::sleep;
@@ -57,6 +57,233 @@ fn thread_pool_drop_block() {
     assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
 }
 
+/// A pool with a maximum size grows when all threads are busy, and shrinks back once the extra
+/// threads are idle for the keep-alive period.
+#[test]
+fn thread_pool_dynamic_size() {
+    let pool = ThreadPool::builder(1)
+        .max_size(NUM_THREADS)
+        .keep_alive(Duration::from_millis(100))
+        .build();
+    assert_eq!(pool.size(), 1);
+
+    // waiting at the barrier needs all threads at once
+    let barrier = Arc::new(Barrier::new(NUM_THREADS));
+    let (done_sender, done_receiver) = bounded(NUM_THREADS);
+    for _ in 0..NUM_THREADS {
+        let barrier = barrier.clone();
+        let done_sender = done_sender.clone();
+        pool.execute(move || {
+            barrier.wait();
+            done_sender.send(()).unwrap();
+        });
+    }
+    for _ in 0..NUM_THREADS {
+        done_receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+    }
+    assert_eq!(pool.size(), NUM_THREADS);
+
+    sleep(Duration::from_millis(500));
+    assert_eq!(pool.size(), 1);
+
+    // the remaining thread still runs jobs, and the pool grows again
+    let counter = Arc::new(AtomicUsize::new(0));
+    run_jobs(&pool, &counter);
+    pool.join();
+    assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
+    assert!(pool.size() <= NUM_THREADS);
+}
+
+#[test]
+fn thread_pool_resize() {
+    let pool = ThreadPool::builder(1)
+        .keep_alive(Duration::from_millis(100))
+        .build();
+    pool.resize(NUM_THREADS, NUM_THREADS);
+    assert_eq!(pool.size(), NUM_THREADS);
+
+    // the new threads run jobs at once
+    let barrier = Arc::new(Barrier::new(NUM_THREADS + 1));
+    for _ in 0..NUM_THREADS {
+        let barrier = barrier.clone();
+        pool.execute(move || {
+            let _ = barrier.wait();
+        });
+    }
+    let _ = barrier.wait();
+
+    pool.resize(1, 1);
+    sleep(Duration::from_millis(500));
+    assert_eq!(pool.size(), 1);
+    assert_eq!(pool.submit(|| 42).join().unwrap(), 42);
+}
+
+#[test]
+fn thread_pool_affinity() {
+    let pool = ThreadPool::builder(NUM_THREADS)
+        .affinity(Affinity::PerCore)
+        .build();
+    let workers = pool.worker_cores();
+    assert_eq!(
+        workers.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        (0..NUM_THREADS).collect::<Vec<_>>()
+    );
+    if cfg!(target_os = "linux") {
+        assert!(workers.iter().all(|(_, cores)| cores.len() == 1));
+    }
+
+    // a thread that can't be pinned still runs jobs
+    let pool = ThreadPool::builder(1)
+        .affinity(Affinity::Cores(vec![1 << 20]))
+        .build();
+    assert_eq!(pool.worker_cores(), [(0, Vec::new())]);
+    assert_eq!(pool.submit(|| 42).join().unwrap(), 42);
+}
+
+/// A bounded queue rejects jobs with `try_execute`, and blocks `execute`, when full.
+#[test]
+fn thread_pool_bounded_queue() {
+    let pool = Arc::new(ThreadPool::with_queue_capacity(1, 2));
+    let (started_sender, started_receiver) = bounded(0);
+    let (release_sender, release_receiver) = bounded::<()>(0);
+    pool.execute(move || {
+        started_sender.send(()).unwrap();
+        let _ = release_receiver.recv();
+    });
+    started_receiver.recv().unwrap();
+
+    // the worker is busy, so the queue fills up
+    let counter = Arc::new(AtomicUsize::new(0));
+    for _ in 0..2 {
+        let counter = counter.clone();
+        assert!(pool
+            .try_execute(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+            .is_ok());
+    }
+    let rejected = {
+        let counter = counter.clone();
+        pool.try_execute(move || {
+            counter.fetch_add(10, Ordering::Relaxed);
+        })
+    };
+    let job = rejected.unwrap_err().0;
+
+    // `execute` waits for room in the queue
+    let (blocked_sender, blocked_receiver) = bounded(1);
+    let executer = {
+        let pool = pool.clone();
+        std::thread::spawn(move || {
+            pool.execute(job);
+            blocked_sender.send(()).unwrap();
+        })
+    };
+    assert!(blocked_receiver
+        .recv_timeout(Duration::from_millis(100))
+        .is_err());
+    drop(release_sender);
+    executer.join().unwrap();
+    pool.join();
+    assert_eq!(counter.load(Ordering::Relaxed), 12);
+}
+
+/// `shutdown_with_timeout` waits for the jobs that finish in time.
+#[test]
+fn thread_pool_shutdown_drained() {
+    let pool = ThreadPool::new(NUM_THREADS);
+    let counter = Arc::new(AtomicUsize::new(0));
+    run_jobs(&pool, &counter);
+    assert!(pool
+        .shutdown_with_timeout(Duration::from_secs(60))
+        .is_empty());
+    assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
+}
+
+/// `submit` returns the value of the job, or its panic as an error.
+#[test]
+fn thread_pool_submit() {
+    let pool = ThreadPool::new(NUM_THREADS);
+    let mut handles = Vec::new();
+    for i in 0..NUM_JOBS {
+        handles.push(pool.submit(move || i * 2));
+    }
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join().unwrap(), i * 2);
+    }
+
+    let (release_sender, release_receiver) = bounded::<()>(0);
+    let mut handle = pool.submit(move || {
+        let _ = release_receiver.recv();
+        "done"
+    });
+    assert!(handle.try_join().is_none());
+    drop(release_sender);
+    let result = loop {
+        if let Some(result) = handle.try_join() {
+            break result;
+        }
+        sleep(Duration::from_millis(1));
+    };
+    assert_eq!(result.unwrap(), "done");
+    assert!(handle.try_join().is_none());
+
+    let handle = pool.submit(|| -> usize { panic!("oops") });
+    match handle.join() {
+        Err(JobError::Panicked(payload)) => {
+            assert_eq!(*payload.downcast::<&str>().unwrap(), "oops")
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    // the panic is caught, so dropping the pool doesn't panic
+    drop(pool);
+}
+
+/// The handle of a job abandoned by `shutdown_with_timeout` returns `Cancelled`.
+#[test]
+fn thread_pool_submit_cancelled() {
+    let pool = ThreadPool::new(1);
+    let (release_sender, release_receiver) = bounded::<()>(0);
+    pool.execute(move || {
+        let _ = release_receiver.recv();
+    });
+    let handle = pool.submit(|| 42);
+    let abandoned = pool.shutdown_with_timeout(Duration::from_millis(100));
+    assert_eq!(abandoned.len(), 1);
+    drop(abandoned);
+    drop(release_sender);
+    assert!(matches!(handle.join(), Err(JobError::Cancelled)));
+}
+
+/// `shutdown_with_timeout` returns the jobs still queued at the deadline, without waiting for the
+/// running ones.
+#[test]
+fn thread_pool_shutdown_abandon() {
+    let pool = ThreadPool::new(1);
+    let (started_sender, started_receiver) = bounded(0);
+    let (release_sender, release_receiver) = bounded::<()>(0);
+    pool.execute(move || {
+        started_sender.send(()).unwrap();
+        let _ = release_receiver.recv();
+    });
+    let counter = Arc::new(AtomicUsize::new(0));
+    for _ in 0..3 {
+        let counter = counter.clone();
+        pool.execute(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+    started_receiver.recv().unwrap();
+
+    let abandoned = pool.shutdown_with_timeout(Duration::from_millis(100));
+    assert_eq!(abandoned.len(), 3);
+    drop(release_sender);
+    for job in abandoned {
+        job.run();
+    }
+    assert_eq!(counter.load(Ordering::Relaxed), 3);
+}
+
 /// This indirectly tests if the worker threads' `JoinHandle`s are joined when the pool is
 /// dropped.
 #[test]
@@ -67,3 +294,132 @@ fn thread_pool_drop_propagate_panic() {
         panic!();
     });
 }
+
+/// A panicking job doesn't kill its thread, and the panic is reported to the panic handler.
+#[test]
+fn thread_pool_panic_handler() {
+    let (panic_sender, panic_receiver) = unbounded();
+    let pool = ThreadPool::builder(NUM_THREADS)
+        .panic_handler(move |payload| {
+            let message = payload.downcast_ref::<&str>().copied().unwrap_or_default();
+            panic_sender.send(message.to_string()).unwrap();
+        })
+        .build();
+    for _ in 0..3 {
+        pool.execute(|| panic!("oops"));
+    }
+    let counter = Arc::new(AtomicUsize::new(0));
+    run_jobs(&pool, &counter);
+    pool.join();
+    assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
+    assert_eq!(pool.size(), NUM_THREADS);
+    assert_eq!(pool.panic_count(), 3);
+    drop(pool);
+    assert_eq!(panic_receiver.iter().collect::<Vec<_>>(), vec!["oops"; 3]);
+}
+
+/// The jobs of a scope may borrow locals, and are all done when the scope returns.
+#[test]
+fn thread_pool_scope() {
+    let pool = ThreadPool::new(NUM_THREADS);
+    let mut chunks = vec![vec![1usize; NUM_JOBS]; NUM_THREADS];
+    let total = AtomicUsize::new(0);
+    let count = pool.scope(|s| {
+        let mut count = 0;
+        for chunk in chunks.iter_mut() {
+            count += 1;
+            let total = &total;
+            s.execute(move || {
+                sleep(Duration::from_millis(10));
+                chunk.iter_mut().for_each(|x| *x *= 2);
+                let _ = total.fetch_add(chunk.iter().sum(), Ordering::Relaxed);
+            });
+        }
+        count
+    });
+    assert_eq!(count, NUM_THREADS);
+    assert_eq!(total.load(Ordering::Relaxed), 2 * NUM_JOBS * NUM_THREADS);
+    assert!(chunks.iter().flatten().all(|&x| x == 2));
+}
+
+/// A panic of a job of a scope propagates from the scope once all its jobs are done.
+#[test]
+fn thread_pool_scope_panic() {
+    let pool = ThreadPool::new(NUM_THREADS);
+    let counter = AtomicUsize::new(0);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        pool.scope(|s| {
+            s.execute(|| panic!("oops"));
+            for _ in 0..NUM_THREADS {
+                s.execute(|| {
+                    sleep(Duration::from_millis(50));
+                    let _ = counter.fetch_add(1, Ordering::Relaxed);
+                });
+            }
+        })
+    }));
+    assert_eq!(*result.unwrap_err().downcast::<&str>().unwrap(), "oops");
+    assert_eq!(counter.load(Ordering::Relaxed), NUM_THREADS);
+    // the panic was caught by the scope, not by the pool
+    assert_eq!(pool.panic_count(), 0);
+}
+
+/// Jobs of higher priority run first, but not always, so that the lower priority ones progress.
+#[test]
+fn thread_pool_priority() {
+    let pool = ThreadPool::new(1);
+    let (started_sender, started_receiver) = bounded(0);
+    let (release_sender, release_receiver) = bounded::<()>(0);
+    pool.execute(move || {
+        started_sender.send(()).unwrap();
+        let _ = release_receiver.recv();
+    });
+    started_receiver.recv().unwrap();
+    let (order_sender, order_receiver) = unbounded();
+    for priority in [Priority::Low, Priority::Normal, Priority::High] {
+        for _ in 0..8 {
+            let order_sender = order_sender.clone();
+            pool.execute_with_priority(move || order_sender.send(priority).unwrap(), priority);
+        }
+    }
+    drop(order_sender);
+    drop(release_sender);
+    let order = order_receiver.iter().collect::<Vec<_>>();
+    assert_eq!(order.len(), 24);
+    assert_eq!(order[0], Priority::High);
+    let high = order[..8].iter().filter(|&&p| p == Priority::High).count();
+    assert_eq!(high, 6);
+    assert!(order[..8].contains(&Priority::Low));
+    assert!(order[..8].contains(&Priority::Normal));
+    // starvation protection aside, the normal priority jobs run before the low priority ones
+    assert_eq!(order[23], Priority::Low);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn thread_pool_spawn_future() {
+    use cs431_homework::timer::sleep;
+    use std::time::Instant;
+
+    let pool = ThreadPool::new(2);
+    let start = Instant::now();
+    let mut handles = Vec::new();
+    for i in 0..40 {
+        handles.push(pool.spawn_future(async move {
+            sleep(Duration::from_millis(25)).await;
+            sleep(Duration::from_millis(25)).await;
+            i
+        }));
+    }
+    for (i, handle) in handles.into_iter().enumerate() {
+        assert_eq!(handle.join().unwrap(), i);
+    }
+    // the futures wait at once, without blocking the threads
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    let handle = pool.spawn_future(async { panic!("oops") });
+    assert!(matches!(handle.join(), Err(JobError::Panicked(_))));
+    // never woken, so dropped with its waker
+    let handle = pool.spawn_future(std::future::pending::<()>());
+    assert!(matches!(handle.join(), Err(JobError::Cancelled)));
+}
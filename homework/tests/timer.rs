@@ -0,0 +1,95 @@
+#![cfg(feature = "async")]
+
+use core::future::Future;
+use core::task::{Context, Poll};
+use core::time::Duration;
+use cs431_homework::timer::{sleep, timeout, Elapsed};
+use std::sync::Arc;
+use std::task::Wake;
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn sleep_waits() {
+    let start = Instant::now();
+    block_on(sleep(Duration::from_millis(50)));
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    // and not a revolution of the wheel later, even in the middle of a tick
+    for i in 0..20 {
+        let start = Instant::now();
+        block_on(sleep(Duration::from_micros(1000 + 150 * i)));
+        assert!(start.elapsed() < Duration::from_millis(250));
+    }
+}
+
+#[test]
+fn sleep_concurrent() {
+    const THREADS: usize = 16;
+    let handles = (0..THREADS)
+        .map(|t| {
+            thread::spawn(move || {
+                let dur = Duration::from_millis(10 * (t as u64 % 4));
+                let start = Instant::now();
+                block_on(sleep(dur));
+                assert!(start.elapsed() >= dur);
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+fn timeout_ok() {
+    let result = block_on(timeout(
+        sleep(Duration::from_millis(10)),
+        Duration::from_secs(10),
+    ));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn timeout_elapsed() {
+    let start = Instant::now();
+    let result = block_on(timeout(
+        sleep(Duration::from_secs(10)),
+        Duration::from_millis(20),
+    ));
+    assert_eq!(result, Err(Elapsed));
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn dropped_sleep() {
+    // a dropped timer must not keep the wheel from firing the others
+    for _ in 0..100 {
+        let mut sleep = Box::pin(sleep(Duration::from_secs(60)));
+        let waker = Arc::new(ThreadWaker(thread::current())).into();
+        assert!(sleep
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .is_pending());
+    }
+    block_on(sleep(Duration::from_millis(10)));
+}
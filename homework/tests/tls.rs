@@ -0,0 +1,95 @@
+#![cfg(feature = "tls")]
+
+use cs431_homework::hello_server::{Server, ServerConfig};
+use rustls::{Certificate, ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use std::fs::File;
+use std::io::{prelude::*, BufReader};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+/// The certificate of `localhost`, signed by the test CA.
+const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls/cert.pem");
+const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls/key.pem");
+const CA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/tls/ca.pem");
+
+fn client_config() -> Arc<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    for der in rustls_pemfile::certs(&mut BufReader::new(File::open(CA).unwrap())).unwrap() {
+        roots.add(&Certificate(der)).unwrap();
+    }
+    Arc::new(
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+}
+
+/// Sends the request over TLS, and returns the response.
+fn get(addr: SocketAddr, request: &str) -> std::io::Result<String> {
+    let conn = ClientConnection::new(client_config(), "localhost".try_into().unwrap()).unwrap();
+    let mut stream = StreamOwned::new(conn, TcpStream::connect(addr)?);
+    stream.write_all(request.as_bytes())?;
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[test]
+fn https() {
+    let server = Server::new(
+        ServerConfig::new()
+            .addr("127.0.0.1:0")
+            .threads(2)
+            .tls(CERT, KEY),
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    let listener = server.listener().clone();
+    let handle = thread::spawn(move || server.run());
+
+    let response = get(
+        addr,
+        "GET / HTTP/1.1\r\n\r\nGET /stats HTTP/1.1\r\nConnection: close\r\n\r\n",
+    )
+    .unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.contains("HTTP/1.1 200 OK\r\n"), "{}", response);
+
+    // a plain HTTP request is a failed handshake, and gets no response
+    let mut plain = TcpStream::connect(addr).unwrap();
+    plain
+        .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = Vec::new();
+    let _ = plain.read_to_end(&mut response);
+    assert!(!response.starts_with(b"HTTP/1.1"));
+
+    // and the server still serves the others
+    let response = get(addr, "GET / HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    listener.cancel().unwrap();
+    let stats = handle.join().unwrap();
+    assert!(stats.requests() >= 3);
+}
+
+#[test]
+fn invalid_tls_config() {
+    // the key isn't a certificate
+    let err = Server::new(ServerConfig::new().addr("127.0.0.1:0").tls(KEY, KEY)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let err = Server::new(
+        ServerConfig::new()
+            .addr("127.0.0.1:0")
+            .tls(CERT, KEY)
+            .shards(2),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
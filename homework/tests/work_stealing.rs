@@ -0,0 +1,114 @@
+use crossbeam_channel::bounded;
+use cs431_homework::hello_server::WorkStealingPool;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread::sleep;
+use std::time::Duration;
+
+const NUM_THREADS: usize = 4;
+const NUM_JOBS: usize = 1024;
+
+#[test]
+fn work_stealing_parallel() {
+    let pool = WorkStealingPool::new(NUM_THREADS);
+    let barrier = Arc::new(Barrier::new(NUM_THREADS));
+    let (done_sender, done_receiver) = bounded(NUM_THREADS);
+    for _ in 0..NUM_THREADS {
+        let barrier = barrier.clone();
+        let done_sender = done_sender.clone();
+        pool.execute(move || {
+            barrier.wait();
+            done_sender.send(()).unwrap();
+        });
+    }
+    for _ in 0..NUM_THREADS {
+        done_receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+    }
+}
+
+/// `join` and `drop` block until all jobs are finished.
+#[test]
+fn work_stealing_join_drop() {
+    let pool = WorkStealingPool::new(NUM_THREADS);
+    let counter = Arc::new(AtomicUsize::new(0));
+    for _ in 0..NUM_JOBS {
+        let counter = counter.clone();
+        pool.execute(move || {
+            sleep(Duration::from_micros(100));
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+    pool.join();
+    assert_eq!(counter.load(Ordering::Relaxed), NUM_JOBS);
+
+    for _ in 0..NUM_JOBS {
+        let counter = counter.clone();
+        pool.execute(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+    drop(pool);
+    assert_eq!(counter.load(Ordering::Relaxed), 2 * NUM_JOBS);
+}
+
+/// Spawns a binary tree of jobs of the given depth, each pushed to the deque of its parent's
+/// worker.
+fn spawn_tree(pool: &Arc<WorkStealingPool>, depth: usize, counter: &Arc<AtomicUsize>) {
+    let (child_pool, counter) = (pool.clone(), counter.clone());
+    pool.execute(move || {
+        counter.fetch_add(1, Ordering::Relaxed);
+        if depth > 0 {
+            spawn_tree(&child_pool, depth - 1, &counter);
+            spawn_tree(&child_pool, depth - 1, &counter);
+        }
+    });
+}
+
+#[test]
+fn work_stealing_nested() {
+    let pool = Arc::new(WorkStealingPool::new(NUM_THREADS));
+    let counter = Arc::new(AtomicUsize::new(0));
+    spawn_tree(&pool, 12, &counter);
+    pool.join();
+    assert_eq!(counter.load(Ordering::Relaxed), (1 << 13) - 1);
+}
+
+/// The jobs of a worker are stolen while it's busy.
+#[test]
+fn work_stealing_steal() {
+    let pool = Arc::new(WorkStealingPool::new(2));
+    let (done_sender, done_receiver) = bounded::<()>(0);
+    let child_pool = pool.clone();
+    pool.execute(move || {
+        // this worker blocks until the other one runs the job it pushed to its own deque
+        child_pool.execute(move || done_sender.send(()).unwrap());
+        done_receiver.recv().unwrap();
+    });
+    pool.join();
+}
+
+#[test]
+#[should_panic]
+fn work_stealing_drop_propagate_panic() {
+    let pool = WorkStealingPool::new(NUM_THREADS);
+    pool.execute(move || {
+        panic!();
+    });
+}
+
+/// A worker survives the panic of a job, so that `join` still returns.
+#[test]
+fn work_stealing_join_after_panic() {
+    let pool = WorkStealingPool::new(1);
+    pool.execute(|| panic!());
+    pool.join();
+    assert_eq!(pool.panic_count(), 1);
+
+    let (done_sender, done_receiver) = bounded(1);
+    pool.execute(move || done_sender.send(()).unwrap());
+    done_receiver.recv_timeout(Duration::from_secs(3)).unwrap();
+    pool.join();
+    // dropping the pool still reports the panic
+    assert!(panic::catch_unwind(AssertUnwindSafe(|| drop(pool))).is_err());
+}
@@ -26,6 +26,14 @@ pub trait RawTryLock: RawLock {
 }
 
 /// A type-safe lock.
+///
+/// # Panics
+///
+/// Unlike `std::sync::Mutex`, locks are not poisoned. A [`LockGuard`] releases the lock when it is
+/// dropped during unwinding, so a thread panicking while holding the lock never blocks the others.
+/// The protected value is left as the panicking thread left it, and it is up to the user to keep it
+/// consistent. A lock held by a leaked guard (e.g., one turned into a raw address with
+/// [`LockGuard::into_raw`] and never restored) can be released with [`Lock::unlock_unchecked`].
 #[repr(C)]
 #[derive(Debug)]
 pub struct Lock<L: RawLock, T> {
@@ -186,4 +194,24 @@ pub mod tests {
         d.sort();
         assert_eq!(d.deref(), &(1..LENGTH).collect::<Vec<usize>>());
     }
+
+    pub fn panic_releases<L: RawLock>() {
+        const THREADS: usize = 8;
+        let d = Lock::<L, usize>::new(0);
+
+        scope(|s| {
+            for _ in 0..THREADS {
+                let d = &d;
+                s.spawn(move |_| {
+                    let mut d = d.lock();
+                    *d += 1;
+                    panic!("panicking while holding the lock");
+                });
+            }
+        })
+        .unwrap_err();
+
+        // not poisoned, and all the updates are visible
+        assert_eq!(*d.lock(), THREADS);
+    }
 }
@@ -63,4 +63,9 @@ mod tests {
     fn smoke() {
         api::tests::smoke::<ClhLock>();
     }
+
+    #[test]
+    fn panic_releases() {
+        api::tests::panic_releases::<ClhLock>();
+    }
 }
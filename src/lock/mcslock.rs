@@ -13,7 +13,23 @@ struct Node {
 #[derive(Debug, Clone)]
 pub struct Token(*mut CachePadded<Node>);
 
-/// An MCS lock.
+/// Mellor-Crummey and Scott's queue lock.
+///
+/// Each thread waiting for the lock appends its own node to a queue and spins on it, until its
+/// predecessor hands the lock over. Unlike with a [`SpinLock`], the waiters don't all spin on the
+/// same cache line, so a release only invalidates the cache of the next one, and the lock is
+/// taken in FIFO order. The other side is that a preempted waiter delays all the threads behind
+/// it.
+///
+/// Like a `std::sync::Mutex`, it protects a value through [`Lock`]:
+///
+/// ```
+/// use cs431::lock::{Lock, McsLock};
+///
+/// let counter = Lock::<McsLock, usize>::new(0);
+/// *counter.lock() += 1;
+/// assert_eq!(*counter.try_lock().unwrap(), 1);
+/// ```
 #[derive(Debug)]
 pub struct McsLock {
     tail: AtomicPtr<CachePadded<Node>>,
@@ -82,13 +98,55 @@ impl RawLock for McsLock {
     }
 }
 
+impl RawTryLock for McsLock {
+    fn try_lock(&self) -> Result<Self::Token, ()> {
+        if !self.tail.load(Ordering::Relaxed).is_null() {
+            return Err(());
+        }
+
+        let node = Box::into_raw(Box::new(CachePadded::new(Node::new())));
+        if self
+            .tail
+            .compare_exchange(ptr::null_mut(), node, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            drop(unsafe { Box::from_raw(node) });
+            return Err(());
+        }
+
+        Ok(Token(node))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use crossbeam_utils::thread::scope;
+
     use super::super::api;
     use super::McsLock;
+    use crate::lock::Lock;
 
     #[test]
     fn smoke() {
         api::tests::smoke::<McsLock>();
     }
+
+    #[test]
+    fn panic_releases() {
+        api::tests::panic_releases::<McsLock>();
+    }
+
+    #[test]
+    fn try_lock() {
+        let lock = Lock::<McsLock, usize>::new(0);
+        let guard = lock.lock();
+        scope(|s| {
+            s.spawn(|_| assert!(lock.try_lock().is_err()));
+        })
+        .unwrap();
+        drop(guard);
+
+        *lock.try_lock().unwrap() += 1;
+        assert_eq!(lock.into_inner(), 1);
+    }
 }
@@ -98,4 +98,9 @@ mod tests {
     fn smoke() {
         api::tests::smoke::<McsParkingLock>();
     }
+
+    #[test]
+    fn panic_releases() {
+        api::tests::panic_releases::<McsParkingLock>();
+    }
 }
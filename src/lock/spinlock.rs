@@ -56,4 +56,9 @@ mod tests {
     fn smoke() {
         api::tests::smoke::<SpinLock>();
     }
+
+    #[test]
+    fn panic_releases() {
+        api::tests::panic_releases::<SpinLock>();
+    }
 }
@@ -48,4 +48,9 @@ mod tests {
     fn smoke() {
         api::tests::smoke::<TicketLock>();
     }
+
+    #[test]
+    fn panic_releases() {
+        api::tests::panic_releases::<TicketLock>();
+    }
 }
@@ -73,12 +73,32 @@ impl<K, V> Node<K, V> {
     pub fn into_value(self) -> V {
         self.value
     }
+
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the value.
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    /// Returns the pointer to the next node. Its tag is set if this node is logically deleted.
+    pub fn next(&self) -> &Atomic<Node<K, V>> {
+        &self.next
+    }
 }
 
 impl<'g, K, V> Cursor<'g, K, V>
 where
     K: Ord,
 {
+    /// Creates a cursor.
+    pub fn new(prev: &'g Atomic<Node<K, V>>, curr: Shared<'g, Node<K, V>>) -> Self {
+        Self { prev, curr }
+    }
+
     /// Creates a cursor from raw pointers.
     ///
     /// # Safety
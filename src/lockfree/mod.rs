@@ -3,9 +3,11 @@
 #[macro_use]
 mod utils;
 pub mod list;
+pub mod ms_queue;
 mod queue;
 mod stack;
 
 pub use list::List;
+pub use ms_queue::MsQueue;
 pub use queue::Queue;
 pub use stack::Stack;
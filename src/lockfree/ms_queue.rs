@@ -0,0 +1,228 @@
+//! Michael-Scott queue that pins the current thread itself.
+
+use crossbeam_epoch::pin;
+use crossbeam_utils::Backoff;
+
+use super::Queue;
+
+/// Michael-Scott queue, like [`Queue`], but taking no guard: each operation pins the current
+/// thread for its duration.
+///
+/// Usable with any number of producers and consumers.
+#[derive(Debug)]
+pub struct MsQueue<T> {
+    queue: Queue<T>,
+}
+
+impl<T> Default for MsQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> MsQueue<T> {
+    /// Create a new, empty queue.
+    pub fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+        }
+    }
+
+    /// Adds `t` to the back of the queue.
+    pub fn push(&self, t: T) {
+        self.queue.push(t, &pin());
+    }
+
+    /// Attempts to dequeue from the front.
+    ///
+    /// Returns `None` if the queue is observed to be empty.
+    pub fn try_pop(&self) -> Option<T> {
+        self.queue.try_pop(&pin())
+    }
+
+    /// Dequeues from the front, waiting for a value if the queue is empty.
+    ///
+    /// The thread spins, then yields, with a backoff between the attempts, so it's meant for
+    /// short waits.
+    pub fn pop(&self) -> T {
+        let backoff = Backoff::new();
+        loop {
+            if let Some(t) = self.try_pop() {
+                return t;
+            }
+            backoff.snooze();
+        }
+    }
+
+    /// Returns `true` if the queue is observed to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty(&pin())
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread;
+
+    const CONC_COUNT: i64 = 100000;
+
+    #[test]
+    fn push_pop_seq() {
+        let q = MsQueue::new();
+        assert!(q.is_empty());
+        for i in 0..200 {
+            q.push(i);
+        }
+        assert!(!q.is_empty());
+        for i in 0..200 {
+            assert_eq!(q.pop(), i);
+        }
+        assert_eq!(q.try_pop(), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn pop_waits() {
+        let q = MsQueue::new();
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                for i in 0..CONC_COUNT {
+                    assert_eq!(q.pop(), i);
+                }
+            });
+            for i in 0..CONC_COUNT {
+                q.push(i);
+            }
+        })
+        .unwrap();
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn mpmc() {
+        const THREADS: i64 = 4;
+        let q = MsQueue::new();
+        let popped = thread::scope(|scope| {
+            for t in 0..THREADS {
+                let q = &q;
+                scope.spawn(move |_| {
+                    for i in 0..CONC_COUNT {
+                        q.push(t * CONC_COUNT + i);
+                    }
+                });
+            }
+            let mut consumers = Vec::new();
+            for _ in 0..THREADS {
+                consumers.push(scope.spawn(|_| {
+                    // the values of each producer are popped in order
+                    let mut last = vec![-1; THREADS as usize];
+                    let mut popped = Vec::new();
+                    for _ in 0..CONC_COUNT {
+                        let v = q.pop();
+                        let producer = (v / CONC_COUNT) as usize;
+                        assert!(v > last[producer]);
+                        last[producer] = v;
+                        popped.push(v);
+                    }
+                    popped
+                }));
+            }
+            consumers
+                .into_iter()
+                .flat_map(|consumer| consumer.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+        .unwrap();
+
+        let mut popped = popped;
+        popped.sort_unstable();
+        assert_eq!(popped, (0..THREADS * CONC_COUNT).collect::<Vec<_>>());
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn drop_values() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+        struct Elem;
+        impl Drop for Elem {
+            fn drop(&mut self) {
+                let _ = DROPS.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let q = MsQueue::new();
+        for _ in 0..10 {
+            q.push(Elem);
+        }
+        drop(q.pop());
+        drop(q);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 10);
+    }
+}
+
+#[cfg(all(test, feature = "check-loom"))]
+mod loom_test {
+    use super::*;
+    use loom::sync::Arc;
+    use loom::thread;
+
+    /// A value is popped at most once, and the values of a producer are popped in order.
+    #[test]
+    fn push_try_pop() {
+        loom::model(|| {
+            let q = Arc::new(MsQueue::new());
+            let producer = {
+                let q = q.clone();
+                thread::spawn(move || {
+                    q.push(1);
+                    q.push(2);
+                })
+            };
+            let consumer = {
+                let q = q.clone();
+                thread::spawn(move || [q.try_pop(), q.try_pop()])
+            };
+            producer.join().unwrap();
+            let mut popped = consumer
+                .join()
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            assert!(popped.windows(2).all(|w| w[0] < w[1]), "{:?}", popped);
+            while let Some(v) = q.try_pop() {
+                popped.push(v);
+            }
+            popped.sort_unstable();
+            assert_eq!(popped, [1, 2]);
+        });
+    }
+
+    /// Concurrent pushes both get in, and concurrent pops never pop the same value.
+    #[test]
+    fn push_push_pop_pop() {
+        loom::model(|| {
+            let q = Arc::new(MsQueue::new());
+            let spawn = |v| {
+                let q = q.clone();
+                thread::spawn(move || {
+                    q.push(v);
+                    q.try_pop()
+                })
+            };
+            let (t1, t2) = (spawn(1), spawn(2));
+            let mut popped = [t1.join().unwrap(), t2.join().unwrap()]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>();
+            while let Some(v) = q.try_pop() {
+                popped.push(v);
+            }
+            popped.sort_unstable();
+            assert_eq!(popped, [1, 2]);
+        });
+    }
+}
@@ -4,13 +4,20 @@
 //!
 //! Michael and Scott.  Simple, Fast, and Practical Non-Blocking and Blocking Concurrent Queue
 //! Algorithms.  PODC 1996.  http://dl.acm.org/citation.cfm?id=248106
+//!
+//! With the `check-loom` feature, the pointers of the queue are loom's atomics, so that the races
+//! on them are model checked. The garbage collector is not.
 
 use core::mem::MaybeUninit;
 use core::ptr;
 use core::sync::atomic::Ordering;
 
-use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+#[cfg(not(feature = "check-loom"))]
+use crossbeam_epoch::Atomic;
+use crossbeam_epoch::{unprotected, Guard, Owned, Shared};
 use crossbeam_utils::CachePadded;
+#[cfg(feature = "check-loom")]
+use loom_atomic::Atomic;
 
 /// Michael-Scott queue.
 // The representation here is a singly-linked list, with a sentinel node at the front. In general
@@ -153,6 +160,13 @@ impl<T> Queue<T> {
             }
         }
     }
+
+    /// Returns `true` if the queue is observed to be empty.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
+        let head = self.head.load(Ordering::Acquire, guard);
+        let h = unsafe { head.deref() };
+        h.next.load(Ordering::Acquire, guard).is_null()
+    }
 }
 
 impl<T> Drop for Queue<T> {
@@ -169,7 +183,59 @@ impl<T> Drop for Queue<T> {
     }
 }
 
-#[cfg(test)]
+/// Atomic pointer of loom, with the part of the interface of `crossbeam_epoch::Atomic` that the
+/// queue uses.
+#[cfg(feature = "check-loom")]
+mod loom_atomic {
+    use core::fmt;
+    use core::ptr;
+    use core::sync::atomic::Ordering;
+    use crossbeam_epoch::{Guard, Shared};
+    use loom::sync::atomic::AtomicPtr;
+
+    pub(super) struct Atomic<T>(AtomicPtr<T>);
+
+    impl<T> Atomic<T> {
+        pub(super) fn null() -> Self {
+            Self(AtomicPtr::new(ptr::null_mut()))
+        }
+
+        pub(super) fn load<'g>(&self, ord: Ordering, _: &'g Guard) -> Shared<'g, T> {
+            Shared::from(self.0.load(ord) as *const T)
+        }
+
+        pub(super) fn store(&self, new: Shared<'_, T>, ord: Ordering) {
+            self.0.store(new.as_raw() as *mut T, ord);
+        }
+
+        pub(super) fn compare_exchange<'g>(
+            &self,
+            current: Shared<'_, T>,
+            new: Shared<'g, T>,
+            success: Ordering,
+            failure: Ordering,
+            _: &'g Guard,
+        ) -> Result<Shared<'g, T>, Shared<'g, T>> {
+            self.0
+                .compare_exchange(
+                    current.as_raw() as *mut T,
+                    new.as_raw() as *mut T,
+                    success,
+                    failure,
+                )
+                .map(|_| new)
+                .map_err(|actual| Shared::from(actual as *const T))
+        }
+    }
+
+    impl<T> fmt::Debug for Atomic<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("Atomic")
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
 mod test {
     use super::*;
     use crossbeam_epoch::pin;
@@ -0,0 +1,334 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned, Shared};
+use crossbeam_utils::Backoff;
+
+/// Exchange point where two threads swap values.
+///
+/// A thread that arrives first publishes an offer and waits, and the next one takes the offer and
+/// completes the exchange. The offers are kept in a Treiber stack, so that a thread waiting for
+/// a partner of a specific role (see [`Rendezvous`](super::Rendezvous)) doesn't block the others.
+#[derive(Debug)]
+pub struct Exchanger<T> {
+    /// Stack of the offers waiting for a partner. All the waiting offers have the same role.
+    head: Atomic<Offer<T>>,
+}
+
+unsafe impl<T: Send> Send for Exchanger<T> {}
+unsafe impl<T: Send> Sync for Exchanger<T> {}
+
+/// Role of a thread in an exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Role {
+    /// Exchanges with anyone.
+    Any,
+    /// Exchanges with a `Take`.
+    Give,
+    /// Exchanges with a `Give`.
+    Take,
+}
+
+impl Role {
+    fn matches(self, other: Role) -> bool {
+        matches!(
+            (self, other),
+            (Role::Any, Role::Any) | (Role::Give, Role::Take) | (Role::Take, Role::Give)
+        )
+    }
+}
+
+/// The offer is waiting for a partner.
+const WAITING: usize = 0;
+/// A partner took the offer and is writing its item.
+const BUSY: usize = 1;
+/// The exchange is done.
+const MATCHED: usize = 2;
+/// The owner gave up waiting.
+const CANCELLED: usize = 3;
+
+/// Offer of a waiting thread.
+///
+/// A matched offer is freed by its owner, and a cancelled one by whoever removes it from the
+/// stack.
+#[derive(Debug)]
+struct Offer<T> {
+    role: Role,
+    state: AtomicUsize,
+    /// The item of the owner, moved out by the partner once it put the offer in `BUSY`, or by the
+    /// owner once it put the offer in `CANCELLED`.
+    item: UnsafeCell<MaybeUninit<T>>,
+    /// The item of the partner, written before the offer is put in `MATCHED`.
+    reply: UnsafeCell<MaybeUninit<T>>,
+    owner: Thread,
+    next: Atomic<Offer<T>>,
+}
+
+impl<T> Default for Exchanger<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Exchanger<T> {
+    /// Creates a new exchanger.
+    pub const fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+
+    /// Swaps `item` with the item of another thread calling `exchange`, waiting for one to come
+    /// if there is none.
+    pub fn exchange(&self, item: T) -> T {
+        match self.exchange_as(item, Role::Any, Some(None)) {
+            Ok(item) => item,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Swaps `item` with the item of another thread, waiting for at most `timeout`. Returns
+    /// `Err(item)` on timeout.
+    pub fn exchange_timeout(&self, item: T, timeout: Duration) -> Result<T, T> {
+        self.exchange_as(item, Role::Any, Some(Instant::now().checked_add(timeout)))
+    }
+
+    /// Swaps `item` with the item of a thread that is already waiting, if any. Returns
+    /// `Err(item)` otherwise.
+    pub fn try_exchange(&self, item: T) -> Result<T, T> {
+        self.exchange_as(item, Role::Any, None)
+    }
+
+    /// Swaps `item` with the item of a thread whose role matches `role`.
+    ///
+    /// With `wait: None`, only tries the waiting threads. With `wait: Some(deadline)`, waits for a
+    /// partner until `deadline`, or forever if the deadline is `None` (or too far to represent).
+    pub(super) fn exchange_as(
+        &self,
+        item: T,
+        role: Role,
+        wait: Option<Option<Instant>>,
+    ) -> Result<T, T> {
+        let mut offer = Owned::new(Offer {
+            role,
+            state: AtomicUsize::new(WAITING),
+            item: UnsafeCell::new(MaybeUninit::new(item)),
+            reply: UnsafeCell::new(MaybeUninit::uninit()),
+            owner: thread::current(),
+            next: Atomic::null(),
+        });
+
+        let backoff = Backoff::new();
+        let guard = epoch::pin();
+        loop {
+            let head = self.head.load(Ordering::Acquire, &guard);
+            let head_ref = match unsafe { head.as_ref() } {
+                Some(head_ref) if head_ref.role.matches(role) => head_ref,
+                // wait for a partner
+                _ if wait.is_some() => {
+                    offer.next.store(head, Ordering::Relaxed);
+                    match self.head.compare_exchange(
+                        head,
+                        offer,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        &guard,
+                    ) {
+                        Ok(offer) => {
+                            let offer = offer.as_raw();
+                            // don't hold back the reclamation while waiting
+                            drop(guard);
+                            return self.wait(offer, wait.unwrap());
+                        }
+                        Err(e) => offer = e.new,
+                    }
+                    backoff.spin();
+                    continue;
+                }
+                _ => return Err(unsafe { offer.item.get().read().assume_init() }),
+            };
+
+            // take the offer at the top
+            let next = head_ref.next.load(Ordering::Relaxed, &guard);
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::Acquire, Ordering::Relaxed, &guard)
+                .is_err()
+            {
+                backoff.spin();
+                continue;
+            }
+            if head_ref
+                .state
+                .compare_exchange(WAITING, BUSY, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                // cancelled, and we removed it
+                unsafe { guard.defer_destroy(head) };
+                continue;
+            }
+
+            let theirs = unsafe { head_ref.item.get().read().assume_init() };
+            unsafe { head_ref.reply.get().write(offer.item.get().read()) };
+            let owner = head_ref.owner.clone();
+            // The owner may free the offer right after this.
+            head_ref.state.store(MATCHED, Ordering::Release);
+            owner.unpark();
+            return Ok(theirs);
+        }
+    }
+
+    /// Waits for a partner to take `offer`, until `deadline` if any.
+    fn wait(&self, offer: *const Offer<T>, deadline: Option<Instant>) -> Result<T, T> {
+        // Only the owner cancels the offer, so until then nobody frees it.
+        let offer_ref = unsafe { &*offer };
+        let backoff = Backoff::new();
+        loop {
+            if offer_ref.state.load(Ordering::Acquire) == MATCHED {
+                return Ok(Self::finish(offer));
+            }
+            if backoff.is_completed() {
+                match deadline {
+                    None => thread::park(),
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        thread::park_timeout(deadline - now);
+                    }
+                }
+            } else {
+                backoff.snooze();
+            }
+        }
+
+        // A partner that already took the offer frees it if we cancel it, so we must be pinned
+        // before.
+        let guard = &epoch::pin();
+        if offer_ref
+            .state
+            .compare_exchange(WAITING, CANCELLED, Ordering::Acquire, Ordering::Acquire)
+            .is_err()
+        {
+            // a partner is writing its item
+            let backoff = Backoff::new();
+            while offer_ref.state.load(Ordering::Acquire) != MATCHED {
+                backoff.snooze();
+            }
+            return Ok(Self::finish(offer));
+        }
+
+        let item = unsafe { offer_ref.item.get().read().assume_init() };
+        // remove the offer if it is at the top, otherwise the next one to see it at the top does
+        let offer = Shared::from(offer);
+        let next = offer_ref.next.load(Ordering::Relaxed, guard);
+        if self
+            .head
+            .compare_exchange(offer, next, Ordering::Relaxed, Ordering::Relaxed, guard)
+            .is_ok()
+        {
+            unsafe { guard.defer_destroy(offer) };
+        }
+        Err(item)
+    }
+
+    /// Takes the reply of the matched `offer` and frees it.
+    fn finish(offer: *const Offer<T>) -> T {
+        let reply = unsafe { (*offer).reply.get().read().assume_init() };
+        // Others may still be reading the offer they saw at the top.
+        unsafe { epoch::pin().defer_destroy(Shared::from(offer)) };
+        reply
+    }
+}
+
+impl<T> Drop for Exchanger<T> {
+    fn drop(&mut self) {
+        // Only cancelled offers are left, whose items are already moved out.
+        let guard = unsafe { epoch::unprotected() };
+        let mut curr = self.head.load(Ordering::Relaxed, guard);
+        while !curr.is_null() {
+            let offer = unsafe { curr.into_owned() };
+            curr = offer.next.load(Ordering::Relaxed, guard);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread::scope;
+
+    #[test]
+    fn exchange() {
+        let exchanger = Exchanger::new();
+        scope(|s| {
+            let _ = s.spawn(|_| assert_eq!(exchanger.exchange(1), 2));
+            assert_eq!(exchanger.exchange(2), 1);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn timeout() {
+        let exchanger = Exchanger::new();
+        assert_eq!(
+            exchanger.try_exchange(String::from("z")),
+            Err(String::from("z"))
+        );
+        assert_eq!(
+            exchanger.exchange_timeout(String::from("a"), Duration::from_millis(10)),
+            Err(String::from("a"))
+        );
+        // the cancelled offers don't get in the way
+        scope(|s| {
+            let _ = s.spawn(|_| {
+                assert_eq!(
+                    exchanger.exchange_timeout(String::from("b"), Duration::from_secs(10)),
+                    Ok(String::from("c"))
+                )
+            });
+            let backoff = Backoff::new();
+            loop {
+                match exchanger.try_exchange(String::from("c")) {
+                    Ok(item) => break assert_eq!(item, "b"),
+                    Err(_) => backoff.snooze(),
+                }
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn concurrent() {
+        const THREADS: usize = 8;
+        const STEPS: usize = 10_000;
+
+        // every item ends up with exactly one thread
+        let exchanger = Exchanger::new();
+        let sum = AtomicUsize::new(0);
+        scope(|s| {
+            for t in 0..THREADS {
+                let (exchanger, sum) = (&exchanger, &sum);
+                let _ = s.spawn(move |_| {
+                    for i in 0..STEPS {
+                        let item = t * STEPS + i;
+                        let item = match exchanger.exchange_timeout(item, Duration::from_millis(1))
+                        {
+                            Ok(theirs) => theirs,
+                            Err(mine) => mine,
+                        };
+                        let _ = sum.fetch_add(item, Ordering::Relaxed);
+                    }
+                });
+            }
+        })
+        .unwrap();
+        let total = THREADS * STEPS;
+        assert_eq!(sum.into_inner(), total * (total - 1) / 2);
+    }
+}
@@ -0,0 +1,7 @@
+//! Synchronization primitives.
+
+mod exchanger;
+mod rendezvous;
+
+pub use exchanger::Exchanger;
+pub use rendezvous::Rendezvous;
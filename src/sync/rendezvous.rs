@@ -0,0 +1,150 @@
+use core::time::Duration;
+use std::time::Instant;
+
+use super::exchanger::{Exchanger, Role};
+
+/// Zero-capacity channel: a sender waits until a receiver takes its value, and vice versa.
+///
+/// Any number of threads may send and receive. Built on an [`Exchanger`] where senders only
+/// exchange with receivers, which makes it handy to line up two threads at an exact point, e.g.,
+/// in a test:
+///
+/// ```
+/// use cs431::sync::Rendezvous;
+/// use std::thread;
+///
+/// let point = Rendezvous::new();
+/// thread::scope(|s| {
+///     s.spawn(|| {
+///         // ... the first half of the interleaving
+///         point.send(());
+///         // ... the second half
+///     });
+///     point.recv();
+///     // here, the other thread has done its first half
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct Rendezvous<T> {
+    exchanger: Exchanger<Option<T>>,
+}
+
+impl<T> Rendezvous<T> {
+    /// Creates a new rendezvous channel.
+    pub const fn new() -> Self {
+        Self {
+            exchanger: Exchanger::new(),
+        }
+    }
+
+    /// Sends `value`, waiting for a receiver to take it.
+    pub fn send(&self, value: T) {
+        let _ = self
+            .exchanger
+            .exchange_as(Some(value), Role::Give, Some(None));
+    }
+
+    /// Sends `value`, waiting for at most `timeout` for a receiver to take it. Returns
+    /// `Err(value)` on timeout.
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), T> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.exchanger
+            .exchange_as(Some(value), Role::Give, Some(deadline))
+            .map(|_| ())
+            .map_err(|value| value.unwrap())
+    }
+
+    /// Sends `value` to a receiver that is already waiting, if any. Returns `Err(value)`
+    /// otherwise.
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        self.exchanger
+            .exchange_as(Some(value), Role::Give, None)
+            .map(|_| ())
+            .map_err(|value| value.unwrap())
+    }
+
+    /// Receives a value, waiting for a sender.
+    pub fn recv(&self) -> T {
+        match self.exchanger.exchange_as(None, Role::Take, Some(None)) {
+            Ok(Some(value)) => value,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Receives a value, waiting for at most `timeout` for a sender. Returns `None` on timeout.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now().checked_add(timeout);
+        self.exchanger
+            .exchange_as(None, Role::Take, Some(deadline))
+            .ok()
+            .flatten()
+    }
+
+    /// Receives a value from a sender that is already waiting, if any.
+    pub fn try_recv(&self) -> Option<T> {
+        self.exchanger
+            .exchange_as(None, Role::Take, None)
+            .ok()
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use crossbeam_utils::thread::scope;
+
+    #[test]
+    fn send_recv() {
+        let channel = Rendezvous::new();
+        assert_eq!(channel.try_recv(), None);
+        assert_eq!(channel.try_send(1), Err(1));
+        assert_eq!(channel.recv_timeout(Duration::from_millis(10)), None);
+        assert_eq!(channel.send_timeout(2, Duration::from_millis(10)), Err(2));
+
+        let received = AtomicUsize::new(0);
+        scope(|s| {
+            let _ = s.spawn(|_| {
+                for i in 0..100 {
+                    channel.send(i);
+                    // the receiver was waiting for this value, so it got all the previous ones
+                    assert!(received.load(Ordering::SeqCst) >= i);
+                }
+            });
+            for i in 0..100 {
+                assert_eq!(channel.recv(), i);
+                let _ = received.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn senders_dont_pair() {
+        const THREADS: usize = 4;
+        const STEPS: usize = 1000;
+
+        let channel = Rendezvous::new();
+        let sum = AtomicUsize::new(0);
+        scope(|s| {
+            for t in 0..THREADS {
+                let channel = &channel;
+                let _ = s.spawn(move |_| {
+                    for i in 0..STEPS {
+                        channel.send(t * STEPS + i);
+                    }
+                });
+            }
+            for _ in 0..THREADS {
+                let _ = s.spawn(|_| {
+                    let received = (0..STEPS).map(|_| channel.recv()).sum::<usize>();
+                    let _ = sum.fetch_add(received, Ordering::Relaxed);
+                });
+            }
+        })
+        .unwrap();
+        let total = THREADS * STEPS;
+        assert_eq!(sum.into_inner(), total * (total - 1) / 2);
+    }
+}